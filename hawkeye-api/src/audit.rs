@@ -0,0 +1,145 @@
+use crate::auth;
+use crate::store::Store;
+use chrono::Utc;
+use kube::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use warp::http::StatusCode;
+use warp::Filter;
+
+/// Maximum number of operations kept for a watcher, the oldest are dropped first.
+const CAPACITY: usize = 200;
+
+/// Builds an idempotent name for the `ConfigMap` with the audit of the `watcher_id`. It's kept
+/// after the watcher is deleted, to know who deleted it.
+pub fn configmap_name(watcher_id: &str) -> String {
+    format!("hawkeye-audit-{}", watcher_id)
+}
+
+/// Who called the API. The API only authenticates its tokens, the user and the address the
+/// request was forwarded for are the ones declared by the client, they're not verified.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Caller {
+    /// Who the token identifies, `admin` or `client`, or the part of the API acting on its own.
+    pub principal: Option<String>,
+    /// The user declared in the `X-Hawkeye-User` header.
+    #[serde(alias = "user")]
+    pub claimed_user: Option<String>,
+    /// The first address of the `X-Forwarded-For` header, when the API is behind a load balancer.
+    pub claimed_address: Option<String>,
+    /// The address the request came from.
+    pub address: Option<String>,
+}
+
+/// Identifies the caller, to be combined with `auth::verify()`.
+pub fn caller() -> impl Filter<Extract = (Caller,), Error = Infallible> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and(warp::header::optional::<String>("x-hawkeye-user"))
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and(warp::addr::remote())
+        .map(
+            |authorization: Option<String>,
+             claimed_user: Option<String>,
+             forwarded_for: Option<String>,
+             remote: Option<SocketAddr>| {
+                let claimed_address = forwarded_for.and_then(|addresses| {
+                    addresses
+                        .split(',')
+                        .next()
+                        .map(|address| address.trim().to_string())
+                });
+                Caller {
+                    principal: authorization
+                        .as_deref()
+                        .and_then(auth::principal)
+                        .map(String::from),
+                    claimed_user,
+                    claimed_address,
+                    address: remote.map(|remote| remote.ip().to_string()),
+                }
+            },
+        )
+}
+
+/// An operation changing a watcher.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Operation {
+    pub timestamp: String,
+    pub operation: String,
+    pub caller: Caller,
+    pub status_code: u16,
+}
+
+/// The operations on the watcher, the oldest first.
+fn store(client: Client, watcher_id: &str) -> Store {
+    Store::new(
+        client,
+        configmap_name(watcher_id),
+        "audit.json",
+        // Not `app=hawkeye`, it's not a watcher configuration
+        json!({
+            "app": "hawkeye-audit",
+            "watcher_id": watcher_id,
+        }),
+    )
+}
+
+/// Records an operation on the watcher. Failures are logged and otherwise ignored, they don't
+/// fail the operation.
+pub async fn record(
+    client: Client,
+    watcher_id: &str,
+    operation: &str,
+    caller: &Caller,
+    status_code: StatusCode,
+) {
+    let entry = Operation {
+        timestamp: Utc::now().to_rfc3339(),
+        operation: operation.to_string(),
+        caller: caller.clone(),
+        status_code: status_code.as_u16(),
+    };
+    log::info!("Watcher {}: {:?}", watcher_id, entry);
+
+    let recorded = store(client, watcher_id)
+        .update(
+            |operations: &mut Vec<Operation>| -> Result<bool, kube::Error> {
+                operations.push(entry.clone());
+                if operations.len() > CAPACITY {
+                    operations.drain(..operations.len() - CAPACITY);
+                }
+                Ok(true)
+            },
+        )
+        .await;
+    if let Err(err) = recorded {
+        log::error!(
+            "Operation on {} was not recorded: {:?}: {:?}",
+            watcher_id,
+            entry,
+            err
+        );
+    }
+}
+
+/// Records the operation with the status code of its reply.
+pub async fn recorded(
+    reply: impl warp::Reply,
+    client: Client,
+    watcher_id: &str,
+    operation: &str,
+    caller: Caller,
+) -> warp::reply::Response {
+    let response = reply.into_response();
+    record(client, watcher_id, operation, &caller, response.status()).await;
+    response
+}
+
+/// Operations of the watcher, the latest first.
+pub async fn list(client: Client, watcher_id: &str) -> Option<Vec<Operation>> {
+    let mut operations: Vec<Operation> = store(client, watcher_id).get().await.ok()??;
+    operations.reverse();
+    Some(operations)
+}
@@ -13,13 +13,36 @@ pub fn verify() -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
 }
 
 fn verify_token(auth_header: String) -> Result<(), ()> {
-    if auth_header.replace("Bearer ", "").as_str() == config::FIXED_TOKEN.as_str() {
-        Ok(())
+    match principal(&auth_header) {
+        Some(_) => Ok(()),
+        None => Err(()),
+    }
+}
+
+/// Who the token of the `authorization` header identifies: `admin` with the administrator token,
+/// `client` with the token of the API.
+pub fn principal(auth_header: &str) -> Option<&'static str> {
+    let token = auth_header.replace("Bearer ", "");
+    if is_admin_token(&token) {
+        Some("admin")
+    } else if token.as_str() == config::FIXED_TOKEN.as_str() {
+        Some("client")
     } else {
-        Err(())
+        None
     }
 }
 
+/// Whether the client is an administrator, to be combined with `verify()`. There is no
+/// administrator without `HAWKEYE_ADMIN_TOKEN`.
+pub fn is_admin() -> impl Filter<Extract = (bool,), Error = warp::Rejection> + Clone {
+    warp::header::<String>("authorization")
+        .map(|auth_header: String| is_admin_token(&auth_header.replace("Bearer ", "")))
+}
+
+fn is_admin_token(token: &str) -> bool {
+    config::ADMIN_TOKEN.as_deref() == Some(token)
+}
+
 #[derive(Debug)]
 pub struct NoAuth;
 
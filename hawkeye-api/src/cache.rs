@@ -0,0 +1,108 @@
+use crate::config::NAMESPACE;
+use crate::crd::WatcherResource;
+use futures::StreamExt;
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::Service;
+use kube::api::ListParams;
+use kube::runtime::reflector::{self, store::Writer, Store};
+use kube::runtime::watcher;
+use kube::{Api, Client, Resource};
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Copies of the resources and objects of all the watchers, kept up to date by watching them, so the watchers
+/// are listed from memory instead of listing their objects from Kubernetes each time.
+#[derive(Clone)]
+pub struct Cache {
+    resources: Reflection<WatcherResource>,
+    deployments: Reflection<Deployment>,
+    services: Reflection<Service>,
+}
+
+/// The objects of all the watchers, at the same time.
+pub struct WatcherObjects {
+    pub resources: Vec<WatcherResource>,
+    pub deployments: Vec<Deployment>,
+    pub services: Vec<Service>,
+}
+
+impl Cache {
+    /// Starts watching the objects of the watchers, in the background.
+    pub fn start(client: Client) -> Self {
+        // The objects of the watchers are labeled with their id, unlike their resources
+        let objects = ListParams::default().labels("app=hawkeye,watcher_id");
+        Cache {
+            resources: Reflection::start(
+                Api::namespaced(client.clone(), &NAMESPACE),
+                ListParams::default(),
+            ),
+            deployments: Reflection::start(
+                Api::namespaced(client.clone(), &NAMESPACE),
+                objects.clone(),
+            ),
+            services: Reflection::start(Api::namespaced(client, &NAMESPACE), objects),
+        }
+    }
+
+    /// The objects of the watchers, or `None` while they're not all up to date, like until they
+    /// are listed when the API starts or after watching them failed.
+    pub fn objects(&self) -> Option<WatcherObjects> {
+        if !(self.resources.is_ready() && self.deployments.is_ready() && self.services.is_ready()) {
+            return None;
+        }
+        Some(WatcherObjects {
+            resources: self.resources.store.state(),
+            deployments: self.deployments.store.state(),
+            services: self.services.store.state(),
+        })
+    }
+}
+
+/// Objects of a kind, kept up to date in the `store`.
+#[derive(Clone)]
+struct Reflection<K: 'static + Resource<DynamicType = ()>> {
+    store: Store<K>,
+    /// Whether the objects were listed, and watched since without errors or successfully again
+    /// after the last one.
+    ready: Arc<AtomicBool>,
+}
+
+impl<K> Reflection<K>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+{
+    fn start(api: Api<K>, lp: ListParams) -> Self {
+        let writer = Writer::default();
+        let reflection = Reflection {
+            store: writer.as_reader(),
+            ready: Arc::new(AtomicBool::new(false)),
+        };
+        let ready = reflection.ready.clone();
+        tokio::spawn(async move {
+            let mut events = reflector::reflector(writer, watcher::watcher(api, lp)).boxed();
+            while let Some(event) = events.next().await {
+                match event {
+                    // The objects were listed, or the watch resumed where it failed without
+                    // missing changes, as it lists them again when it can't
+                    Ok(_) => ready.store(true, Ordering::Relaxed),
+                    Err(err) => {
+                        // The copies may be out of date until the next event
+                        ready.store(false, Ordering::Relaxed);
+                        log::error!("Error while watching {}s: {:?}", K::kind(&()), err);
+                        tokio::time::sleep(Duration::from_secs(5)).await;
+                    }
+                }
+            }
+            ready.store(false, Ordering::Relaxed);
+            log::warn!("Stopped watching {}s", K::kind(&()));
+        });
+        reflection
+    }
+
+    fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+}
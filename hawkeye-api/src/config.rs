@@ -1,16 +1,44 @@
 use lazy_static::lazy_static;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::collections::BTreeMap;
 use std::iter;
 
 // Environment variable names
 const NAMESPACE_ENV: &str = "HAWKEYE_NAMESPACE";
 const DOCKER_IMAGE_ENV: &str = "HAWKEYE_DOCKER_IMAGE";
 const FIXED_TOKEN_ENV: &str = "HAWKEYE_FIXED_TOKEN";
+const ADMIN_TOKEN_ENV: &str = "HAWKEYE_ADMIN_TOKEN";
 const CALL_WATCHER_TIMEOUT_ENV: &str = "HAWKEYE_CALL_WATCHER_TIMEOUT_TOKEN";
+const WEBHOOK_URL_ENV: &str = "HAWKEYE_WEBHOOK_URL";
+const WEBHOOK_SECRET_ENV: &str = "HAWKEYE_WEBHOOK_SECRET";
+const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const TRACES_SAMPLE_RATIO_ENV: &str = "HAWKEYE_TRACES_SAMPLE_RATIO";
+const SLATES_DIR_ENV: &str = "HAWKEYE_SLATES_DIR";
+const SLATE_ASSETS_URL_ENV: &str = "HAWKEYE_SLATE_ASSETS_URL";
+const CONTROL_TOKEN_ENV: &str = "HAWKEYE_CONTROL_TOKEN";
+const LIVENESS_INITIAL_DELAY_ENV: &str = "HAWKEYE_LIVENESS_INITIAL_DELAY";
+const LIVENESS_PERIOD_ENV: &str = "HAWKEYE_LIVENESS_PERIOD";
+const LIVENESS_FAILURE_THRESHOLD_ENV: &str = "HAWKEYE_LIVENESS_FAILURE_THRESHOLD";
+const READINESS_PERIOD_ENV: &str = "HAWKEYE_READINESS_PERIOD";
+const READINESS_FAILURE_THRESHOLD_ENV: &str = "HAWKEYE_READINESS_FAILURE_THRESHOLD";
+const INGEST_PORT_MIN_ENV: &str = "HAWKEYE_INGEST_PORT_MIN";
+const INGEST_PORT_MAX_ENV: &str = "HAWKEYE_INGEST_PORT_MAX";
+const API_POD_LABELS_ENV: &str = "HAWKEYE_API_POD_LABELS";
+const API_NAMESPACE_ENV: &str = "HAWKEYE_API_NAMESPACE";
+const SCRAPER_POD_LABELS_ENV: &str = "HAWKEYE_SCRAPER_POD_LABELS";
+const SCRAPER_NAMESPACE_ENV: &str = "HAWKEYE_SCRAPER_NAMESPACE";
 
 // Configuration defaults
 const DEFAULT_CALL_WATCHER_TIMEOUT: u64 = 2;
+const DEFAULT_LIVENESS_INITIAL_DELAY: u32 = 10;
+const DEFAULT_LIVENESS_PERIOD: u32 = 10;
+const DEFAULT_LIVENESS_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_READINESS_PERIOD: u32 = 5;
+const DEFAULT_READINESS_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_INGEST_PORT_MIN: u32 = 40_000;
+const DEFAULT_INGEST_PORT_MAX: u32 = 49_999;
+const DEFAULT_API_POD_LABELS: &str = "app=hawkeye-api";
 
 lazy_static! {
     /// Kubernetes namespace where the resources are managed (created/deleted/updated)
@@ -25,8 +53,115 @@ lazy_static! {
     pub static ref FIXED_TOKEN: String =
         std::env::var(FIXED_TOKEN_ENV).unwrap_or_else(|_| gen_token());
 
+    /// Token of the administrators, the only clients allowed to choose the worker image of a
+    /// watcher. Without it, all the clients are administrators.
+    pub static ref ADMIN_TOKEN: Option<String> = std::env::var(ADMIN_TOKEN_ENV).ok();
+
     pub static ref CALL_WATCHER_TIMEOUT: u64 =
         std::env::var(CALL_WATCHER_TIMEOUT_ENV).map(|val| val.parse::<u64>()).unwrap_or_else(|_| Ok(DEFAULT_CALL_WATCHER_TIMEOUT)).unwrap_or(DEFAULT_CALL_WATCHER_TIMEOUT);
+
+    /// Optional URL notified about events happening to the watchers (ie. ingest address changes)
+    pub static ref WEBHOOK_URL: Option<String> = std::env::var(WEBHOOK_URL_ENV).ok();
+
+    /// Optional secret the calls to the webhook are signed with, so it can reject calls not made by the API
+    pub static ref WEBHOOK_SECRET: Option<String> = std::env::var(WEBHOOK_SECRET_ENV).ok();
+
+    /// Directory the uploaded slates are stored in, a persistent volume shared by the replicas of the API
+    pub static ref SLATES_DIR: String =
+        std::env::var(SLATES_DIR_ENV).unwrap_or_else(|_| "/var/lib/hawkeye/slates".into());
+
+    /// URL the workers download the uploaded slates from (ie. `http://hawkeye-api/v1/slates`)
+    pub static ref SLATE_ASSETS_URL: Option<String> = std::env::var(SLATE_ASSETS_URL_ENV).ok();
+
+    /// Token of the control endpoints of the workers, given to the workers so the API can change
+    /// their log level while they run
+    pub static ref CONTROL_TOKEN: Option<String> = std::env::var(CONTROL_TOKEN_ENV).ok();
+
+    /// Seconds before the first liveness probe of the workers, while their pipeline starts
+    pub static ref LIVENESS_INITIAL_DELAY: u32 =
+        env_u32(LIVENESS_INITIAL_DELAY_ENV, DEFAULT_LIVENESS_INITIAL_DELAY);
+
+    /// Seconds between the liveness probes of the workers
+    pub static ref LIVENESS_PERIOD: u32 = env_u32(LIVENESS_PERIOD_ENV, DEFAULT_LIVENESS_PERIOD);
+
+    /// Failed liveness probes in a row before Kubernetes restarts a worker, like one with a hung pipeline
+    pub static ref LIVENESS_FAILURE_THRESHOLD: u32 =
+        env_u32(LIVENESS_FAILURE_THRESHOLD_ENV, DEFAULT_LIVENESS_FAILURE_THRESHOLD);
+
+    /// Seconds between the readiness probes of the workers
+    pub static ref READINESS_PERIOD: u32 = env_u32(READINESS_PERIOD_ENV, DEFAULT_READINESS_PERIOD);
+
+    /// Failed readiness probes in a row before a worker is not ready anymore
+    pub static ref READINESS_FAILURE_THRESHOLD: u32 =
+        env_u32(READINESS_FAILURE_THRESHOLD_ENV, DEFAULT_READINESS_FAILURE_THRESHOLD);
+
+    /// First port of the pool the ingest ports of the watchers created without one are allocated from
+    pub static ref INGEST_PORT_MIN: u32 = env_u32(INGEST_PORT_MIN_ENV, DEFAULT_INGEST_PORT_MIN);
+
+    /// Last port of the pool of ingest ports
+    pub static ref INGEST_PORT_MAX: u32 = env_u32(INGEST_PORT_MAX_ENV, DEFAULT_INGEST_PORT_MAX);
+
+    /// Labels of the pods of the API, the only ones the workers with `allowed_sources` accept HTTP
+    /// calls from
+    pub static ref API_POD_LABELS: BTreeMap<String, String> = labels(
+        &std::env::var(API_POD_LABELS_ENV).unwrap_or_else(|_| DEFAULT_API_POD_LABELS.into())
+    );
+
+    /// Kubernetes namespace the API runs in, the namespace of the watchers by default
+    pub static ref API_NAMESPACE: String =
+        std::env::var(API_NAMESPACE_ENV).unwrap_or_else(|_| NAMESPACE.clone());
+
+    /// Labels of the pods of Prometheus, allowed to scrape the workers with `allowed_sources` too.
+    /// Without them, the metrics of these workers are not scraped
+    pub static ref SCRAPER_POD_LABELS: Option<BTreeMap<String, String>> =
+        std::env::var(SCRAPER_POD_LABELS_ENV)
+            .ok()
+            .map(|value| labels(&value))
+            .filter(|labels| !labels.is_empty());
+
+    /// Kubernetes namespace Prometheus runs in, the namespace of the API by default
+    pub static ref SCRAPER_NAMESPACE: String =
+        std::env::var(SCRAPER_NAMESPACE_ENV).unwrap_or_else(|_| API_NAMESPACE.clone());
+
+    /// Tracing settings of the API, passed on to the workers so their traces go to the same collector
+    pub static ref TRACING_ENV: Vec<(&'static str, String)> = [OTLP_ENDPOINT_ENV, TRACES_SAMPLE_RATIO_ENV]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok().map(|value| (*name, value)))
+        .collect();
+}
+
+/// The number in the environment variable, at least 1, or the `default` when it's missing or
+/// invalid.
+fn env_u32(name: &str, default: u32) -> u32 {
+    positive_u32(name, std::env::var(name).ok().as_deref(), default)
+}
+
+fn positive_u32(name: &str, value: Option<&str>, default: u32) -> u32 {
+    let value = match value {
+        Some(value) => value,
+        None => return default,
+    };
+    match value.parse() {
+        Ok(number) if number >= 1 => number,
+        _ => {
+            log::warn!(
+                "Invalid {} {:?}, it must be a number of at least 1, using {}",
+                name,
+                value,
+                default
+            );
+            default
+        }
+    }
+}
+
+/// Labels written like `app=hawkeye-api,tier=control`, the ones without `=` are ignored.
+fn labels(value: &str) -> BTreeMap<String, String> {
+    value
+        .split(',')
+        .filter_map(|label| label.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .collect()
 }
 
 /// In case the environment variable `HAWKEYE_FIXED_TOKEN` is not present, a
@@ -47,3 +182,27 @@ fn gen_token() -> String {
     );
     random_token
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positive_numbers() {
+        assert_eq!(positive_u32("HAWKEYE_TEST", Some("5"), 3), 5);
+        assert_eq!(positive_u32("HAWKEYE_TEST", Some("1"), 3), 1);
+        assert_eq!(positive_u32("HAWKEYE_TEST", None, 3), 3);
+        assert_eq!(positive_u32("HAWKEYE_TEST", Some("0"), 3), 3);
+        assert_eq!(positive_u32("HAWKEYE_TEST", Some("-1"), 3), 3);
+        assert_eq!(positive_u32("HAWKEYE_TEST", Some("ten"), 3), 3);
+    }
+
+    #[test]
+    fn pod_labels() {
+        let parsed = labels("app=hawkeye-api, tier=control,invalid");
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed["app"], "hawkeye-api");
+        assert_eq!(parsed["tier"], "control");
+        assert!(labels("").is_empty());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::config::NAMESPACE;
+use crate::crd::{WatcherResource, WatcherResourceStatus};
+use crate::errors::ApiError;
+use crate::handlers::{self, WatcherStatus};
+use crate::ports;
+use crate::revisions;
+use crate::store;
+use crate::templates;
+use futures::StreamExt;
+use hawkeye_core::models::Watcher;
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::{ConfigMap, Service};
+use k8s_openapi::api::networking::v1::NetworkPolicy;
+use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
+use kube::runtime::controller::{Context, Controller, ReconcilerAction};
+use kube::{Api, Client};
+use serde_json::json;
+use std::time::Duration;
+
+/// Time between the reconciliations of an unchanged watcher, refreshing its status.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time before reconciling again a watcher that failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Runs the controller of the `Watcher` resources: it creates the objects of the watchers, like the
+/// ones created with `kubectl` or a GitOps tool, applies the changes of their spec and reports
+/// their status.
+pub async fn run(client: Client) {
+    let resources: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    Controller::new(resources, ListParams::default())
+        .owns(
+            config_maps,
+            ListParams::default().labels("app=hawkeye,watcher_id"),
+        )
+        .run(reconcile, error_policy, Context::new(client))
+        .for_each(|result| async move {
+            if let Err(err) = result {
+                log::warn!("Could not reconcile a watcher: {:?}", err);
+            }
+        })
+        .await;
+}
+
+async fn reconcile(
+    resource: WatcherResource,
+    ctx: Context<Client>,
+) -> Result<ReconcilerAction, ApiError> {
+    let client = ctx.get_ref().clone();
+    let id = resource.id();
+    if resource.spec.source.ingest_port == 0 {
+        // Created without a port, like with `kubectl`: the change of the spec reconciles it again
+        let port = ports::allocate(client.clone(), id).await?;
+        let resources: Api<WatcherResource> = Api::namespaced(client, &NAMESPACE);
+        let patch = json!({ "spec": { "source": { "ingest_port": port } } });
+        resources
+            .patch(id, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+        return Ok(ReconcilerAction {
+            requeue_after: None,
+        });
+    }
+    let mut status = WatcherResourceStatus {
+        observed_generation: resource.metadata.generation,
+        ..WatcherResourceStatus::default()
+    };
+    let port = resource.spec.source.ingest_port;
+    match handlers::validate(&spec(&resource)) {
+        // The Service and the port allocation of the watcher are not changed
+        Ok(()) if source_changed(&resource, client.clone()).await? => {
+            status.message =
+                Some("The source of a Watcher can't change, it has to be deleted first".to_string())
+        }
+        // The feeds would be sent to both watchers
+        Ok(()) => match ports::used_by(client.clone(), port, id).await? {
+            Some(other) => status.message = Some(handlers::port_conflict_message(port, &other)),
+            None => {
+                apply(&resource, client.clone()).await?;
+                let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+                status.status = deployments
+                    .get(&templates::deployment_name(id))
+                    .await
+                    .ok()
+                    .map(|deployment| deployment.get_watcher_status());
+            }
+        },
+        // Nothing is applied until the spec is fixed
+        Err(errors) => status.message = Some(errors.to_string()),
+    }
+
+    if resource.status.as_ref() != Some(&status) {
+        let resources: Api<WatcherResource> = Api::namespaced(client, &NAMESPACE);
+        // Unset fields are removed from the status
+        let patch = json!({
+            "status": {
+                "status": status.status,
+                "observedGeneration": status.observed_generation,
+                "message": status.message,
+            }
+        });
+        resources
+            .patch_status(id, &PatchParams::default(), &Patch::Merge(&patch))
+            .await?;
+    }
+    Ok(ReconcilerAction {
+        requeue_after: Some(RESYNC_INTERVAL),
+    })
+}
+
+fn error_policy(_: &ApiError, _: Context<Client>) -> ReconcilerAction {
+    ReconcilerAction {
+        requeue_after: Some(RETRY_INTERVAL),
+    }
+}
+
+/// The watcher defined by the resource, identified by its name.
+pub(crate) fn spec(resource: &WatcherResource) -> Watcher {
+    let mut watcher = handlers::definition(resource.spec.clone());
+    watcher.id = Some(resource.id().to_string());
+    watcher
+}
+
+/// Whether the source of the spec differs from the one the objects of the watcher were created
+/// with, stored in its `ConfigMap`.
+async fn source_changed(resource: &WatcherResource, client: Client) -> Result<bool, kube::Error> {
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let config_map = match config_maps
+        .get(&templates::configmap_name(resource.id()))
+        .await
+    {
+        Ok(config_map) => config_map,
+        // Not created yet
+        Err(kube::Error::Api(response)) if response.code == 404 => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    let stored: Option<Watcher> = store::document(&config_map, "watcher.json");
+    Ok(stored.map_or(false, |stored| {
+        handlers::definition(stored).source != spec(resource).source
+    }))
+}
+
+/// Makes the objects of the watcher match its resource: creates the missing ones, changes the ones
+/// depending on its spec and then stores it in the `ConfigMap` when it changed, keeping the previous
+/// one as a revision. It's called by the controller and by the handlers, so it only writes what
+/// differs.
+pub async fn apply(resource: &WatcherResource, client: Client) -> Result<(), ApiError> {
+    let id = resource.id();
+    let mut watcher = spec(resource);
+    let config_map = create_objects(id, &watcher, resource, client.clone()).await?;
+
+    // Kept as is, even when it's not a valid configuration anymore
+    let previous = config_map
+        .data
+        .as_ref()
+        .and_then(|data| data.get("watcher.json"))
+        .cloned()
+        .unwrap_or_default();
+    let stored: Option<Watcher> = serde_json::from_str(&previous).ok();
+    if let Some(stored) = stored.as_ref() {
+        if handlers::definition(stored.clone()) == watcher {
+            return Ok(());
+        }
+        // Assigned once the LoadBalancer is provisioned, it's not in the spec
+        watcher.source.ingest_ip = stored.source.ingest_ip.clone();
+    }
+
+    // The other objects are changed first: until the stored configuration changes, the next
+    // reconciliation changes them again when it fails
+    if let Some(stored) = stored.as_ref() {
+        if stored.scheduling != watcher.scheduling
+            || stored.annotations != watcher.annotations
+            || templates::is_scraped(stored) != templates::is_scraped(&watcher)
+        {
+            update_deployment(id, stored, &watcher, client.clone()).await?;
+        }
+        if stored.annotations != watcher.annotations
+            || stored.hostname != watcher.hostname
+            || stored.allowed_sources != watcher.allowed_sources
+        {
+            update_service(id, stored, &watcher, client.clone()).await?;
+        }
+        if stored.allowed_sources != watcher.allowed_sources {
+            update_network_policy(id, &watcher, &config_map, client.clone()).await?;
+        }
+    }
+
+    // Stored last, the worker loads it when it starts
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let patch = json!({
+        "metadata": {
+            "labels": {
+                // Removed from the ConfigMap when the group is unset
+                (templates::GROUP_LABEL): watcher.group,
+            }
+        },
+        "data": {
+            "watcher.json": serde_json::to_string(&watcher).unwrap(),
+        }
+    });
+    config_maps
+        .patch(
+            &templates::configmap_name(id),
+            &PatchParams::default(),
+            &Patch::Merge(&patch),
+        )
+        .await?;
+    revisions::record(client, id, &previous).await;
+    Ok(())
+}
+
+/// Changes the nodes the worker of the watcher runs on, its annotations and whether it's scraped,
+/// from the `previous` configuration of the watcher. Kubernetes replaces the worker when it's
+/// running.
+async fn update_deployment(
+    id: &str,
+    previous: &Watcher,
+    watcher: &Watcher,
+    client: Client,
+) -> Result<(), kube::Error> {
+    let deployments: Api<Deployment> = Api::namespaced(client, &NAMESPACE);
+    let name = templates::deployment_name(id);
+    let mut deployment = deployments.get(&name).await?;
+    templates::reannotate(
+        &mut deployment.metadata,
+        templates::deployment_annotations(previous),
+        templates::deployment_annotations(watcher),
+    );
+    if let Some(spec) = deployment.spec.as_mut() {
+        let template = &mut spec.template;
+        let metadata = template.metadata.get_or_insert_with(Default::default);
+        templates::reannotate(
+            metadata,
+            templates::pod_annotations(previous),
+            templates::pod_annotations(watcher),
+        );
+        metadata.labels.get_or_insert_with(Default::default).insert(
+            "prometheus.io/scrape".to_string(),
+            templates::is_scraped(watcher).to_string(),
+        );
+        if let Some(pod_spec) = template.spec.as_mut() {
+            templates::schedule(pod_spec, watcher);
+        }
+    }
+    deployments
+        .replace(&name, &PostParams::default(), &deployment)
+        .await
+        .map(drop)
+}
+
+/// Changes the annotations of the Service of the watcher, if it's exposed, like its hostname, from
+/// the `previous` configuration of the watcher, and its external traffic policy.
+async fn update_service(
+    id: &str,
+    previous: &Watcher,
+    watcher: &Watcher,
+    client: Client,
+) -> Result<(), kube::Error> {
+    let services: Api<Service> = Api::namespaced(client, &NAMESPACE);
+    let name = templates::service_name(id);
+    let mut service = match services.get(&name).await {
+        Ok(service) => service,
+        Err(kube::Error::Api(response)) if response.code == 404 => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    templates::reannotate(
+        &mut service.metadata,
+        templates::service_annotations(previous),
+        templates::service_annotations(watcher),
+    );
+    if let Some(spec) = service.spec.as_mut() {
+        if spec.type_.as_deref() != Some("ClusterIP") {
+            let policy = templates::external_traffic_policy(watcher);
+            spec.external_traffic_policy = Some(policy.to_string());
+        }
+    }
+    services
+        .replace(&name, &PostParams::default(), &service)
+        .await
+        .map(drop)
+}
+
+/// Makes the `NetworkPolicy` of the watcher allow the feed from its `allowed_sources`, deleting it
+/// when the feed is allowed from anywhere again.
+async fn update_network_policy(
+    id: &str,
+    watcher: &Watcher,
+    config_map: &ConfigMap,
+    client: Client,
+) -> Result<(), kube::Error> {
+    let network_policies: Api<NetworkPolicy> = Api::namespaced(client, &NAMESPACE);
+    let name = templates::network_policy_name(id);
+    let mut network_policy = match templates::build_network_policy(id, watcher, config_map) {
+        Some(network_policy) => network_policy,
+        None => {
+            return match network_policies
+                .delete(&name, &DeleteParams::default())
+                .await
+            {
+                Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+                result => result.map(drop),
+            }
+        }
+    };
+    // Created with the other objects when it was missing
+    let current = network_policies.get(&name).await?;
+    network_policy.metadata.resource_version = current.metadata.resource_version;
+    network_policies
+        .replace(&name, &PostParams::default(), &network_policy)
+        .await
+        .map(drop)
+}
+
+/// Creates the objects of the watcher missing: its `ConfigMap`, owned by the resource, and its
+/// Deployment with no replicas, its Service, if exposed, and its `NetworkPolicy`, if it restricts
+/// the addresses of the feed, owned by the `ConfigMap`. Returns the `ConfigMap`.
+async fn create_objects(
+    id: &str,
+    watcher: &Watcher,
+    resource: &WatcherResource,
+    client: Client,
+) -> Result<ConfigMap, kube::Error> {
+    let pp = PostParams::default();
+
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let config = templates::build_configmap(id, watcher, resource);
+    let config_map = match config_maps.create(&pp, &config).await {
+        Err(err) if already_exists(&err) => config_maps.get(&templates::configmap_name(id)).await?,
+        result => result?,
+    };
+
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let deploy = templates::build_deployment(id, watcher, &config_map);
+    if let Err(err) = deployments.create(&pp, &deploy).await {
+        if !already_exists(&err) {
+            return Err(err);
+        }
+    }
+
+    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+    if let Some(svc) = templates::build_service(id, watcher, &config_map) {
+        if let Err(err) = services.create(&pp, &svc).await {
+            if !already_exists(&err) {
+                return Err(err);
+            }
+        }
+    }
+
+    let network_policies: Api<NetworkPolicy> = Api::namespaced(client, &NAMESPACE);
+    if let Some(network_policy) = templates::build_network_policy(id, watcher, &config_map) {
+        if let Err(err) = network_policies.create(&pp, &network_policy).await {
+            if !already_exists(&err) {
+                return Err(err);
+            }
+        }
+    }
+    Ok(config_map)
+}
+
+/// The object exists, created by an earlier reconciliation or concurrently by the handlers.
+fn already_exists(err: &kube::Error) -> bool {
+    matches!(err, kube::Error::Api(response) if response.code == 409)
+}
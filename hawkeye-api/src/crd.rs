@@ -0,0 +1,101 @@
+use hawkeye_core::models::{Status, Watcher};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::Resource;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::borrow::Cow;
+
+pub const GROUP: &str = "hawkeye.io";
+pub const VERSION: &str = "v1";
+pub const KIND: &str = "Watcher";
+const PLURAL: &str = "watchers";
+
+/// A watcher as a Kubernetes resource, `kubectl get watchers`: its name is the id of the watcher
+/// and its spec the definition of the watcher. It owns the `ConfigMap` of the watcher, which owns
+/// the other objects, so deleting it deletes the watcher.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WatcherResource {
+    #[serde(rename = "apiVersion")]
+    pub api_version: String,
+    pub kind: String,
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+    pub spec: Watcher,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<WatcherResourceStatus>,
+}
+
+/// Status of the resource, reported by the controller.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct WatcherResourceStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    /// Generation of the spec applied to the objects of the watcher.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_generation: Option<i64>,
+    /// Why the spec could not be applied, like its validation errors.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl WatcherResource {
+    pub fn new(id: &str, spec: Watcher) -> Self {
+        WatcherResource {
+            api_version: format!("{}/{}", GROUP, VERSION),
+            kind: KIND.to_string(),
+            metadata: ObjectMeta {
+                name: Some(id.to_string()),
+                ..ObjectMeta::default()
+            },
+            spec,
+            status: None,
+        }
+    }
+
+    /// Id of the watcher, the name of the resource.
+    pub fn id(&self) -> &str {
+        self.metadata.name.as_deref().unwrap_or_default()
+    }
+
+    /// Makes the resource the owner of the `ConfigMap` of the watcher.
+    pub fn owner_references(&self) -> serde_json::Value {
+        json!([
+            {
+                "apiVersion": self.api_version,
+                "kind": KIND,
+                "name": self.metadata.name,
+                "uid": self.metadata.uid,
+                "controller": true,
+            }
+        ])
+    }
+}
+
+impl Resource for WatcherResource {
+    type DynamicType = ();
+
+    fn kind(_: &()) -> Cow<'_, str> {
+        KIND.into()
+    }
+
+    fn group(_: &()) -> Cow<'_, str> {
+        GROUP.into()
+    }
+
+    fn version(_: &()) -> Cow<'_, str> {
+        VERSION.into()
+    }
+
+    fn plural(_: &()) -> Cow<'_, str> {
+        PLURAL.into()
+    }
+
+    fn meta(&self) -> &ObjectMeta {
+        &self.metadata
+    }
+
+    fn meta_mut(&mut self) -> &mut ObjectMeta {
+        &mut self.metadata
+    }
+}
@@ -0,0 +1,135 @@
+use serde_json::json;
+use std::fmt;
+use warp::http::StatusCode;
+use warp::reply;
+use warp::Reply;
+
+/// Why a handler failed, replied with its status code and a body like
+/// `{"message": "...", "reason": "kubernetes_unavailable", "retriable": true}`, so the clients know
+/// whether to retry the request.
+#[derive(Debug)]
+pub enum ApiError {
+    /// The Watcher, or what was asked for, does not exist.
+    NotFound(&'static str),
+    /// The Watcher must be running, like to reach its worker.
+    NotRunning,
+    /// The Kubernetes API replied with an error or could not be reached.
+    Kubernetes(kube::Error),
+    /// An object stored in Kubernetes can't be read, like a ConfigMap without `watcher.json`.
+    InvalidObject(String),
+    /// The worker of the Watcher could not be reached or failed.
+    Worker(String),
+    /// All the ports of the pool are allocated, none is left for a new Watcher.
+    NoIngestPort,
+}
+
+impl ApiError {
+    /// Error getting an object of a Watcher, a missing object means the Watcher doesn't exist.
+    pub fn watcher(err: kube::Error) -> Self {
+        match err {
+            kube::Error::Api(ref response) if response.code == 404 => {
+                ApiError::NotFound("Watcher does not exist")
+            }
+            err => ApiError::Kubernetes(err),
+        }
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::NotRunning => StatusCode::NOT_ACCEPTABLE,
+            ApiError::Kubernetes(kube::Error::Api(response)) => match response.code {
+                404 => StatusCode::NOT_FOUND,
+                // Changed concurrently
+                409 => StatusCode::CONFLICT,
+                429 | 500..=599 => StatusCode::SERVICE_UNAVAILABLE,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            // Connection errors, timeouts..
+            ApiError::Kubernetes(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::InvalidObject(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::Worker(_) => StatusCode::EXPECTATION_FAILED,
+            ApiError::NoIngestPort => StatusCode::SERVICE_UNAVAILABLE,
+        }
+    }
+
+    /// Short identifier of the error, for the clients to tell them apart.
+    pub fn reason(&self) -> &'static str {
+        match self {
+            ApiError::NotFound(_) => "not_found",
+            ApiError::NotRunning => "not_running",
+            ApiError::Kubernetes(_) => match self.status_code() {
+                StatusCode::NOT_FOUND => "not_found",
+                StatusCode::CONFLICT => "conflict",
+                StatusCode::SERVICE_UNAVAILABLE => "kubernetes_unavailable",
+                _ => "kubernetes_error",
+            },
+            ApiError::InvalidObject(_) => "invalid_object",
+            ApiError::Worker(_) => "worker_unreachable",
+            ApiError::NoIngestPort => "no_ingest_port",
+        }
+    }
+
+    /// Whether the same request may succeed later, without any change.
+    pub fn retriable(&self) -> bool {
+        match self {
+            ApiError::Kubernetes(_) => matches!(
+                self.status_code(),
+                StatusCode::CONFLICT | StatusCode::SERVICE_UNAVAILABLE
+            ),
+            ApiError::Worker(_) => true,
+            _ => false,
+        }
+    }
+
+    pub fn body(&self) -> serde_json::Value {
+        json!({
+            "message": self.to_string(),
+            "reason": self.reason(),
+            "retriable": self.retriable(),
+        })
+    }
+
+    /// Reply with the error, for the handlers replying with JSON.
+    pub fn reply(self) -> reply::WithStatus<reply::Json> {
+        if self.status_code().is_server_error() {
+            log::error!("{:?}", self);
+        }
+        reply::with_status(reply::json(&self.body()), self.status_code())
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::NotFound(message) => write!(f, "{}", message),
+            ApiError::NotRunning => write!(f, "The Watcher is not running"),
+            ApiError::Kubernetes(err) => write!(f, "Error while calling Kubernetes API: {}", err),
+            ApiError::InvalidObject(message) => write!(f, "{}", message),
+            ApiError::Worker(message) => write!(f, "{}", message),
+            ApiError::NoIngestPort => write!(f, "No ingest port is left to allocate"),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl From<kube::Error> for ApiError {
+    fn from(err: kube::Error) -> Self {
+        ApiError::Kubernetes(err)
+    }
+}
+
+impl Reply for ApiError {
+    fn into_response(self) -> reply::Response {
+        self.reply().into_response()
+    }
+}
+
+/// Replies with the result of the handler, or with its error.
+pub fn replied<R: Reply>(result: Result<R, ApiError>) -> reply::Response {
+    match result {
+        Ok(reply) => reply.into_response(),
+        Err(err) => err.into_response(),
+    }
+}
@@ -1,22 +1,54 @@
-use crate::{auth, handlers};
-use hawkeye_core::models::Watcher;
+use crate::cache::Cache;
+use crate::{audit, auth, handlers, slates};
+use hawkeye_core::models::{Watcher, WatcherUpdate};
 use kube::Client;
 use serde::Serialize;
+use std::collections::HashMap;
 use warp::hyper::StatusCode;
 use warp::Filter;
 
 /// API root for v1
 pub fn v1(
     client: Client,
+    cache: Cache,
 ) -> impl Filter<Extract = impl warp::Reply, Error = std::convert::Infallible> + Clone {
-    watchers_list(client.clone())
+    watchers_list(client.clone(), cache.clone())
+        .or(fleet_status(client.clone()))
         .or(watcher_create(client.clone()))
+        .or(fleet_export(client.clone()))
+        .or(fleet_import(client.clone()))
         .or(watcher_get(client.clone()))
+        .or(watcher_update(client.clone()))
         .or(watcher_delete(client.clone()))
         .or(watcher_upgrade(client.clone()))
+        .or(watchers_start(client.clone()))
+        .or(watchers_stop(client.clone()))
+        .or(watchers_upgrade(client.clone()))
+        .or(maintenance_get(client.clone()))
+        .or(maintenance_enter(client.clone()))
+        .or(maintenance_exit(client.clone()))
         .or(watcher_start(client.clone()))
         .or(watcher_stop(client.clone()))
+        .or(watcher_restart(client.clone()))
+        .or(watcher_revisions(client.clone()))
+        .or(watcher_rollback(client.clone()))
+        .or(watcher_audit(client.clone()))
+        .or(job_get(client.clone()))
         .or(watcher_video_frame(client.clone()))
+        .or(watcher_state(client.clone()))
+        .or(watcher_simulate(client.clone()))
+        .or(watcher_log_level(client.clone()))
+        .or(watcher_validate_slate(client.clone()))
+        .or(groups_list(client.clone()))
+        .or(group_watchers(client.clone(), cache))
+        .or(group_start(client.clone()))
+        .or(group_stop(client.clone()))
+        .or(group_upgrade(client.clone()))
+        .or(slate_upload())
+        .or(slates_list())
+        .or(slate_get())
+        .or(openapi())
+        .or(docs())
         .or(healthcheck(client))
         .recover(handle_rejection)
 }
@@ -24,14 +56,27 @@ pub fn v1(
 /// GET /v1/watchers
 pub fn watchers_list(
     client: Client,
+    cache: Cache,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("v1" / "watchers")
         .and(auth::verify())
         .and(warp::get())
+        .and(with_cache(cache))
         .and(with_client(client))
         .and_then(handlers::list_watchers)
 }
 
+/// GET /v1/status
+pub fn fleet_status(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "status")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::fleet_status)
+}
+
 /// POST /v1/watchers
 pub fn watcher_create(
     client: Client,
@@ -40,10 +85,45 @@ pub fn watcher_create(
         .and(auth::verify())
         .and(warp::post())
         .and(json_body())
+        .and(auth::is_admin())
+        .and(warp::query::<handlers::JobOptions>())
+        .and(audit::caller())
         .and(with_client(client))
         .and_then(handlers::create_watcher)
 }
 
+/// GET /v1/export
+pub fn fleet_export(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "export")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(warp::query::<handlers::ExportOptions>())
+        .and(with_client(client))
+        .and_then(handlers::export_watchers)
+}
+
+/// POST /v1/import
+/// POST /v1/watchers/import
+pub fn fleet_import(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "import")
+        .or(warp::path!("v1" / "watchers" / "import"))
+        .unify()
+        .and(auth::verify())
+        .and(warp::post())
+        // Room for a few hundred watchers, in JSON or YAML
+        .and(warp::body::content_length_limit(1024 * 1024))
+        .and(warp::body::bytes())
+        .and(warp::query::<handlers::FleetImportOptions>())
+        .and(auth::is_admin())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::import_fleet)
+}
+
 /// GET /v1/watchers/{id}
 pub fn watcher_get(
     client: Client,
@@ -55,6 +135,22 @@ pub fn watcher_get(
         .and_then(handlers::get_watcher)
 }
 
+/// PATCH /v1/watchers/{id}
+pub fn watcher_update(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String)
+        .and(auth::verify())
+        .and(warp::patch())
+        .and(update_body())
+        .and(auth::is_admin())
+        .and(warp::query::<handlers::JobOptions>())
+        .and(warp::header::optional::<String>("if-match"))
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::update_watcher)
+}
+
 /// DELETE /v1/watchers/{id}
 pub fn watcher_delete(
     client: Client,
@@ -62,6 +158,7 @@ pub fn watcher_delete(
     warp::path!("v1" / "watchers" / String)
         .and(auth::verify())
         .and(warp::delete())
+        .and(audit::caller())
         .and(with_client(client))
         .and_then(handlers::delete_watcher)
 }
@@ -73,10 +170,88 @@ pub fn watcher_upgrade(
     warp::path!("v1" / "watchers" / String / "upgrade")
         .and(auth::verify())
         .and(warp::post())
+        .and(auth::is_admin())
+        .and(warp::query::<handlers::UpgradeOptions>())
+        .and(audit::caller())
         .and(with_client(client))
         .and_then(handlers::upgrade_watcher)
 }
 
+/// POST /v1/watchers/start
+pub fn watchers_start(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / "start")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(selector_body())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::start_watchers)
+}
+
+/// POST /v1/watchers/stop
+pub fn watchers_stop(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / "stop")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(selector_body())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::stop_watchers)
+}
+
+/// POST /v1/watchers/upgrade
+pub fn watchers_upgrade(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / "upgrade")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(fleet_upgrade_body())
+        .and(warp::query::<handlers::JobOptions>())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::upgrade_watchers)
+}
+
+/// GET /v1/maintenance
+pub fn maintenance_get(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "maintenance")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::get_maintenance)
+}
+
+/// POST /v1/maintenance/enter
+pub fn maintenance_enter(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "maintenance" / "enter")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::enter_maintenance)
+}
+
+/// POST /v1/maintenance/exit
+pub fn maintenance_exit(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "maintenance" / "exit")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::exit_maintenance)
+}
+
 /// POST /v1/watchers/{id}/start
 pub fn watcher_start(
     client: Client,
@@ -84,6 +259,7 @@ pub fn watcher_start(
     warp::path!("v1" / "watchers" / String / "start")
         .and(auth::verify())
         .and(warp::post())
+        .and(audit::caller())
         .and(with_client(client))
         .and_then(handlers::start_watcher)
 }
@@ -95,20 +271,171 @@ pub fn watcher_stop(
     warp::path!("v1" / "watchers" / String / "stop")
         .and(auth::verify())
         .and(warp::post())
+        .and(audit::caller())
         .and(with_client(client))
         .and_then(handlers::stop_watcher)
 }
 
+/// POST /v1/watchers/{id}/restart
+pub fn watcher_restart(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "restart")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::restart_watcher)
+}
+
+/// GET /v1/watchers/{id}/revisions
+pub fn watcher_revisions(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "revisions")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::get_watcher_revisions)
+}
+
+/// POST /v1/watchers/{id}/rollback/{revision}
+pub fn watcher_rollback(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "rollback" / u32)
+        .and(auth::verify())
+        .and(warp::post())
+        .and(auth::is_admin())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::rollback_watcher)
+}
+
+/// GET /v1/watchers/{id}/audit
+pub fn watcher_audit(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "audit")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::get_watcher_audit)
+}
+
 /// GET /v1/watchers/{id}/video-frame
 pub fn watcher_video_frame(
     client: Client,
 ) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
     warp::path!("v1" / "watchers" / String / "video-frame")
         .and(warp::get())
+        .and(warp::query::<HashMap<String, String>>())
         .and(with_client(client))
         .and_then(handlers::get_video_frame)
 }
 
+/// GET /v1/watchers/{id}/state
+pub fn watcher_state(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "state")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::get_watcher_state)
+}
+
+/// POST /v1/watchers/{id}/simulate
+pub fn watcher_simulate(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "simulate")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(simulation_body())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::simulate_transition)
+}
+
+/// PUT /v1/watchers/{id}/log-level
+pub fn watcher_log_level(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "log-level")
+        .and(auth::verify())
+        .and(warp::put())
+        .and(log_level_body())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::set_log_level)
+}
+
+/// GET /v1/jobs/{id}
+pub fn job_get(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "jobs" / String)
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::get_job)
+}
+
+/// POST /v1/watchers/{id}/validate-slate
+pub fn watcher_validate_slate(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "watchers" / String / "validate-slate")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(slate_validation_body())
+        .and(with_client(client))
+        .and_then(handlers::validate_slate)
+}
+
+/// POST /v1/slates
+pub fn slate_upload() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "slates")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(warp::header::optional::<String>("content-type"))
+        .and(warp::body::content_length_limit(slates::MAX_SIZE))
+        .and(warp::body::bytes())
+        .and_then(handlers::upload_slate)
+}
+
+/// GET /v1/slates
+pub fn slates_list() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "slates")
+        .and(auth::verify())
+        .and(warp::get())
+        .and_then(handlers::list_slates)
+}
+
+/// GET /v1/slates/{id}
+///
+/// Not authenticated, the workers download the slates they reference from it.
+pub fn slate_get() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "slates" / String)
+        .and(warp::get())
+        .and_then(handlers::get_slate)
+}
+
+/// GET /v1/openapi.json
+pub fn openapi() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "openapi.json")
+        .and(warp::get())
+        .and_then(handlers::openapi)
+}
+
+/// GET /v1/docs
+pub fn docs() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "docs")
+        .and(warp::get())
+        .and_then(handlers::docs)
+}
+
 /// GET /healthcheck
 pub fn healthcheck(
     client: Client,
@@ -125,12 +452,113 @@ fn with_client(
     warp::any().map(move || client.clone())
 }
 
+fn with_cache(
+    cache: Cache,
+) -> impl Filter<Extract = (Cache,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || cache.clone())
+}
+
 fn json_body() -> impl Filter<Extract = (Watcher,), Error = warp::Rejection> + Clone {
     // When accepting a body, we want a JSON body
     // (and to reject huge payloads)...
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
 
+fn update_body() -> impl Filter<Extract = (WatcherUpdate,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+/// GET /v1/groups
+pub fn groups_list(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "groups")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_client(client))
+        .and_then(handlers::list_groups)
+}
+
+/// GET /v1/groups/{group}/watchers
+pub fn group_watchers(
+    client: Client,
+    cache: Cache,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "groups" / String / "watchers")
+        .and(auth::verify())
+        .and(warp::get())
+        .and(with_cache(cache))
+        .and(with_client(client))
+        .and_then(handlers::list_group_watchers)
+}
+
+/// POST /v1/groups/{group}/start
+pub fn group_start(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "groups" / String / "start")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::start_group)
+}
+
+/// POST /v1/groups/{group}/stop
+pub fn group_stop(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "groups" / String / "stop")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::stop_group)
+}
+
+/// POST /v1/groups/{group}/upgrade
+pub fn group_upgrade(
+    client: Client,
+) -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    warp::path!("v1" / "groups" / String / "upgrade")
+        .and(auth::verify())
+        .and(warp::post())
+        .and(group_upgrade_body())
+        .and(audit::caller())
+        .and(with_client(client))
+        .and_then(handlers::upgrade_group)
+}
+
+fn selector_body(
+) -> impl Filter<Extract = (handlers::WatcherSelector,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn fleet_upgrade_body(
+) -> impl Filter<Extract = (handlers::FleetUpgrade,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn group_upgrade_body(
+) -> impl Filter<Extract = (handlers::GroupUpgrade,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn log_level_body() -> impl Filter<Extract = (handlers::LogLevel,), Error = warp::Rejection> + Clone
+{
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn simulation_body(
+) -> impl Filter<Extract = (handlers::TransitionSimulation,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn slate_validation_body(
+) -> impl Filter<Extract = (handlers::SlateValidation,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
 /// An API error serializable to JSON.
 #[derive(Serialize)]
 struct ErrorMessage {
@@ -165,3 +593,60 @@ async fn handle_rejection(
     let json = warp::reply::json(&ErrorMessage { message });
     Ok(warp::reply::with_status(json, code))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    /// Path with its parameters unnamed, `{id}` and `{watcher_id}` are the same parameter.
+    fn normalize(path: &str) -> String {
+        path.split('/')
+            .map(|segment| {
+                if segment.starts_with('{') {
+                    "{}"
+                } else {
+                    segment
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+
+    #[test]
+    fn routes_are_documented() {
+        let document: serde_json::Value =
+            serde_yaml::from_str(include_str!("../../api.yaml")).expect("api.yaml is not valid");
+        let documented: HashSet<(String, String)> = document["paths"]
+            .as_object()
+            .expect("api.yaml has no paths")
+            .iter()
+            .flat_map(|(path, operations)| {
+                operations
+                    .as_object()
+                    .into_iter()
+                    .flat_map(|operations| operations.keys())
+                    .map(move |method| (method.to_uppercase(), normalize(path)))
+            })
+            .collect();
+
+        // Each route is introduced by its method and path, like `/// GET /v1/watchers`
+        let routes: Vec<(String, String)> = include_str!("filters.rs")
+            .lines()
+            .filter_map(|line| line.strip_prefix("/// "))
+            .filter_map(|route| route.split_once(' '))
+            .filter(|(method, path)| {
+                ["GET", "POST", "PUT", "PATCH", "DELETE"].contains(method) && path.starts_with('/')
+            })
+            .map(|(method, path)| (method.to_string(), normalize(path)))
+            .collect();
+        assert!(!routes.is_empty());
+        for route in routes.iter() {
+            assert!(
+                documented.contains(route),
+                "{} {} is not in api.yaml",
+                route.0,
+                route.1
+            );
+        }
+    }
+}
@@ -1,557 +1,2772 @@
-use crate::config::{CALL_WATCHER_TIMEOUT, NAMESPACE};
+use crate::audit::{self, Caller};
+use crate::cache::{Cache, WatcherObjects};
+use crate::config::{CALL_WATCHER_TIMEOUT, CONTROL_TOKEN, NAMESPACE};
+use crate::controller;
+use crate::crd::WatcherResource;
+use crate::errors::{replied, ApiError};
+use crate::jobs;
+use crate::maintenance::{self, Maintenance};
+use crate::ports;
+use crate::revisions;
+use crate::slates;
 use crate::templates;
 use crate::templates::container_spec;
-use hawkeye_core::models::{Status, Watcher};
+use crate::webhooks::{self, WebhookEvent};
+use chrono::Utc;
+use futures::StreamExt;
+use hawkeye_core::models::{
+    is_label_value, FieldError, SecretValue, Status, ValidationErrors, VideoMode, Watcher,
+    WatcherUpdate, SLATE_URL_SCHEMES,
+};
 use k8s_openapi::api::apps::v1::Deployment;
 use k8s_openapi::api::core::v1::{ConfigMap, Pod, Service};
+use k8s_openapi::api::networking::v1::NetworkPolicy;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use kube::api::{DeleteParams, ListParams, Patch, PatchParams, PostParams};
 use kube::{Api, Client};
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::convert::Infallible;
 use std::time::Duration;
+use tracing::instrument;
 use uuid::Uuid;
-use warp::http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use warp::http::header::{CACHE_CONTROL, CONTENT_TYPE, ETAG};
 use warp::http::{HeaderValue, StatusCode};
+use warp::hyper::body::Bytes;
 use warp::hyper::Body;
 use warp::reply;
+use warp::Reply;
 
-pub async fn list_watchers(client: Client) -> Result<impl warp::Reply, Infallible> {
+#[instrument(skip(cache, client))]
+pub async fn list_watchers(cache: Cache, client: Client) -> Result<impl warp::Reply, Infallible> {
+    list_watchers_of(None, cache, client).await
+}
+
+/// The objects of all the Watchers, from the cache when it's up to date.
+async fn watcher_objects(cache: &Cache, client: Client) -> Result<WatcherObjects, kube::Error> {
+    if let Some(objects) = cache.objects() {
+        return Ok(objects);
+    }
     let lp = ListParams::default()
         .labels("app=hawkeye,watcher_id")
         .timeout(10);
-
-    // Get all K8S deployments we know, we want to return the status of each watcher
+    let resources_client: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
     let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let deployments = deployments_client.list(&lp).await.unwrap();
+    let services_client: Api<Service> = Api::namespaced(client, &NAMESPACE);
+    let (resources, deployments, services) = futures::try_join!(
+        resources_client.list(&ListParams::default().timeout(10)),
+        deployments_client.list(&lp),
+        services_client.list(&lp)
+    )?;
+    Ok(WatcherObjects {
+        resources: resources.items,
+        deployments: deployments.items,
+        services: services.items,
+    })
+}
+
+/// The Watchers, or the ones of the `group`, with their status.
+async fn list_watchers_of(
+    group: Option<&str>,
+    cache: Cache,
+    client: Client,
+) -> Result<reply::WithStatus<reply::Json>, Infallible> {
+    // The resources, Deployments and Services of all watchers are fetched at once and joined by
+    // watcher id, instead of a request per watcher
+    let objects = match watcher_objects(&cache, client).await {
+        Ok(objects) => objects,
+        Err(err) => return Ok(ApiError::from(err).reply()),
+    };
+
+    // Status of each watcher
     let mut deployments_index = HashMap::new();
-    for deploy in deployments.items {
-        if let Some(watcher_id) = deploy.metadata.labels.as_ref().unwrap().get("watcher_id") {
+    for deploy in objects.deployments {
+        if let Some(watcher_id) = deploy
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+        {
             deployments_index.insert(watcher_id.clone(), deploy.get_watcher_status());
         }
     }
 
-    let config_maps_client: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let config_maps = config_maps_client.list(&lp).await.unwrap();
+    // Address of the LoadBalancer of each watcher
+    let mut services_index = HashMap::new();
+    for service in objects.services {
+        let watcher_id = service
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+            .cloned();
+        if let Some(watcher_id) = watcher_id {
+            services_index.insert(watcher_id, service.ingest_address());
+        }
+    }
 
     let mut watchers: Vec<Watcher> = Vec::new();
-    for config in config_maps.items {
-        let data = config.data.unwrap();
-        let mut watcher: Watcher = serde_json::from_str(data.get("watcher.json").unwrap()).unwrap();
-        let calculated_status = if let Some(status) =
-            deployments_index.get(watcher.id.as_ref().unwrap_or(&"undefined".to_string()))
-        {
+    for resource in objects.resources {
+        if group.is_some() && resource.spec.group.as_deref() != group {
+            continue;
+        }
+        let mut watcher = controller::spec(&resource);
+        let watcher_id = watcher
+            .id
+            .clone()
+            .unwrap_or_else(|| "undefined".to_string());
+        let calculated_status = if let Some(status) = deployments_index.get(&watcher_id) {
             *status
         } else {
             Status::Error
         };
         watcher.status = Some(calculated_status);
-        // TODO: Comes from the service
-        watcher.source.ingest_ip = None;
+        // Like in `get_watcher`, comes from the Service
+        watcher.source.ingest_ip = if calculated_status != Status::Error {
+            services_index.remove(&watcher_id).flatten()
+        } else {
+            None
+        };
+        set_expires_in(&mut watcher);
         watchers.push(watcher);
     }
+    // Listed in the same order from the cache, which has none, as from Kubernetes
+    watchers.sort_by(|a, b| a.id.cmp(&b.id));
 
-    Ok(warp::reply::json(&watchers))
+    Ok(reply::with_status(reply::json(&watchers), StatusCode::OK))
 }
 
-pub async fn create_watcher(
-    mut watcher: Watcher,
-    client: Client,
-) -> Result<impl warp::Reply, Infallible> {
-    log::debug!("v1.create_watcher: {:?}", watcher);
+/// Summary of all the Watchers: how many are in each status, why the ones in error are, and the
+/// ingest ports in use, for dashboards and health monitors.
+#[instrument(skip(client))]
+pub async fn fleet_status(client: Client) -> Result<impl warp::Reply, Infallible> {
+    let lp = ListParams::default()
+        .labels("app=hawkeye,watcher_id")
+        .timeout(10);
+    let resources_client: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let pods_client: Api<Pod> = Api::namespaced(client, &NAMESPACE);
+    let (resources, deployments, pods) = match futures::try_join!(
+        resources_client.list(&ListParams::default().timeout(10)),
+        deployments_client.list(&lp),
+        pods_client.list(&lp)
+    ) {
+        Ok(lists) => lists,
+        Err(err) => {
+            log::error!("Could not list the watchers: {:?}", err);
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not list the watchers"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
 
-    let new_id = Uuid::new_v4().to_string();
-    watcher.id = Some(new_id.clone());
-    let pp = PostParams::default();
+    let mut deployments_index = HashMap::new();
+    for deploy in deployments.items {
+        let watcher_id = deploy
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+            .cloned();
+        if let Some(watcher_id) = watcher_id {
+            deployments_index.insert(watcher_id, deploy);
+        }
+    }
+    let mut waiting_index = waiting_reasons(pods.items);
 
-    // 1. Create ConfigMap
-    log::debug!("Creating ConfigMap instance");
-    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let config_file_contents = serde_json::to_string(&watcher).unwrap();
-    let config = templates::build_configmap(&new_id, &config_file_contents);
-    // TODO: Handle errors
-    let _ = config_maps.create(&pp, &config).await.unwrap();
+    let mut statuses = Vec::new();
+    let mut errors = Vec::new();
+    let mut ports = Vec::new();
+    for resource in resources.items {
+        let watcher_id = resource.id().to_string();
+        ports.push(resource.spec.source.ingest_port);
+        let status = match deployments_index.get(&watcher_id) {
+            Some(deploy) => deploy.get_watcher_status(),
+            None => Status::Error,
+        };
+        statuses.push(status);
+        if status == Status::Error {
+            let reason = match (
+                deployments_index.get(&watcher_id),
+                waiting_index.remove(&watcher_id),
+            ) {
+                (None, _) => "The Deployment of the Watcher does not exist".to_string(),
+                (Some(_), Some(reason)) if !reason.is_empty() => reason,
+                (Some(_), _) => "The Deployment of the Watcher is in an unknown state".to_string(),
+            };
+            errors.push(json!({"id": watcher_id, "reason": reason}));
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
 
-    // 2. Create Deployment with replicas=0
-    log::debug!("Creating Deployment instance");
-    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let deploy = templates::build_deployment(&new_id, watcher.source.ingest_port);
-    // TODO: Handle errors
-    let _ = deployments.create(&pp, &deploy).await.unwrap();
+    let count = |status: Status| statuses.iter().filter(|&&s| s == status).count();
+    Ok(reply::with_status(
+        reply::json(&json!({
+            "total": statuses.len(),
+            "by_status": {
+                "running": count(Status::Running),
+                "pending": count(Status::Pending),
+                "ready": count(Status::Ready),
+                "error": count(Status::Error),
+            },
+            "errors": errors,
+            "ports_in_use": ports,
+        })),
+        StatusCode::OK,
+    ))
+}
 
-    // 3. Create Service/LoadBalancer
-    log::debug!("Creating Service instance");
-    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
-    let svc = templates::build_service(&new_id, watcher.source.ingest_port);
-    // TODO: Handle errors
-    let _ = services.create(&pp, &svc).await.unwrap();
+/// Why the container of the worker is not running, like `CrashLoopBackOff`, by Watcher id.
+pub(crate) fn waiting_reasons(pods: Vec<Pod>) -> HashMap<String, String> {
+    let mut reasons = HashMap::new();
+    for pod in pods {
+        let watcher_id = pod
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+            .cloned();
+        let waiting = pod
+            .status
+            .and_then(|status| status.container_statuses)
+            .and_then(|statuses| statuses.into_iter().next())
+            .and_then(|status| status.state)
+            .and_then(|state| state.waiting);
+        if let (Some(watcher_id), Some(waiting)) = (watcher_id, waiting) {
+            let reason = match (waiting.reason, waiting.message) {
+                (Some(reason), Some(message)) => format!("{}: {}", reason, message),
+                (reason, message) => reason.or(message).unwrap_or_default(),
+            };
+            reasons.insert(watcher_id, reason);
+        }
+    }
+    reasons
+}
 
-    watcher.status = Some(Status::Pending);
+/// Creates the Kubernetes objects of a Watcher: its `Watcher` resource, then without waiting for
+/// the controller its ConfigMap, its Deployment with no replicas and its Service. The objects
+/// already created are deleted when one fails. A Watcher without an ingest port is allocated one.
+async fn create_objects(id: &str, watcher: &mut Watcher, client: Client) -> Result<(), ApiError> {
+    // Assigned once the LoadBalancer is provisioned
     watcher.source.ingest_ip = None;
+    watcher.expires_in_seconds = None;
+    if watcher.source.ingest_port == 0 {
+        watcher.source.ingest_port = ports::allocate(client.clone(), id).await?;
+    }
 
-    Ok(reply::with_status(
-        reply::json(&watcher),
-        StatusCode::CREATED,
-    ))
+    log::debug!("Creating Watcher resource");
+    let resources: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let resource = WatcherResource::new(id, definition(watcher.clone()));
+    let resource = match resources.create(&PostParams::default(), &resource).await {
+        Ok(resource) => resource,
+        Err(err) => {
+            // Fails when the Watcher exists, which is kept as is with its port
+            if !matches!(&err, kube::Error::Api(response) if response.code == 409) {
+                ports::release(client, id).await;
+            }
+            return Err(err.into());
+        }
+    };
+
+    if let Err(err) = controller::apply(&resource, client.clone()).await {
+        log::error!("Could not create watcher {}: {:?}", id, err);
+        delete_watcher_objects(id, client).await;
+        return Err(err);
+    }
+    Ok(())
 }
 
-pub async fn upgrade_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    log::debug!("v1.upgrade_watcher: {}", id);
-    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let deployment = match deployments.get(&templates::deployment_name(&id)).await {
-        Ok(d) => d,
-        Err(_) => {
-            return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
-            ))
-        }
+/// Validates the Watcher like `Watcher::validate`, and the Kubernetes objects of its scheduling
+/// too, which would otherwise be left out of its Deployment.
+pub(crate) fn validate(watcher: &Watcher) -> Result<(), ValidationErrors> {
+    let mut errors = match watcher.validate() {
+        Ok(()) => Vec::new(),
+        Err(errors) => errors.0,
     };
+    let affinity = watcher
+        .scheduling
+        .as_ref()
+        .and_then(|scheduling| scheduling.affinity.clone());
+    if let Some(Err(err)) = affinity.map(templates::affinity) {
+        let message = format!("Invalid affinity: {}", err);
+        errors.push(field_error("scheduling.affinity", message));
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationErrors(errors))
+    }
+}
 
-    // We use the ConfigMap as source of truth for what are the watchers we have
-    let config_maps_client: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let config_map = match config_maps_client
-        .get(&templates::configmap_name(&id))
+/// Seconds left until the Watcher expires, shown in the responses.
+fn set_expires_in(watcher: &mut Watcher) {
+    watcher.expires_in_seconds = watcher
+        .expiration()
+        .map(|expiration| (expiration - Utc::now()).num_seconds().max(0));
+}
+
+/// Whether the Watcher expired, it can't be started until its `expires_at` is extended.
+async fn watcher_expired(id: &str, client: Client) -> bool {
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let watcher: Option<Watcher> = config_maps
+        .get(&templates::configmap_name(id))
         .await
-    {
-        Ok(c) => c,
-        Err(_) => {
-            return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
-            ))
-        }
+        .ok()
+        .and_then(|config_map| config_map.data)
+        .and_then(|mut data| data.remove("watcher.json"))
+        .and_then(|contents| serde_json::from_str(&contents).ok());
+    matches!(watcher, Some(watcher) if watcher.expired(Utc::now()))
+}
+
+/// The Deployment of the Watcher.
+async fn get_deployment(id: &str, client: Client) -> Result<Deployment, ApiError> {
+    let deployments: Api<Deployment> = Api::namespaced(client, &NAMESPACE);
+    deployments
+        .get(&templates::deployment_name(id))
+        .await
+        .map_err(ApiError::watcher)
+}
+
+/// The `Watcher` resource of the Watcher, the source of truth for its definition.
+async fn get_resource(id: &str, client: Client) -> Result<WatcherResource, ApiError> {
+    let resources: Api<WatcherResource> = Api::namespaced(client, &NAMESPACE);
+    resources.get(id).await.map_err(ApiError::watcher)
+}
+
+/// The configuration of the Watcher kept in its ConfigMap, as stored and parsed.
+pub(crate) fn stored_watcher(config_map: &ConfigMap) -> Result<(String, Watcher), ApiError> {
+    let name = config_map.metadata.name.as_deref().unwrap_or_default();
+    let contents = config_map
+        .data
+        .as_ref()
+        .and_then(|data| data.get("watcher.json"))
+        .ok_or_else(|| {
+            ApiError::InvalidObject(format!("ConfigMap {} without watcher.json", name))
+        })?;
+    let watcher = serde_json::from_str(contents).map_err(|err| {
+        ApiError::InvalidObject(format!(
+            "Invalid watcher.json in ConfigMap {}: {}",
+            name, err
+        ))
+    })?;
+    Ok((contents.clone(), watcher))
+}
+
+/// Deletes the Kubernetes objects of a Watcher, returns whether it existed.
+pub(crate) async fn delete_watcher_objects(id: &str, client: Client) -> bool {
+    let dp = DeleteParams::default();
+
+    // Kubernetes deletes the objects it owns too, they're deleted here all the same
+    let resources: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let resource_deleted = resources.delete(id, &dp).await.is_ok();
+
+    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let _ = deployments_client
+        .delete(&templates::deployment_name(id), &dp)
+        .await;
+
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let config_map_deleted = config_maps
+        .delete(&templates::configmap_name(id), &dp)
+        .await
+        .is_ok();
+
+    revisions::delete(client.clone(), id).await;
+    ports::release(client.clone(), id).await;
+
+    // Missing when the worker isn't exposed
+    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+    let _ = services.delete(&templates::service_name(id), &dp).await;
+
+    // Missing when the feed is allowed from anywhere
+    let network_policies: Api<NetworkPolicy> = Api::namespaced(client, &NAMESPACE);
+    let _ = network_policies
+        .delete(&templates::network_policy_name(id), &dp)
+        .await;
+
+    resource_deleted || config_map_deleted
+}
+
+/// All the Watchers of the cluster, the document exported and imported to recover it.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Fleet {
+    pub watchers: Vec<Watcher>,
+}
+
+/// Format of the exported document, `json` (default) or `yaml`.
+#[derive(Deserialize, Debug)]
+pub struct ExportOptions {
+    pub format: Option<String>,
+}
+
+/// Options of the import of a fleet.
+#[derive(Deserialize, Debug)]
+pub struct FleetImportOptions {
+    /// Only returns the changes the import would make.
+    pub dry_run: Option<bool>,
+    /// Deletes the Watchers not in the document.
+    pub prune: Option<bool>,
+}
+
+/// The fleet document of an import, or the array of its Watchers.
+fn import_document(contents: &[u8]) -> Result<Fleet, serde_yaml::Error> {
+    // JSON documents are YAML documents too
+    serde_yaml::from_slice(contents).or_else(|err| {
+        serde_yaml::from_slice(contents)
+            .map(|watchers| Fleet { watchers })
+            .map_err(|_| err)
+    })
+}
+
+/// Definition of the Watcher, without the state of the cluster.
+pub(crate) fn definition(mut watcher: Watcher) -> Watcher {
+    watcher.status = None;
+    watcher.source.ingest_ip = None;
+    watcher.expires_in_seconds = None;
+    watcher
+}
+
+/// Definitions of all the Watchers, from their `Watcher` resources, by id.
+async fn defined_watchers(client: Client) -> Result<HashMap<String, Watcher>, kube::Error> {
+    let resources: Api<WatcherResource> = Api::namespaced(client, &NAMESPACE);
+    let lp = ListParams::default().timeout(10);
+    Ok(resources
+        .list(&lp)
+        .await?
+        .items
+        .iter()
+        .map(|resource| (resource.id().to_string(), controller::spec(resource)))
+        .collect())
+}
+
+/// The definitions of all the Watchers, without their status, in a single document.
+#[instrument(skip(client))]
+pub async fn export_watchers(
+    options: ExportOptions,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let mut watchers: Vec<Watcher> = match defined_watchers(client).await {
+        Ok(defined) => defined.into_values().collect(),
+        Err(err) => return Ok(ApiError::from(err).into_response()),
     };
+    watchers.sort_by(|a, b| a.id.cmp(&b.id));
+    let fleet = Fleet { watchers };
 
-    let mut watcher: Watcher =
-        serde_json::from_str(config_map.data.unwrap().get("watcher.json").unwrap()).unwrap();
-    let watcher_status = deployment.get_watcher_status();
-    if watcher_status != Status::Ready {
-        return Ok(reply::with_status(
-            reply::json(
-                &json!({"message": "The Watcher must be stopped before the upgrade can be applied"}),
-            ),
+    match options.format.as_deref() {
+        None | Some("json") => {
+            Ok(reply::with_status(reply::json(&fleet), StatusCode::OK).into_response())
+        }
+        Some("yaml") => {
+            let mut resp =
+                warp::reply::Response::new(serde_yaml::to_string(&fleet).unwrap().into());
+            resp.headers_mut()
+                .insert(CONTENT_TYPE, HeaderValue::from_static("application/yaml"));
+            Ok(resp)
+        }
+        Some(format) => Ok(reply::with_status(
+            reply::json(&json!({ "message": format!("Unknown format {}", format) })),
             StatusCode::BAD_REQUEST,
-        ));
+        )
+        .into_response()),
     }
-    watcher.status = Some(watcher_status);
+}
 
-    let patch_params = PatchParams::default();
-    let spec_updated = json!({
-        "spec": {
-            "template": {
-                "spec": {
-                    "containers": [
-                        container_spec(&id, watcher.source.ingest_port)
-                    ]
-                }
+/// Change made to the cluster by the import of a fleet.
+enum Reconciliation {
+    Create(Box<Watcher>),
+    Update {
+        watcher: Box<Watcher>,
+        changes: Vec<String>,
+    },
+    Delete(String),
+}
+
+impl Reconciliation {
+    fn operation(&self) -> &'static str {
+        match self {
+            Reconciliation::Create(_) => "create",
+            Reconciliation::Update { .. } => "update",
+            Reconciliation::Delete(_) => "delete",
+        }
+    }
+
+    fn id(&self) -> Option<&str> {
+        match self {
+            Reconciliation::Create(watcher) | Reconciliation::Update { watcher, .. } => {
+                watcher.id.as_deref()
             }
+            Reconciliation::Delete(id) => Some(id),
         }
-    });
+    }
 
-    match deployments
-        .patch(
-            deployment.metadata.name.as_ref().unwrap(),
-            &patch_params,
-            &Patch::Apply(spec_updated),
-        )
-        .await
-    {
-        Ok(_) => Ok(reply::with_status(reply::json(&watcher), StatusCode::OK)),
-        Err(e) => {
-            let msg: String = format!("Error while calling Kubernetes API: {:?}", e);
-            log::error!("{}", msg);
-            let error_body = json!({ "message": msg });
-            return Ok(reply::with_status(
-                reply::json(&error_body),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ));
+    fn describe(&self) -> serde_json::Value {
+        match self {
+            Reconciliation::Update { changes, .. } => json!({
+                "operation": self.operation(),
+                "id": self.id(),
+                "changes": changes,
+            }),
+            _ => json!({
+                "operation": self.operation(),
+                "id": self.id(),
+            }),
+        }
+    }
+
+    /// Gives a new id to a Watcher created without one in the document.
+    fn identified(self) -> Self {
+        match self {
+            Reconciliation::Create(mut watcher) if watcher.id.is_none() => {
+                watcher.id = Some(Uuid::new_v4().to_string());
+                Reconciliation::Create(watcher)
+            }
+            reconciliation => reconciliation,
         }
     }
+
+    /// Applies the change, with its status code and message.
+    async fn apply(self, client: Client) -> (StatusCode, String) {
+        let result = match self {
+            Reconciliation::Create(mut watcher) => {
+                let id = watcher.id.clone().unwrap_or_default();
+                create_objects(&id, &mut watcher, client)
+                    .await
+                    .map(|_| (StatusCode::CREATED, "Watcher created".to_string()))
+            }
+            Reconciliation::Update { watcher, .. } => {
+                let id = watcher.id.clone().unwrap_or_default();
+                replace_configuration(&id, &watcher, None, client)
+                    .await
+                    .map(|_| (StatusCode::OK, "Watcher updated".to_string()))
+            }
+            Reconciliation::Delete(id) => {
+                delete_watcher_objects(&id, client).await;
+                Ok((StatusCode::OK, "Watcher has been deleted".to_string()))
+            }
+        };
+        result.unwrap_or_else(|e| {
+            let msg = format!("Error while calling Kubernetes API: {:?}", e);
+            log::error!("{}", msg);
+            (StatusCode::INTERNAL_SERVER_ERROR, msg)
+        })
+    }
 }
 
-pub async fn get_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    // TODO: searching for a deployment could be a filter in this route
-    let deployment = match deployments_client
-        .get(&templates::deployment_name(&id))
-        .await
-    {
-        Ok(d) => d,
-        Err(_) => {
+/// Releases the ports allocated to the Watchers of an import which are not created.
+async fn release_ports(ids: &[String], client: Client) {
+    for id in ids {
+        ports::release(client.clone(), id).await;
+    }
+}
+
+/// Top level fields of the Watchers with a different value.
+fn changed_fields(previous: &Watcher, watcher: &Watcher) -> Vec<String> {
+    let previous = serde_json::to_value(previous).unwrap();
+    let watcher = serde_json::to_value(watcher).unwrap();
+    let empty = serde_json::Map::new();
+    let previous = previous.as_object().unwrap_or(&empty);
+    let watcher = watcher.as_object().unwrap_or(&empty);
+    let mut fields: Vec<String> = previous
+        .keys()
+        .chain(watcher.keys())
+        .filter(|field| previous.get(*field) != watcher.get(*field))
+        .cloned()
+        .collect();
+    fields.sort();
+    fields.dedup();
+    fields
+}
+
+/// Reconciles the Watchers of the cluster with the fleet document, in JSON or YAML, exported by
+/// `export_watchers`, or with an array of Watchers: the Watchers missing are created, with their id
+/// when they have one, and the ones with a different definition are updated. The ones not in the
+/// document are only deleted with `prune`, and with `dry_run` the changes are only listed.
+///
+/// All the Watchers are validated before any change is made, and the import is rejected when one of
+/// them has the ingest port of a Watcher kept. Like with updates, the running Watchers load their
+/// new configuration when they restart.
+#[instrument(skip(contents, client))]
+pub async fn import_fleet(
+    contents: Bytes,
+    options: FleetImportOptions,
+    admin: bool,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let mut fleet = match import_document(&contents) {
+        Ok(fleet) => fleet,
+        Err(err) => {
             return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
+                reply::json(&json!({ "message": format!("Invalid document: {}", err) })),
+                StatusCode::BAD_REQUEST,
             ))
         }
     };
+    if fleet.watchers.iter().any(admin_only) && !admin {
+        return Ok(admin_only_forbidden());
+    }
 
-    // We use the ConfigMap as source of truth for what are the watchers we have
-    let config_maps_client: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let config_map = match config_maps_client
-        .get(&templates::configmap_name(&id))
-        .await
-    {
-        Ok(c) => c,
-        Err(_) => {
-            return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
-            ))
-        }
+    let mut stored = match defined_watchers(client.clone()).await {
+        Ok(stored) => stored,
+        Err(err) => return Ok(ApiError::from(err).reply()),
     };
 
-    let mut w: Watcher =
-        serde_json::from_str(config_map.data.unwrap().get("watcher.json").unwrap()).unwrap();
-    w.status = Some(deployment.get_watcher_status());
+    let dry_run = options.dry_run.unwrap_or(false);
+    // The Watchers created without an ingest port are given their id and port first, so they're
+    // validated with it, except in a dry run
+    let mut allocated = Vec::new();
+    if !dry_run {
+        for watcher in fleet.watchers.iter_mut() {
+            let exists = matches!(watcher.id.as_ref(), Some(id) if stored.contains_key(id));
+            if watcher.source.ingest_port != 0 || exists {
+                continue;
+            }
+            let id = watcher
+                .id
+                .get_or_insert_with(|| Uuid::new_v4().to_string())
+                .clone();
+            match ports::allocate(client.clone(), &id).await {
+                Ok(port) => watcher.source.ingest_port = port,
+                Err(err) => {
+                    release_ports(&allocated, client).await;
+                    return Ok(err.reply());
+                }
+            }
+            allocated.push(id);
+        }
+    }
 
-    w.status_description = if let Some(Status::Pending) = w.status.as_ref() {
-        // Load more information why it's in pending status
-        // We get the reason the container is waiting, if available
-        let pods_client: Api<Pod> = Api::namespaced(client.clone(), &NAMESPACE);
-        let lp = ListParams::default().labels(&format!("app=hawkeye,watcher_id={}", id));
-        let pods = pods_client.list(&lp).await.unwrap();
-        let status_description = pods
-            .items
-            .first()
-            .map(|p| p.status.as_ref())
-            .flatten()
-            .map(|ps| ps.container_statuses.as_ref())
-            .flatten()
-            .map(|css| css.first())
-            .flatten()
-            .map(|cs| cs.state.as_ref())
-            .flatten()
-            .map(|cs| cs.waiting.as_ref())
-            .flatten()
-            .map(|csw| csw.message.clone())
-            .flatten();
-        log::debug!(
-            "Additional information for the Pending status: {:?}",
-            status_description.as_ref()
-        );
-        status_description
+    let mut invalid = Vec::new();
+    let mut reconciliations = Vec::new();
+    let mut unchanged = Vec::new();
+    let mut ids = std::collections::HashSet::new();
+    let prune = options.prune.unwrap_or(false);
+    // The Watchers kept, not in the document, keep their ports
+    let kept_ports: HashMap<u32, String> = if prune {
+        HashMap::new()
     } else {
-        None
+        let imported: std::collections::HashSet<_> = fleet
+            .watchers
+            .iter()
+            .filter_map(|watcher| watcher.id.as_deref())
+            .collect();
+        stored
+            .iter()
+            .filter(|(id, _)| !imported.contains(id.as_str()))
+            .map(|(id, watcher)| (watcher.source.ingest_port, id.clone()))
+            .collect()
     };
-
-    // Comes from the service
-    w.source.ingest_ip = if w.status != Some(Status::Error) {
-        log::debug!("Getting ingest_ip from Service's LoadBalancer");
-        let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
-        let service = services
-            .get_status(&templates::service_name(&id))
-            .await
-            .unwrap();
-        service
-            .status
-            .as_ref()
-            .map(|s| s.load_balancer.as_ref())
-            .flatten()
-            .map(|lbs| lbs.ingress.as_ref())
-            .flatten()
-            .map(|lbs| lbs.first())
-            .flatten()
-            .map(|lb| lb.clone().hostname.or(lb.clone().ip))
-            .flatten()
+    // Rejected like a Watcher created with the port of another one
+    let conflict = fleet.watchers.iter().find_map(|watcher| {
+        let port = watcher.source.ingest_port;
+        kept_ports
+            .get(&port)
+            .filter(|_| port != 0)
+            .map(|id| (port, id))
+    });
+    if let Some((port, id)) = conflict {
+        release_ports(&allocated, client).await;
+        return Ok(port_conflict(port, id));
+    }
+    let mut ports = HashMap::new();
+    for (index, watcher) in fleet.watchers.into_iter().enumerate() {
+        let watcher = definition(watcher);
+        let mut errors = Vec::new();
+        let port = watcher.source.ingest_port;
+        if let Err(invalid) = validate(&watcher) {
+            // The port of a Watcher created without one is only known once it's allocated, not in
+            // a dry run
+            errors.extend(
+                invalid
+                    .0
+                    .into_iter()
+                    .filter(|error| port != 0 || error.field != "source.ingest_port"),
+            );
+        }
+        if port != 0 {
+            if let Some(other) = ports.insert(port, index) {
+                errors.push(field_error("source.ingest_port", port_used_at(port, other)));
+            }
+        }
+        if let Some(id) = slates::missing(&watcher).await {
+            let field = if watcher.slate_url.contains(&id) {
+                "slate_url"
+            } else {
+                "slate_variants"
+            };
+            errors.push(field_error(
+                field,
+                format!("Slate asset {} does not exist", id),
+            ));
+        }
+        let previous = match watcher.id.as_ref() {
+            Some(id) if !ids.insert(id.clone()) => {
+                let message = format!("Watcher {} is defined more than once", id);
+                errors.push(field_error("id", message));
+                None
+            }
+            Some(id) => stored.remove(id),
+            None => None,
+        };
+        let reconciliation = match previous {
+            None => Some(Reconciliation::Create(Box::new(watcher))),
+            Some(current) => {
+                if current.source != watcher.source {
+                    let message =
+                        "The source of a Watcher can't change, it has to be deleted first";
+                    errors.push(field_error("source", message.to_string()));
+                }
+                let changes = changed_fields(&current, &watcher);
+                if changes.is_empty() {
+                    unchanged.push(watcher.id.clone());
+                    None
+                } else {
+                    Some(Reconciliation::Update {
+                        watcher: Box::new(watcher),
+                        changes,
+                    })
+                }
+            }
+        };
+        if errors.is_empty() {
+            reconciliations.extend(reconciliation);
+        } else {
+            let mut result = validation_body(ValidationErrors(errors));
+            result["index"] = json!(index);
+            invalid.push(result);
+        }
+    }
+    if !invalid.is_empty() {
+        release_ports(&allocated, client).await;
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "message": "Invalid watchers, nothing was changed",
+                "results": invalid,
+            })),
+            StatusCode::UNPROCESSABLE_ENTITY,
+        ));
+    }
+    if prune {
+        let mut deleted: Vec<String> = stored.into_keys().collect();
+        deleted.sort();
+        reconciliations.extend(deleted.into_iter().map(Reconciliation::Delete));
+    }
+
+    if dry_run {
+        let changes: Vec<_> = reconciliations
+            .iter()
+            .map(Reconciliation::describe)
+            .collect();
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "dry_run": true,
+                "changes": changes,
+                "unchanged": unchanged,
+            })),
+            StatusCode::OK,
+        ));
+    }
+
+    let results: Vec<_> = futures::stream::iter(reconciliations)
+        .map(|reconciliation| {
+            let client = client.clone();
+            let caller = caller.clone();
+            async move {
+                let reconciliation = reconciliation.identified();
+                let mut result = reconciliation.describe();
+                let id = reconciliation.id().unwrap_or_default().to_string();
+                let operation = reconciliation.operation();
+                let (status_code, message) = reconciliation.apply(client.clone()).await;
+                audit::record(client, &id, "import", &caller, status_code).await;
+                if status_code.is_success() {
+                    let event = match operation {
+                        "create" => Some(WebhookEvent::WatcherCreated {
+                            watcher_id: id.clone(),
+                        }),
+                        "delete" => Some(WebhookEvent::WatcherDeleted {
+                            watcher_id: id.clone(),
+                        }),
+                        _ => None,
+                    };
+                    if let Some(event) = event {
+                        webhooks::notify(event);
+                    }
+                }
+                result["status_code"] = json!(status_code.as_u16());
+                result["message"] = json!(message);
+                result
+            }
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(reply::with_status(
+        reply::json(&json!({
+            "dry_run": false,
+            "changes": results,
+            "unchanged": unchanged,
+        })),
+        StatusCode::OK,
+    ))
+}
+
+/// Runs the operation in the background with `?async=true`, replying with its job instead of
+/// waiting for it.
+#[derive(Deserialize, Debug)]
+pub struct JobOptions {
+    #[serde(rename = "async")]
+    pub in_background: Option<bool>,
+}
+
+#[instrument(skip(watcher, client))]
+pub async fn create_watcher(
+    mut watcher: Watcher,
+    admin: bool,
+    options: JobOptions,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    log::debug!("v1.create_watcher: {:?}", watcher);
+    if admin_only(&watcher) && !admin {
+        return Ok(admin_only_forbidden().into_response());
+    }
+    // Given its id first, the Watcher is validated with the port allocated to it
+    let id = Uuid::new_v4().to_string();
+    watcher.id = Some(id.clone());
+    let allocate = watcher.source.ingest_port == 0;
+    if allocate {
+        match ports::allocate(client.clone(), &id).await {
+            Ok(port) => watcher.source.ingest_port = port,
+            Err(err) => return Ok(err.into_response()),
+        }
+    }
+    if let Err(rejection) = check_new(&watcher, client.clone()).await {
+        if allocate {
+            ports::release(client, &id).await;
+        }
+        return Ok(rejection);
+    }
+
+    let work = create(watcher, caller.clone(), client.clone());
+    let in_background = options.in_background.unwrap_or(false);
+    Ok(jobs::maybe_spawn(in_background, client, "create", None, caller, work).await)
+}
+
+/// Rejects the Watcher to create when it is not valid, its slates are missing or another Watcher
+/// has its ingest port.
+async fn check_new(watcher: &Watcher, client: Client) -> Result<(), reply::Response> {
+    if let Err(errors) = validate(watcher) {
+        return Err(invalid_watcher(errors).into_response());
+    }
+    if let Some(id) = slates::missing(watcher).await {
+        return Err(slate_missing(&id).into_response());
+    }
+    let port = watcher.source.ingest_port;
+    let id = watcher.id.as_deref().unwrap_or_default();
+    match ports::used_by(client, port, id).await {
+        Ok(Some(other)) => Err(port_conflict(port, &other).into_response()),
+        Ok(None) => Ok(()),
+        Err(err) => Err(ApiError::from(err).into_response()),
+    }
+}
+
+async fn create(
+    mut watcher: Watcher,
+    caller: Caller,
+    client: Client,
+) -> reply::WithStatus<reply::Json> {
+    let id = watcher.id.clone().unwrap_or_default();
+    match create_objects(&id, &mut watcher, client.clone()).await {
+        Ok(()) => {
+            audit::record(client, &id, "create", &caller, StatusCode::CREATED).await;
+            webhooks::notify(WebhookEvent::WatcherCreated { watcher_id: id });
+            watcher.status = Some(Status::Pending);
+            reply::with_status(reply::json(&watcher), StatusCode::CREATED)
+        }
+        Err(err) => err.reply(),
+    }
+}
+
+/// Options of the upgrade of a Watcher.
+#[derive(Deserialize, Debug)]
+pub struct UpgradeOptions {
+    /// Worker image the Watcher runs from now on, the default image when empty.
+    pub worker_image: Option<String>,
+    /// Upgrades the Watcher in the background, like `JobOptions`.
+    #[serde(rename = "async")]
+    pub in_background: Option<bool>,
+}
+
+#[instrument(skip(client))]
+pub async fn upgrade_watcher(
+    id: String,
+    admin: bool,
+    options: UpgradeOptions,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    if options.worker_image.is_some() && !admin {
+        return Ok(audit::recorded(admin_only_forbidden(), client, &id, "upgrade", caller).await);
+    }
+    let in_background = options.in_background.unwrap_or(false);
+    let work = {
+        let (id, caller, client) = (id.clone(), caller.clone(), client.clone());
+        async move {
+            let (status_code, body) = upgrade(&id, options.worker_image, client.clone()).await;
+            let reply = reply::with_status(reply::json(&body), status_code);
+            audit::recorded(reply, client, &id, "upgrade", caller).await
+        }
+    };
+    Ok(jobs::maybe_spawn(in_background, client, "upgrade", Some(id), caller, work).await)
+}
+
+/// Applies the current worker image and secrets to the Deployment of a stopped Watcher, after
+/// pinning it to `worker_image` when given.
+async fn upgrade(
+    id: &str,
+    worker_image: Option<String>,
+    client: Client,
+) -> (StatusCode, serde_json::Value) {
+    log::debug!("v1.upgrade_watcher: {}", id);
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let stored = match get_deployment(id, client.clone()).await {
+        Ok(deployment) => get_resource(id, client.clone())
+            .await
+            .map(|resource| (deployment, controller::spec(&resource))),
+        Err(err) => Err(err),
+    };
+    let (deployment, mut watcher) = match stored {
+        Ok(stored) => stored,
+        Err(err) => {
+            log::warn!("Could not upgrade {}: {}", id, err);
+            return (err.status_code(), err.body());
+        }
+    };
+    let watcher_status = deployment.get_watcher_status();
+    if watcher_status != Status::Ready {
+        return (
+            StatusCode::BAD_REQUEST,
+            json!({"message": "The Watcher must be stopped before the upgrade can be applied"}),
+        );
+    }
+
+    if let Some(image) = worker_image {
+        watcher.worker_image = Some(image).filter(|image| !image.is_empty());
+        if let Err(errors) = validate(&watcher) {
+            return (StatusCode::UNPROCESSABLE_ENTITY, validation_body(errors));
+        }
+        if let Err(err) = replace_configuration(id, &watcher, None, client.clone()).await {
+            log::error!("Error while calling Kubernetes API: {:?}", err);
+            return (err.status_code(), err.body());
+        }
+    }
+    watcher.status = Some(watcher_status);
+
+    let patch_params = PatchParams::default();
+    let spec_updated = json!({
+        "spec": {
+            "template": {
+                "spec": {
+                    "containers": [
+                        container_spec(id, &watcher)
+                    ]
+                }
+            }
+        }
+    });
+
+    match deployments
+        .patch(
+            &templates::deployment_name(id),
+            &patch_params,
+            &Patch::Apply(spec_updated),
+        )
+        .await
+    {
+        Ok(_) => (StatusCode::OK, serde_json::to_value(&watcher).unwrap()),
+        Err(e) => {
+            log::error!("Error while calling Kubernetes API: {:?}", e);
+            let err = ApiError::from(e);
+            (err.status_code(), err.body())
+        }
+    }
+}
+
+/// Stores the configuration of the watcher in its `Watcher` resource and applies it to its
+/// ConfigMap, keeping the previous one as a revision to roll back to. With the `resource_version` of
+/// the resource, it fails with a conflict when the resource was changed since.
+async fn replace_configuration(
+    id: &str,
+    watcher: &Watcher,
+    resource_version: Option<&str>,
+    client: Client,
+) -> Result<WatcherResource, ApiError> {
+    let mut resource = get_resource(id, client.clone()).await?;
+    resource.spec = definition(watcher.clone());
+    if let Some(resource_version) = resource_version {
+        resource.metadata.resource_version = Some(resource_version.to_string());
+    }
+    let resources: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let resource = resources
+        .replace(id, &PostParams::default(), &resource)
+        .await?;
+    // Only the stored configuration changes, the worker loads it when it starts
+    controller::apply(&resource, client).await?;
+    Ok(resource)
+}
+
+/// Entity tag of the Watcher, the version of its `Watcher` resource changed by every update.
+fn etag(metadata: &ObjectMeta) -> Option<String> {
+    metadata
+        .resource_version
+        .as_ref()
+        .map(|version| format!("\"{}\"", version))
+}
+
+/// Whether the `If-Match` header, a list of entity tags or `*`, matches the entity tag.
+fn etag_matches(if_match: &str, etag: Option<&str>) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|candidate| candidate == "*" || Some(candidate) == etag)
+}
+
+fn with_etag(reply: impl warp::Reply, etag: Option<String>) -> reply::Response {
+    let mut response = reply.into_response();
+    if let Some(value) = etag.and_then(|etag| HeaderValue::from_str(&etag).ok()) {
+        response.headers_mut().insert(ETAG, value);
+    }
+    response
+}
+
+/// The Watcher changed since the client read it, another client updated it.
+fn precondition_failed() -> reply::Response {
+    reply::with_status(
+        reply::json(&json!({
+            "message": "The Watcher was changed since it was read, get it again before updating it"
+        })),
+        StatusCode::PRECONDITION_FAILED,
+    )
+    .into_response()
+}
+
+#[instrument(skip(update, client))]
+pub async fn update_watcher(
+    id: String,
+    update: WatcherUpdate,
+    admin: bool,
+    options: JobOptions,
+    if_match: Option<String>,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let admin_only_update = update.worker_image.is_some()
+        || update.scheduling.is_some()
+        || update.annotations.is_some();
+    if admin_only_update && !admin {
+        return Ok(audit::recorded(admin_only_forbidden(), client, &id, "update", caller).await);
+    }
+    let work = {
+        let (id, caller, client) = (id.clone(), caller.clone(), client.clone());
+        async move {
+            let reply = apply_update(id.clone(), update, admin, if_match, client.clone()).await;
+            audit::recorded(reply, client, &id, "update", caller).await
+        }
+    };
+    let in_background = options.in_background.unwrap_or(false);
+    Ok(jobs::maybe_spawn(in_background, client, "update", Some(id), caller, work).await)
+}
+
+/// The ingest port is the port of another Watcher, the feeds would be sent to both.
+fn port_conflict(port: u32, watcher_id: &str) -> reply::WithStatus<reply::Json> {
+    reply::with_status(
+        reply::json(&json!({
+            "message": port_conflict_message(port, watcher_id),
+            "watcher_id": watcher_id,
+        })),
+        StatusCode::CONFLICT,
+    )
+}
+
+pub(crate) fn port_conflict_message(port: u32, watcher_id: &str) -> String {
+    format!("Ingest port {} is used by Watcher {}", port, watcher_id)
+}
+
+/// The ingest port is the port of another Watcher of the same request.
+fn port_used_at(port: u32, index: usize) -> String {
+    format!(
+        "Ingest port {} is used by the Watcher at index {} too",
+        port, index
+    )
+}
+
+fn slate_missing(id: &str) -> reply::WithStatus<reply::Json> {
+    reply::with_status(
+        reply::json(&json!({ "message": format!("Slate asset {} does not exist", id) })),
+        StatusCode::BAD_REQUEST,
+    )
+}
+
+/// The problems of the fields of the Watcher, all of them so they're fixed at once.
+fn validation_body(errors: ValidationErrors) -> serde_json::Value {
+    json!({
+        "message": errors.to_string(),
+        "errors": errors.0,
+    })
+}
+
+fn field_error(field: &str, message: String) -> FieldError {
+    FieldError {
+        field: field.to_string(),
+        message,
+    }
+}
+
+fn invalid_watcher(errors: ValidationErrors) -> reply::WithStatus<reply::Json> {
+    reply::with_status(
+        reply::json(&validation_body(errors)),
+        StatusCode::UNPROCESSABLE_ENTITY,
+    )
+}
+
+/// Whether the Watcher sets fields only the administrators set: they choose the worker a Watcher
+/// runs, the nodes it runs on and the annotations of its objects, read by the tools of the cluster.
+fn admin_only(watcher: &Watcher) -> bool {
+    watcher.worker_image.is_some()
+        || watcher.scheduling.is_some()
+        || watcher.annotations.is_some()
+        || !privileged_secrets(watcher).is_empty()
+}
+
+/// Whether replacing the `current` configuration of the Watcher with the `target` one changes
+/// fields only the administrators set.
+fn admin_only_changed(current: &Watcher, target: &Watcher) -> bool {
+    current.worker_image != target.worker_image
+        || current.scheduling != target.scheduling
+        || current.annotations != target.annotations
+        || privileged_secrets(current) != privileged_secrets(target)
+}
+
+/// The credentials read from the Kubernetes Secrets exposed to the worker or from its files, any
+/// Secret of the namespace or file of the worker, like the token of its service account, can be
+/// read.
+fn privileged_secrets(watcher: &Watcher) -> Vec<&SecretValue> {
+    watcher
+        .secret_values()
+        .into_iter()
+        .map(|(_, secret)| secret)
+        .filter(|secret| match secret {
+            SecretValue::Reference { value_from } => value_from.secret.is_some(),
+            SecretValue::File { .. } => true,
+            SecretValue::Plain(_) => false,
+        })
+        .collect()
+}
+
+fn admin_only_forbidden() -> reply::WithStatus<reply::Json> {
+    reply::with_status(
+        reply::json(&json!({
+            "message": "Only administrators can set the worker_image, the scheduling, the annotations, the Kubernetes Secrets and the secret files"
+        })),
+        StatusCode::FORBIDDEN,
+    )
+}
+
+async fn apply_update(
+    id: String,
+    update: WatcherUpdate,
+    admin: bool,
+    if_match: Option<String>,
+    client: Client,
+) -> reply::Response {
+    log::debug!("v1.update_watcher: {} {:?}", id, update);
+    let deployment = match get_deployment(&id, client.clone()).await {
+        Ok(d) => d,
+        Err(err) => return err.into_response(),
+    };
+    let resource = match get_resource(&id, client.clone()).await {
+        Ok(r) => r,
+        Err(err) => return err.into_response(),
+    };
+    // Only the client updating the version it read updates the Watcher
+    let resource_version = match if_match {
+        Some(if_match) if !etag_matches(&if_match, etag(&resource.metadata).as_deref()) => {
+            return precondition_failed()
+        }
+        Some(_) => resource.metadata.resource_version.clone(),
+        None => None,
+    };
+
+    // The version checked is the one updated
+    let mut watcher = controller::spec(&resource);
+    let current = watcher.clone();
+    if let Err(err) = update.apply(&mut watcher) {
+        // The changes of the transitions don't apply to the transitions of the Watcher
+        let errors = ValidationErrors(vec![field_error("transitions", err.to_string())]);
+        return invalid_watcher(errors).into_response();
+    }
+    // The Kubernetes Secrets and the secret files are referenced in the credentials and the actions
+    if admin_only_changed(&current, &watcher) && !admin {
+        return admin_only_forbidden().into_response();
+    }
+    if let Err(errors) = validate(&watcher) {
+        return invalid_watcher(errors).into_response();
+    }
+    if let Some(id) = slates::missing(&watcher).await {
+        return slate_missing(&id).into_response();
+    }
+
+    match replace_configuration(&id, &watcher, resource_version.as_deref(), client).await {
+        Ok(resource) => {
+            watcher.status = Some(deployment.get_watcher_status());
+            let reply = reply::with_status(reply::json(&watcher), StatusCode::OK);
+            with_etag(reply, etag(&resource.metadata))
+        }
+        // Updated by another client between the check and the update
+        Err(ApiError::Kubernetes(kube::Error::Api(err))) if err.code == 409 => {
+            precondition_failed()
+        }
+        Err(err) => err.into_response(),
+    }
+}
+
+#[instrument(skip(client))]
+pub async fn get_watcher_revisions(
+    id: String,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Err(err) = get_resource(&id, client.clone()).await {
+        return Ok(err.reply());
+    }
+    Ok(reply::with_status(
+        reply::json(&revisions::list(client, &id).await),
+        StatusCode::OK,
+    ))
+}
+
+#[instrument(skip(client))]
+pub async fn rollback_watcher(
+    id: String,
+    revision: u32,
+    admin: bool,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply = rollback(id.clone(), revision, admin, client.clone()).await?;
+    Ok(audit::recorded(reply, client, &id, "rollback", caller).await)
+}
+
+async fn rollback(
+    id: String,
+    revision: u32,
+    admin: bool,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    log::debug!("v1.rollback_watcher: {} {}", id, revision);
+    let deployment = match get_deployment(&id, client.clone()).await {
+        Ok(d) => d,
+        Err(err) => return Ok(err.reply()),
+    };
+    let current = match get_resource(&id, client.clone()).await {
+        Ok(resource) => controller::spec(&resource),
+        Err(err) => return Ok(err.reply()),
+    };
+
+    let revision = match revisions::get(client.clone(), &id, revision).await {
+        Some(revision) => revision,
+        None => {
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Revision does not exist"})),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+    };
+    // The revision may predate a change of the format or of the validation of the watchers
+    let mut watcher = match serde_json::from_value::<Watcher>(revision.watcher) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            let message = format!("The revision is not a valid Watcher: {}", err);
+            let errors = ValidationErrors(vec![field_error("revision", message)]);
+            return Ok(invalid_watcher(errors));
+        }
+    };
+    if let Err(errors) = validate(&watcher) {
+        return Ok(invalid_watcher(errors));
+    }
+    watcher.id = Some(id.clone());
+    if admin_only_changed(&current, &watcher) && !admin {
+        return Ok(admin_only_forbidden());
+    }
+
+    // The configuration rolled back from is kept as a revision too, to undo the rollback
+    match replace_configuration(&id, &watcher, None, client).await {
+        Ok(_) => {
+            watcher.status = Some(deployment.get_watcher_status());
+            Ok(reply::with_status(reply::json(&watcher), StatusCode::OK))
+        }
+        Err(err) => Ok(err.reply()),
+    }
+}
+
+#[instrument(skip(client))]
+pub async fn get_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
+    Ok(replied(watcher_with_status(&id, client).await))
+}
+
+async fn watcher_with_status(id: &str, client: Client) -> Result<reply::Response, ApiError> {
+    // TODO: searching for a deployment could be a filter in this route
+    let deployment = get_deployment(id, client.clone()).await?;
+    // The `Watcher` resource defines the Watcher, its ConfigMap follows it
+    let resource = get_resource(id, client.clone()).await?;
+
+    // Sent back in `If-Match` by the updates
+    let etag = etag(&resource.metadata);
+    let mut w = controller::spec(&resource);
+    w.status = Some(deployment.get_watcher_status());
+    set_expires_in(&mut w);
+
+    w.status_description = if let Some(Status::Pending) = w.status.as_ref() {
+        // Load more information why it's in pending status
+        // We get the reason the container is waiting, if available
+        let pods_client: Api<Pod> = Api::namespaced(client.clone(), &NAMESPACE);
+        let lp = ListParams::default().labels(&format!("app=hawkeye,watcher_id={}", id));
+        let pods = pods_client.list(&lp).await?;
+        let status_description = pods
+            .items
+            .first()
+            .map(|p| p.status.as_ref())
+            .flatten()
+            .map(|ps| ps.container_statuses.as_ref())
+            .flatten()
+            .map(|css| css.first())
+            .flatten()
+            .map(|cs| cs.state.as_ref())
+            .flatten()
+            .map(|cs| cs.waiting.as_ref())
+            .flatten()
+            .map(|csw| csw.message.clone())
+            .flatten();
+        log::debug!(
+            "Additional information for the Pending status: {:?}",
+            status_description.as_ref()
+        );
+        status_description
+    } else {
+        None
+    };
+
+    // Comes from the service
+    w.source.ingest_ip = if w.status != Some(Status::Error) {
+        log::debug!("Getting ingest_ip from Service's LoadBalancer");
+        let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+        let service = services
+            .get_status(&templates::service_name(id))
+            .await
+            .map_err(ApiError::watcher)?;
+        service.ingest_address()
+    } else {
+        None
+    };
+
+    Ok(with_etag(
+        reply::with_status(reply::json(&w), StatusCode::OK),
+        etag,
+    ))
+}
+
+/// The latest frame of the Watcher, the `width`, `height` and `format` (`png` or `jpeg`) query
+/// parameters are passed on to the worker, which resizes and encodes it.
+#[instrument(skip(client))]
+pub async fn get_video_frame(
+    id: String,
+    query: HashMap<String, String>,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(replied(video_frame(&id, query, client).await))
+}
+
+async fn video_frame(
+    id: &str,
+    query: HashMap<String, String>,
+    client: Client,
+) -> Result<reply::Response, ApiError> {
+    let mut resp = warp::reply::Response::new(Body::empty());
+
+    // The `Watcher` resource is the source of truth for what are the watchers we have
+    let watcher = controller::spec(&get_resource(id, client.clone()).await?);
+
+    if Status::Running
+        != get_deployment(id, client.clone())
+            .await?
+            .get_watcher_status()
+    {
+        log::debug!("Watcher is not running...");
+        return Err(ApiError::NotRunning);
+    }
+    let pods_client: Api<Pod> = Api::namespaced(client.clone(), &NAMESPACE);
+    let lp = ListParams::default().labels(&format!("app=hawkeye,watcher_id={}", id));
+    let pods = pods_client.list(&lp).await?;
+    let pod_ip = match pods
+        .items
+        .first()
+        .map(|p| p.status.as_ref())
+        .flatten()
+        .map(|ps| ps.pod_ip.clone())
+        .flatten()
+    {
+        Some(pod_ip) => pod_ip,
+        None => {
+            log::debug!("Not able to get Pod IP");
+            return Err(worker_unreachable());
+        }
+    };
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
+        .build()
+        .unwrap();
+    // Try for new and old ports in pod
+    for port in vec![watcher.source.ingest_port, 3030] {
+        let url = format!("http://{}:{}/latest_frame", pod_ip, port);
+
+        log::info!("Calling Pod using url: {}", url);
+        let response = match http_client.get(url.as_str()).query(&query).send().await {
+            Ok(r) => r,
+            Err(error) => {
+                log::error!("Could not call {} endpoint: {:?}", url, error);
+                return Err(worker_unreachable());
+            }
+        };
+
+        // Invalid width, height or format
+        if response.status() == reqwest::StatusCode::BAD_REQUEST {
+            *resp.status_mut() = StatusCode::BAD_REQUEST;
+            *resp.body_mut() = Body::from(response.bytes().await.unwrap_or_default().to_vec());
+            return Ok(resp);
+        }
+        match response.error_for_status() {
+            Ok(image_response) => {
+                let content_type = image_response
+                    .headers()
+                    .get(CONTENT_TYPE)
+                    .and_then(|value| HeaderValue::from_bytes(value.as_bytes()).ok())
+                    .unwrap_or_else(|| HeaderValue::from_static("image/png"));
+                let image_bytes = match image_response.bytes().await {
+                    Ok(image_bytes) => image_bytes,
+                    Err(error) => {
+                        log::error!("Could not read the frame from {}: {:?}", url, error);
+                        return Err(worker_unreachable());
+                    }
+                };
+                let headers = resp.headers_mut();
+                headers.insert(CONTENT_TYPE, content_type);
+                headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+                *resp.body_mut() = Body::from(image_bytes.to_vec());
+
+                return Ok(resp);
+            }
+            Err(_) => {
+                continue;
+            }
+        }
+    }
+    log::error!("Error calling Pod using old and new urls");
+    Err(worker_unreachable())
+}
+
+/// Runs or stops the worker of a Watcher by scaling its Kubernetes deployment, returns the
+/// outcome to reply with.
+pub(crate) async fn scale_watcher(
+    id: &str,
+    target: Status,
+    client: Client,
+) -> (StatusCode, &'static str) {
+    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+
+    // Get the Kubernetes deployment for the Watcher.
+    // TODO: probably better to just get the scale
+    let deployment = match deployments_client
+        .get(&templates::deployment_name(id))
+        .await
+    {
+        Ok(d) => d,
+        Err(kube::Error::Api(err)) if err.code == 404 => {
+            return (StatusCode::NOT_FOUND, "Watcher does not exist")
+        }
+        Err(err) => {
+            log::error!("Error while calling Kubernetes API: {:?}", err);
+            return (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Error while calling Kubernetes API",
+            );
+        }
+    };
+
+    // Actions and guards based on the current Watcher status.
+    let replicas = match (deployment.get_watcher_status(), target) {
+        // No op, already there!
+        (Status::Running, Status::Running) => {
+            return (StatusCode::OK, "Watcher is already running")
+        }
+        (Status::Ready, Status::Ready) => return (StatusCode::OK, "Watcher is already stopped"),
+        // No op, committing other changes.
+        (Status::Pending, _) => return (StatusCode::CONFLICT, "Watcher is currently updating"),
+        (Status::Ready, Status::Running) => 1,
+        (Status::Running, Status::Ready) => 0,
+        (_, Status::Running) => {
+            return (
+                StatusCode::NOT_ACCEPTABLE,
+                "Watcher in error state cannot be set to running",
+            )
+        }
+        (_, _) => {
+            return (
+                StatusCode::NOT_ACCEPTABLE,
+                "Watcher in error state cannot be set to stopped",
+            )
+        }
+    };
+
+    if replicas == 1 && watcher_expired(id, client).await {
+        return (
+            StatusCode::CONFLICT,
+            "Watcher expired, its expires_at must be extended first",
+        );
+    }
+
+    let mut patch_params = PatchParams::default();
+    patch_params.field_manager = Some("hawkeye_api".to_string());
+
+    // Set Kubernetes deployment replicas via patch.
+    let deployment_scale_json = json!({
+        "apiVersion": "autoscaling/v1",
+        "spec": { "replicas": replicas },
+    });
+    // Update the status of the Watcher to indicate whether it should be running.
+    let status_label_json = json!({
+        "apiVersion": "apps/v1",
+        "metadata": {
+            "labels": {
+                "target_status": target,
+            }
+        }
+    });
+    let name = &templates::deployment_name(id);
+    let result = match deployments_client
+        .patch_scale(name, &patch_params, &Patch::Merge(&deployment_scale_json))
+        .await
+    {
+        Ok(_) => {
+            deployments_client
+                .patch(name, &patch_params, &Patch::Merge(status_label_json))
+                .await
+        }
+        Err(err) => Err(err),
+    };
+    match (result, target) {
+        (Ok(_), Status::Running) => (StatusCode::OK, "Watcher is starting"),
+        (Ok(_), _) => (StatusCode::OK, "Watcher is stopping"),
+        (Err(err), _) => {
+            log::error!("Error while calling Kubernetes API: {:?}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Error while calling Kubernetes API",
+            )
+        }
+    }
+}
+
+fn scale_reply((status_code, message): (StatusCode, &'static str)) -> impl warp::Reply {
+    let body = if status_code == StatusCode::NOT_FOUND {
+        json!({})
+    } else {
+        json!({ "message": message })
+    };
+    reply::with_status(reply::json(&body), status_code)
+}
+
+/// Start a Watcher worker by making sure there's a positive replica count for the Kubernetes
+/// deployment.
+#[instrument(skip(client))]
+pub async fn start_watcher(
+    id: String,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Err(reply) = outside_maintenance(client.clone()).await {
+        return Ok(audit::recorded(reply, client, &id, "start", caller).await);
+    }
+    let reply = scale_reply(scale_watcher(&id, Status::Running, client.clone()).await);
+    Ok(audit::recorded(reply, client, &id, "start", caller).await)
+}
+
+/// Stop a Watcher worker by making sure there's a replica count of 0 for the Kubernetes
+/// deployment.
+#[instrument(skip(client))]
+pub async fn stop_watcher(
+    id: String,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply = scale_reply(scale_watcher(&id, Status::Ready, client.clone()).await);
+    Ok(audit::recorded(reply, client, &id, "stop", caller).await)
+}
+
+/// Time given to the worker of a restarting Watcher to stop before it's started again.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(60);
+const RESTART_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stops the Watcher and waits for its worker to be gone.
+async fn stop_and_wait(id: &str, client: Client) -> Result<(), (StatusCode, &'static str)> {
+    let (status_code, message) = scale_watcher(id, Status::Ready, client.clone()).await;
+    if status_code != StatusCode::OK {
+        return Err((status_code, message));
+    }
+
+    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let stopped = async {
+        loop {
+            match deployments_client
+                .get(&templates::deployment_name(id))
+                .await
+            {
+                Ok(deployment) if deployment.get_watcher_status() == Status::Ready => return true,
+                Ok(_) => {}
+                // Deleted while restarting
+                Err(kube::Error::Api(err)) if err.code == 404 => return false,
+                // Checked again in the next poll
+                Err(err) => log::warn!("Could not get the status of {}: {:?}", id, err),
+            }
+            tokio::time::sleep(RESTART_POLL_INTERVAL).await;
+        }
+    };
+    match tokio::time::timeout(RESTART_TIMEOUT, stopped).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err((StatusCode::NOT_FOUND, "Watcher does not exist")),
+        Err(_) => Err((
+            StatusCode::GATEWAY_TIMEOUT,
+            "Watcher did not stop in time, it was not started again",
+        )),
+    }
+}
+
+/// Stops the Watcher, waits for its worker to be gone and starts it again. A stopped Watcher is
+/// just started.
+async fn restart(id: &str, client: Client) -> (StatusCode, &'static str) {
+    if let Err(result) = stop_and_wait(id, client.clone()).await {
+        return result;
+    }
+
+    match scale_watcher(id, Status::Running, client).await {
+        (StatusCode::OK, _) => (StatusCode::OK, "Watcher is restarting"),
+        result => result,
+    }
+}
+
+/// Restart a Watcher worker, for example to load a new configuration, in a single call.
+#[instrument(skip(client))]
+pub async fn restart_watcher(
+    id: String,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    if let Err(reply) = outside_maintenance(client.clone()).await {
+        return Ok(audit::recorded(reply, client, &id, "restart", caller).await);
+    }
+    let reply = scale_reply(restart(&id, client.clone()).await);
+    Ok(audit::recorded(reply, client, &id, "restart", caller).await)
+}
+
+/// Watchers a bulk operation applies to, by id or by their current status.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct WatcherSelector {
+    pub ids: Option<Vec<String>>,
+    pub status: Option<Status>,
+}
+
+/// Maximum number of Watchers started or stopped at the same time by a bulk operation.
+const BULK_CONCURRENCY: usize = 10;
+
+/// Starts (`Status::Running`) or stops (`Status::Ready`) the selected Watchers concurrently,
+/// with the result of each.
+async fn scale_watchers(
+    selector: WatcherSelector,
+    target: Status,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let ids = match (selector.ids, selector.status) {
+        (Some(ids), None) => ids,
+        (None, Some(status)) => {
+            let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+            let lp = ListParams::default()
+                .labels("app=hawkeye,watcher_id")
+                .timeout(10);
+            match deployments_client.list(&lp).await {
+                Ok(deployments) => deployments
+                    .items
+                    .into_iter()
+                    .filter(|deploy| deploy.get_watcher_status() == status)
+                    .filter_map(|deploy| {
+                        deploy
+                            .metadata
+                            .labels
+                            .as_ref()
+                            .and_then(|labels| labels.get("watcher_id"))
+                            .cloned()
+                    })
+                    .collect(),
+                Err(err) => {
+                    log::error!("Could not list the watchers: {:?}", err);
+                    return Ok(reply::with_status(
+                        reply::json(&json!({"message": "Could not list the watchers"})),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    ));
+                }
+            }
+        }
+        (_, _) => {
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Either `ids` or `status` is required"})),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+
+    let results = scale_ids(ids, target, &caller, client).await;
+    Ok(reply::with_status(
+        reply::json(&json!({ "results": results })),
+        StatusCode::OK,
+    ))
+}
+
+/// Starts or stops the Watchers concurrently, with the status code and message of each.
+async fn scale_ids(
+    ids: Vec<String>,
+    target: Status,
+    caller: &Caller,
+    client: Client,
+) -> Vec<serde_json::Value> {
+    futures::stream::iter(ids)
+        .map(|id| {
+            let client = client.clone();
+            let caller = caller.clone();
+            async move {
+                let (status_code, message) = scale_watcher(&id, target, client.clone()).await;
+                let operation = if target == Status::Running {
+                    "start"
+                } else {
+                    "stop"
+                };
+                audit::record(client, &id, operation, &caller, status_code).await;
+                json!({
+                    "id": id,
+                    "status_code": status_code.as_u16(),
+                    "message": message,
+                })
+            }
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect()
+        .await
+}
+
+#[instrument(skip(client))]
+pub async fn start_watchers(
+    selector: WatcherSelector,
+    caller: Caller,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    if let Err(reply) = outside_maintenance(client.clone()).await {
+        return Ok(reply.into_response());
+    }
+    Ok(scale_watchers(selector, Status::Running, caller, client)
+        .await?
+        .into_response())
+}
+
+#[instrument(skip(client))]
+pub async fn stop_watchers(
+    selector: WatcherSelector,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    scale_watchers(selector, Status::Ready, caller, client).await
+}
+
+#[instrument(skip(client))]
+pub async fn get_maintenance(client: Client) -> Result<impl warp::Reply, Infallible> {
+    match maintenance::get(client).await {
+        Ok(Some(maintenance)) => Ok(reply::with_status(
+            reply::json(&maintenance),
+            StatusCode::OK,
+        )),
+        Ok(None) => Ok(not_in_maintenance()),
+        Err(err) => {
+            log::error!("Could not get the maintenance: {:?}", err);
+            Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not get the maintenance"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
+
+/// Fails with `409 Conflict` while the cluster is in maintenance: the Watchers are only started
+/// again when it ends, by `exit_maintenance`.
+async fn outside_maintenance(client: Client) -> Result<(), reply::WithStatus<reply::Json>> {
+    match maintenance::get(client).await {
+        Ok(None) => Ok(()),
+        Ok(Some(_)) => Err(reply::with_status(
+            reply::json(&json!({
+                "message": "The cluster is in maintenance, the watchers are started when it ends"
+            })),
+            StatusCode::CONFLICT,
+        )),
+        Err(err) => Err(ApiError::from(err).reply()),
+    }
+}
+
+fn not_in_maintenance() -> reply::WithStatus<reply::Json> {
+    reply::with_status(
+        reply::json(&json!({"message": "The cluster is not in maintenance"})),
+        StatusCode::NOT_FOUND,
+    )
+}
+
+/// Stops all the Watchers for a maintenance of the cluster, recording the ones running, or
+/// starting, to start them again when it ends.
+#[instrument(skip(client))]
+pub async fn enter_maintenance(
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let lp = ListParams::default()
+        .labels("app=hawkeye,watcher_id,target_status=running")
+        .timeout(10);
+    let mut ids: Vec<String> = match deployments_client.list(&lp).await {
+        Ok(deployments) => deployments
+            .items
+            .into_iter()
+            .filter_map(|deploy| {
+                deploy
+                    .metadata
+                    .labels
+                    .as_ref()
+                    .and_then(|labels| labels.get("watcher_id"))
+                    .cloned()
+            })
+            .collect(),
+        Err(err) => {
+            log::error!("Could not list the watchers: {:?}", err);
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not list the watchers"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+    ids.sort();
+
+    let maintenance = Maintenance {
+        entered_at: Utc::now().to_rfc3339(),
+        caller: caller.clone(),
+        watchers: ids.clone(),
+    };
+    match maintenance::start(client.clone(), &maintenance).await {
+        Ok(()) => {}
+        Err(kube::Error::Api(err)) if err.code == 409 => {
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "The cluster is already in maintenance"})),
+                StatusCode::CONFLICT,
+            ))
+        }
+        Err(err) => {
+            log::error!("Could not start the maintenance: {:?}", err);
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not start the maintenance"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    }
+
+    let results = scale_ids(ids, Status::Ready, &caller, client).await;
+    Ok(reply::with_status(
+        reply::json(&json!({
+            "entered_at": maintenance.entered_at,
+            "watchers": maintenance.watchers,
+            "results": results,
+        })),
+        StatusCode::OK,
+    ))
+}
+
+/// Starts again the Watchers stopped by the maintenance. It only ends once all of them are
+/// started, or were deleted in the meantime, so it can be retried.
+#[instrument(skip(client))]
+pub async fn exit_maintenance(
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let maintenance = match maintenance::get(client.clone()).await {
+        Ok(Some(maintenance)) => maintenance,
+        Ok(None) => return Ok(not_in_maintenance()),
+        Err(err) => {
+            log::error!("Could not get the maintenance: {:?}", err);
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not get the maintenance"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+
+    let results = scale_ids(
+        maintenance.watchers,
+        Status::Running,
+        &caller,
+        client.clone(),
+    )
+    .await;
+    let failed = results.iter().any(|result| {
+        let status_code = result["status_code"].as_u64().unwrap_or_default();
+        status_code >= 400 && status_code != u64::from(StatusCode::NOT_FOUND.as_u16())
+    });
+    if failed {
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "message": "Some watchers could not be started, the cluster is still in maintenance",
+                "results": results,
+            })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    if let Err(err) = maintenance::end(client).await {
+        log::error!("Could not end the maintenance: {:?}", err);
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "message": "Could not end the maintenance",
+                "results": results,
+            })),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ));
+    }
+    Ok(reply::with_status(
+        reply::json(&json!({ "results": results })),
+        StatusCode::OK,
+    ))
+}
+
+/// Watchers upgraded by a fleet upgrade, all of them when no `ids` are given.
+#[derive(Deserialize, Debug)]
+pub struct FleetUpgrade {
+    pub ids: Option<Vec<String>>,
+    /// Watchers upgraded at once, the next batch starts when the whole batch is done.
+    pub batch_size: Option<usize>,
+    /// Stops before the next batch when a Watcher of the batch could not be upgraded.
+    pub pause_on_error: Option<bool>,
+}
+
+const DEFAULT_UPGRADE_BATCH_SIZE: usize = 10;
+
+/// Upgrades the Watcher, stopping it before and starting it again after when it's running.
+async fn rolling_upgrade(id: &str, client: Client) -> (StatusCode, String) {
+    let was_running = match get_deployment(id, client.clone()).await {
+        Ok(deployment) => deployment.get_watcher_status() == Status::Running,
+        Err(err) => return (err.status_code(), err.to_string()),
+    };
+    if was_running {
+        if let Err((status_code, message)) = stop_and_wait(id, client.clone()).await {
+            return (status_code, message.to_string());
+        }
+    }
+
+    let (status_code, body) = upgrade(id, None, client.clone()).await;
+    if status_code != StatusCode::OK {
+        let message = body["message"]
+            .as_str()
+            .unwrap_or("Watcher could not be upgraded");
+        // Left stopped when it was running, an upgrade that failed is not run
+        return (status_code, message.to_string());
+    }
+    if !was_running {
+        return (StatusCode::OK, "Watcher has been upgraded".to_string());
+    }
+    match scale_watcher(id, Status::Running, client).await {
+        (StatusCode::OK, _) => (
+            StatusCode::OK,
+            "Watcher has been upgraded and is starting".to_string(),
+        ),
+        (status_code, message) => (status_code, message.to_string()),
+    }
+}
+
+/// Upgrade many Watchers in batches, instead of one call per Watcher.
+#[instrument(skip(client))]
+pub async fn upgrade_watchers(
+    fleet: FleetUpgrade,
+    options: JobOptions,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    if fleet.batch_size == Some(0) {
+        return Ok(reply::with_status(
+            reply::json(&json!({"message": "`batch_size` must be greater than 0"})),
+            StatusCode::BAD_REQUEST,
+        )
+        .into_response());
+    }
+    if options.in_background.unwrap_or(false) {
+        // Each Watcher upgraded is a step of the job
+        let work = |progress| upgrade_fleet(fleet, caller.clone(), client.clone(), Some(progress));
+        return Ok(jobs::spawn(client.clone(), "upgrade", None, caller.clone(), work).await);
+    }
+    Ok(upgrade_fleet(fleet, caller, client, None)
+        .await
+        .into_response())
+}
+
+/// Upgrades the Watchers in batches, reporting each of them to the job when it's given.
+async fn upgrade_fleet(
+    fleet: FleetUpgrade,
+    caller: Caller,
+    client: Client,
+    progress: Option<jobs::Progress>,
+) -> reply::WithStatus<reply::Json> {
+    let batch_size = fleet.batch_size.unwrap_or(DEFAULT_UPGRADE_BATCH_SIZE);
+    let pause_on_error = fleet.pause_on_error.unwrap_or(true);
+
+    let ids = match fleet.ids {
+        Some(ids) => ids,
+        None => {
+            let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+            let lp = ListParams::default()
+                .labels("app=hawkeye,watcher_id")
+                .timeout(10);
+            match deployments_client.list(&lp).await {
+                Ok(deployments) => deployments
+                    .items
+                    .into_iter()
+                    .filter_map(|deploy| {
+                        deploy
+                            .metadata
+                            .labels
+                            .as_ref()
+                            .and_then(|labels| labels.get("watcher_id"))
+                            .cloned()
+                    })
+                    .collect(),
+                Err(err) => {
+                    log::error!("Could not list the watchers: {:?}", err);
+                    return reply::with_status(
+                        reply::json(&json!({"message": "Could not list the watchers"})),
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                    );
+                }
+            }
+        }
+    };
+
+    if let Some(progress) = progress.as_ref() {
+        progress.expect(ids.len()).await;
+    }
+    let mut results = Vec::with_capacity(ids.len());
+    let mut paused = false;
+    for batch in ids.chunks(batch_size) {
+        if paused {
+            results.extend(batch.iter().map(|id| {
+                json!({
+                    "id": id,
+                    "message": "Not upgraded, the upgrade was paused",
+                })
+            }));
+            continue;
+        }
+        let outcomes = futures::future::join_all(batch.iter().map(|id| {
+            let client = client.clone();
+            let caller = caller.clone();
+            async move {
+                let (status_code, message) = rolling_upgrade(id, client.clone()).await;
+                audit::record(client, id, "upgrade", &caller, status_code).await;
+                (id, status_code, message)
+            }
+        }))
+        .await;
+        for (id, status_code, message) in outcomes {
+            if status_code != StatusCode::OK && pause_on_error {
+                log::warn!("Upgrade paused, watcher {} failed: {}", id, message);
+                paused = true;
+            }
+            if let Some(progress) = progress.as_ref() {
+                progress.step(id, status_code, Some(message.clone())).await;
+            }
+            results.push(json!({
+                "id": id,
+                "status_code": status_code.as_u16(),
+                "message": message,
+            }));
+        }
+    }
+    reply::with_status(
+        reply::json(&json!({ "results": results, "paused": paused })),
+        StatusCode::OK,
+    )
+}
+
+/// Watchers of each group, groups are only listed while they have Watchers.
+#[instrument(skip(client))]
+pub async fn list_groups(client: Client) -> Result<impl warp::Reply, Infallible> {
+    let watchers = match defined_watchers(client).await {
+        Ok(watchers) => watchers,
+        Err(err) => {
+            log::error!("Could not list the groups: {:?}", err);
+            return Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not list the groups"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ));
+        }
+    };
+    // Sorted by group
+    let mut groups: BTreeMap<String, usize> = BTreeMap::new();
+    for group in watchers.into_values().filter_map(|watcher| watcher.group) {
+        *groups.entry(group).or_default() += 1;
+    }
+    let groups: Vec<_> = groups
+        .into_iter()
+        .map(|(group, watchers)| json!({"group": group, "watchers": watchers}))
+        .collect();
+    Ok(reply::with_status(reply::json(&groups), StatusCode::OK))
+}
+
+fn invalid_group() -> reply::Response {
+    reply::with_status(
+        reply::json(&json!({"message": "Invalid group"})),
+        StatusCode::BAD_REQUEST,
+    )
+    .into_response()
+}
+
+/// Ids of the Watchers in the `group`, `Err` with the reply when there are none.
+async fn group_ids(group: &str, client: Client) -> Result<Vec<String>, reply::Response> {
+    if !is_label_value(group) {
+        return Err(invalid_group());
+    }
+    let mut ids: Vec<String> = match defined_watchers(client).await {
+        Ok(watchers) => watchers
+            .into_iter()
+            .filter(|(_, watcher)| watcher.group.as_deref() == Some(group))
+            .map(|(id, _)| id)
+            .collect(),
+        Err(err) => {
+            log::error!("Could not list the watchers of {}: {:?}", group, err);
+            return Err(reply::with_status(
+                reply::json(&json!({"message": "Could not list the watchers"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+    // In the order they were listed in before
+    ids.sort_unstable();
+    if ids.is_empty() {
+        return Err(reply::with_status(
+            reply::json(&json!({"message": "Group does not exist"})),
+            StatusCode::NOT_FOUND,
+        )
+        .into_response());
+    }
+    Ok(ids)
+}
+
+#[instrument(skip(cache, client))]
+pub async fn list_group_watchers(
+    group: String,
+    cache: Cache,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    if !is_label_value(&group) {
+        return Ok(invalid_group());
+    }
+    Ok(list_watchers_of(Some(&group), cache, client)
+        .await?
+        .into_response())
+}
+
+/// Starts or stops all the Watchers of the group, like the bulk operations.
+async fn scale_group(
+    group: &str,
+    target: Status,
+    caller: Caller,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    let ids = match group_ids(group, client.clone()).await {
+        Ok(ids) => ids,
+        Err(response) => return Ok(response),
+    };
+    let selector = WatcherSelector {
+        ids: Some(ids),
+        status: None,
+    };
+    Ok(scale_watchers(selector, target, caller, client)
+        .await?
+        .into_response())
+}
+
+#[instrument(skip(client))]
+pub async fn start_group(
+    group: String,
+    caller: Caller,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    if let Err(reply) = outside_maintenance(client.clone()).await {
+        return Ok(reply.into_response());
+    }
+    scale_group(&group, Status::Running, caller, client).await
+}
+
+#[instrument(skip(client))]
+pub async fn stop_group(
+    group: String,
+    caller: Caller,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    scale_group(&group, Status::Ready, caller, client).await
+}
+
+/// Batches of the upgrade of a group, like a fleet upgrade of the Watchers of the group.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct GroupUpgrade {
+    pub batch_size: Option<usize>,
+    pub pause_on_error: Option<bool>,
+}
+
+#[instrument(skip(client))]
+pub async fn upgrade_group(
+    group: String,
+    options: GroupUpgrade,
+    caller: Caller,
+    client: Client,
+) -> Result<reply::Response, Infallible> {
+    let ids = match group_ids(&group, client.clone()).await {
+        Ok(ids) => ids,
+        Err(response) => return Ok(response),
+    };
+    let fleet = FleetUpgrade {
+        ids: Some(ids),
+        batch_size: options.batch_size,
+        pause_on_error: options.pause_on_error,
+    };
+    Ok(upgrade_fleet(fleet, caller, client, None)
+        .await
+        .into_response())
+}
+
+#[instrument(skip(client))]
+pub async fn delete_watcher(
+    id: String,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply = if delete_watcher_objects(&id, client.clone()).await {
+        webhooks::notify(WebhookEvent::WatcherDeleted {
+            watcher_id: id.clone(),
+        });
+        reply::with_status(
+            reply::json(&json!({
+                "message": "Watcher has been deleted"
+            })),
+            StatusCode::OK,
+        )
     } else {
-        None
+        reply::with_status(
+            reply::json(&json!({
+                "message": "Watcher does not exist"
+            })),
+            StatusCode::NOT_FOUND,
+        )
     };
-
-    Ok(reply::with_status(reply::json(&w), StatusCode::OK))
+    Ok(audit::recorded(reply, client, &id, "delete", caller).await)
 }
 
-pub async fn get_video_frame(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    let mut resp = warp::reply::Response::new(Body::empty());
+#[instrument(skip(client))]
+pub async fn get_job(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
+    match jobs::get(client, &id).await {
+        Some(job) => Ok(reply::with_status(reply::json(&job), StatusCode::OK)),
+        None => Ok(reply::with_status(
+            reply::json(&json!({})),
+            StatusCode::NOT_FOUND,
+        )),
+    }
+}
 
-    // We use the ConfigMap as source of truth for what are the watchers we have
-    let config_maps_client: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let config_map = match config_maps_client
-        .get(&templates::configmap_name(&id))
-        .await
-    {
-        Ok(c) => c,
-        Err(_) => {
-            log::debug!("ConfigMap object not found for this watcher: {}", id);
-            *resp.status_mut() = StatusCode::NOT_FOUND;
-            return Ok(resp);
-        }
-    };
-    let watcher: Watcher =
-        serde_json::from_str(config_map.data.unwrap().get("watcher.json").unwrap()).unwrap();
+#[instrument(skip(client))]
+pub async fn get_watcher_audit(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
+    match audit::list(client, &id).await {
+        Some(operations) => Ok(reply::with_status(reply::json(&operations), StatusCode::OK)),
+        None => Ok(reply::with_status(
+            reply::json(&json!({})),
+            StatusCode::NOT_FOUND,
+        )),
+    }
+}
 
-    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let deployment = match deployments_client
-        .get(&templates::deployment_name(&id))
-        .await
+/// Address of the worker of a running Watcher, its metrics server listens on the ingest port.
+async fn worker_address(id: &str, ingest_port: u32, client: Client) -> Result<String, ApiError> {
+    if get_deployment(id, client.clone())
+        .await?
+        .get_watcher_status()
+        != Status::Running
     {
-        Ok(d) => d,
-        Err(_) => {
-            *resp.status_mut() = StatusCode::NOT_FOUND;
-            return Ok(resp);
-        }
-    };
-    if Status::Running != deployment.get_watcher_status() {
-        log::debug!("Watcher is not running...");
-        *resp.status_mut() = StatusCode::NOT_ACCEPTABLE;
-        return Ok(resp);
+        return Err(ApiError::NotRunning);
     }
-    let pods_client: Api<Pod> = Api::namespaced(client.clone(), &NAMESPACE);
+
+    let pods_client: Api<Pod> = Api::namespaced(client, &NAMESPACE);
     let lp = ListParams::default().labels(&format!("app=hawkeye,watcher_id={}", id));
-    let pods = pods_client.list(&lp).await.unwrap();
-    if let Some(pod_ip) = pods
+    pods_client
+        .list(&lp)
+        .await?
         .items
-        .first()
-        .map(|p| p.status.as_ref())
-        .flatten()
-        .map(|ps| ps.pod_ip.clone())
-        .flatten()
-    {
-        let http_client = reqwest::Client::builder()
-            .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
-            .build()
-            .unwrap();
-        // Try for new and old ports in pod
-        for port in vec![watcher.source.ingest_port, 3030] {
-            let url = format!("http://{}:{}/latest_frame", pod_ip, port);
-
-            log::info!("Calling Pod using url: {}", url);
-            let response = match http_client.get(url.as_str()).send().await {
-                Ok(r) => r,
-                Err(error) => {
-                    log::error!("Could not call {} endpoint: {:?}", url, error);
-                    *resp.status_mut() = StatusCode::EXPECTATION_FAILED;
-                    return Ok(resp);
-                }
-            };
+        .into_iter()
+        .next()
+        .and_then(|pod| pod.status)
+        .and_then(|status| status.pod_ip)
+        .map(|pod_ip| format!("http://{}:{}", pod_ip, ingest_port))
+        .ok_or_else(worker_unreachable)
+}
 
-            match response.error_for_status() {
-                Ok(image_response) => {
-                    let headers = resp.headers_mut();
-                    headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
-                    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+fn worker_unreachable() -> ApiError {
+    ApiError::Worker("The worker of the Watcher is not reachable".to_string())
+}
 
-                    let image_bytes = image_response.bytes().await.unwrap();
-                    *resp.body_mut() = Body::from(image_bytes.to_vec());
+/// Live detection state of a running Watcher: the current mode and since when, the slate matched,
+/// the last transition and the latest scores.
+#[instrument(skip(client))]
+pub async fn get_watcher_state(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
+    Ok(replied(watcher_state(&id, client).await))
+}
 
-                    return Ok(resp);
-                }
-                Err(_) => {
-                    continue;
-                }
-            }
+async fn watcher_state(id: &str, client: Client) -> Result<reply::Json, ApiError> {
+    let watcher = controller::spec(&get_resource(id, client.clone()).await?);
+    let worker = worker_address(id, watcher.source.ingest_port, client).await?;
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
+        .build()
+        .unwrap();
+    let url = format!("{}/state", worker);
+    let state = match http_client.get(url.as_str()).send().await {
+        Ok(response) => match response.error_for_status() {
+            Ok(response) => response.json::<serde_json::Value>().await,
+            Err(error) => Err(error),
+        },
+        Err(error) => Err(error),
+    };
+    match state {
+        Ok(state) => Ok(reply::json(&state)),
+        Err(error) => {
+            log::error!("Could not call {} endpoint: {:?}", url, error);
+            Err(worker_unreachable())
         }
-        log::error!("Error calling Pod using old and new urls");
-        *resp.status_mut() = StatusCode::EXPECTATION_FAILED;
-    } else {
-        log::debug!("Not able to get Pod IP");
-        *resp.status_mut() = StatusCode::EXPECTATION_FAILED;
     }
-    Ok(resp)
 }
 
-/// Start a Watcher worker by making sure there's a positive replica count for the Kubernetes
-/// deployment.
-pub async fn start_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+/// Transition sent to the actions of a Watcher to test them.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionSimulation {
+    pub from: VideoMode,
+    pub to: VideoMode,
+    /// Logs the actions instead of executing them.
+    pub dry_run: Option<bool>,
+}
 
-    // Get the Kubernetes deployment for the Watcher.
-    // TODO: probably better to just get the scale
-    let deployment = match deployments_client
-        .get(&templates::deployment_name(&id))
-        .await
-    {
-        Ok(d) => d,
-        Err(_) => {
+/// Makes the worker of a running Watcher execute the actions of a transition, as if it was
+/// detected in the stream, so the systems they call can be tested end-to-end.
+#[instrument(skip(client))]
+pub async fn simulate_transition(
+    id: String,
+    simulation: TransitionSimulation,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply = replied(simulate(&id, simulation, client.clone()).await);
+    Ok(audit::recorded(reply, client, &id, "simulate", caller).await)
+}
+
+async fn simulate(
+    id: &str,
+    simulation: TransitionSimulation,
+    client: Client,
+) -> Result<reply::WithStatus<reply::Json>, ApiError> {
+    if simulation.to == VideoMode::Any || simulation.from == simulation.to {
+        return Ok(reply::with_status(
+            reply::json(&json!({
+                "message": format!("Not a transition: {} to {}", simulation.from, simulation.to)
+            })),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let token = match CONTROL_TOKEN.as_ref() {
+        Some(token) => token,
+        None => {
             return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
+                reply::json(&json!({
+                    "message": "The transitions can only be simulated with HAWKEYE_CONTROL_TOKEN"
+                })),
+                StatusCode::BAD_REQUEST,
             ))
         }
     };
 
-    // Actions and guards based on the current Watcher status.
-    match deployment.get_watcher_status() {
-        Status::Running => Ok(reply::with_status(
-            // No op, already running!
-            reply::json(&json!({
-                "message": "Watcher is already running"
-            })),
-            StatusCode::OK,
+    let watcher = controller::spec(&get_resource(id, client.clone()).await?);
+    let worker = worker_address(id, watcher.source.ingest_port, client).await?;
+    let body = json!({
+        "from": simulation.from,
+        "to": simulation.to,
+        "dry_run": simulation.dry_run.unwrap_or(false),
+    });
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
+        .build()
+        .unwrap();
+    let url = format!("{}/simulate", worker);
+    let response = match http_client
+        .post(url.as_str())
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(error) => {
+            log::error!("Could not call {} endpoint: {:?}", url, error);
+            return Err(worker_unreachable());
+        }
+    };
+    let status_code = response.status();
+    match response.json::<serde_json::Value>().await {
+        // The actions run in the background, their results are in the history of the worker
+        Ok(accepted) if status_code.is_success() => Ok(reply::with_status(
+            reply::json(&json!({ "event_id": accepted["event_id"] })),
+            StatusCode::ACCEPTED,
         )),
-        Status::Pending => Ok(reply::with_status(
-            // No op, committing other changes.
-            reply::json(&json!({
-                "message": "Watcher is currently updating"
-            })),
-            StatusCode::CONFLICT,
+        Ok(error) => Ok(reply::with_status(
+            reply::json(&json!({ "message": error["error"] })),
+            StatusCode::from_u16(status_code.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
         )),
-        Status::Ready => {
-            // Start Watcher by setting Kubernetes deployment replicas=1
-            let mut patch_params = PatchParams::default();
-            patch_params.field_manager = Some("hawkeye_api".to_string());
-
-            // Set Kubernetes deployment replica=1 via patch.
-            let deployment_scale_json = json!({
-                "apiVersion": "autoscaling/v1",
-                "spec": { "replicas": 1 },
-            });
-            deployments_client
-                .patch_scale(
-                    deployment.metadata.name.as_ref().unwrap(),
-                    &patch_params,
-                    &Patch::Merge(&deployment_scale_json),
-                )
-                .await
-                .unwrap();
-
-            // Update the status of the Watcher to indicate it should be running.
-            let status_label_json = json!({
-                "apiVersion": "apps/v1",
-                "metadata": {
-                    "labels": {
-                        "target_status": Status::Running,
-                    }
-                }
-            });
-            deployments_client
-                .patch(
-                    deployment.metadata.name.as_ref().unwrap(),
-                    &patch_params,
-                    &Patch::Merge(status_label_json),
-                )
-                .await
-                .unwrap();
-
+        Err(error) => {
+            log::error!("Invalid response of {}: {:?}", url, error);
             Ok(reply::with_status(
+                reply::json(&json!({"message": "Invalid response of the worker"})),
+                StatusCode::BAD_GATEWAY,
+            ))
+        }
+    }
+}
+
+/// Log filters of the worker of a Watcher, like `debug` or `info,hawkeye_worker=trace`.
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct LogLevel {
+    pub level: String,
+    /// Changes the filters of the running worker too, instead of the next time it starts.
+    pub reload: Option<bool>,
+}
+
+/// Changes the log filters the worker of the Watcher starts with, kept in its ConfigMap, and
+/// the ones of its running worker with `reload`.
+#[instrument(skip(client))]
+pub async fn set_log_level(
+    id: String,
+    log_level: LogLevel,
+    caller: Caller,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    let reply = replied(change_log_level(&id, log_level, client.clone()).await);
+    Ok(audit::recorded(reply, client, &id, "log-level", caller).await)
+}
+
+async fn change_log_level(
+    id: &str,
+    log_level: LogLevel,
+    client: Client,
+) -> Result<reply::WithStatus<reply::Json>, ApiError> {
+    let level = log_level.level.trim();
+    if level.is_empty() || level.chars().any(char::is_whitespace) {
+        return Ok(reply::with_status(
+            reply::json(&json!({"message": "Invalid log level"})),
+            StatusCode::BAD_REQUEST,
+        ));
+    }
+    let reload = log_level.reload.unwrap_or(false);
+    // Checked first, so the level isn't saved when the request can't be fulfilled
+    let token = match (reload, CONTROL_TOKEN.as_ref()) {
+        (true, None) => {
+            return Ok(reply::with_status(
                 reply::json(&json!({
-                    "message": "Watcher is starting"
+                    "message": "The workers can only be reloaded with HAWKEYE_CONTROL_TOKEN"
                 })),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+        (_, token) => token,
+    };
+
+    let watcher = controller::spec(&get_resource(id, client.clone()).await?);
+    let config_maps_client: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let patch = json!({
+        "data": {
+            "log_level": level,
+        }
+    });
+    config_maps_client
+        .patch(
+            &templates::configmap_name(id),
+            &PatchParams::default(),
+            &Patch::Merge(&patch),
+        )
+        .await?;
+    let token = match token {
+        Some(token) if reload => token,
+        _ => {
+            return Ok(reply::with_status(
+                reply::json(&json!({"level": level, "reloaded": false})),
                 StatusCode::OK,
             ))
         }
-        Status::Error => Ok(reply::with_status(
-            reply::json(&json!({
-                "message": "Watcher in error state cannot be set to running"
-            })),
-            StatusCode::NOT_ACCEPTABLE,
+    };
+
+    let worker = match worker_address(id, watcher.source.ingest_port, client).await {
+        Ok(worker) => worker,
+        // Not running, the worker starts with the new level
+        Err(ApiError::NotFound(_)) | Err(ApiError::NotRunning) => {
+            return Ok(reply::with_status(
+                reply::json(&json!({"level": level, "reloaded": false})),
+                StatusCode::OK,
+            ))
+        }
+        Err(err) => return Err(err),
+    };
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
+        .build()
+        .unwrap();
+    let url = format!("{}/log_level", worker);
+    // Kept until the worker stops, it starts again with the level of the ConfigMap
+    let body = json!({"level": level, "duration_seconds": 0});
+    let result = http_client
+        .post(url.as_str())
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+    match result {
+        Ok(_) => Ok(reply::with_status(
+            reply::json(&json!({"level": level, "reloaded": true})),
+            StatusCode::OK,
         )),
+        Err(error) => {
+            log::error!("Could not call {} endpoint: {:?}", url, error);
+            Err(ApiError::Worker(
+                "The log level was saved but the worker of the Watcher could not reload it"
+                    .to_string(),
+            ))
+        }
     }
 }
 
-/// Stop a Watcher worker by making sure there's a replica count of 0 for the Kubernetes
-/// deployment.
-pub async fn stop_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    // TODO: probably better to just get the scale
-    let deployment = match deployments_client
-        .get(&templates::deployment_name(&id))
-        .await
-    {
-        Ok(d) => d,
-        Err(_) => {
+/// Slate compared with the stream by the validation, the slates of the Watcher when missing.
+#[derive(Deserialize, Debug)]
+pub struct SlateValidation {
+    pub slate_url: Option<String>,
+    pub slate_threshold: Option<u32>,
+}
+
+/// Scores the latest frame of a running Watcher against a slate, to confirm a new slate matches
+/// the stream before the Watcher is changed to use it.
+#[instrument(skip(client))]
+pub async fn validate_slate(
+    id: String,
+    validation: SlateValidation,
+    client: Client,
+) -> Result<impl warp::Reply, Infallible> {
+    Ok(replied(score_slate(&id, validation, client).await))
+}
+
+async fn score_slate(
+    id: &str,
+    validation: SlateValidation,
+    client: Client,
+) -> Result<reply::WithStatus<reply::Json>, ApiError> {
+    if let Some(url) = validation.slate_url.as_ref() {
+        if !SLATE_URL_SCHEMES
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+        {
             return Ok(reply::with_status(
-                reply::json(&json!({})),
-                StatusCode::NOT_FOUND,
+                reply::json(&json!({
+                    "message": format!("{} not recognized as a valid URL!", url)
+                })),
+                StatusCode::BAD_REQUEST,
+            ));
+        }
+    }
+    let token = match CONTROL_TOKEN.as_ref() {
+        Some(token) => token,
+        None => {
+            return Ok(reply::with_status(
+                reply::json(&json!({
+                    "message": "The slates can only be validated with HAWKEYE_CONTROL_TOKEN"
+                })),
+                StatusCode::BAD_REQUEST,
             ))
         }
     };
-    // TODO: Set target_status to Ready
-    match deployment.get_watcher_status() {
-        Status::Ready => Ok(reply::with_status(
-            reply::json(&json!({
-                "message": "Watcher is already stopped"
-            })),
+
+    let watcher = controller::spec(&get_resource(id, client.clone()).await?);
+    let worker = worker_address(id, watcher.source.ingest_port, client).await?;
+
+    let slate_urls: Vec<String> = match validation.slate_url {
+        Some(url) => vec![url],
+        None => watcher.slate_urls().cloned().collect(),
+    };
+    let body = json!({
+        "slate_urls": slate_urls,
+        "slate_threshold": validation.slate_threshold.or(watcher.slate_threshold),
+        "slate_region": watcher.slate_region,
+        "slate_authorization": watcher.slate_authorization,
+        "slate_headers": watcher.slate_headers,
+    });
+    // The slates are downloaded by the worker, it takes longer than fetching a frame
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT + 30))
+        .build()
+        .unwrap();
+    let url = format!("{}/slate_score", worker);
+    log::info!("Calling Pod using url: {}", url);
+    let response = match http_client
+        .post(url.as_str())
+        .bearer_auth(token)
+        .json(&body)
+        .send()
+        .await
+    {
+        Ok(r) => r,
+        Err(error) => {
+            log::error!("Could not call {} endpoint: {:?}", url, error);
+            return Err(worker_unreachable());
+        }
+    };
+    let status_code = response.status();
+    match response.json::<serde_json::Value>().await {
+        Ok(scores) if status_code.is_success() => Ok(reply::with_status(
+            reply::json(&json!({ "scores": scores })),
             StatusCode::OK,
         )),
-        Status::Pending => Ok(reply::with_status(
-            reply::json(&json!({
-                "message": "Watcher is currently updating"
-            })),
-            StatusCode::CONFLICT,
+        Ok(error) => Ok(reply::with_status(
+            reply::json(&json!({ "message": error["error"] })),
+            StatusCode::from_u16(status_code.as_u16()).unwrap_or(StatusCode::BAD_GATEWAY),
         )),
-        Status::Running => {
-            // Stop watcher / replicas to 0
-            let mut patch_params = PatchParams::default();
-            patch_params.field_manager = Some("hawkeye_api".to_string());
-
-            let deployment_scale_json = json!({
-                "apiVersion": "autoscaling/v1",
-                "spec": { "replicas": 0 },
-            });
-            deployments_client
-                .patch_scale(
-                    deployment.metadata.name.as_ref().unwrap(),
-                    &patch_params,
-                    &Patch::Merge(&deployment_scale_json),
-                )
-                .await
-                .unwrap();
-
-            // Update the status of the Watcher to indicate it should be running.
-            let status_label_json = json!({
-                "apiVersion": "apps/v1",
-                "metadata": {
-                    "labels": {
-                        "target_status": Status::Ready,
-                    }
-                }
-            });
-            deployments_client
-                .patch(
-                    deployment.metadata.name.as_ref().unwrap(),
-                    &patch_params,
-                    &Patch::Merge(status_label_json),
-                )
-                .await
-                .unwrap();
-
+        Err(error) => {
+            log::error!("Invalid response of {}: {:?}", url, error);
             Ok(reply::with_status(
-                reply::json(&json!({
-                    "message": "Watcher is stopping"
-                })),
-                StatusCode::OK,
+                reply::json(&json!({"message": "Invalid response of the worker"})),
+                StatusCode::BAD_GATEWAY,
             ))
         }
-        Status::Error => Ok(reply::with_status(
+    }
+}
+
+#[instrument(skip(contents))]
+pub async fn upload_slate(
+    content_type: Option<String>,
+    contents: Bytes,
+) -> Result<impl warp::Reply, Infallible> {
+    // Without its parameters, like `charset`
+    let content_type = content_type.unwrap_or_default();
+    let content_type = content_type.split(';').next().unwrap_or_default().trim();
+    match slates::save(content_type, &contents).await {
+        Ok(Some(asset)) => Ok(reply::with_status(reply::json(&asset), StatusCode::CREATED)),
+        Ok(None) => Ok(reply::with_status(
             reply::json(&json!({
-                "message": "Watcher in error state cannot be set to stopped"
+                "message": format!("Slates of type {:?} are not supported", content_type)
             })),
-            StatusCode::NOT_ACCEPTABLE,
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
         )),
+        Err(err) => {
+            log::error!("Could not store the slate: {:?}", err);
+            Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not store the slate"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
     }
 }
 
-pub async fn delete_watcher(id: String, client: Client) -> Result<impl warp::Reply, Infallible> {
-    let dp = DeleteParams::default();
+pub async fn list_slates() -> Result<impl warp::Reply, Infallible> {
+    match slates::list().await {
+        Ok(assets) => Ok(reply::with_status(reply::json(&assets), StatusCode::OK)),
+        Err(err) => {
+            log::error!("Could not list the slates: {:?}", err);
+            Ok(reply::with_status(
+                reply::json(&json!({"message": "Could not list the slates"})),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ))
+        }
+    }
+}
 
-    let deployments_client: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
-    let _ = deployments_client
-        .delete(&templates::deployment_name(&id), &dp)
-        .await;
+pub async fn get_slate(id: String) -> Result<impl warp::Reply, Infallible> {
+    let mut resp = warp::reply::Response::new(Body::empty());
+    match slates::load(&id).await {
+        Some((content_type, contents)) => {
+            let headers = resp.headers_mut();
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+            // The contents of an id never change
+            headers.insert(
+                CACHE_CONTROL,
+                HeaderValue::from_static("public, max-age=31536000, immutable"),
+            );
+            *resp.body_mut() = Body::from(contents);
+        }
+        None => *resp.status_mut() = StatusCode::NOT_FOUND,
+    }
+    Ok(resp)
+}
 
-    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
-    let _ = config_maps
-        .delete(&templates::configmap_name(&id), &dp)
-        .await;
+lazy_static! {
+    /// The OpenAPI document of the API, maintained next to the code in `api.yaml`.
+    static ref OPENAPI: Option<serde_json::Value> =
+        match serde_yaml::from_str(include_str!("../../api.yaml")) {
+            Ok(document) => Some(document),
+            Err(err) => {
+                log::error!("api.yaml is not a valid YAML document: {}", err);
+                None
+            }
+        };
+}
 
-    let services: Api<Service> = Api::namespaced(client, &NAMESPACE);
-    match services.delete(&templates::service_name(&id), &dp).await {
-        Ok(_) => Ok(reply::with_status(
-            reply::json(&json!({
-                "message": "Watcher has been deleted"
-            })),
-            StatusCode::OK,
-        )),
-        Err(_) => Ok(reply::with_status(
-            reply::json(&json!({
-                "message": "Watcher does not exist"
-            })),
-            StatusCode::NOT_FOUND,
-        )),
-    }
+pub async fn openapi() -> Result<impl warp::Reply, Infallible> {
+    Ok(match &*OPENAPI {
+        Some(document) => reply::with_status(reply::json(document), StatusCode::OK),
+        None => reply::with_status(
+            reply::json(&json!({"message": "The OpenAPI document is not available"})),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        ),
+    })
+}
+
+/// Renders the OpenAPI document with ReDoc, like the `index.html` of the repository.
+pub async fn docs() -> Result<impl warp::Reply, Infallible> {
+    Ok(reply::html(include_str!("docs.html")))
 }
 
+#[instrument(skip(client))]
 pub async fn healthcheck(client: Client) -> Result<impl warp::Reply, Infallible> {
     match client.apiserver_version().await {
         Ok(_info) => Ok(reply::with_status(
@@ -572,7 +2787,7 @@ pub async fn healthcheck(client: Client) -> Result<impl warp::Reply, Infallible>
     }
 }
 
-trait WatcherStatus {
+pub(crate) trait WatcherStatus {
     fn get_watcher_status(&self) -> Status;
 }
 
@@ -590,7 +2805,7 @@ impl WatcherStatus for Deployment {
             .flatten()
             .flatten()
             .unwrap_or({
-                let name = self.metadata.name.as_ref().expect("Name must be present");
+                let name = self.metadata.name.as_deref().unwrap_or_default();
                 log::error!(
                     "Deployment {} is missing required 'target_status' label",
                     name
@@ -616,3 +2831,58 @@ impl WatcherStatus for Deployment {
         }
     }
 }
+
+pub(crate) trait IngestAddress {
+    fn ingest_address(&self) -> Option<String>;
+}
+
+impl IngestAddress for Service {
+    /// The hostname (or IP) of the LoadBalancer receiving the video feed, when provisioned, or the
+    /// address of a `ClusterIP` Service.
+    fn ingest_address(&self) -> Option<String> {
+        let cluster_ip = self
+            .spec
+            .as_ref()
+            .filter(|spec| spec.type_.as_deref() == Some("ClusterIP"))
+            .and_then(|spec| spec.cluster_ip.clone());
+        if cluster_ip.is_some() {
+            return cluster_ip;
+        }
+        self.status
+            .as_ref()
+            .map(|s| s.load_balancer.as_ref())
+            .flatten()
+            .map(|lbs| lbs.ingress.as_ref())
+            .flatten()
+            .map(|lbs| lbs.first())
+            .flatten()
+            .map(|lb| lb.clone().hostname.or(lb.clone().ip))
+            .flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etags_match() {
+        let metadata = ObjectMeta {
+            resource_version: Some("42".to_string()),
+            ..ObjectMeta::default()
+        };
+        let etag = etag(&metadata);
+        assert_eq!(etag.as_deref(), Some("\"42\""));
+
+        assert!(etag_matches("\"42\"", etag.as_deref()));
+        assert!(etag_matches("\"41\", \"42\"", etag.as_deref()));
+        assert!(etag_matches("*", etag.as_deref()));
+        assert!(etag_matches("*", None));
+
+        assert!(!etag_matches("\"41\"", etag.as_deref()));
+        // Entity tags are quoted
+        assert!(!etag_matches("42", etag.as_deref()));
+        assert!(!etag_matches("\"42\"", None));
+        assert!(!etag_matches("", etag.as_deref()));
+    }
+}
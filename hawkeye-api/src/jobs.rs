@@ -0,0 +1,245 @@
+use crate::audit::Caller;
+use crate::config::NAMESPACE;
+use crate::store::{document, Store};
+use chrono::{DateTime, Duration, Utc};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{DeleteParams, ListParams};
+use kube::{Api, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::http::StatusCode;
+use warp::hyper::body;
+use warp::reply;
+use warp::Reply;
+
+/// Hours the jobs are kept after their last change, the ones of an API replica which stopped
+/// while running them included.
+const RETENTION_HOURS: i64 = 1;
+
+/// Builds an idempotent name for the `ConfigMap` with the progress of the job `id`.
+pub fn configmap_name(id: &str) -> String {
+    format!("hawkeye-job-{}", id)
+}
+
+/// Key of the job in its `ConfigMap`.
+const KEY: &str = "job.json";
+
+/// The progress of the job `id`.
+fn store(client: Client, id: &str) -> Store {
+    // Not `app=hawkeye`, it's not a watcher configuration
+    let labels = json!({ "app": "hawkeye-jobs" });
+    Store::new(client, configmap_name(id), KEY, labels)
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A part of the job done, like the upgrade of one of the watchers of a fleet upgrade.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Step {
+    pub name: String,
+    pub status_code: u16,
+    pub message: Option<String>,
+}
+
+/// An operation running in the background. Its progress is kept in a ConfigMap, so any replica of
+/// the API reports it.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Job {
+    pub id: String,
+    pub operation: String,
+    pub watcher_id: Option<String>,
+    pub caller: Caller,
+    pub status: JobStatus,
+    pub created_at: String,
+    pub updated_at: String,
+    /// Number of steps of the job, once known.
+    pub total_steps: Option<usize>,
+    pub steps: Vec<Step>,
+    /// Status code and body the operation replied with, once done.
+    pub status_code: Option<u16>,
+    pub result: Option<serde_json::Value>,
+}
+
+/// Handle of a running job, to report its steps.
+#[derive(Clone)]
+pub struct Progress {
+    job: Arc<Mutex<Job>>,
+    client: Client,
+}
+
+impl Progress {
+    /// Sets the number of steps of the job.
+    pub async fn expect(&self, total_steps: usize) {
+        let mut job = self.job.lock().await;
+        job.total_steps = Some(total_steps);
+        save(&mut job, self.client.clone()).await;
+    }
+
+    /// Records a step of the job.
+    pub async fn step(&self, name: &str, status_code: StatusCode, message: Option<String>) {
+        let mut job = self.job.lock().await;
+        job.steps.push(Step {
+            name: name.to_string(),
+            status_code: status_code.as_u16(),
+            message,
+        });
+        save(&mut job, self.client.clone()).await;
+    }
+
+    async fn finish(&self, response: reply::Response) {
+        let status_code = response.status();
+        let result: Option<serde_json::Value> = match body::to_bytes(response.into_body()).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).ok(),
+            Err(_) => None,
+        };
+        let mut job = self.job.lock().await;
+        // Operations on a single watcher are a single step
+        if job.steps.is_empty() {
+            let message = result
+                .as_ref()
+                .and_then(|result| result["message"].as_str())
+                .map(String::from);
+            job.steps.push(Step {
+                name: job.operation.clone(),
+                status_code: status_code.as_u16(),
+                message,
+            });
+        }
+        // Like a fleet upgrade replying with the Watchers which failed
+        let step_failed = job.steps.iter().any(|step| step.status_code >= 400);
+        job.status = if status_code.is_success() && !step_failed {
+            JobStatus::Succeeded
+        } else {
+            JobStatus::Failed
+        };
+        job.status_code = Some(status_code.as_u16());
+        job.result = result;
+        save(&mut job, self.client.clone()).await;
+    }
+}
+
+/// Runs the operation in the background and replies with the id of its job, `202 Accepted`.
+pub async fn spawn<F, Fut, R>(
+    client: Client,
+    operation: &str,
+    watcher_id: Option<String>,
+    caller: Caller,
+    work: F,
+) -> reply::Response
+where
+    F: FnOnce(Progress) -> Fut,
+    Fut: Future<Output = R> + Send + 'static,
+    R: Reply,
+{
+    let now = Utc::now().to_rfc3339();
+    let job = Job {
+        id: Uuid::new_v4().to_string(),
+        operation: operation.to_string(),
+        watcher_id,
+        caller,
+        status: JobStatus::Running,
+        created_at: now.clone(),
+        updated_at: now,
+        total_steps: None,
+        steps: Vec::new(),
+        status_code: None,
+        result: None,
+    };
+    if let Err(err) = store(client.clone(), &job.id).create(&job).await {
+        log::error!("Could not create the job of {}: {:?}", operation, err);
+        return reply::with_status(
+            reply::json(&json!({"message": "Could not start the job"})),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response();
+    }
+    tokio::spawn(prune(client.clone()));
+
+    let id = job.id.clone();
+    let progress = Progress {
+        job: Arc::new(Mutex::new(job)),
+        client,
+    };
+    let running = work(progress.clone());
+    tokio::spawn(async move {
+        let response = running.await.into_response();
+        progress.finish(response).await;
+    });
+    reply::with_status(
+        reply::json(&json!({
+            "job_id": id,
+            "url": format!("/v1/jobs/{}", id),
+        })),
+        StatusCode::ACCEPTED,
+    )
+    .into_response()
+}
+
+/// Runs the operation in the background when asked to, like with `?async=true`, otherwise replies
+/// with its result.
+pub async fn maybe_spawn<Fut, R>(
+    in_background: bool,
+    client: Client,
+    operation: &str,
+    watcher_id: Option<String>,
+    caller: Caller,
+    work: Fut,
+) -> reply::Response
+where
+    Fut: Future<Output = R> + Send + 'static,
+    R: Reply,
+{
+    if in_background {
+        spawn(client, operation, watcher_id, caller, |_| work).await
+    } else {
+        work.await.into_response()
+    }
+}
+
+/// The job, unless it doesn't exist or was pruned.
+pub async fn get(client: Client, id: &str) -> Option<Job> {
+    store(client, id).get().await.ok().flatten()
+}
+
+/// Stores the progress of the job. Failures are logged and otherwise ignored, they don't fail
+/// the operation.
+async fn save(job: &mut Job, client: Client) {
+    job.updated_at = Utc::now().to_rfc3339();
+    // Only the replica running the job changes it
+    if let Err(err) = store(client, &job.id).put(job).await {
+        log::warn!("Could not save the progress of job {}: {:?}", job.id, err);
+    }
+}
+
+/// Deletes the jobs not changed within the retention.
+async fn prune(client: Client) {
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let lp = ListParams::default().labels("app=hawkeye-jobs").timeout(10);
+    let jobs = match config_maps.list(&lp).await {
+        Ok(jobs) => jobs,
+        Err(err) => {
+            log::warn!("Could not list the jobs: {:?}", err);
+            return;
+        }
+    };
+    let oldest = Utc::now() - Duration::hours(RETENTION_HOURS);
+    for config_map in jobs.items {
+        let updated_at = document::<Job>(&config_map, KEY)
+            .and_then(|job| DateTime::parse_from_rfc3339(&job.updated_at).ok());
+        if matches!(updated_at, Some(updated_at) if updated_at < oldest) {
+            if let Some(name) = config_map.metadata.name.as_ref() {
+                let _ = config_maps.delete(name, &DeleteParams::default()).await;
+            }
+        }
+    }
+}
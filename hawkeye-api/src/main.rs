@@ -1,9 +1,23 @@
+mod audit;
 mod auth;
+mod cache;
 mod config;
+mod controller;
+mod crd;
+mod errors;
 mod filters;
 mod handlers;
+mod jobs;
+mod maintenance;
+mod monitor;
+mod ports;
+mod revisions;
+mod slates;
+mod store;
 mod templates;
+mod webhooks;
 
+use hawkeye_core::telemetry::maybe_bootstrap_tracing;
 use hawkeye_core::utils::maybe_bootstrap_sentry;
 use kube::Client;
 use std::env;
@@ -20,13 +34,25 @@ async fn main() -> anyhow::Result<()> {
     // `sentry_client` must be in scope in main() to stay alive and functional.
     let sentry_client = maybe_bootstrap_sentry();
     if sentry_client.is_none() {
-        pretty_env_logger::init();
+        hawkeye_core::logging::init();
     }
+    // `tracing` must be in scope in main() for the remaining spans to be exported on exit.
+    let _tracing = maybe_bootstrap_tracing("hawkeye-api");
 
+    ports::check_pool()?;
     let client = Client::try_default().await?;
 
-    let v1 = filters::v1(client);
-    let routes = v1.with(warp::log("watchers"));
+    // Before the workers are deployed, they read it when they start
+    monitor::store_control_token(client.clone()).await;
+    tokio::spawn(monitor::adopt_watcher_objects(client.clone()));
+    tokio::spawn(controller::run(client.clone()));
+    tokio::spawn(monitor::watch_ingest_addresses(client.clone()));
+    tokio::spawn(monitor::expire_watchers(client.clone()));
+    tokio::spawn(monitor::watch_errors(client.clone()));
+
+    let cache = cache::Cache::start(client.clone());
+    let v1 = filters::v1(client, cache);
+    let routes = v1.with(warp::log("watchers")).with(warp::trace::request());
 
     log::info!("Running API at 0.0.0.0:8080 ..");
     warp::serve(routes).run(([0, 0, 0, 0], 8080)).await;
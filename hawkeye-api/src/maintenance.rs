@@ -0,0 +1,45 @@
+use crate::audit::Caller;
+use crate::store::Store;
+use kube::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Name of the `ConfigMap` kept while the cluster is in maintenance, there is only one.
+pub const CONFIGMAP_NAME: &str = "hawkeye-maintenance";
+
+/// The cluster is in maintenance, like during a cluster upgrade: the watchers running when it
+/// started were stopped, and they're started again when it ends.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Maintenance {
+    pub entered_at: String,
+    pub caller: Caller,
+    /// Watchers running, or starting, when the maintenance started.
+    pub watchers: Vec<String>,
+}
+
+/// The maintenance in progress, kept while it lasts.
+fn store(client: Client) -> Store {
+    // Not `app=hawkeye`, it's not a watcher configuration
+    let labels = json!({ "app": "hawkeye-maintenance" });
+    Store::new(
+        client,
+        CONFIGMAP_NAME.to_string(),
+        "maintenance.json",
+        labels,
+    )
+}
+
+/// The maintenance in progress, if any.
+pub async fn get(client: Client) -> Result<Option<Maintenance>, kube::Error> {
+    store(client).get().await
+}
+
+/// Stores the maintenance, it fails with a `409 Conflict` when one is already in progress.
+pub async fn start(client: Client, maintenance: &Maintenance) -> Result<(), kube::Error> {
+    store(client).create(maintenance).await
+}
+
+/// Ends the maintenance, once its watchers were started again.
+pub async fn end(client: Client) -> Result<(), kube::Error> {
+    store(client).delete().await
+}
@@ -0,0 +1,416 @@
+use crate::audit::{self, Caller};
+use crate::config::{CONTROL_TOKEN, NAMESPACE};
+use crate::crd::WatcherResource;
+use crate::handlers::{self, IngestAddress, WatcherStatus};
+use crate::store;
+use crate::templates;
+use crate::webhooks::{self, WebhookEvent};
+use chrono::Utc;
+use futures::{StreamExt, TryStreamExt};
+use hawkeye_core::models::{ExpiryAction, Status, Watcher};
+use k8s_openapi::api::apps::v1::Deployment;
+use k8s_openapi::api::core::v1::{ConfigMap, Pod, Secret, Service};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::api::{ListParams, Patch, PatchParams, PostParams};
+use kube::runtime::utils::try_flatten_applied;
+use kube::runtime::watcher;
+use kube::{Api, Client};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+use warp::http::StatusCode;
+
+/// Time between the checks of the expiration of the watchers.
+const EXPIRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time between the checks of the statuses of the watchers.
+const STATUS_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Watches the Services of all watchers and keeps their stored `ingest_ip` in sync with the
+/// LoadBalancer address, notifying the webhook when an address changes.
+///
+/// Encoder operators need to know when the address changes, otherwise the feed silently stops
+/// arriving at the watcher.
+pub async fn watch_ingest_addresses(client: Client) {
+    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+    let lp = ListParams::default().labels("app=hawkeye,watcher_id");
+    let mut events = try_flatten_applied(watcher(services, lp)).boxed();
+
+    log::info!("Watching ingest addresses of the watchers..");
+    loop {
+        match events.try_next().await {
+            Ok(Some(service)) => {
+                if let Err(err) = sync_ingest_address(client.clone(), &service).await {
+                    log::error!("Could not sync ingest address: {:?}", err);
+                }
+            }
+            Ok(None) => break,
+            Err(err) => {
+                // The watcher restarts itself in the next poll, we just avoid a busy loop here
+                log::error!("Error while watching Services: {:?}", err);
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+        }
+    }
+    log::warn!("Stopped watching ingest addresses");
+}
+
+async fn sync_ingest_address(client: Client, service: &Service) -> anyhow::Result<()> {
+    let watcher_id = match service
+        .metadata
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get("watcher_id"))
+    {
+        Some(id) => id.clone(),
+        None => return Ok(()),
+    };
+    let ingest_ip = match service.ingest_address() {
+        Some(address) => address,
+        // LoadBalancer still provisioning, nothing to sync
+        None => return Ok(()),
+    };
+
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let name = templates::configmap_name(&watcher_id);
+    let mut attempt = 1;
+    let previous_ingest_ip = loop {
+        let mut config_map = config_maps.get(&name).await?;
+        let data = config_map.data.get_or_insert_with(Default::default);
+        let mut watcher: Watcher = serde_json::from_str(
+            data.get("watcher.json")
+                .ok_or_else(|| anyhow::anyhow!("ConfigMap without watcher.json"))?,
+        )?;
+        if watcher.source.ingest_ip.as_ref() == Some(&ingest_ip) {
+            return Ok(());
+        }
+
+        let previous_ingest_ip = watcher.source.ingest_ip.replace(ingest_ip.clone());
+        data.insert("watcher.json".to_string(), serde_json::to_string(&watcher)?);
+        // Fails when the configuration was changed since it was read, like by the controller,
+        // instead of overwriting the change
+        match config_maps
+            .replace(&name, &PostParams::default(), &config_map)
+            .await
+        {
+            Ok(_) => break previous_ingest_ip,
+            Err(kube::Error::Api(err)) if err.code == 409 && attempt < store::ATTEMPTS => {
+                log::debug!("{} changed concurrently, syncing it again", name);
+                attempt += 1;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+
+    match previous_ingest_ip {
+        Some(previous) => {
+            log::warn!(
+                "Ingest address of watcher {} changed from {} to {}",
+                watcher_id,
+                previous,
+                ingest_ip
+            );
+            webhooks::notify(WebhookEvent::IngestAddressChanged {
+                watcher_id,
+                previous_ingest_ip: Some(previous),
+                ingest_ip,
+            });
+        }
+        None => log::info!("Watcher {} is reachable at {}", watcher_id, ingest_ip),
+    }
+    Ok(())
+}
+
+/// Stops the watchers once they reach their `expires_at`, or deletes them with
+/// `on_expiry: delete`, so temporary watchers aren't left running by mistake.
+pub async fn expire_watchers(client: Client) {
+    log::info!("Expiring the watchers..");
+    loop {
+        if let Err(err) = expire(client.clone()).await {
+            log::error!("Could not expire the watchers: {:?}", err);
+        }
+        tokio::time::sleep(EXPIRY_INTERVAL).await;
+    }
+}
+
+async fn expire(client: Client) -> anyhow::Result<()> {
+    let lp = ListParams::default().labels("app=hawkeye,watcher_id");
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let (config_maps, deployments) =
+        futures::try_join!(config_maps.list(&lp), deployments.list(&lp))?;
+
+    let mut statuses = HashMap::new();
+    for deploy in deployments.items {
+        if let Some(watcher_id) = deploy
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+        {
+            statuses.insert(watcher_id.clone(), deploy.get_watcher_status());
+        }
+    }
+
+    let now = Utc::now();
+    for config_map in config_maps.items {
+        let watcher: Watcher = match config_map
+            .data
+            .as_ref()
+            .and_then(|data| data.get("watcher.json"))
+            .and_then(|contents| serde_json::from_str(contents).ok())
+        {
+            Some(watcher) => watcher,
+            None => continue,
+        };
+        if !watcher.expired(now) {
+            continue;
+        }
+        let watcher_id = watcher.id.clone().unwrap_or_default();
+        let (operation, status_code) = match watcher.on_expiry.unwrap_or(ExpiryAction::Stop) {
+            ExpiryAction::Delete => {
+                handlers::delete_watcher_objects(&watcher_id, client.clone()).await;
+                ("delete", StatusCode::OK)
+            }
+            // Stopped watchers are left as they are, they can't be started until they're extended
+            ExpiryAction::Stop if statuses.get(&watcher_id) == Some(&Status::Running) => {
+                let (status_code, _) =
+                    handlers::scale_watcher(&watcher_id, Status::Ready, client.clone()).await;
+                ("stop", status_code)
+            }
+            ExpiryAction::Stop => continue,
+        };
+
+        let expires_at = watcher.expires_at.unwrap_or_default();
+        log::info!(
+            "Watcher {} expired at {}: {} ({})",
+            watcher_id,
+            expires_at,
+            operation,
+            status_code
+        );
+        let caller = Caller {
+            principal: Some("hawkeye-expiry".to_string()),
+            ..Caller::default()
+        };
+        audit::record(client.clone(), &watcher_id, operation, &caller, status_code).await;
+        if status_code.is_success() {
+            webhooks::notify(WebhookEvent::WatcherExpired {
+                watcher_id: watcher_id.clone(),
+                expires_at,
+                operation,
+            });
+            if operation == "delete" {
+                webhooks::notify(WebhookEvent::WatcherDeleted { watcher_id });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Notifies the webhook when a watcher enters or leaves the error state, so incident tooling is
+/// informed without polling the API.
+///
+/// The first check only records the statuses: the watchers already failing when the API starts
+/// were reported before it restarted.
+pub async fn watch_errors(client: Client) {
+    log::info!("Watching the errors of the watchers..");
+    let mut statuses = None;
+    loop {
+        match check_errors(client.clone(), statuses.as_ref()).await {
+            Ok(current) => statuses = Some(current),
+            Err(err) => log::error!("Could not check the statuses of the watchers: {:?}", err),
+        }
+        tokio::time::sleep(STATUS_INTERVAL).await;
+    }
+}
+
+async fn check_errors(
+    client: Client,
+    previous: Option<&HashMap<String, Status>>,
+) -> anyhow::Result<HashMap<String, Status>> {
+    let lp = ListParams::default().labels("app=hawkeye,watcher_id");
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let pods: Api<Pod> = Api::namespaced(client, &NAMESPACE);
+    let (deployments, pods) = futures::try_join!(deployments.list(&lp), pods.list(&lp))?;
+    let mut reasons = handlers::waiting_reasons(pods.items);
+
+    let mut statuses = HashMap::new();
+    for deploy in deployments.items {
+        let watcher_id = match deploy
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+        {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+        let status = deploy.get_watcher_status();
+        if let Some(previous) = previous {
+            let was_error = previous.get(&watcher_id) == Some(&Status::Error);
+            let event = match (was_error, status == Status::Error) {
+                (false, true) => {
+                    let reason = reasons.remove(&watcher_id).filter(|r| !r.is_empty());
+                    log::warn!("Watcher {} is in error: {:?}", watcher_id, reason);
+                    Some(WebhookEvent::WatcherErrored {
+                        watcher_id: watcher_id.clone(),
+                        reason,
+                    })
+                }
+                (true, false) => {
+                    log::info!("Watcher {} recovered, it is {:?}", watcher_id, status);
+                    Some(WebhookEvent::WatcherRecovered {
+                        watcher_id: watcher_id.clone(),
+                        status,
+                    })
+                }
+                _ => None,
+            };
+            if let Some(event) = event {
+                webhooks::notify(event);
+            }
+        }
+        statuses.insert(watcher_id, status);
+    }
+    Ok(statuses)
+}
+
+/// Stores the `HAWKEYE_CONTROL_TOKEN` of the API in the `Secret` the workers read it from, when
+/// it's set, replacing the token stored before.
+pub async fn store_control_token(client: Client) {
+    let token = match CONTROL_TOKEN.as_ref() {
+        Some(token) => token,
+        None => return,
+    };
+    let secrets: Api<Secret> = Api::namespaced(client, &NAMESPACE);
+    let secret = templates::build_control_token_secret(token);
+    let pp = PatchParams::apply("hawkeye-api").force();
+    if let Err(err) = secrets
+        .patch(templates::CONTROL_TOKEN_SECRET, &pp, &Patch::Apply(&secret))
+        .await
+    {
+        log::error!(
+            "Could not store the control token of the workers: {:?}",
+            err
+        );
+    }
+}
+
+/// Makes the ConfigMaps of the watchers created before they owned the other objects of the
+/// watcher the owners of their Deployment and Service, so they're deleted along with them too.
+/// The watchers created before the `Watcher` resources get one, owning their ConfigMap.
+pub async fn adopt_watcher_objects(client: Client) {
+    if let Err(err) = adopt(client).await {
+        log::error!("Could not adopt the objects of the watchers: {:?}", err);
+    }
+}
+
+async fn adopt(client: Client) -> anyhow::Result<()> {
+    let lp = ListParams::default().labels("app=hawkeye,watcher_id");
+    let config_maps: Api<ConfigMap> = Api::namespaced(client.clone(), &NAMESPACE);
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &NAMESPACE);
+    let services: Api<Service> = Api::namespaced(client.clone(), &NAMESPACE);
+    let (config_map_list, deployment_list, service_list) = futures::try_join!(
+        config_maps.list(&lp),
+        deployments.list(&lp),
+        services.list(&lp)
+    )?;
+
+    // Id of the watcher of an object without owner
+    let orphan = |metadata: &ObjectMeta| {
+        let owned =
+            matches!(metadata.owner_references.as_ref(), Some(owners) if !owners.is_empty());
+        if owned {
+            None
+        } else {
+            metadata
+                .labels
+                .as_ref()
+                .and_then(|labels| labels.get("watcher_id"))
+                .cloned()
+        }
+    };
+    let orphan_deployments: HashSet<String> = deployment_list
+        .items
+        .iter()
+        .filter_map(|deploy| orphan(&deploy.metadata))
+        .collect();
+    let orphan_services: HashSet<String> = service_list
+        .items
+        .iter()
+        .filter_map(|service| orphan(&service.metadata))
+        .collect();
+
+    let pp = PatchParams::default();
+    for config_map in config_map_list.items {
+        let watcher_id = match config_map
+            .metadata
+            .labels
+            .as_ref()
+            .and_then(|labels| labels.get("watcher_id"))
+        {
+            Some(id) => id.clone(),
+            None => continue,
+        };
+        if orphan(&config_map.metadata).is_some() {
+            match adopt_config_map(&config_map, &watcher_id, client.clone()).await {
+                Ok(()) => log::info!("ConfigMap of watcher {} adopted", watcher_id),
+                Err(err) => {
+                    log::warn!("Could not adopt the ConfigMap of {}: {:?}", watcher_id, err)
+                }
+            }
+        }
+        let patch = json!({
+            "metadata": {
+                "ownerReferences": templates::owner_references(&config_map),
+            }
+        });
+        if orphan_deployments.contains(&watcher_id) {
+            let name = templates::deployment_name(&watcher_id);
+            match deployments.patch(&name, &pp, &Patch::Merge(&patch)).await {
+                Ok(_) => log::info!("Deployment of watcher {} adopted", watcher_id),
+                Err(err) => log::warn!("Could not adopt {}: {:?}", name, err),
+            }
+        }
+        if orphan_services.contains(&watcher_id) {
+            let name = templates::service_name(&watcher_id);
+            match services.patch(&name, &pp, &Patch::Merge(&patch)).await {
+                Ok(_) => log::info!("Service of watcher {} adopted", watcher_id),
+                Err(err) => log::warn!("Could not adopt {}: {:?}", name, err),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Creates the `Watcher` resource of the watcher from its ConfigMap, and makes it the owner of the
+/// ConfigMap.
+async fn adopt_config_map(
+    config_map: &ConfigMap,
+    watcher_id: &str,
+    client: Client,
+) -> anyhow::Result<()> {
+    let (_, watcher) = handlers::stored_watcher(config_map)?;
+    let resources: Api<WatcherResource> = Api::namespaced(client.clone(), &NAMESPACE);
+    let resource = WatcherResource::new(watcher_id, handlers::definition(watcher));
+    let resource = match resources.create(&PostParams::default(), &resource).await {
+        Err(kube::Error::Api(err)) if err.code == 409 => resources.get(watcher_id).await?,
+        result => result?,
+    };
+
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let patch = json!({
+        "metadata": {
+            "ownerReferences": resource.owner_references(),
+        }
+    });
+    config_maps
+        .patch(
+            &templates::configmap_name(watcher_id),
+            &PatchParams::default(),
+            &Patch::Merge(&patch),
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+use crate::config::{INGEST_PORT_MAX, INGEST_PORT_MIN, NAMESPACE};
+use crate::errors::ApiError;
+use crate::store::{document, Store};
+use hawkeye_core::models::Watcher;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::ListParams;
+use kube::{Api, Client};
+use serde_json::json;
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
+
+/// Name of the `ConfigMap` recording the ingest ports allocated to the watchers.
+const CONFIGMAP_NAME: &str = "hawkeye-ingest-ports";
+
+/// The id of the watcher each port is allocated to.
+type Allocations = BTreeMap<u32, String>;
+
+/// The allocations of the ports. The ones recorded before in the data of the `ConfigMap` itself
+/// are replaced by the first allocation, the ports of the existing watchers are in use anyway.
+fn store(client: Client) -> Store {
+    // Not `app=hawkeye`, it's not the configuration of a watcher
+    let labels = json!({ "app": "hawkeye-ports" });
+    Store::new(client, CONFIGMAP_NAME.to_string(), "ports.json", labels)
+}
+
+/// Checks the pool of ports, `HAWKEYE_INGEST_PORT_MIN` to `HAWKEYE_INGEST_PORT_MAX`, when the API
+/// starts, instead of failing the creations of the watchers.
+pub fn check_pool() -> anyhow::Result<()> {
+    check_range(*INGEST_PORT_MIN, *INGEST_PORT_MAX)
+}
+
+fn check_range(min: u32, max: u32) -> anyhow::Result<()> {
+    if min > max {
+        anyhow::bail!(
+            "HAWKEYE_INGEST_PORT_MIN ({}) is above HAWKEYE_INGEST_PORT_MAX ({})",
+            min,
+            max
+        );
+    }
+    if max > u32::from(u16::MAX) {
+        anyhow::bail!("HAWKEYE_INGEST_PORT_MAX ({}) is not a port", max);
+    }
+    Ok(())
+}
+
+/// The ids of the watchers by their ingest port, allocated or chosen by their clients. Listed from
+/// Kubernetes rather than the cache, which lags behind the watchers just created.
+pub async fn in_use(client: Client) -> Result<HashMap<u32, String>, kube::Error> {
+    let config_maps: Api<ConfigMap> = Api::namespaced(client, &NAMESPACE);
+    let lp = ListParams::default()
+        .labels("app=hawkeye,watcher_id")
+        .timeout(10);
+    let ports = config_maps
+        .list(&lp)
+        .await?
+        .items
+        .iter()
+        .filter_map(|config_map| {
+            let id = config_map.metadata.labels.as_ref()?.get("watcher_id")?;
+            let watcher: Watcher = document(config_map, "watcher.json")?;
+            Some((watcher.source.ingest_port, id.clone()))
+        })
+        .collect();
+    Ok(ports)
+}
+
+/// The other watcher the ingest port of the watcher is used by.
+pub async fn used_by(
+    client: Client,
+    port: u32,
+    watcher_id: &str,
+) -> Result<Option<String>, kube::Error> {
+    Ok(in_use(client)
+        .await?
+        .remove(&port)
+        .filter(|id| id.as_str() != watcher_id))
+}
+
+/// The first port of the pool neither used by a watcher nor allocated to one.
+fn free_port(
+    pool: RangeInclusive<u32>,
+    in_use: &HashMap<u32, String>,
+    allocations: &Allocations,
+) -> Option<u32> {
+    pool.into_iter()
+        .find(|port| !in_use.contains_key(port) && !allocations.contains_key(port))
+}
+
+/// The port allocated to the watcher.
+fn allocated(allocations: &Allocations, watcher_id: &str) -> Option<u32> {
+    allocations
+        .iter()
+        .find(|(_, id)| id.as_str() == watcher_id)
+        .map(|(port, _)| *port)
+}
+
+/// Allocates the first free port of the pool, `HAWKEYE_INGEST_PORT_MIN` to
+/// `HAWKEYE_INGEST_PORT_MAX`, to the watcher, and records it so it's not allocated again until the
+/// watcher is deleted. A watcher keeps the port it was allocated already.
+pub async fn allocate(client: Client, watcher_id: &str) -> Result<u32, ApiError> {
+    let in_use = in_use(client.clone()).await?;
+    let allocations = store(client)
+        .update(|allocations: &mut Allocations| -> Result<bool, ApiError> {
+            if allocated(allocations, watcher_id).is_some() {
+                return Ok(false);
+            }
+            let pool = *INGEST_PORT_MIN..=*INGEST_PORT_MAX;
+            let port = free_port(pool, &in_use, allocations).ok_or(ApiError::NoIngestPort)?;
+            allocations.insert(port, watcher_id.to_string());
+            Ok(true)
+        })
+        .await?;
+    allocated(&allocations, watcher_id).ok_or(ApiError::NoIngestPort)
+}
+
+/// Releases the port allocated to the watcher, when it's deleted. Failures are logged and otherwise
+/// ignored, the port stays allocated.
+pub async fn release(client: Client, watcher_id: &str) {
+    let released = store(client)
+        .update(|allocations: &mut Allocations| {
+            let before = allocations.len();
+            allocations.retain(|_, id| id.as_str() != watcher_id);
+            Ok::<_, kube::Error>(allocations.len() != before)
+        })
+        .await;
+    if let Err(err) = released {
+        log::error!("Port of {} was not released: {:?}", watcher_id, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn free_ports() {
+        let mut allocations = Allocations::new();
+        let mut in_use = HashMap::new();
+        in_use.insert(40_000, "used".to_string());
+        assert_eq!(
+            free_port(40_000..=40_002, &in_use, &allocations),
+            Some(40_001)
+        );
+
+        allocations.insert(40_001, "watcher".to_string());
+        assert_eq!(
+            free_port(40_000..=40_002, &in_use, &allocations),
+            Some(40_002)
+        );
+        assert_eq!(allocated(&allocations, "watcher"), Some(40_001));
+        assert_eq!(allocated(&allocations, "other"), None);
+
+        // The pool is exhausted
+        allocations.insert(40_002, "other".to_string());
+        assert_eq!(free_port(40_000..=40_002, &in_use, &allocations), None);
+        assert_eq!(
+            free_port(40_000..=40_000, &HashMap::new(), &Allocations::new()),
+            Some(40_000)
+        );
+    }
+
+    #[test]
+    fn port_ranges() {
+        assert!(check_range(40_000, 49_999).is_ok());
+        assert!(check_range(40_000, 40_000).is_ok());
+        assert!(check_range(40_000, 65_535).is_ok());
+        assert!(check_range(49_999, 40_000).is_err());
+        assert!(check_range(40_000, 65_536).is_err());
+    }
+}
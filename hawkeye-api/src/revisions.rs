@@ -0,0 +1,104 @@
+use crate::store::Store;
+use chrono::Utc;
+use kube::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+/// Maximum number of revisions kept for a watcher, the oldest are dropped first.
+const CAPACITY: usize = 20;
+
+/// Builds an idempotent name for the `ConfigMap` with the revisions of the `watcher_id`.
+pub fn configmap_name(watcher_id: &str) -> String {
+    format!("hawkeye-revisions-{}", watcher_id)
+}
+
+/// A configuration the watcher had before it was replaced.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Revision {
+    pub revision: u32,
+    /// When the configuration was replaced.
+    pub timestamp: String,
+    pub watcher: serde_json::Value,
+}
+
+/// The revisions of the watcher, the oldest first.
+fn store(client: Client, watcher_id: &str) -> Store {
+    Store::new(
+        client,
+        configmap_name(watcher_id),
+        "revisions.json",
+        // Not `app=hawkeye`, it's not the configuration the worker runs
+        json!({
+            "app": "hawkeye-revisions",
+            "watcher_id": watcher_id,
+        }),
+    )
+}
+
+/// Keeps the configuration `contents` replaced in the watcher as its next revision. Failures are
+/// logged and otherwise ignored, they don't fail the update.
+pub async fn record(client: Client, watcher_id: &str, contents: &str) {
+    let watcher: serde_json::Value = match serde_json::from_str(contents) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            log::error!("Invalid configuration of {}: {:?}", watcher_id, err);
+            return;
+        }
+    };
+    let timestamp = Utc::now().to_rfc3339();
+
+    let saved = store(client, watcher_id)
+        .update(
+            |revisions: &mut Vec<Revision>| -> Result<bool, kube::Error> {
+                revisions.push(Revision {
+                    revision: revisions.last().map(|last| last.revision + 1).unwrap_or(1),
+                    timestamp: timestamp.clone(),
+                    watcher: watcher.clone(),
+                });
+                if revisions.len() > CAPACITY {
+                    revisions.drain(..revisions.len() - CAPACITY);
+                }
+                Ok(true)
+            },
+        )
+        .await;
+    if let Err(err) = saved {
+        log::error!(
+            "Revision of {} was not saved: {}: {:?}",
+            watcher_id,
+            contents,
+            err
+        );
+    }
+}
+
+/// Revisions of the watcher, the latest first.
+pub async fn list(client: Client, watcher_id: &str) -> Vec<Revision> {
+    let mut revisions: Vec<Revision> = store(client, watcher_id)
+        .get()
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    revisions.reverse();
+    revisions
+}
+
+/// The `revision` of the watcher, unless it was dropped or never existed.
+pub async fn get(client: Client, watcher_id: &str, revision: u32) -> Option<Revision> {
+    let revisions: Vec<Revision> = store(client, watcher_id).get().await.ok()??;
+    revisions
+        .into_iter()
+        .find(|candidate| candidate.revision == revision)
+}
+
+/// Deletes the revisions of the watcher, with the watcher.
+pub async fn delete(client: Client, watcher_id: &str) {
+    if let Err(err) = store(client, watcher_id).delete().await {
+        log::warn!(
+            "Could not delete the revisions of {}: {:?}",
+            watcher_id,
+            err
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::SLATES_DIR;
+use hawkeye_core::models::Watcher;
+use serde::Serialize;
+use std::io;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// Largest slate accepted, video slates included.
+pub const MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+const ASSET_SCHEME: &str = "asset://";
+
+/// Content types of the slates and their extension, the worker recognizes the format of the
+/// slates by their extension.
+const CONTENT_TYPES: [(&str, &str); 4] = [
+    ("image/png", "png"),
+    ("image/jpeg", "jpg"),
+    ("video/mp4", "mp4"),
+    ("video/x-matroska", "mkv"),
+];
+
+/// A slate uploaded to the API, the watchers reference it as `asset://{id}`.
+#[derive(Serialize, Debug)]
+pub struct Asset {
+    pub id: String,
+    pub url: String,
+    pub content_type: &'static str,
+    pub size: u64,
+}
+
+impl Asset {
+    fn new(id: String, size: u64) -> Option<Self> {
+        Some(Self {
+            url: format!("{}{}", ASSET_SCHEME, id),
+            content_type: content_type(&id)?,
+            id,
+            size,
+        })
+    }
+}
+
+fn content_type(id: &str) -> Option<&'static str> {
+    let extension = Path::new(id).extension()?.to_str()?;
+    CONTENT_TYPES
+        .iter()
+        .find(|(_, candidate)| *candidate == extension)
+        .map(|(content_type, _)| *content_type)
+}
+
+/// Location of the slate, unless the id is not one given by the API.
+fn path(id: &str) -> Option<PathBuf> {
+    let valid = id.matches('.').count() == 1
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.');
+    if valid && content_type(id).is_some() {
+        Some(Path::new(SLATES_DIR.as_str()).join(id))
+    } else {
+        None
+    }
+}
+
+/// Stores the slate under a new id, unless its content type is not supported.
+pub async fn save(content_type: &str, contents: &[u8]) -> io::Result<Option<Asset>> {
+    let extension = match CONTENT_TYPES
+        .iter()
+        .find(|(candidate, _)| *candidate == content_type)
+    {
+        Some((_, extension)) => extension,
+        None => return Ok(None),
+    };
+    let id = format!("{}.{}", Uuid::new_v4(), extension);
+    tokio::fs::create_dir_all(SLATES_DIR.as_str()).await?;
+    tokio::fs::write(Path::new(SLATES_DIR.as_str()).join(&id), contents).await?;
+    Ok(Asset::new(id, contents.len() as u64))
+}
+
+/// All the slates stored.
+pub async fn list() -> io::Result<Vec<Asset>> {
+    let mut assets = Vec::new();
+    let mut entries = match tokio::fs::read_dir(SLATES_DIR.as_str()).await {
+        Ok(entries) => entries,
+        // Nothing uploaded yet
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(assets),
+        Err(err) => return Err(err),
+    };
+    while let Some(entry) = entries.next_entry().await? {
+        let size = entry.metadata().await?.len();
+        if let Some(asset) = entry
+            .file_name()
+            .to_str()
+            .and_then(|id| Asset::new(id.to_string(), size))
+        {
+            assets.push(asset);
+        }
+    }
+    assets.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(assets)
+}
+
+/// Content type and contents of the slate.
+pub async fn load(id: &str) -> Option<(&'static str, Vec<u8>)> {
+    let contents = tokio::fs::read(path(id)?).await.ok()?;
+    Some((content_type(id)?, contents))
+}
+
+/// First slate referenced by the watcher which is not stored.
+pub async fn missing(watcher: &Watcher) -> Option<String> {
+    for url in watcher.slate_urls() {
+        if let Some(id) = url.strip_prefix(ASSET_SCHEME) {
+            // Video slates select their frame with a time offset, like `asset://{id}#t=2.5`
+            let id = id.split("#t=").next().unwrap_or_default();
+            let stored = match path(id) {
+                Some(path) => tokio::fs::metadata(path).await.is_ok(),
+                None => false,
+            };
+            if !stored {
+                return Some(id.to_string());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slate_paths() {
+        let id = "0e6c4f5a-8f1c-4c3b-9d3e-2a7b1c9d8e7f.png";
+        assert_eq!(path(id), Some(Path::new(SLATES_DIR.as_str()).join(id)));
+        assert!(path("slate.mkv").is_some());
+
+        assert_eq!(path("slate.gif"), None);
+        assert_eq!(path("slate"), None);
+        assert_eq!(path("slate.tar.png"), None);
+        assert_eq!(path("../slate.png"), None);
+        assert_eq!(path("..png"), None);
+        assert_eq!(path("slates/slate.png"), None);
+        assert_eq!(path("/etc/slate.png"), None);
+        assert_eq!(path("slate%2Fx.png"), None);
+    }
+
+    #[test]
+    fn slate_content_types() {
+        assert_eq!(content_type("slate.jpg"), Some("image/jpeg"));
+        assert_eq!(content_type("slate.mp4"), Some("video/mp4"));
+        assert_eq!(content_type("slate.jpeg"), None);
+
+        let asset = Asset::new("slate.png".to_string(), 42).unwrap();
+        assert_eq!(asset.url, "asset://slate.png");
+        assert_eq!(asset.content_type, "image/png");
+        assert!(Asset::new("slate.txt".to_string(), 42).is_none());
+    }
+}
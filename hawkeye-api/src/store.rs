@@ -0,0 +1,155 @@
+use crate::config::NAMESPACE;
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::api::{DeleteParams, Patch, PatchParams, PostParams};
+use kube::{Api, Client};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Attempts to change a document when it's changed concurrently.
+pub(crate) const ATTEMPTS: usize = 3;
+
+/// A JSON document of the API kept in a `ConfigMap`, like the revisions of a watcher, so any
+/// replica of the API reads and changes it.
+pub struct Store {
+    config_maps: Api<ConfigMap>,
+    name: String,
+    key: &'static str,
+    labels: serde_json::Value,
+}
+
+impl Store {
+    /// The document in the `key` of the `ConfigMap` named `name`, created with the `labels`.
+    pub fn new(client: Client, name: String, key: &'static str, labels: serde_json::Value) -> Self {
+        Self {
+            config_maps: Api::namespaced(client, &NAMESPACE),
+            name,
+            key,
+            labels,
+        }
+    }
+
+    /// The document, unless it doesn't exist. Invalid contents are read as a missing document.
+    pub async fn get<T: DeserializeOwned>(&self) -> Result<Option<T>, kube::Error> {
+        match self.config_maps.get(&self.name).await {
+            Ok(config_map) => Ok(document(&config_map, self.key)),
+            Err(err) if is_status(&err, 404) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Changes the document, the default one when it doesn't exist, and returns it. `change` tells
+    /// whether the document changed, it's only stored then.
+    ///
+    /// The document is replaced only if it wasn't changed since it was read, otherwise it's read
+    /// and changed again, so concurrent changes are not lost.
+    pub async fn update<T, E, F>(&self, mut change: F) -> Result<T, E>
+    where
+        T: Serialize + DeserializeOwned + Default,
+        E: From<kube::Error>,
+        F: FnMut(&mut T) -> Result<bool, E>,
+    {
+        let mut attempt = 1;
+        loop {
+            let config_map = match self.config_maps.get(&self.name).await {
+                Ok(config_map) => Some(config_map),
+                Err(err) if is_status(&err, 404) => None,
+                Err(err) => return Err(err.into()),
+            };
+            let mut contents: T = config_map
+                .as_ref()
+                .and_then(|config_map| document(config_map, self.key))
+                .unwrap_or_default();
+            if !change(&mut contents)? {
+                return Ok(contents);
+            }
+
+            let pp = PostParams::default();
+            let saved = match config_map {
+                // Fails when the document changed since it was read, it has a new resource version
+                Some(mut config_map) => {
+                    config_map.data = Some(self.data(&contents));
+                    self.config_maps.replace(&self.name, &pp, &config_map).await
+                }
+                None => {
+                    let config_map = self.build_configmap(&contents);
+                    self.config_maps.create(&pp, &config_map).await
+                }
+            };
+            match saved {
+                Ok(_) => return Ok(contents),
+                Err(err) if is_status(&err, 409) && attempt < ATTEMPTS => {
+                    log::debug!("{} changed concurrently, changing it again", self.name);
+                    attempt += 1;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    /// Stores a new document, it fails with a `409 Conflict` when it exists already.
+    pub async fn create<T: Serialize>(&self, contents: &T) -> Result<(), kube::Error> {
+        self.config_maps
+            .create(&PostParams::default(), &self.build_configmap(contents))
+            .await
+            .map(drop)
+    }
+
+    /// Replaces the contents of the document, regardless of its changes since it was read. Only for
+    /// documents with a single writer.
+    pub async fn put<T: Serialize>(&self, contents: &T) -> Result<(), kube::Error> {
+        let patch = json!({ "data": self.data(contents) });
+        self.config_maps
+            .patch(&self.name, &PatchParams::default(), &Patch::Merge(&patch))
+            .await
+            .map(drop)
+    }
+
+    /// Deletes the document, if it exists.
+    pub async fn delete(&self) -> Result<(), kube::Error> {
+        match self
+            .config_maps
+            .delete(&self.name, &DeleteParams::default())
+            .await
+        {
+            Err(err) if is_status(&err, 404) => Ok(()),
+            result => result.map(drop),
+        }
+    }
+
+    fn data<T: Serialize>(&self, contents: &T) -> BTreeMap<String, String> {
+        let mut data = BTreeMap::new();
+        data.insert(
+            self.key.to_string(),
+            serde_json::to_string(contents).unwrap(),
+        );
+        data
+    }
+
+    fn build_configmap<T: Serialize>(&self, contents: &T) -> ConfigMap {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": self.name,
+                "labels": self.labels,
+            },
+            "data": self.data(contents),
+        }))
+        .unwrap()
+    }
+}
+
+/// The document in the `key` of a `ConfigMap`, like the ones listed by their labels.
+pub fn document<T: DeserializeOwned>(config_map: &ConfigMap, key: &str) -> Option<T> {
+    config_map
+        .data
+        .as_ref()
+        .and_then(|data| data.get(key))
+        .and_then(|contents| serde_json::from_str(contents).ok())
+}
+
+fn is_status(err: &kube::Error, code: u16) -> bool {
+    matches!(err, kube::Error::Api(response) if response.code == code)
+}
@@ -1,52 +1,109 @@
-use crate::config::DOCKER_IMAGE;
-use hawkeye_core::models::Status;
+use crate::config::{
+    API_NAMESPACE, API_POD_LABELS, CONTROL_TOKEN, DOCKER_IMAGE, LIVENESS_FAILURE_THRESHOLD,
+    LIVENESS_INITIAL_DELAY, LIVENESS_PERIOD, READINESS_FAILURE_THRESHOLD, READINESS_PERIOD,
+    SCRAPER_NAMESPACE, SCRAPER_POD_LABELS, SLATE_ASSETS_URL, TRACING_ENV,
+};
+use crate::crd::WatcherResource;
+use hawkeye_core::models::{Exposure, Status, Watcher};
 use k8s_openapi::api::apps::v1::Deployment;
-use k8s_openapi::api::core::v1::{ConfigMap, Service};
+use k8s_openapi::api::core::v1::{Affinity, ConfigMap, PodSpec, Secret, Service};
+use k8s_openapi::api::networking::v1::NetworkPolicy;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
 use serde_json::json;
+use std::collections::BTreeMap;
 
 /// Builds an idempotent name for the `ConfigMap` based on the `watcher_id`.
 pub fn configmap_name(watcher_id: &str) -> String {
     format!("hawkeye-config-{}", watcher_id)
 }
 
-/// Builds a `ConfigMap` in the format expected to run the hawkeye-worker.
-pub fn build_configmap(watcher_id: &str, contents: &str) -> ConfigMap {
+/// Name of the `Secret` with the `HAWKEYE_CONTROL_TOKEN` given to the workers.
+pub const CONTROL_TOKEN_SECRET: &str = "hawkeye-control-token";
+
+/// Key of the token in the `Secret` of the control token.
+const CONTROL_TOKEN_KEY: &str = "token";
+
+/// Builds the `Secret` with the control token the workers read.
+pub fn build_control_token_secret(token: &str) -> Secret {
     serde_json::from_value(json!({
         "apiVersion": "v1",
-        "kind": "ConfigMap",
+        "kind": "Secret",
         "metadata": {
-            "name": configmap_name(watcher_id),
+            "name": CONTROL_TOKEN_SECRET,
             "labels": {
-                "app": "hawkeye",
-                "watcher_id": watcher_id,
+                "app": "hawkeye-control-token",
             }
         },
+        "stringData": {
+            (CONTROL_TOKEN_KEY): token,
+        }
+    }))
+    .unwrap()
+}
+
+/// Label of the `ConfigMap` with the group of the watcher.
+pub const GROUP_LABEL: &str = "group";
+
+/// Builds a `ConfigMap` in the format expected to run the hawkeye-worker, labeled with the group
+/// of the watcher and owned by its `Watcher` resource.
+pub fn build_configmap(watcher_id: &str, watcher: &Watcher, owner: &WatcherResource) -> ConfigMap {
+    let mut labels = json!({
+        "app": "hawkeye",
+        "watcher_id": watcher_id,
+    });
+    if let Some(group) = watcher.group.as_ref() {
+        labels[GROUP_LABEL] = json!(group);
+    }
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": configmap_name(watcher_id),
+            "labels": labels,
+            "ownerReferences": owner.owner_references(),
+        },
         "data": {
             "log_level": "INFO",
-            "watcher.json": contents,
+            "watcher.json": serde_json::to_string(watcher).unwrap(),
         }
     }))
     .unwrap()
 }
 
+/// Makes the `ConfigMap` of the watcher the owner of its other objects, so Kubernetes deletes
+/// them along with it, even when it's deleted with `kubectl`.
+pub fn owner_references(config_map: &ConfigMap) -> serde_json::Value {
+    json!([
+        {
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "name": config_map.metadata.name,
+            "uid": config_map.metadata.uid,
+        }
+    ])
+}
+
 /// Builds an idempotent name for the `Deployment` based on the `watcher_id`.
 pub fn deployment_name(watcher_id: &str) -> String {
     format!("hawkeye-deploy-{}", watcher_id)
 }
 
-/// Builds a `Deployment` configured to run the hawkeye-worker process.
-pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
-    let metric_port_str = ingest_port.to_string();
-    serde_json::from_value(json!({
+/// Builds a `Deployment` configured to run the hawkeye-worker process, owned by the `ConfigMap`
+/// of the watcher.
+pub fn build_deployment(watcher_id: &str, watcher: &Watcher, owner: &ConfigMap) -> Deployment {
+    let metric_port_str = watcher.source.ingest_port.to_string();
+    let mut deployment: Deployment = serde_json::from_value(json!({
         "apiVersion": "apps/v1",
         "kind": "Deployment",
         "metadata": {
             "name": deployment_name(watcher_id),
+            "ownerReferences": owner_references(owner),
             "labels": {
                 "app": "hawkeye",
                 "watcher_id": watcher_id,
                 "target_status": Status::Ready,
-            }
+            },
+            "annotations": deployment_annotations(watcher),
         },
         "spec": {
             "replicas": 0,
@@ -58,16 +115,12 @@ pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
             },
             "template": {
                 "metadata": {
-                    "annotations": {
-                        "prometheus.io/port": metric_port_str,
-                        "prometheus.io/scrape": "true",
-                        "prometheus.io/path": "metrics",
-                    },
+                    "annotations": pod_annotations(watcher),
                     "labels": {
                         "app": "hawkeye",
                         "watcher_id": watcher_id,
                         "prometheus.io/port": metric_port_str,
-                        "prometheus.io/scrape": "true",
+                        "prometheus.io/scrape": is_scraped(watcher).to_string(),
                         "prometheus.io/path": "metrics",
                     }
                 },
@@ -76,7 +129,7 @@ pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
                     "restartPolicy": "Always",
                     "terminationGracePeriodSeconds": 5,
                     "containers": [
-                        container_spec(watcher_id, ingest_port)
+                        container_spec(watcher_id, watcher)
                     ],
                     "volumes": [
                         {
@@ -96,29 +149,112 @@ pub fn build_deployment(watcher_id: &str, ingest_port: u32) -> Deployment {
             }
         }
     }))
-    .unwrap()
+    .unwrap();
+    if let Some(pod_spec) = deployment
+        .spec
+        .as_mut()
+        .and_then(|spec| spec.template.spec.as_mut())
+    {
+        schedule(pod_spec, watcher);
+    }
+    deployment
+}
+
+/// Sets the nodes the worker runs on in its pod, from the `scheduling` of the watcher.
+pub fn schedule(pod_spec: &mut PodSpec, watcher: &Watcher) {
+    let scheduling = watcher.scheduling.clone().unwrap_or_default();
+    pod_spec.node_selector = scheduling.node_selector;
+    pod_spec.tolerations = scheduling.tolerations.map(|tolerations| {
+        tolerations
+            .iter()
+            .filter_map(|toleration| {
+                serde_json::from_value(json!({
+                    "key": toleration.key,
+                    "operator": toleration.operator,
+                    "value": toleration.value,
+                    "effect": toleration.effect,
+                    "tolerationSeconds": toleration.toleration_seconds,
+                }))
+                .ok()
+            })
+            .collect()
+    });
+    // Validated with the watcher by the API
+    pod_spec.affinity = scheduling.affinity.and_then(|affinity| {
+        self::affinity(affinity)
+            .map_err(|err| log::warn!("Invalid affinity of the watcher: {}", err))
+            .ok()
+    });
+}
+
+/// The Kubernetes `Affinity` of the scheduling of a watcher.
+pub fn affinity(affinity: serde_json::Value) -> Result<Affinity, serde_json::Error> {
+    serde_json::from_value(affinity)
 }
 
 /// Returns a fragment of the container specification
-pub fn container_spec(watcher_id: &str, ingest_port: u32) -> serde_json::Value {
+pub fn container_spec(watcher_id: &str, watcher: &Watcher) -> serde_json::Value {
+    let ingest_port = watcher.source.ingest_port;
+    let mut env = vec![json!({
+        "name": "RUST_LOG",
+        "valueFrom": {
+            "configMapKeyRef": {
+                "name": configmap_name(watcher_id),
+                "key": "log_level"
+            }
+        }
+    })];
+    for (name, value) in TRACING_ENV.iter() {
+        env.push(json!({
+            "name": name,
+            "value": value
+        }));
+    }
+    if let Some(url) = SLATE_ASSETS_URL.as_ref() {
+        env.push(json!({
+            "name": "HAWKEYE_SLATE_ASSETS_URL",
+            "value": url
+        }));
+    }
+    // Read from its Secret, the token is not in the spec of the Deployment
+    if CONTROL_TOKEN.is_some() {
+        env.push(json!({
+            "name": "HAWKEYE_CONTROL_TOKEN",
+            "valueFrom": {
+                "secretKeyRef": {
+                    "name": CONTROL_TOKEN_SECRET,
+                    "key": CONTROL_TOKEN_KEY
+                }
+            }
+        }));
+    }
+    // Secrets referenced by the watcher are exposed as the environment variables it reads. The
+    // variables set above are rejected by the validation, they're never replaced
+    for source in watcher.secret_sources() {
+        if let Some(secret) = &source.secret {
+            if env.iter().any(|entry| entry["name"] == source.env.as_str()) {
+                continue;
+            }
+            env.push(json!({
+                "name": source.env,
+                "valueFrom": {
+                    "secretKeyRef": {
+                        "name": secret,
+                        "key": source.key.as_ref().unwrap_or(&source.env)
+                    }
+                }
+            }));
+        }
+    }
+
     json!({
         "name": "hawkeye-app",
         "imagePullPolicy": "IfNotPresent",
-        "image": DOCKER_IMAGE.as_str(),
+        "image": watcher.worker_image.as_deref().unwrap_or_else(|| DOCKER_IMAGE.as_str()),
         "args": [
             "/config/watcher.json"
         ],
-        "env": [
-            {
-                "name": "RUST_LOG",
-                "valueFrom": {
-                    "configMapKeyRef": {
-                        "name": configmap_name(watcher_id),
-                        "key": "log_level"
-                    }
-                }
-            }
-        ],
+        "env": env,
         "resources": {
             "limits": {
                 "cpu": "2000m",
@@ -139,6 +275,24 @@ pub fn container_spec(watcher_id: &str, ingest_port: u32) -> serde_json::Value {
                 "protocol": "TCP"
             }
         ],
+        // The worker serves the probes along with the metrics, on the TCP ingest port
+        "livenessProbe": {
+            "httpGet": {
+                "path": "/healthz",
+                "port": ingest_port
+            },
+            "initialDelaySeconds": *LIVENESS_INITIAL_DELAY,
+            "periodSeconds": *LIVENESS_PERIOD,
+            "failureThreshold": *LIVENESS_FAILURE_THRESHOLD
+        },
+        "readinessProbe": {
+            "httpGet": {
+                "path": "/readyz",
+                "port": ingest_port
+            },
+            "periodSeconds": *READINESS_PERIOD,
+            "failureThreshold": *READINESS_FAILURE_THRESHOLD
+        },
         "volumeMounts": [
             {
                 "mountPath": "/config",
@@ -154,25 +308,26 @@ pub fn service_name(watcher_id: &str) -> String {
     format!("hawkeye-vid-svc-{}", watcher_id)
 }
 
-/// Builds a `Service` in the format expected to expose the hawkeye-worker.
-pub fn build_service(watcher_id: &str, ingest_port: u32) -> Service {
-    serde_json::from_value(json!({
+/// Builds a `Service` in the format expected to expose the hawkeye-worker, owned by the
+/// `ConfigMap` of the watcher, so its LoadBalancer isn't left behind. There is none when the
+/// worker isn't exposed.
+pub fn build_service(watcher_id: &str, watcher: &Watcher, owner: &ConfigMap) -> Option<Service> {
+    let ingest_port = watcher.source.ingest_port;
+    let (service_type, _) = exposure(watcher)?;
+    let mut service = json!({
         "apiVersion": "v1",
         "kind": "Service",
         "metadata": {
             "name": service_name(watcher_id),
+            "ownerReferences": owner_references(owner),
             "labels": {
                 "app": "hawkeye",
                 "watcher_id": watcher_id,
             },
-            "annotations": {
-                // "external-dns.alpha.kubernetes.io/hostname": "",
-                "service.beta.kubernetes.io/aws-load-balancer-type": "nlb"
-            }
+            "annotations": service_annotations(watcher),
         },
         "spec": {
-            "type": "LoadBalancer",
-            "externalTrafficPolicy": "Cluster",
+            "type": service_type,
             "selector": {
                 "app": "hawkeye",
                 "watcher_id": watcher_id,
@@ -186,6 +341,308 @@ pub fn build_service(watcher_id: &str, ingest_port: u32) -> Service {
                 }
             ]
         }
-    }))
-    .unwrap()
+    });
+    // Only for the Services reachable from outside the cluster
+    if service_type != "ClusterIP" {
+        service["spec"]["externalTrafficPolicy"] = json!(external_traffic_policy(watcher));
+    }
+    Some(serde_json::from_value(service).unwrap())
+}
+
+/// Keeps the address the feed is sent from when only some addresses are allowed, `Local`, so the
+/// `NetworkPolicy` of the watcher sees it. The feed then only reaches the node running the worker.
+pub fn external_traffic_policy(watcher: &Watcher) -> &'static str {
+    if watcher.allowed_sources.is_some() {
+        "Local"
+    } else {
+        "Cluster"
+    }
+}
+
+/// Builds an idempotent name for the `NetworkPolicy` based on the `watcher_id`.
+pub fn network_policy_name(watcher_id: &str) -> String {
+    format!("hawkeye-netpol-{}", watcher_id)
+}
+
+/// Builds a `NetworkPolicy` allowing the feed of the watcher from its `allowed_sources` only, and
+/// its HTTP endpoints from the API only, owned by the `ConfigMap` of the watcher. `None` when the
+/// feed is allowed from anywhere.
+pub fn build_network_policy(
+    watcher_id: &str,
+    watcher: &Watcher,
+    owner: &ConfigMap,
+) -> Option<NetworkPolicy> {
+    let ingest_port = watcher.source.ingest_port;
+    let ranges: Vec<_> = watcher
+        .allowed_sources
+        .as_ref()?
+        .iter()
+        .map(|range| json!({ "ipBlock": { "cidr": range } }))
+        .collect();
+    let mut callers = vec![pods_in(&API_POD_LABELS, &API_NAMESPACE)];
+    if let Some(labels) = SCRAPER_POD_LABELS.as_ref() {
+        callers.push(pods_in(labels, &SCRAPER_NAMESPACE));
+    }
+    let network_policy = json!({
+        "apiVersion": "networking.k8s.io/v1",
+        "kind": "NetworkPolicy",
+        "metadata": {
+            "name": network_policy_name(watcher_id),
+            "ownerReferences": owner_references(owner),
+            "labels": {
+                "app": "hawkeye",
+                "watcher_id": watcher_id,
+            },
+        },
+        "spec": {
+            "podSelector": {
+                "matchLabels": {
+                    "app": "hawkeye",
+                    "watcher_id": watcher_id,
+                }
+            },
+            "policyTypes": ["Ingress"],
+            "ingress": [
+                {
+                    "from": ranges,
+                    "ports": [
+                        {
+                            "protocol": "UDP",
+                            "port": ingest_port
+                        }
+                    ]
+                },
+                // The calls of the API and the scrapes of Prometheus, on the TCP ingest port. The
+                // probes of the kubelet are not subject to the policy
+                {
+                    "from": callers,
+                    "ports": [
+                        {
+                            "protocol": "TCP",
+                            "port": ingest_port
+                        }
+                    ]
+                }
+            ]
+        }
+    });
+    Some(serde_json::from_value(network_policy).unwrap())
+}
+
+/// Peer of a `NetworkPolicy` matching the pods with the `labels` in the `namespace`.
+fn pods_in(labels: &BTreeMap<String, String>, namespace: &str) -> serde_json::Value {
+    json!({
+        "podSelector": { "matchLabels": labels },
+        "namespaceSelector": {
+            "matchLabels": {
+                "kubernetes.io/metadata.name": namespace,
+            }
+        },
+    })
+}
+
+/// Whether Prometheus can scrape the metrics of the worker, its `NetworkPolicy` only lets it when
+/// the labels of its pods are configured.
+pub fn is_scraped(watcher: &Watcher) -> bool {
+    watcher.allowed_sources.is_none() || SCRAPER_POD_LABELS.is_some()
+}
+
+/// Type and annotations of the `Service` exposing the worker, none when it isn't exposed.
+fn exposure(watcher: &Watcher) -> Option<(&'static str, serde_json::Value)> {
+    let exposure = match watcher.source.exposure.unwrap_or(Exposure::LoadBalancer) {
+        Exposure::LoadBalancer => (
+            "LoadBalancer",
+            json!({
+                "service.beta.kubernetes.io/aws-load-balancer-type": "nlb"
+            }),
+        ),
+        Exposure::InternalLoadBalancer => (
+            "LoadBalancer",
+            json!({
+                "service.beta.kubernetes.io/aws-load-balancer-type": "nlb",
+                "service.beta.kubernetes.io/aws-load-balancer-internal": "true"
+            }),
+        ),
+        Exposure::NodePort => ("NodePort", json!({})),
+        Exposure::ClusterIp => ("ClusterIP", json!({})),
+        Exposure::None => return None,
+    };
+    Some(exposure)
+}
+
+/// The `generated` annotations of an object of the watcher, along with the `annotations` of the
+/// watcher, which don't replace them.
+fn annotations(watcher: &Watcher, generated: serde_json::Value) -> BTreeMap<String, String> {
+    let mut annotations = watcher.annotations.clone().unwrap_or_default();
+    annotations.extend(serde_json::from_value::<BTreeMap<String, String>>(generated).unwrap());
+    annotations
+}
+
+/// Annotations of the `Service` of the watcher, external-dns names its address with the hostname.
+pub fn service_annotations(watcher: &Watcher) -> BTreeMap<String, String> {
+    let mut generated = exposure(watcher)
+        .map(|(_, annotations)| annotations)
+        .unwrap_or_else(|| json!({}));
+    if let Some(hostname) = watcher.hostname.as_ref() {
+        generated["external-dns.alpha.kubernetes.io/hostname"] = json!(hostname);
+    }
+    annotations(watcher, generated)
+}
+
+/// Annotations of the pod of the worker, the Prometheus ones locate its metrics.
+pub fn pod_annotations(watcher: &Watcher) -> BTreeMap<String, String> {
+    annotations(
+        watcher,
+        json!({
+            "prometheus.io/port": watcher.source.ingest_port.to_string(),
+            "prometheus.io/scrape": is_scraped(watcher).to_string(),
+            "prometheus.io/path": "metrics",
+        }),
+    )
+}
+
+/// Annotations of the `Deployment` of the watcher.
+pub fn deployment_annotations(watcher: &Watcher) -> BTreeMap<String, String> {
+    annotations(watcher, json!({}))
+}
+
+/// Changes the annotations of an object of the watcher from the `previous` ones to `annotations`.
+/// The ones added by others, like Kubernetes, stay.
+pub fn reannotate(
+    metadata: &mut ObjectMeta,
+    previous: BTreeMap<String, String>,
+    annotations: BTreeMap<String, String>,
+) {
+    let current = metadata.annotations.get_or_insert_with(BTreeMap::new);
+    for key in previous.keys() {
+        if !annotations.contains_key(key) {
+            current.remove(key);
+        }
+    }
+    current.extend(annotations);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hawkeye_core::models::{HttpAuth, SecretSource, SecretValue};
+
+    fn watcher(allowed_sources: Option<Vec<String>>) -> Watcher {
+        let mut watcher: Watcher =
+            serde_json::from_str(include_str!("../../fixtures/watcher.json")).unwrap();
+        watcher.allowed_sources = allowed_sources;
+        watcher
+    }
+
+    #[test]
+    fn secret_environment_variables() {
+        let mut watcher = watcher(None);
+        let token = |env: &str| HttpAuth::Bearer {
+            token: SecretValue::Reference {
+                value_from: SecretSource {
+                    env: env.to_string(),
+                    secret: Some("credentials".to_string()),
+                    key: None,
+                },
+            },
+        };
+        watcher.slate_authorization = Some(token("SLATE_TOKEN"));
+        let env = &container_spec("id", &watcher)["env"];
+        let secret = env
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["name"] == "SLATE_TOKEN")
+            .unwrap();
+        assert_eq!(
+            secret["valueFrom"]["secretKeyRef"],
+            json!({ "name": "credentials", "key": "SLATE_TOKEN" })
+        );
+
+        // The variables of the worker are not replaced
+        watcher.slate_authorization = Some(token("RUST_LOG"));
+        let env = &container_spec("id", &watcher)["env"];
+        let log_levels: Vec<_> = env
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|entry| entry["name"] == "RUST_LOG")
+            .collect();
+        assert_eq!(log_levels.len(), 1);
+        assert!(log_levels[0]["valueFrom"]["configMapKeyRef"].is_object());
+    }
+
+    #[test]
+    fn traffic_policies() {
+        assert_eq!(external_traffic_policy(&watcher(None)), "Cluster");
+        let allowed = Some(vec!["203.0.113.0/24".to_string()]);
+        assert_eq!(external_traffic_policy(&watcher(allowed)), "Local");
+    }
+
+    #[test]
+    fn scraped_workers() {
+        assert!(is_scraped(&watcher(None)));
+        assert_eq!(
+            pod_annotations(&watcher(None))["prometheus.io/scrape"],
+            "true"
+        );
+
+        // Prometheus isn't allowed to scrape them without the labels of its pods
+        let allowed = Some(vec!["203.0.113.0/24".to_string()]);
+        assert!(!is_scraped(&watcher(allowed.clone())));
+        assert_eq!(
+            pod_annotations(&watcher(allowed))["prometheus.io/scrape"],
+            "false"
+        );
+    }
+
+    #[test]
+    fn network_policies() {
+        let owner = ConfigMap::default();
+        assert!(build_network_policy("id", &watcher(None), &owner).is_none());
+
+        let allowed = Some(vec!["203.0.113.0/24".to_string()]);
+        let policy = build_network_policy("id", &watcher(allowed), &owner).unwrap();
+        let policy = serde_json::to_value(policy).unwrap();
+        assert_eq!(policy["metadata"]["name"], "hawkeye-netpol-id");
+        assert_eq!(
+            policy["spec"]["podSelector"]["matchLabels"]["watcher_id"],
+            "id"
+        );
+
+        let feed = &policy["spec"]["ingress"][0];
+        assert_eq!(
+            feed["from"],
+            json!([{ "ipBlock": { "cidr": "203.0.113.0/24" } }])
+        );
+        assert_eq!(feed["ports"], json!([{ "protocol": "UDP", "port": 5000 }]));
+
+        // Only the API calls the HTTP endpoints
+        let endpoints = &policy["spec"]["ingress"][1];
+        assert_eq!(
+            endpoints["from"],
+            json!([{
+                "podSelector": { "matchLabels": { "app": "hawkeye-api" } },
+                "namespaceSelector": {
+                    "matchLabels": { "kubernetes.io/metadata.name": "default" }
+                },
+            }])
+        );
+        assert_eq!(
+            endpoints["ports"],
+            json!([{ "protocol": "TCP", "port": 5000 }])
+        );
+
+        let labels: BTreeMap<String, String> =
+            [("app".to_string(), "prometheus".to_string())].into();
+        assert_eq!(
+            pods_in(&labels, "monitoring"),
+            json!({
+                "podSelector": { "matchLabels": { "app": "prometheus" } },
+                "namespaceSelector": {
+                    "matchLabels": { "kubernetes.io/metadata.name": "monitoring" }
+                },
+            })
+        );
+    }
 }
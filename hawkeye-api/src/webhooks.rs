@@ -0,0 +1,124 @@
+use crate::config::{CALL_WATCHER_TIMEOUT, WEBHOOK_SECRET, WEBHOOK_URL};
+use chrono::Utc;
+use hawkeye_core::models::Status;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use std::time::Duration;
+
+/// Header with the time the call was signed at, in seconds since the epoch.
+const TIMESTAMP_HEADER: &str = "X-Hawkeye-Timestamp";
+
+/// Header with the signature of the call, `sha256=` and the hex HMAC of the timestamp and body.
+const SIGNATURE_HEADER: &str = "X-Hawkeye-Signature";
+
+/// Events reported to the configured webhook.
+#[derive(Serialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    /// The LoadBalancer receiving the video feed of a watcher has a new address.
+    IngestAddressChanged {
+        watcher_id: String,
+        previous_ingest_ip: Option<String>,
+        ingest_ip: String,
+    },
+    /// A watcher reached its `expires_at`, it was stopped or deleted (`operation`).
+    WatcherExpired {
+        watcher_id: String,
+        expires_at: String,
+        operation: &'static str,
+    },
+    /// A watcher was created, by a client or by an import.
+    WatcherCreated { watcher_id: String },
+    /// A watcher was deleted, by a client, an import or its expiry.
+    WatcherDeleted { watcher_id: String },
+    /// A watcher entered the error state, like when its worker doesn't start.
+    WatcherErrored {
+        watcher_id: String,
+        reason: Option<String>,
+    },
+    /// A watcher in the error state left it, `status` is its new status.
+    WatcherRecovered { watcher_id: String, status: Status },
+}
+
+/// Sends the event to the configured webhook, if any, in the background so the operation
+/// reported doesn't wait for it.
+///
+/// With the configured secret, the calls are signed: the webhook computes the HMAC-SHA256 of the
+/// timestamp header, a `.` and the body with the secret, compares it with the signature header and
+/// rejects the calls signed too long ago, so a recorded call can't be replayed.
+/// Failures are logged and otherwise ignored, notifications are best effort and may arrive out of
+/// order.
+pub fn notify(event: WebhookEvent) {
+    let url = match WEBHOOK_URL.as_ref() {
+        Some(url) => url,
+        None => {
+            log::debug!("No webhook configured, skipping notification: {:?}", event);
+            return;
+        }
+    };
+    tokio::spawn(send(url, event));
+}
+
+async fn send(url: &'static str, event: WebhookEvent) {
+    let body = serde_json::to_vec(&event).unwrap();
+    let http_client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(*CALL_WATCHER_TIMEOUT))
+        .build()
+        .unwrap();
+    let mut request = http_client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json");
+    if let Some(secret) = WEBHOOK_SECRET.as_ref() {
+        let timestamp = Utc::now().timestamp().to_string();
+        request = request
+            .header(SIGNATURE_HEADER, signature(secret, &timestamp, &body))
+            .header(TIMESTAMP_HEADER, timestamp);
+    }
+    match request.body(body).send().await {
+        Ok(response) => {
+            if let Err(err) = response.error_for_status() {
+                log::error!("Webhook returned an error for {:?}: {:?}", event, err);
+            }
+        }
+        Err(err) => log::error!("Could not call webhook for {:?}: {:?}", event, err),
+    }
+}
+
+/// Signature of the call, the HMAC-SHA256 of the timestamp and the body with the secret.
+fn signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    let digest: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    format!("sha256={}", digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signatures() {
+        // HMAC-SHA256 of `1700000000.{}` with the key `secret`
+        assert_eq!(
+            signature("secret", "1700000000", b"{}"),
+            "sha256=b8569b78799ff9e3cbff0fc2d63a33a2b57f3282abd07c37ae5e8e7d79a5f163"
+        );
+        assert_ne!(
+            signature("secret", "1700000001", b"{}"),
+            signature("secret", "1700000000", b"{}")
+        );
+        assert_ne!(
+            signature("other", "1700000000", b"{}"),
+            signature("secret", "1700000000", b"{}")
+        );
+    }
+}
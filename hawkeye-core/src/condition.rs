@@ -0,0 +1,194 @@
+use color_eyre::{eyre::eyre, Result};
+use std::cmp::Ordering;
+
+/// Expression deciding if an action runs, like `to == 'slate' && similarity < 0.2`.
+///
+/// Comparisons of a variable with a number or a quoted string, combined with `&&` and `||`
+/// (`&&` binds tighter). Variables are compared as numbers when both sides are numbers, a
+/// missing variable doesn't satisfy any comparison.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Condition {
+    /// Alternatives of comparisons that must all be true.
+    any: Vec<Vec<Comparison>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Comparison {
+    variable: String,
+    operator: Operator,
+    value: String,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Literal(String),
+    Operator(Operator),
+    And,
+    Or,
+}
+
+impl Condition {
+    pub fn parse(expression: &str) -> Result<Self> {
+        let tokens = tokenize(expression)?;
+        let mut any = Vec::new();
+        for alternative in tokens.split(|t| *t == Token::Or) {
+            let mut all = Vec::new();
+            for comparison in alternative.split(|t| *t == Token::And) {
+                all.push(match comparison {
+                    [Token::Ident(variable), Token::Operator(operator), Token::Literal(value)] => {
+                        Comparison {
+                            variable: variable.clone(),
+                            operator: *operator,
+                            value: value.clone(),
+                        }
+                    }
+                    _ => {
+                        return Err(eyre!(
+                            "Invalid condition {}, expected comparisons like `variable == 'value'`",
+                            expression
+                        ))
+                    }
+                });
+            }
+            any.push(all);
+        }
+        Ok(Self { any })
+    }
+
+    /// Evaluates the condition with the values of the variables given by `lookup`.
+    pub fn evaluate<F: Fn(&str) -> Option<String>>(&self, lookup: F) -> bool {
+        self.any
+            .iter()
+            .any(|all| all.iter().all(|comparison| comparison.evaluate(&lookup)))
+    }
+}
+
+impl Comparison {
+    fn evaluate<F: Fn(&str) -> Option<String>>(&self, lookup: &F) -> bool {
+        let actual = match lookup(&self.variable) {
+            Some(actual) if !actual.is_empty() => actual,
+            _ => return false,
+        };
+        let ordering = match (actual.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => match self.operator {
+                Operator::Eq | Operator::Ne => Some(actual.as_str().cmp(self.value.as_str())),
+                // Only numbers can be ordered
+                _ => None,
+            },
+        };
+        match ordering {
+            None => false,
+            Some(ordering) => match self.operator {
+                Operator::Eq => ordering == Ordering::Equal,
+                Operator::Ne => ordering != Ordering::Equal,
+                Operator::Lt => ordering == Ordering::Less,
+                Operator::Le => ordering != Ordering::Greater,
+                Operator::Gt => ordering == Ordering::Greater,
+                Operator::Ge => ordering != Ordering::Less,
+            },
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '\'' || c == '"' {
+            chars.next();
+            let literal: String = chars.by_ref().take_while(|&next| next != c).collect();
+            tokens.push(Token::Literal(literal));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut ident = String::new();
+            while let Some(&next) = chars.peek() {
+                if !(next.is_ascii_alphanumeric() || next == '_') {
+                    break;
+                }
+                ident.push(next);
+                chars.next();
+            }
+            tokens.push(Token::Ident(ident));
+        } else if c.is_ascii_digit() || c == '-' || c == '.' {
+            let mut number = String::new();
+            while let Some(&next) = chars.peek() {
+                if !(next.is_ascii_digit() || next == '-' || next == '.') {
+                    break;
+                }
+                number.push(next);
+                chars.next();
+            }
+            tokens.push(Token::Literal(number));
+        } else {
+            let mut symbol = String::new();
+            while let Some(&next) = chars.peek() {
+                if !"=!<>&|".contains(next) {
+                    break;
+                }
+                symbol.push(next);
+                chars.next();
+            }
+            tokens.push(match symbol.as_str() {
+                "==" => Token::Operator(Operator::Eq),
+                "!=" => Token::Operator(Operator::Ne),
+                "<" => Token::Operator(Operator::Lt),
+                "<=" => Token::Operator(Operator::Le),
+                ">" => Token::Operator(Operator::Gt),
+                ">=" => Token::Operator(Operator::Ge),
+                "&&" => Token::And,
+                "||" => Token::Or,
+                _ => return Err(eyre!("Unexpected `{}` in condition {}", c, expression)),
+            });
+        }
+    }
+    Ok(tokens)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "to" => Some("slate".to_string()),
+            "similarity" => Some("0.15".to_string()),
+            "slate_url" => Some(String::new()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn evaluate_conditions() {
+        let eval = |expression: &str| Condition::parse(expression).unwrap().evaluate(lookup);
+
+        assert!(eval("to == 'slate'"));
+        assert!(eval("to != \"content\""));
+        assert!(eval("similarity < 0.2 && to == 'slate'"));
+        assert!(!eval("similarity >= 0.2 && to == 'slate'"));
+        assert!(eval("similarity >= 0.2 || to == 'slate'"));
+        assert!(!eval("to > 'content'"));
+        // Missing and empty variables never match
+        assert!(!eval("unknown != 'slate'"));
+        assert!(!eval("slate_url != 'https://example.com/slate.jpg'"));
+    }
+
+    #[test]
+    fn parse_invalid_conditions() {
+        assert!(Condition::parse("to = 'slate'").is_err());
+        assert!(Condition::parse("to == 'slate' &&").is_err());
+        assert!(Condition::parse("'slate' == to").is_err());
+    }
+}
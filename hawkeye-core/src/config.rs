@@ -3,6 +3,9 @@ use lazy_static::lazy_static;
 const HAWKEYE_ENV_ENV: &str = "HAWKEYE_ENV";
 const SENTRY_DSN_ENV: &str = "HAWKEYE_SENTRY_DSN";
 const SENTRY_ENABLED_ENV: &str = "HAWKEYE_SENTRY_ENABLED";
+const LOG_FORMAT_ENV: &str = "LOG_FORMAT";
+const OTLP_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const TRACES_SAMPLE_RATIO_ENV: &str = "HAWKEYE_TRACES_SAMPLE_RATIO";
 
 lazy_static! {
     // The environment to base logic decisions off of.
@@ -12,4 +15,12 @@ lazy_static! {
     // Sentry URL to send events to. Available on the Sentry Project page.
     pub static ref SENTRY_DSN: String = std::env::var(SENTRY_DSN_ENV).unwrap_or_else(|_| "".into());
     pub static ref SENTRY_ENABLED: bool = std::env::var(SENTRY_ENABLED_ENV).unwrap_or_else(|_| "".into()) == "1";
+
+    // Logs a JSON object per record when set to `json`, for log pipelines to index their fields.
+    pub static ref LOG_FORMAT_JSON: bool = std::env::var(LOG_FORMAT_ENV).map(|format| format == "json").unwrap_or(false);
+
+    // OpenTelemetry collector the traces are exported to, using OTLP over gRPC.
+    pub static ref OTLP_ENDPOINT: Option<String> = std::env::var(OTLP_ENDPOINT_ENV).ok();
+    // Fraction of the traces exported, the worker starts one for every frame.
+    pub static ref TRACES_SAMPLE_RATIO: f64 = std::env::var(TRACES_SAMPLE_RATIO_ENV).ok().and_then(|ratio| ratio.parse().ok()).unwrap_or(1.0);
 }
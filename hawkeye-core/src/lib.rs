@@ -1,3 +1,6 @@
+pub mod condition;
 mod config;
+pub mod logging;
 pub mod models;
+pub mod telemetry;
 pub mod utils;
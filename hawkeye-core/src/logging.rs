@@ -0,0 +1,219 @@
+use crate::config;
+use chrono::Utc;
+use env_logger::Builder;
+use lazy_static::lazy_static;
+use log::{Log, Metadata, Record};
+use serde_json::{json, Map, Value};
+use std::cell::RefCell;
+use std::io::Write;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref GLOBAL_FIELDS: RwLock<Map<String, Value>> = RwLock::new(Map::new());
+    static ref LOGGER: RwLock<Option<env_logger::Logger>> = RwLock::new(None);
+    static ref FILTERS: Mutex<Filters> = Mutex::new(Filters::default());
+}
+
+/// Filters of the logger, like `RUST_LOG`.
+#[derive(Default)]
+struct Filters {
+    /// Filters the logger started with, restored after a temporary change.
+    default: String,
+    current: String,
+    revert_at: Option<Instant>,
+    /// Incremented on every change, so only the latest change is reverted.
+    generation: u64,
+}
+
+thread_local! {
+    static FIELDS: RefCell<Vec<(&'static str, Value)>> = RefCell::new(Vec::new());
+}
+
+/// Builder of the logger of the binaries. With `LOG_FORMAT=json`, every record is logged as a JSON
+/// object in a line of its own, with the fields set with [`set_field`] and [`with_fields`].
+pub fn formatted_builder() -> Builder {
+    let mut builder = pretty_env_logger::formatted_builder();
+    if *config::LOG_FORMAT_JSON {
+        builder.format(|buf, record| writeln!(buf, "{}", to_json(record)));
+    }
+    builder
+}
+
+/// Logger whose filters can be changed while running with [`set_filters`].
+pub struct ReloadableLogger;
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        matches!(LOGGER.read().unwrap().as_ref(), Some(logger) if logger.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if let Some(logger) = LOGGER.read().unwrap().as_ref() {
+            logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(logger) = LOGGER.read().unwrap().as_ref() {
+            logger.flush();
+        }
+    }
+}
+
+/// Logger of the binaries, starting with the given filters.
+pub fn logger(filters: &str) -> ReloadableLogger {
+    let mut current = FILTERS.lock().unwrap();
+    current.default = filters.to_string();
+    current.current = filters.to_string();
+    apply(filters);
+    ReloadableLogger
+}
+
+fn apply(filters: &str) {
+    let mut builder = formatted_builder();
+    builder.parse_filters(filters);
+    let logger = builder.build();
+    log::set_max_level(logger.filter());
+    *LOGGER.write().unwrap() = Some(logger);
+}
+
+/// Initializes the logger like `pretty_env_logger::init()`, honoring `LOG_FORMAT`.
+pub fn init() {
+    let filters = std::env::var("RUST_LOG").unwrap_or_default();
+    log::set_boxed_logger(Box::new(logger(&filters))).unwrap();
+}
+
+/// Changes the filters of the logger, like `hawkeye_worker=trace`, until `revert_after` elapses
+/// when given.
+pub fn set_filters(filters: &str, revert_after: Option<Duration>) {
+    let mut current = FILTERS.lock().unwrap();
+    apply(filters);
+    current.current = filters.to_string();
+    current.revert_at = revert_after.map(|after| Instant::now() + after);
+    current.generation += 1;
+
+    if let Some(after) = revert_after {
+        let generation = current.generation;
+        thread::spawn(move || {
+            thread::sleep(after);
+            if FILTERS.lock().unwrap().generation == generation {
+                reset_filters();
+            }
+        });
+    }
+}
+
+/// Restores the filters the logger started with.
+pub fn reset_filters() {
+    let default = FILTERS.lock().unwrap().default.clone();
+    set_filters(&default, None);
+    log::info!("Log filters reverted to \"{}\"", default);
+}
+
+/// Current filters of the logger and when they are reverted.
+pub fn filters() -> Value {
+    let current = FILTERS.lock().unwrap();
+    json!({
+        "filters": current.current,
+        "default_filters": current.default,
+        "revert_in_seconds": current.revert_at.map(|at| {
+            at.saturating_duration_since(Instant::now()).as_secs()
+        }),
+    })
+}
+
+/// Adds the field to all the JSON records, like the identifier of the watcher.
+pub fn set_field(name: &str, value: Value) {
+    GLOBAL_FIELDS
+        .write()
+        .unwrap()
+        .insert(name.to_string(), value);
+}
+
+/// Adds the fields to the JSON records logged by `f`, in the same thread.
+pub fn with_fields<R>(fields: &[(&'static str, Value)], f: impl FnOnce() -> R) -> R {
+    FIELDS.with(|current| current.borrow_mut().extend(fields.iter().cloned()));
+    let result = f();
+    FIELDS.with(|current| {
+        let mut current = current.borrow_mut();
+        let len = current.len() - fields.len();
+        current.truncate(len);
+    });
+    result
+}
+
+fn to_json(record: &Record) -> Value {
+    let mut entry = GLOBAL_FIELDS.read().unwrap().clone();
+    FIELDS.with(|fields| {
+        for (name, value) in fields.borrow().iter() {
+            entry.insert(name.to_string(), value.clone());
+        }
+    });
+    entry.insert("timestamp".into(), json!(Utc::now().to_rfc3339()));
+    entry.insert("level".into(), json!(record.level().as_str()));
+    entry.insert(
+        "module".into(),
+        json!(record.module_path().unwrap_or_else(|| record.target())),
+    );
+    entry.insert("message".into(), json!(record.args().to_string()));
+    Value::Object(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_as_json() {
+        set_field("watcher_id", json!("watcher-id"));
+        let entry = with_fields(
+            &[
+                ("transition", json!("content -> slate")),
+                ("duration_ms", json!(12)),
+            ],
+            || {
+                to_json(
+                    &Record::builder()
+                        .args(format_args!("Action executed"))
+                        .level(log::Level::Info)
+                        .module_path(Some("hawkeye_worker::actions"))
+                        .build(),
+                )
+            },
+        );
+        assert_eq!(entry["watcher_id"], "watcher-id");
+        assert_eq!(entry["transition"], "content -> slate");
+        assert_eq!(entry["duration_ms"], 12);
+        assert_eq!(entry["level"], "INFO");
+        assert_eq!(entry["module"], "hawkeye_worker::actions");
+        assert_eq!(entry["message"], "Action executed");
+
+        // Only the records logged within `with_fields` have the fields
+        let entry = to_json(&Record::builder().args(format_args!("Done")).build());
+        assert!(entry.get("transition").is_none());
+    }
+
+    #[test]
+    fn change_filters_while_running() {
+        let logger = logger("info");
+        let trace = Metadata::builder()
+            .level(log::Level::Trace)
+            .target("hawkeye_worker::video_stream")
+            .build();
+        assert!(!logger.enabled(&trace));
+
+        set_filters("hawkeye_worker=trace", Some(Duration::from_secs(600)));
+        assert!(logger.enabled(&trace));
+        assert_eq!(log::max_level(), log::LevelFilter::Trace);
+        let current = filters();
+        assert_eq!(current["filters"], "hawkeye_worker=trace");
+        assert_eq!(current["default_filters"], "info");
+        assert!(current["revert_in_seconds"].as_u64().unwrap() > 590);
+
+        reset_filters();
+        assert!(!logger.enabled(&trace));
+        assert!(filters()["revert_in_seconds"].is_null());
+    }
+}
@@ -1,14 +1,78 @@
-use color_eyre::{eyre::eyre, Result};
-use serde::{Deserialize, Serialize};
+use crate::condition::Condition;
+use chrono::{DateTime, Datelike, Duration as TimeDelta, Timelike, Utc};
+use chrono_tz::Tz;
+use color_eyre::{eyre::eyre, Report, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::skip_serializing_none;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// URL schemes supported for the slate reference images, `asset://` references a slate uploaded
+/// to the API.
+pub const SLATE_URL_SCHEMES: [&str; 5] = ["http://", "https://", "file://", "s3://", "asset://"];
 
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Watcher {
     pub id: Option<String>,
     pub description: Option<String>,
+    /// Group of watchers, like the channels of a lineup, operated as a whole.
+    pub group: Option<String>,
     pub slate_url: String,
+    /// Additional reference images of the same slate (e.g. day and night versions), a match with
+    /// any of them means the frame is a slate.
+    pub slate_variants: Option<Vec<String>>,
+    /// Credentials used when downloading the slate images over HTTP.
+    pub slate_authorization: Option<HttpAuth>,
+    /// Additional headers sent when downloading the slate images over HTTP.
+    pub slate_headers: Option<HashMap<String, String>>,
+    /// Regular expression matched against the text found in the frame, a match means the frame
+    /// is a slate. Useful when the slate image changes often but keeps the same message.
+    pub slate_text: Option<String>,
+    /// Maximum DSSIM score (multiplied by 1000) of a frame to be considered the slate.
+    pub slate_threshold: Option<u32>,
+    /// Learns the slate threshold from the frames seen after the worker starts.
+    pub slate_threshold_learning: Option<ThresholdLearning>,
+    /// Restricts the comparison with the slate to this region of the frame.
+    pub slate_region: Option<Region>,
+    pub debounce: Option<Debounce>,
+    /// Maximum average luminance (0-255) of a frame to be considered black.
+    pub black_threshold: Option<u8>,
+    /// Report the standard SMPTE color bars as `VideoMode::Bars`.
+    pub detect_bars: Option<bool>,
+    /// Default `cooldown_seconds` of the transitions.
+    pub cooldown_seconds: Option<u32>,
+    /// Logs the actions the worker would execute instead of executing them.
+    pub dry_run: Option<bool>,
+    pub metrics: Option<MetricsSettings>,
+    /// Records a clip of the stream around each transition.
+    pub clips: Option<ClipSettings>,
+    /// Keeps the frames of each transition.
+    pub snapshots: Option<SnapshotSettings>,
+    /// Image of the worker running the watcher, instead of the one the API deploys by default.
+    /// Lets canary watchers run a new worker build while the others stay pinned.
+    pub worker_image: Option<String>,
+    /// Nodes the worker runs on, like the node pool of the watchers decoding on GPUs. Like the
+    /// `worker_image`, only the administrators set it.
+    pub scheduling: Option<Scheduling>,
+    /// Added to the annotations of the Kubernetes objects of the watcher, its Service, its Deployment
+    /// and the pod of its worker, like the flags of a service mesh or a cost center.
+    pub annotations: Option<BTreeMap<String, String>>,
+    /// DNS name of the ingest address, like `channel-1.ingest.example.com`, kept pointing at the
+    /// Service of the watcher by external-dns so it doesn't change when the watcher is recreated.
+    pub hostname: Option<String>,
+    /// Address ranges (CIDR) the feed can be sent from, like the ones of the encoders. Without it,
+    /// the ingest port is open to any address.
+    pub allowed_sources: Option<Vec<String>>,
+    /// Time (RFC 3339) the watcher expires at, the API stops or deletes it then so temporary
+    /// watchers aren't left running.
+    pub expires_at: Option<String>,
+    /// What happens to the watcher when it expires, it's stopped by default.
+    pub on_expiry: Option<ExpiryAction>,
+    /// Seconds left until the watcher expires, set by the API in its responses.
+    pub expires_in_seconds: Option<i64>,
     pub status: Option<Status>,
     pub status_description: Option<String>,
     pub source: Source,
@@ -17,13 +81,832 @@ pub struct Watcher {
 
 impl Watcher {
     pub fn is_valid(&self) -> Result<()> {
-        if self.slate_url.starts_with("http://")
-            || self.slate_url.starts_with("https://")
-            || self.slate_url.starts_with("file://")
+        self.validate().map_err(Report::new)
+    }
+
+    /// Checks the whole watcher, with the problems of all its fields instead of the first one.
+    pub fn validate(&self) -> std::result::Result<(), ValidationErrors> {
+        let mut validation = Validation::default();
+        validation.check("slate_url", slate_url_is_valid(&self.slate_url));
+        for (index, url) in self.slate_variants.iter().flatten().enumerate() {
+            validation.check(
+                format!("slate_variants[{}]", index),
+                slate_url_is_valid(url),
+            );
+        }
+        if let Some(pattern) = self.slate_text.as_ref() {
+            validation.check(
+                "slate_text",
+                Regex::new(pattern)
+                    .map(drop)
+                    .map_err(|err| eyre!("Invalid slate_text expression {}: {}", pattern, err)),
+            );
+        }
+        if let Some(region) = self.slate_region.as_ref() {
+            validation.check("slate_region", region.is_valid());
+        }
+        if let Some(debounce) = self.debounce.as_ref() {
+            validation.check("debounce", debounce.is_valid());
+        }
+        if let Some(learning) = self.slate_threshold_learning.as_ref() {
+            validation.check("slate_threshold_learning", learning.is_valid());
+        }
+        if let Some(metrics) = self.metrics.as_ref() {
+            validation.check("metrics", metrics.is_valid());
+        }
+        if let Some(clips) = self.clips.as_ref() {
+            validation.check("clips", clips.is_valid());
+        }
+        if let Some(snapshots) = self.snapshots.as_ref() {
+            validation.check("snapshots", snapshots.is_valid());
+        }
+        if let Some(image) = self.worker_image.as_ref() {
+            if image.is_empty() || image.contains(char::is_whitespace) {
+                validation.check(
+                    "worker_image",
+                    Err(eyre!("Invalid worker_image {:?}", image)),
+                );
+            }
+        }
+        if let Some(scheduling) = self.scheduling.as_ref() {
+            validation.check("scheduling", scheduling.is_valid());
+        }
+        for key in self.annotations.iter().flat_map(BTreeMap::keys) {
+            if !is_label_key(key) {
+                validation.check(
+                    format!("annotations.{}", key),
+                    Err(eyre!("Invalid annotation {:?}", key)),
+                );
+            }
+        }
+        if let Some(hostname) = self.hostname.as_ref() {
+            if !is_dns_name(hostname) {
+                validation.check("hostname", Err(eyre!("Invalid hostname {:?}", hostname)));
+            } else if self.source.exposure == Some(Exposure::None) {
+                validation.check(
+                    "hostname",
+                    Err(eyre!(
+                        "A watcher without exposure has no ingest address to name"
+                    )),
+                );
+            }
+        }
+        if let Some(ranges) = self.allowed_sources.as_ref() {
+            // A NetworkPolicy allowing no range would allow them all
+            if ranges.is_empty() {
+                validation.check(
+                    "allowed_sources",
+                    Err(eyre!("At least one address range is required")),
+                );
+            }
+            for (index, range) in ranges.iter().enumerate() {
+                if !is_cidr(range) {
+                    validation.check(
+                        format!("allowed_sources[{}]", index),
+                        Err(eyre!("Invalid address range {:?}, like 10.0.0.0/16", range)),
+                    );
+                }
+            }
+        }
+        if let Some(group) = self.group.as_ref() {
+            if !is_label_value(group) {
+                validation.check(
+                    "group",
+                    Err(eyre!(
+                        "Invalid group {:?}, up to 63 letters, digits, '-', '_' or '.' are allowed",
+                        group
+                    )),
+                );
+            }
+        }
+        if let Some(expires_at) = self.expires_at.as_ref() {
+            validation.check(
+                "expires_at",
+                DateTime::parse_from_rfc3339(expires_at)
+                    .map(drop)
+                    .map_err(|err| eyre!("Invalid expires_at {:?}: {}", expires_at, err)),
+            );
+        }
+        for (index, transition) in self.transitions.iter().enumerate() {
+            transition.validate(&format!("transitions[{}]", index), &mut validation);
+        }
+        for (field, secret) in self.secret_values() {
+            if let SecretValue::Reference { value_from } = secret {
+                if is_reserved_env(&value_from.env) {
+                    validation.check(
+                        field,
+                        Err(eyre!(
+                            "The environment variable {} is set by Hawkeye, it can't hold a secret",
+                            value_from.env
+                        )),
+                    );
+                }
+            }
+        }
+        validation.check("source.ingest_port", self.source.is_valid());
+        validation.finish()
+    }
+
+    /// The sensitive values of the watcher, with the field they're in.
+    pub fn secret_values(&self) -> Vec<(String, &SecretValue)> {
+        let mut secrets: Vec<(String, &SecretValue)> = Vec::new();
+        if let Some(authorization) = self.slate_authorization.as_ref() {
+            secrets.push(("slate_authorization".to_string(), authorization.secret()));
+        }
+        for (index, transition) in self.transitions.iter().enumerate() {
+            for (action_index, action) in transition.actions.iter().enumerate() {
+                let field = format!("transitions[{}].actions[{}]", index, action_index);
+                match action {
+                    Action::HttpCall(call) => {
+                        if let Some(authorization) = call.authorization.as_ref() {
+                            secrets
+                                .push((format!("{}.authorization", field), authorization.secret()));
+                        }
+                        if let Some(identity) = call.client_certificate.as_ref() {
+                            secrets.push((
+                                format!("{}.client_certificate.certificate", field),
+                                &identity.certificate,
+                            ));
+                            secrets
+                                .push((format!("{}.client_certificate.key", field), &identity.key));
+                        }
+                    }
+                    Action::MqttPublish(mqtt) => {
+                        if let Some(password) = mqtt.password.as_ref() {
+                            secrets.push((format!("{}.password", field), password));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        secrets
+    }
+
+    /// Secrets referenced by the watcher, they must be available to the worker as environment
+    /// variables.
+    pub fn secret_sources(&self) -> Vec<&SecretSource> {
+        self.secret_values()
+            .into_iter()
+            .filter_map(|(_, secret)| match secret {
+                SecretValue::Reference { value_from } => Some(value_from),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// When the watcher expires, if it does.
+    pub fn expiration(&self) -> Option<DateTime<Utc>> {
+        let expires_at = DateTime::parse_from_rfc3339(self.expires_at.as_ref()?).ok()?;
+        Some(expires_at.with_timezone(&Utc))
+    }
+
+    /// Whether the watcher expired at the time.
+    pub fn expired(&self, at: DateTime<Utc>) -> bool {
+        matches!(self.expiration(), Some(expiration) if expiration <= at)
+    }
+
+    /// URLs of all the reference images of the slate, starting with `slate_url`.
+    pub fn slate_urls(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.slate_url).chain(self.slate_variants.iter().flatten())
+    }
+}
+
+fn slate_url_is_valid(url: &str) -> Result<()> {
+    if SLATE_URL_SCHEMES
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+    {
+        Ok(())
+    } else {
+        Err(eyre!("{} not recognized as a valid URL!", url))
+    }
+}
+
+/// A problem of a field of a configuration, like `transitions[0].schedule`.
+#[derive(Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// All the problems found validating a configuration.
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<FieldError>);
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let messages: Vec<&str> = self.0.iter().map(|error| error.message.as_str()).collect();
+        write!(f, "{}", messages.join("; "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+/// Collects the problems of the fields, to report them all at once.
+#[derive(Default)]
+struct Validation {
+    errors: Vec<FieldError>,
+}
+
+impl Validation {
+    fn check(&mut self, field: impl Into<String>, result: Result<()>) {
+        if let Err(err) = result {
+            self.errors.push(FieldError {
+                field: field.into(),
+                message: err.to_string(),
+            });
+        }
+    }
+
+    fn finish(self) -> std::result::Result<(), ValidationErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationErrors(self.errors))
+        }
+    }
+}
+
+/// Whether the environment variable configures the worker, like `RUST_LOG` or the ones set by the
+/// API, rather than holding a secret of the watcher.
+pub fn is_reserved_env(name: &str) -> bool {
+    name == "RUST_LOG" || name.starts_with("HAWKEYE_") || name.starts_with("OTEL_")
+}
+
+/// Whether the value can be the value of a Kubernetes label, like the group of the watchers.
+pub fn is_label_value(value: &str) -> bool {
+    value.len() <= 63
+        && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value.ends_with(|c: char| c.is_ascii_alphanumeric())
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+/// Whether the key can be the key of a Kubernetes label, a name with an optional DNS prefix like
+/// `node.kubernetes.io/instance-type`.
+pub fn is_label_key(key: &str) -> bool {
+    let (prefix, name) = match key.rsplit_once('/') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, key),
+    };
+    let valid_prefix = match prefix {
+        Some(prefix) => is_dns_name(prefix),
+        None => true,
+    };
+    is_label_value(name) && valid_prefix
+}
+
+/// Whether the name is a lowercase DNS name, like `channel-1.ingest.example.com`.
+pub fn is_dns_name(name: &str) -> bool {
+    name.len() <= 253
+        && name.split('.').all(|label| {
+            !label.is_empty()
+                && label.len() <= 63
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// Whether the range is a CIDR block, like `10.0.0.0/16` or `2001:db8::/32`.
+pub fn is_cidr(range: &str) -> bool {
+    let (address, prefix) = match range.split_once('/') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    match (address.parse::<IpAddr>(), prefix.parse::<u8>()) {
+        (Ok(IpAddr::V4(_)), Ok(prefix)) => prefix <= 32,
+        (Ok(IpAddr::V6(_)), Ok(prefix)) => prefix <= 128,
+        _ => false,
+    }
+}
+
+/// Deserializes a field that can be left out, set to `null` or set to a value, to tell apart the
+/// fields of an update that are kept (`None`) from the ones removed (`Some(None)`).
+fn present<'de, T, D>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Replaces the value when the update has one, `null` removes it.
+fn apply_field<T>(field: &mut Option<T>, update: Option<Option<T>>) {
+    if let Some(value) = update {
+        *field = value;
+    }
+}
+
+/// Changes to a watcher, the fields left out are kept and the optional ones set to `null` are
+/// removed. The source and the identity of the watcher can't be changed.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WatcherUpdate {
+    #[serde(default, deserialize_with = "present")]
+    pub description: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub group: Option<Option<String>>,
+    pub slate_url: Option<String>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_variants: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_authorization: Option<Option<HttpAuth>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_headers: Option<Option<HashMap<String, String>>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_text: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_threshold: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_threshold_learning: Option<Option<ThresholdLearning>>,
+    #[serde(default, deserialize_with = "present")]
+    pub slate_region: Option<Option<Region>>,
+    #[serde(default, deserialize_with = "present")]
+    pub debounce: Option<Option<Debounce>>,
+    #[serde(default, deserialize_with = "present")]
+    pub black_threshold: Option<Option<u8>>,
+    #[serde(default, deserialize_with = "present")]
+    pub detect_bars: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "present")]
+    pub cooldown_seconds: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub dry_run: Option<Option<bool>>,
+    #[serde(default, deserialize_with = "present")]
+    pub metrics: Option<Option<MetricsSettings>>,
+    #[serde(default, deserialize_with = "present")]
+    pub clips: Option<Option<ClipSettings>>,
+    #[serde(default, deserialize_with = "present")]
+    pub snapshots: Option<Option<SnapshotSettings>>,
+    #[serde(default, deserialize_with = "present")]
+    pub worker_image: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub scheduling: Option<Option<Scheduling>>,
+    #[serde(default, deserialize_with = "present")]
+    pub annotations: Option<Option<BTreeMap<String, String>>>,
+    #[serde(default, deserialize_with = "present")]
+    pub hostname: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub allowed_sources: Option<Option<Vec<String>>>,
+    #[serde(default, deserialize_with = "present")]
+    pub expires_at: Option<Option<String>>,
+    #[serde(default, deserialize_with = "present")]
+    pub on_expiry: Option<Option<ExpiryAction>>,
+    /// Changes to the transitions, matched by their `from` and `to` modes.
+    pub transitions: Option<Vec<TransitionUpdate>>,
+}
+
+/// Changes to the transition between two video modes, it's added when the watcher doesn't have
+/// it and removed with `remove`.
+#[derive(Deserialize, Clone, Debug, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct TransitionUpdate {
+    pub from: VideoMode,
+    pub to: VideoMode,
+    #[serde(default)]
+    pub remove: bool,
+    #[serde(default, deserialize_with = "present")]
+    pub min_dwell: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub cooldown_seconds: Option<Option<u32>>,
+    #[serde(default, deserialize_with = "present")]
+    pub schedule: Option<Option<Schedule>>,
+    #[serde(default, deserialize_with = "present")]
+    pub circuit_breaker: Option<Option<CircuitBreaker>>,
+    #[serde(default, deserialize_with = "present")]
+    pub chain: Option<Option<bool>>,
+    pub actions: Option<Vec<Action>>,
+}
+
+impl WatcherUpdate {
+    /// Applies the changes to the watcher, which must be validated afterwards.
+    pub fn apply(self, watcher: &mut Watcher) -> Result<()> {
+        apply_field(&mut watcher.description, self.description);
+        apply_field(&mut watcher.group, self.group);
+        if let Some(slate_url) = self.slate_url {
+            watcher.slate_url = slate_url;
+        }
+        apply_field(&mut watcher.slate_variants, self.slate_variants);
+        apply_field(&mut watcher.slate_authorization, self.slate_authorization);
+        apply_field(&mut watcher.slate_headers, self.slate_headers);
+        apply_field(&mut watcher.slate_text, self.slate_text);
+        apply_field(&mut watcher.slate_threshold, self.slate_threshold);
+        apply_field(
+            &mut watcher.slate_threshold_learning,
+            self.slate_threshold_learning,
+        );
+        apply_field(&mut watcher.slate_region, self.slate_region);
+        apply_field(&mut watcher.debounce, self.debounce);
+        apply_field(&mut watcher.black_threshold, self.black_threshold);
+        apply_field(&mut watcher.detect_bars, self.detect_bars);
+        apply_field(&mut watcher.cooldown_seconds, self.cooldown_seconds);
+        apply_field(&mut watcher.dry_run, self.dry_run);
+        apply_field(&mut watcher.metrics, self.metrics);
+        apply_field(&mut watcher.clips, self.clips);
+        apply_field(&mut watcher.snapshots, self.snapshots);
+        apply_field(&mut watcher.worker_image, self.worker_image);
+        apply_field(&mut watcher.scheduling, self.scheduling);
+        apply_field(&mut watcher.annotations, self.annotations);
+        apply_field(&mut watcher.hostname, self.hostname);
+        apply_field(&mut watcher.allowed_sources, self.allowed_sources);
+        apply_field(&mut watcher.expires_at, self.expires_at);
+        apply_field(&mut watcher.on_expiry, self.on_expiry);
+        for update in self.transitions.into_iter().flatten() {
+            update.apply(&mut watcher.transitions)?;
+        }
+        Ok(())
+    }
+}
+
+impl TransitionUpdate {
+    fn apply(self, transitions: &mut Vec<Transition>) -> Result<()> {
+        let position = transitions
+            .iter()
+            .position(|transition| transition.from == self.from && transition.to == self.to);
+        let transition = match (position, self.remove) {
+            (Some(position), true) => {
+                transitions.remove(position);
+                return Ok(());
+            }
+            (None, true) => {
+                return Err(eyre!(
+                    "No transition from {} to {} to remove",
+                    self.from,
+                    self.to
+                ))
+            }
+            (Some(position), false) => &mut transitions[position],
+            (None, false) => {
+                if self.actions.is_none() {
+                    return Err(eyre!(
+                        "The new transition from {} to {} requires actions",
+                        self.from,
+                        self.to
+                    ));
+                }
+                transitions.push(Transition {
+                    from: self.from,
+                    to: self.to,
+                    min_dwell: None,
+                    cooldown_seconds: None,
+                    schedule: None,
+                    circuit_breaker: None,
+                    chain: None,
+                    actions: Vec::new(),
+                });
+                transitions.last_mut().unwrap()
+            }
+        };
+        apply_field(&mut transition.min_dwell, self.min_dwell);
+        apply_field(&mut transition.cooldown_seconds, self.cooldown_seconds);
+        apply_field(&mut transition.schedule, self.schedule);
+        apply_field(&mut transition.circuit_breaker, self.circuit_breaker);
+        apply_field(&mut transition.chain, self.chain);
+        if let Some(actions) = self.actions {
+            transition.actions = actions;
+        }
+        Ok(())
+    }
+}
+
+/// What happens to a watcher when it expires.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpiryAction {
+    Stop,
+    Delete,
+}
+
+/// A rectangular area of the video frame.
+///
+/// Values are percentages of the frame dimensions, so the region does not depend on the
+/// resolution of the video feed.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Region {
+    fn is_valid(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            Err(eyre!("Region {:?} must have a width and height", self))
+        } else if self.x.saturating_add(self.width) > 100
+            || self.y.saturating_add(self.height) > 100
         {
-            Ok(self.source.is_valid()?)
+            Err(eyre!(
+                "Region {:?} must be within the frame (values are percentages)",
+                self
+            ))
         } else {
-            Err(eyre!("{} not recognized as a valid URL!", self.slate_url))
+            Ok(())
+        }
+    }
+}
+
+/// Settings of the Prometheus metrics of the worker.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct MetricsSettings {
+    /// Prepended to the names of the metrics, like `hawkeye_`.
+    pub prefix: Option<String>,
+    /// Upper bounds, in milliseconds, of the buckets of the duration histograms.
+    pub buckets_ms: Option<Vec<u32>>,
+}
+
+impl MetricsSettings {
+    pub fn is_valid(&self) -> Result<()> {
+        if let Some(prefix) = self.prefix.as_ref() {
+            if !Regex::new("^[a-zA-Z_:][a-zA-Z0-9_:]*$")
+                .unwrap()
+                .is_match(prefix)
+            {
+                return Err(eyre!("Invalid metrics prefix {}", prefix));
+            }
+        }
+        if let Some(buckets) = self.buckets_ms.as_ref() {
+            if buckets.is_empty() || buckets.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(eyre!(
+                    "The metrics buckets must be increasing: {:?}",
+                    buckets
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Constraints on the nodes the worker of a watcher runs on, set in the pod of the worker.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Scheduling {
+    /// Labels of the nodes the worker can run on, like `{"pool": "gpu"}`.
+    pub node_selector: Option<BTreeMap<String, String>>,
+    /// Taints of the nodes the worker tolerates.
+    pub tolerations: Option<Vec<Toleration>>,
+    /// Kubernetes `affinity` of the pod of the worker, as is, like its `nodeAffinity`.
+    pub affinity: Option<serde_json::Value>,
+}
+
+impl Scheduling {
+    /// Kinds of affinity of a Kubernetes pod.
+    pub const AFFINITIES: [&'static str; 3] = ["nodeAffinity", "podAffinity", "podAntiAffinity"];
+
+    pub fn is_valid(&self) -> Result<()> {
+        for (key, value) in self.node_selector.iter().flatten() {
+            if !is_label_key(key) || !is_label_value(value) {
+                return Err(eyre!("Invalid node_selector label {}={:?}", key, value));
+            }
+        }
+        for toleration in self.tolerations.iter().flatten() {
+            toleration.is_valid()?;
+        }
+        match self.affinity.as_ref() {
+            None => {}
+            Some(serde_json::Value::Object(affinity)) => {
+                if let Some(kind) = affinity
+                    .keys()
+                    .find(|kind| !Self::AFFINITIES.contains(&kind.as_str()))
+                {
+                    return Err(eyre!(
+                        "Unknown affinity {}, only {} are allowed",
+                        kind,
+                        Self::AFFINITIES.join(", ")
+                    ));
+                }
+            }
+            Some(_) => return Err(eyre!("The affinity must be an object")),
+        }
+        Ok(())
+    }
+}
+
+/// Taint of the nodes tolerated by the worker, like a Kubernetes pod toleration.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Toleration {
+    /// Key of the taint, all the taints are tolerated without it when the operator is `Exists`.
+    pub key: Option<String>,
+    /// `Equal` by default.
+    pub operator: Option<TolerationOperator>,
+    pub value: Option<String>,
+    /// Effect of the taint tolerated, all of them by default.
+    pub effect: Option<TaintEffect>,
+    /// Seconds the worker stays on a node after the `NoExecute` taint is added.
+    pub toleration_seconds: Option<i64>,
+}
+
+impl Toleration {
+    pub fn is_valid(&self) -> Result<()> {
+        let exists = self.operator == Some(TolerationOperator::Exists);
+        if self.key.is_none() && !exists {
+            return Err(eyre!(
+                "A toleration without key must use the Exists operator"
+            ));
+        }
+        if exists && self.value.is_some() {
+            return Err(eyre!(
+                "A toleration with the Exists operator can't have a value"
+            ));
+        }
+        if self.toleration_seconds.is_some() && self.effect != Some(TaintEffect::NoExecute) {
+            return Err(eyre!(
+                "Only the tolerations of the NoExecute effect can have toleration_seconds"
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TolerationOperator {
+    Equal,
+    Exists,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TaintEffect {
+    NoSchedule,
+    PreferNoSchedule,
+    NoExecute,
+}
+
+/// Recording of the stream around the transitions, to review the detections afterwards.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ClipSettings {
+    /// Location of the clips, like `s3://bucket/clips`. The keys of the clips start with the id
+    /// of the watcher and the time of the transition.
+    pub url: String,
+    /// Seconds of the stream kept before the transition, defaults to 15.
+    pub before_seconds: Option<u32>,
+    /// Seconds of the stream recorded after the transition, defaults to 15.
+    pub after_seconds: Option<u32>,
+}
+
+impl ClipSettings {
+    /// Longest clip recorded, the stream is kept in memory.
+    pub const MAX_SECONDS: u32 = 120;
+
+    pub fn is_valid(&self) -> Result<()> {
+        self.s3_location()?;
+        if self.before_seconds().saturating_add(self.after_seconds()) > Self::MAX_SECONDS {
+            return Err(eyre!(
+                "Clips can't be longer than {} seconds",
+                Self::MAX_SECONDS
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn before_seconds(&self) -> u32 {
+        self.before_seconds.unwrap_or(15)
+    }
+
+    pub fn after_seconds(&self) -> u32 {
+        self.after_seconds.unwrap_or(15)
+    }
+
+    /// Bucket and prefix of the keys of the clips.
+    pub fn s3_location(&self) -> Result<(&str, &str)> {
+        if !self.url.starts_with("s3://") {
+            return Err(eyre!("Clips can only be uploaded to S3: {}", self.url));
+        }
+        s3_location(&self.url)
+    }
+}
+
+/// Frames kept for each transition: the frame the new video mode was reported in and the frame
+/// before it.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SnapshotSettings {
+    /// Local directory of the worker, like `/var/lib/hawkeye/snapshots`, or S3 location, like
+    /// `s3://bucket/snapshots`.
+    pub url: String,
+    /// Number of transitions whose frames are kept, the oldest are deleted first. Defaults to 100.
+    pub retention: Option<u32>,
+}
+
+impl SnapshotSettings {
+    pub fn is_valid(&self) -> Result<()> {
+        if self.url.is_empty() {
+            return Err(eyre!("The location of the snapshots is required"));
+        }
+        if self.url.starts_with("s3://") {
+            s3_location(&self.url)?;
+        }
+        if self.retention == Some(0) {
+            return Err(eyre!(
+                "The snapshots of at least one transition must be kept"
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn retention(&self) -> u32 {
+        self.retention.unwrap_or(100)
+    }
+}
+
+/// Bucket and prefix of an `s3://bucket/prefix` URL.
+pub fn s3_location(url: &str) -> Result<(&str, &str)> {
+    let location = url.trim_start_matches("s3://");
+    let (bucket, prefix) = location.split_once('/').unwrap_or((location, ""));
+    if bucket.is_empty() {
+        return Err(eyre!("Invalid S3 URL: {}", url));
+    }
+    Ok((bucket, prefix.trim_end_matches('/')))
+}
+
+/// Requires a video mode to be found in a number of frames before it is reported, so a single
+/// noisy frame does not trigger a transition.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Debounce {
+    /// Number of frames the video mode must be found in.
+    pub frames: u32,
+    /// Number of most recent frames considered. Defaults to `frames`, meaning the video mode
+    /// must be found in consecutive frames.
+    pub window: Option<u32>,
+}
+
+/// Most recent frames a debounce can consider, they're kept in memory.
+const MAX_DEBOUNCE_WINDOW: u32 = 1000;
+
+impl Debounce {
+    pub fn window(&self) -> u32 {
+        self.window.unwrap_or(self.frames)
+    }
+
+    fn is_valid(&self) -> Result<()> {
+        if self.frames == 0 {
+            Err(eyre!("Debounce frames must be at least 1"))
+        } else if self.window() > MAX_DEBOUNCE_WINDOW {
+            Err(eyre!(
+                "Debounce window ({}) cannot be more than {} frames",
+                self.window(),
+                MAX_DEBOUNCE_WINDOW
+            ))
+        } else if self.frames > self.window() {
+            Err(eyre!(
+                "Debounce frames ({}) cannot be more than the window ({})",
+                self.frames,
+                self.window()
+            ))
+        } else if u64::from(self.frames) * 2 <= u64::from(self.window()) {
+            Err(eyre!(
+                "Debounce frames ({}) must be more than half of the window ({})",
+                self.frames,
+                self.window()
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Records the similarity scores of the frames during a window of time to recommend a slate
+/// threshold that separates the slate from the content.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ThresholdLearning {
+    /// Duration of the learning window in seconds.
+    pub window: u32,
+    /// Use the learned threshold, otherwise it's only reported.
+    pub adjust: Option<bool>,
+    /// Lower bound of the learned threshold.
+    pub min_threshold: Option<u32>,
+    /// Upper bound of the learned threshold.
+    pub max_threshold: Option<u32>,
+}
+
+impl ThresholdLearning {
+    /// Restricts the threshold to the configured bounds.
+    pub fn bound(&self, threshold: u32) -> u32 {
+        threshold.clamp(
+            self.min_threshold.unwrap_or(0),
+            self.max_threshold.unwrap_or(u32::MAX),
+        )
+    }
+
+    fn is_valid(&self) -> Result<()> {
+        if self.window == 0 {
+            Err(eyre!("Threshold learning window must be at least 1 second"))
+        } else if self.min_threshold.unwrap_or(0) > self.max_threshold.unwrap_or(u32::MAX) {
+            Err(eyre!(
+                "Threshold learning bounds are inverted: {:?} > {:?}",
+                self.min_threshold,
+                self.max_threshold
+            ))
+        } else {
+            Ok(())
         }
     }
 }
@@ -41,10 +924,31 @@ pub enum Status {
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Source {
     pub ingest_ip: Option<String>,
+    /// Port the feed is sent to. Left out, or 0, when creating the watcher, the API allocates a free
+    /// one.
+    #[serde(default)]
     pub ingest_port: u32,
     pub container: Container,
     pub codec: Codec,
     pub transport: Protocol,
+    /// How the feed reaches the worker, through a public load balancer by default.
+    pub exposure: Option<Exposure>,
+}
+
+/// How the ingest port of the worker is exposed, the Kubernetes Service in front of it.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Exposure {
+    /// Public load balancer, an AWS NLB.
+    LoadBalancer,
+    /// Load balancer only reachable from the private network of the cluster, for internal feeds.
+    InternalLoadBalancer,
+    /// Port open on every node of the cluster, for clusters without load balancers.
+    NodePort,
+    /// Address only reachable from inside the cluster.
+    ClusterIp,
+    /// No Service, for the sources the worker pulls itself.
+    None,
 }
 
 impl Source {
@@ -81,30 +985,278 @@ pub enum Protocol {
     Rtp,
 }
 
+#[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct Transition {
     pub from: VideoMode,
     pub to: VideoMode,
+    /// Minimum time in seconds the video must stay in the `from` mode for the transition to
+    /// execute its actions.
+    pub min_dwell: Option<u32>,
+    /// Minimum time in seconds between two executions of the actions, defaults to the cooldown of
+    /// the watcher or 5 seconds.
+    pub cooldown_seconds: Option<u32>,
+    /// Restricts the actions to a window of time, like the broadcast hours.
+    pub schedule: Option<Schedule>,
+    /// Stops calling the actions of the transition that keep failing.
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// Runs the actions one after the other, in order, instead of independently of each other.
+    pub chain: Option<bool>,
     pub actions: Vec<Action>,
 }
 
+/// Days of the week and time of the day the actions of a transition can run.
+///
+/// When `end` is before `start` the window goes past midnight, and belongs to the day it starts.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct Schedule {
+    /// Defaults to every day.
+    pub days: Option<Vec<Weekday>>,
+    /// Time of the day (`HH:MM`) the window starts, defaults to midnight.
+    pub start: Option<String>,
+    /// Time of the day (`HH:MM`) the window ends, defaults to the end of the day.
+    pub end: Option<String>,
+    /// Timezone of the days and times, like `America/New_York`. Defaults to UTC.
+    pub timezone: Option<String>,
+}
+
+impl Schedule {
+    /// Check if the time is within the window.
+    pub fn is_active(&self, at: DateTime<Utc>) -> bool {
+        let timezone: Tz = self
+            .timezone
+            .as_deref()
+            .and_then(|tz| tz.parse().ok())
+            .unwrap_or(Tz::UTC);
+        let local = at.with_timezone(&timezone);
+        let minute = local.hour() * 60 + local.minute();
+        let start = self.start.as_deref().and_then(minute_of_day).unwrap_or(0);
+        let end = self
+            .end
+            .as_deref()
+            .and_then(minute_of_day)
+            .unwrap_or(24 * 60);
+
+        let day = if start <= end {
+            if minute < start || minute >= end {
+                return false;
+            }
+            local.weekday()
+        } else if minute >= start {
+            local.weekday()
+        } else if minute < end {
+            // Past midnight, in the window that started the day before
+            (local - TimeDelta::days(1)).weekday()
+        } else {
+            return false;
+        };
+        match &self.days {
+            None => true,
+            Some(days) => days.iter().any(|d| d.to_chrono() == day),
+        }
+    }
+
+    fn is_valid(&self) -> Result<()> {
+        for time in self.start.iter().chain(self.end.iter()) {
+            if minute_of_day(time).is_none() {
+                return Err(eyre!("Invalid schedule time {}, expected HH:MM", time));
+            }
+        }
+        if let Some(timezone) = self.timezone.as_ref() {
+            timezone
+                .parse::<Tz>()
+                .map_err(|err| eyre!("Invalid schedule timezone {}: {}", timezone, err))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses `HH:MM` as the number of minutes since midnight, `24:00` being the end of the day.
+fn minute_of_day(time: &str) -> Option<u32> {
+    let (hours, minutes) = time.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    if minutes >= 60 || hours * 60 + minutes > 24 * 60 {
+        return None;
+    }
+    Some(hours * 60 + minutes)
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl Weekday {
+    fn to_chrono(self) -> chrono::Weekday {
+        match self {
+            Weekday::Monday => chrono::Weekday::Mon,
+            Weekday::Tuesday => chrono::Weekday::Tue,
+            Weekday::Wednesday => chrono::Weekday::Wed,
+            Weekday::Thursday => chrono::Weekday::Thu,
+            Weekday::Friday => chrono::Weekday::Fri,
+            Weekday::Saturday => chrono::Weekday::Sat,
+            Weekday::Sunday => chrono::Weekday::Sun,
+        }
+    }
+}
+
+/// Skips an action after a number of consecutive failures, until the `reset_timeout` passes and a
+/// call is let through to probe if the action works again.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct CircuitBreaker {
+    /// Consecutive failures that open the circuit.
+    pub failures: u32,
+    /// Seconds the circuit stays open before probing the action, defaults to 60.
+    pub reset_timeout: Option<u32>,
+}
+
+impl Transition {
+    /// Checks the transition, the `field` of the watcher.
+    fn validate(&self, field: &str, validation: &mut Validation) {
+        if self.to == VideoMode::Any {
+            validation.check(
+                format!("{}.to", field),
+                Err(eyre!(
+                    "Transitions can only use the `any` video mode in `from`"
+                )),
+            );
+        }
+        if let Some(schedule) = self.schedule.as_ref() {
+            validation.check(format!("{}.schedule", field), schedule.is_valid());
+        }
+        if matches!(self.circuit_breaker, Some(breaker) if breaker.failures == 0) {
+            validation.check(
+                format!("{}.circuit_breaker", field),
+                Err(eyre!("Circuit breaker failures must be at least 1")),
+            );
+        }
+        for (index, action) in self.actions.iter().enumerate() {
+            let field = format!("{}.actions[{}]", field, index);
+            if let Some(condition) = action.condition() {
+                validation.check(
+                    format!("{}.condition", field),
+                    Condition::parse(condition).map(drop),
+                );
+            }
+            if let Action::HttpCall(call) = action {
+                if let Some(retry) = call.retry.as_ref() {
+                    validation.check(format!("{}.retry", field), retry.is_valid());
+                }
+                if let Some(pattern) = call.expected_body.as_ref() {
+                    validation.check(
+                        format!("{}.expected_body", field),
+                        Regex::new(pattern).map(drop).map_err(|err| {
+                            eyre!("Invalid expected_body expression {}: {}", pattern, err)
+                        }),
+                    );
+                }
+            }
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum VideoMode {
     Slate,
     Content,
+    Black,
+    Bars,
+    /// Matches every other video mode, used as the `from` of a transition.
+    Any,
+}
+
+impl std::fmt::Display for VideoMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VideoMode::Slate => write!(f, "slate"),
+            VideoMode::Content => write!(f, "content"),
+            VideoMode::Black => write!(f, "black"),
+            VideoMode::Bars => write!(f, "bars"),
+            VideoMode::Any => write!(f, "any"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum Action {
     HttpCall(HttpCall),
+    SnsPublish(SnsPublish),
+    EventBridgePut(EventBridgePut),
+    ChatNotification(ChatNotification),
+    MqttPublish(MqttPublish),
+    RedisPublish(RedisPublish),
 
     // #[cfg(test)]
     #[serde(skip_serializing, skip_deserializing)]
     FakeAction(FakeAction),
 }
 
+impl Action {
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Action::HttpCall(a) => a.description.as_deref(),
+            Action::SnsPublish(a) => a.description.as_deref(),
+            Action::EventBridgePut(a) => a.description.as_deref(),
+            Action::ChatNotification(a) => a.description.as_deref(),
+            Action::MqttPublish(a) => a.description.as_deref(),
+            Action::RedisPublish(a) => a.description.as_deref(),
+            Action::FakeAction(_) => None,
+        }
+    }
+
+    /// Expression deciding if the action runs, see [`Condition`].
+    pub fn condition(&self) -> Option<&str> {
+        match self {
+            Action::HttpCall(a) => a.condition.as_deref(),
+            Action::SnsPublish(a) => a.condition.as_deref(),
+            Action::EventBridgePut(a) => a.condition.as_deref(),
+            Action::ChatNotification(a) => a.condition.as_deref(),
+            Action::MqttPublish(a) => a.condition.as_deref(),
+            Action::RedisPublish(a) => a.condition.as_deref(),
+            Action::FakeAction(_) => None,
+        }
+    }
+
+    /// Delay before running the action in a chain.
+    pub fn delay(&self) -> Option<u32> {
+        match self {
+            Action::HttpCall(a) => a.delay,
+            Action::SnsPublish(a) => a.delay,
+            Action::EventBridgePut(a) => a.delay,
+            Action::ChatNotification(a) => a.delay,
+            Action::MqttPublish(a) => a.delay,
+            Action::RedisPublish(a) => a.delay,
+            Action::FakeAction(_) => None,
+        }
+    }
+
+    /// Check if a failure of the action stops the chain.
+    pub fn abort_on_failure(&self) -> bool {
+        let abort = match self {
+            Action::HttpCall(a) => a.abort_on_failure,
+            Action::SnsPublish(a) => a.abort_on_failure,
+            Action::EventBridgePut(a) => a.abort_on_failure,
+            Action::ChatNotification(a) => a.abort_on_failure,
+            Action::MqttPublish(a) => a.abort_on_failure,
+            Action::RedisPublish(a) => a.abort_on_failure,
+            Action::FakeAction(_) => None,
+        };
+        abort.unwrap_or(false)
+    }
+}
+
 // #[cfg(test)]
 #[derive(Clone, Debug)]
 pub struct FakeAction {
@@ -112,43 +1264,246 @@ pub struct FakeAction {
     pub execute_returns: Option<Result<(), ()>>,
 }
 
-// #[cfg(test)]
-impl PartialEq for FakeAction {
-    fn eq(&self, _other: &Self) -> bool {
-        true
-    }
+// #[cfg(test)]
+impl PartialEq for FakeAction {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+// #[cfg(test)]
+impl Eq for FakeAction {}
+
+// #[cfg(test)]
+impl FakeAction {
+    pub fn execute(&mut self) -> color_eyre::Result<()> {
+        self.called
+            .store(true, std::sync::atomic::Ordering::Release);
+        if let Some(result) = self.execute_returns.take() {
+            match result {
+                Ok(()) => Ok(()),
+                Err(_) => Err(color_eyre::Report::msg("Err")),
+            }
+        } else {
+            Err(color_eyre::Report::msg("Err"))
+        }
+    }
+}
+
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct HttpCall {
+    pub method: HttpMethod,
+    /// The URL, header values and body can contain placeholders for the details of the event,
+    /// like in the chat notification template.
+    pub url: String,
+    pub description: Option<String>,
+    /// Expression the details of the event must satisfy for the action to run, like
+    /// `to == 'slate' && similarity < 0.1`.
+    pub condition: Option<String>,
+    /// Milliseconds to wait before running the action, when the transition chains its actions.
+    pub delay: Option<u32>,
+    /// Stops the chain of actions of the transition unless this one runs successfully.
+    pub abort_on_failure: Option<bool>,
+    pub authorization: Option<HttpAuth>,
+    pub headers: Option<HashMap<String, String>>,
+    pub body: Option<String>,
+    /// Maximum number of attempts, using the default delays between them. Superseded by `retry`.
+    pub retries: Option<u8>,
+    pub retry: Option<RetryPolicy>,
+    /// Status codes of a successful call, defaults to any 2xx status.
+    pub expected_status: Option<Vec<u16>>,
+    /// Regular expression the body of the response must match for the call to be successful.
+    pub expected_body: Option<String>,
+    pub timeout: Option<u32>,
+    /// Presented to servers requiring mutual TLS.
+    pub client_certificate: Option<Box<ClientCertificate>>,
+    pub proxy: Option<Box<HttpProxy>>,
+}
+
+/// Proxy the requests of an action go through, instead of the proxy of the worker.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct HttpProxy {
+    /// URL of the proxy, like `http://proxy.example.com:3128`. It can include the credentials.
+    pub url: String,
+    /// Hosts reached without the proxy, like `api.example.com`, `.example.com` for its
+    /// subdomains or `10.0.0.0/8`.
+    pub no_proxy: Option<Vec<String>>,
+}
+
+/// Certificate and private key, PEM encoded, identifying the worker to a server.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ClientCertificate {
+    pub certificate: SecretValue,
+    pub key: SecretValue,
+}
+
+impl HttpCall {
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry
+            .unwrap_or_else(|| RetryPolicy::attempts(self.retries.unwrap_or(1).max(1) as u32))
+    }
+}
+
+/// How often a failed action is attempted again, with exponentially growing delays between the
+/// attempts.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry in milliseconds, defaults to 500.
+    pub initial_delay: Option<u64>,
+    /// Maximum delay between attempts in milliseconds, defaults to 30 seconds.
+    pub max_delay: Option<u64>,
+    /// Multiplier applied to the delay after each retry, defaults to 2.
+    pub factor: Option<u32>,
+    /// Wait a random time between zero and the delay, so many workers failing at the same time
+    /// don't retry all at once. Defaults to true.
+    pub jitter: Option<bool>,
+}
+
+impl RetryPolicy {
+    pub fn attempts(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            initial_delay: None,
+            max_delay: None,
+            factor: None,
+            jitter: None,
+        }
+    }
+
+    /// Delay before the retry number `retry` (starting at 1), without jitter.
+    pub fn delay(&self, retry: u32) -> Duration {
+        let max_delay = self.max_delay.unwrap_or(30_000);
+        let factor = self.factor.unwrap_or(2) as u64;
+        let mut delay = self.initial_delay.unwrap_or(500).min(max_delay);
+        for _ in 1..retry {
+            delay = delay.saturating_mul(factor).min(max_delay);
+        }
+        Duration::from_millis(delay)
+    }
+
+    fn is_valid(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            Err(eyre!("Retry policy must allow at least 1 attempt"))
+        } else if self.factor == Some(0) {
+            Err(eyre!("Retry policy factor must be at least 1"))
+        } else if self.initial_delay.unwrap_or(500) > self.max_delay.unwrap_or(30_000) {
+            Err(eyre!(
+                "Retry policy initial delay cannot be more than the maximum delay"
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Publishes the transition event to an SNS topic.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SnsPublish {
+    pub topic_arn: String,
+    pub description: Option<String>,
+    pub condition: Option<String>,
+    pub delay: Option<u32>,
+    pub abort_on_failure: Option<bool>,
+    pub subject: Option<String>,
+}
+
+/// Puts the transition event on an EventBridge event bus.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct EventBridgePut {
+    /// Name or ARN of the event bus.
+    pub event_bus: String,
+    pub detail_type: String,
+    pub description: Option<String>,
+    pub condition: Option<String>,
+    pub delay: Option<u32>,
+    pub abort_on_failure: Option<bool>,
+    /// Source of the events, defaults to `hawkeye`.
+    pub source: Option<String>,
+}
+
+/// Sends a readable message about the transition to a Slack or Teams incoming webhook.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct ChatNotification {
+    pub webhook_url: String,
+    pub platform: ChatPlatform,
+    pub description: Option<String>,
+    pub condition: Option<String>,
+    pub delay: Option<u32>,
+    pub abort_on_failure: Option<bool>,
+    /// Message with placeholders for the details of the event, like `{{watcher_id}}`,
+    /// `{{description}}`, `{{from}}`, `{{to}}` and `{{detected_at}}`.
+    pub template: Option<String>,
+    /// Base URL of the Hawkeye API, used to link the latest frame of the watcher.
+    pub api_url: Option<String>,
+    pub proxy: Option<HttpProxy>,
 }
 
-// #[cfg(test)]
-impl Eq for FakeAction {}
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatPlatform {
+    Slack,
+    Teams,
+}
 
-// #[cfg(test)]
-impl FakeAction {
-    pub fn execute(&mut self) -> color_eyre::Result<()> {
-        self.called
-            .store(true, std::sync::atomic::Ordering::Release);
-        if let Some(result) = self.execute_returns.take() {
-            match result {
-                Ok(()) => Ok(()),
-                Err(_) => Err(color_eyre::Report::msg("Err")),
-            }
-        } else {
-            Err(color_eyre::Report::msg("Err"))
-        }
-    }
+/// Publishes the transition event to a topic of an MQTT broker.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct MqttPublish {
+    /// URL of the broker, `mqtt://host:port` or `mqtts://host:port` to connect using TLS.
+    pub broker_url: String,
+    pub topic: String,
+    pub description: Option<String>,
+    pub condition: Option<String>,
+    pub delay: Option<u32>,
+    pub abort_on_failure: Option<bool>,
+    /// Quality of service of the message (0, 1 or 2), defaults to 1.
+    pub qos: Option<u8>,
+    pub retain: Option<bool>,
+    /// Message with placeholders for the details of the event, like in the chat notification
+    /// template. Defaults to the event as JSON.
+    pub payload: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<SecretValue>,
+    /// PEM encoded certificate of the authority that signed the certificate of the broker,
+    /// required to connect using TLS.
+    pub ca_certificate: Option<String>,
 }
 
+/// Adds the transition event to a Redis stream or publishes it to a Redis channel.
 #[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
-pub struct HttpCall {
-    pub method: HttpMethod,
-    pub url: String,
+pub struct RedisPublish {
+    /// URL of the server, like `redis://:password@host:6379/0`.
+    pub redis_url: String,
+    /// Name of the stream or channel.
+    pub key: String,
+    pub delivery: RedisDelivery,
     pub description: Option<String>,
-    pub authorization: Option<HttpAuth>,
-    pub headers: Option<HashMap<String, String>>,
-    pub body: Option<String>,
-    pub retries: Option<u8>,
-    pub timeout: Option<u32>,
+    pub condition: Option<String>,
+    pub delay: Option<u32>,
+    pub abort_on_failure: Option<bool>,
+    /// Message with placeholders for the details of the event, like in the chat notification
+    /// template. Defaults to the event as JSON.
+    pub payload: Option<String>,
+    /// Approximate number of entries kept in the stream, older entries are trimmed.
+    pub max_len: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedisDelivery {
+    /// `XADD` the event to a stream, with the payload in the `event` field.
+    Stream,
+    /// `PUBLISH` the event to a channel.
+    Channel,
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, Eq, PartialEq)]
@@ -172,10 +1527,88 @@ impl ToString for HttpMethod {
     }
 }
 
+#[skip_serializing_none]
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum HttpAuth {
-    Basic { username: String, password: String },
+    Basic {
+        username: String,
+        password: SecretValue,
+    },
+    Bearer {
+        token: SecretValue,
+    },
+    /// Sends the key in a custom header, like `X-Api-Key`.
+    #[serde(rename = "api_key")]
+    ApiKey {
+        header: String,
+        value: SecretValue,
+    },
+    /// Uses a token requested from the authorization server with the client credentials grant.
+    OAuth2 {
+        token_url: String,
+        client_id: String,
+        client_secret: SecretValue,
+        scopes: Option<Vec<String>>,
+    },
+}
+
+impl HttpAuth {
+    /// The sensitive part of the credentials.
+    pub fn secret(&self) -> &SecretValue {
+        match self {
+            HttpAuth::Basic { password, .. } => password,
+            HttpAuth::Bearer { token } => token,
+            HttpAuth::ApiKey { value, .. } => value,
+            HttpAuth::OAuth2 { client_secret, .. } => client_secret,
+        }
+    }
+}
+
+/// A sensitive value, either written in the configuration or referencing an environment variable
+/// of the worker with `{"valueFrom": {"env": "NAME"}}`, so it isn't stored in the configuration.
+/// It can also be read from a file mounted in the worker with `{"file": "/path"}`.
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum SecretValue {
+    Plain(String),
+    Reference {
+        #[serde(rename = "valueFrom")]
+        value_from: SecretSource,
+    },
+    File {
+        file: String,
+    },
+}
+
+impl SecretValue {
+    /// The value of the secret, read from the environment or the file when it's a reference.
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            SecretValue::Plain(value) => Ok(value.clone()),
+            SecretValue::Reference { value_from } => std::env::var(&value_from.env)
+                .map_err(|_| eyre!("Environment variable {} is not set", value_from.env)),
+            SecretValue::File { file } => std::fs::read_to_string(file)
+                .map_err(|err| eyre!("Could not read secret file {}: {}", file, err)),
+        }
+    }
+}
+
+impl From<&str> for SecretValue {
+    fn from(value: &str) -> Self {
+        SecretValue::Plain(value.to_string())
+    }
+}
+
+/// Environment variable of the worker holding a secret.
+#[skip_serializing_none]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+pub struct SecretSource {
+    pub env: String,
+    /// Kubernetes Secret the API exposes to the worker as the environment variable.
+    pub secret: Option<String>,
+    /// Key of the value in the Kubernetes Secret, defaults to the name of the variable.
+    pub key: Option<String>,
 }
 
 #[cfg(test)]
@@ -189,12 +1622,37 @@ mod tests {
         Watcher {
             id: Some("ee21fc9a-7225-450b-a2a7-2faf914e35b8".to_string()),
             description: Some("UEFA 2020 - Lyon vs. Bayern".to_string()),
+            group: None,
             slate_url: "file://./resources/slate_120px.jpg".to_string(),
+            slate_variants: None,
+            slate_authorization: None,
+            slate_headers: None,
+            slate_text: None,
+            slate_threshold: None,
+            slate_threshold_learning: None,
+            slate_region: None,
+            debounce: None,
+            black_threshold: None,
+            detect_bars: None,
+            cooldown_seconds: None,
+            dry_run: None,
+            metrics: None,
+            clips: None,
+            snapshots: None,
+            worker_image: None,
+            scheduling: None,
+            annotations: None,
+            hostname: None,
+            allowed_sources: None,
+            expires_at: None,
+            on_expiry: None,
+            expires_in_seconds: None,
             status: Some(Status::Running),
             status_description: None,
             source: Source {
                 ingest_ip: None,
                 ingest_port: 5000,
+                exposure: None,
                 container: Container::MpegTs,
                 codec: Codec::H264,
                 transport: Protocol::Rtp
@@ -203,38 +1661,70 @@ mod tests {
                 Transition {
                     from: VideoMode::Content,
                     to: VideoMode::Slate,
+                    min_dwell: None,
+                    cooldown_seconds: None,
+                    schedule: None,
+                    circuit_breaker: None,
+                    chain: None,
                     actions: vec![
                         Action::HttpCall( HttpCall {
                             description: Some("Trigger AdBreak using API".to_string()),
+                            condition: None,
+                            delay: None,
+                            abort_on_failure: None,
                             method: HttpMethod::POST,
                             url: "http://non-existent.cbs.com/v1/organization/cbsa/channel/slate4/ad-break".to_string(),
                             authorization: Some(HttpAuth::Basic {
                                 username: "dev_user".to_string(),
-                                password: "something".to_string()
+                                password: "something".into()
                             }),
                             headers: Some([("Content-Type", "application/json")].iter().map(|(k, v)| (k.to_string(), v.to_string())).collect::<HashMap<String, String>>()),
                             body: Some("{\"duration\":300}".to_string()),
                             retries: Some(3),
+                            retry: None,
+                            expected_status: None,
+                            expected_body: None,
                             timeout: Some(10),
+                            client_certificate: None,
+                            proxy: None,
                         })
                     ]
                 },
                 Transition {
                     from: VideoMode::Slate,
                     to: VideoMode::Content,
+                    min_dwell: None,
+                    cooldown_seconds: None,
+                    schedule: None,
+                    circuit_breaker: None,
+                    chain: None,
                     actions: vec![
                         Action::HttpCall( HttpCall {
                             description: Some("Use dump out of AdBreak API call".to_string()),
+                            condition: None,
+                            delay: None,
+                            abort_on_failure: None,
                             method: HttpMethod::DELETE,
                             url: "http://non-existent.cbs.com/v1/organization/cbsa/channel/slate4/ad-break".to_string(),
                             authorization: Some(HttpAuth::Basic {
                                 username: "dev_user".to_string(),
-                                password: "something".to_string()
+                                password: SecretValue::Reference {
+                                    value_from: SecretSource {
+                                        env: "ADBREAK_PASSWORD".to_string(),
+                                        secret: Some("adbreak-api".to_string()),
+                                        key: Some("password".to_string()),
+                                    }
+                                }
                             }),
                             headers: None,
                             body: None,
                             retries: None,
+                            retry: None,
+                            expected_status: None,
+                            expected_body: None,
                             timeout: Some(10),
+                            client_certificate: None,
+                            proxy: None,
                         })
                     ]
                 }
@@ -251,6 +1741,441 @@ mod tests {
         assert!(w.is_valid().is_err());
     }
 
+    #[test]
+    fn check_slate_variants_are_urls() {
+        let mut w = get_watcher();
+        w.slate_variants = Some(vec![
+            String::from("file:///slates/night.jpg"),
+            String::from("s3://slates/day.jpg"),
+        ]);
+        assert!(w.is_valid().is_ok());
+
+        w.slate_variants = Some(vec![String::from("night.jpg")]);
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_slate_text_is_valid_expression() {
+        let mut w = get_watcher();
+        w.slate_text = Some("(?i)we'll be right back".to_string());
+        assert!(w.is_valid().is_ok());
+
+        w.slate_text = Some("right back(".to_string());
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_scheduling_is_valid() {
+        let mut w = get_watcher();
+        w.scheduling = Some(
+            serde_json::from_str(
+                r#"{
+                    "node_selector": {"node.kubernetes.io/instance-type": "g4dn.xlarge"},
+                    "tolerations": [
+                        {"key": "nvidia.com/gpu", "operator": "Exists", "effect": "NoSchedule"}
+                    ],
+                    "affinity": {"nodeAffinity": {}}
+                }"#,
+            )
+            .unwrap(),
+        );
+        assert!(w.is_valid().is_ok());
+
+        let mut scheduling = w.scheduling.clone().unwrap();
+        scheduling.node_selector = Some(
+            vec![("Pool/gpu".to_string(), "yes".to_string())]
+                .into_iter()
+                .collect(),
+        );
+        assert!(scheduling.is_valid().is_err());
+
+        let mut scheduling = w.scheduling.clone().unwrap();
+        scheduling.tolerations.as_mut().unwrap()[0].value = Some("true".to_string());
+        assert!(scheduling.is_valid().is_err());
+
+        let mut scheduling = w.scheduling.clone().unwrap();
+        scheduling.affinity = Some(serde_json::json!({"nodeSelector": {}}));
+        assert!(scheduling.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_annotations_are_valid() {
+        let mut w = get_watcher();
+        w.annotations = Some(
+            vec![
+                ("linkerd.io/inject".to_string(), "enabled".to_string()),
+                ("cost-center".to_string(), "sports / live".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert!(w.is_valid().is_ok());
+
+        w.annotations
+            .as_mut()
+            .unwrap()
+            .insert("cost center".to_string(), "sports".to_string());
+        let errors = w.validate().unwrap_err();
+        assert_eq!(errors.0[0].field, "annotations.cost center");
+    }
+
+    #[test]
+    fn check_secret_environment_variables_are_not_reserved() {
+        let mut w = get_watcher();
+        assert!(w.is_valid().is_ok());
+
+        for env in &[
+            "HAWKEYE_CONTROL_TOKEN",
+            "RUST_LOG",
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+        ] {
+            w.slate_authorization = Some(HttpAuth::Bearer {
+                token: SecretValue::Reference {
+                    value_from: SecretSource {
+                        env: env.to_string(),
+                        secret: None,
+                        key: None,
+                    },
+                },
+            });
+            let errors = w.validate().unwrap_err();
+            assert_eq!(errors.0[0].field, "slate_authorization");
+        }
+    }
+
+    #[test]
+    fn check_hostname_is_valid() {
+        let mut w = get_watcher();
+        w.hostname = Some("channel-1.ingest.example.com".to_string());
+        assert!(w.is_valid().is_ok());
+
+        w.source.exposure = Some(Exposure::None);
+        assert!(w.is_valid().is_err());
+
+        w.source.exposure = None;
+        w.hostname = Some("channel_1.example.com".to_string());
+        assert!(w.is_valid().is_err());
+        w.hostname = Some("ingest..example.com".to_string());
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_allowed_sources_are_valid() {
+        let mut w = get_watcher();
+        w.allowed_sources = Some(vec!["10.0.0.0/16".to_string(), "2001:db8::/32".to_string()]);
+        assert!(w.is_valid().is_ok());
+
+        w.allowed_sources = Some(vec![
+            "10.0.0.0/16".to_string(),
+            "10.0.0.0".to_string(),
+            "10.0.0.0/33".to_string(),
+        ]);
+        let errors = w.validate().unwrap_err();
+        let fields: Vec<&str> = errors.0.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(fields, ["allowed_sources[1]", "allowed_sources[2]"]);
+
+        w.allowed_sources = Some(vec![]);
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_worker_image_is_valid() {
+        let mut w = get_watcher();
+        w.worker_image = Some("hawkeye-worker:1.2.0-rc1".to_string());
+        assert!(w.is_valid().is_ok());
+
+        w.worker_image = Some("hawkeye-worker: 1.2.0".to_string());
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_group_is_label_value() {
+        let mut w = get_watcher();
+        w.group = Some("sports-lineup_2.0".to_string());
+        assert!(w.is_valid().is_ok());
+
+        for group in [
+            "",
+            "-sports",
+            "sports lineup",
+            "app=hawkeye",
+            &"a".repeat(64),
+        ]
+        .iter()
+        {
+            w.group = Some(group.to_string());
+            assert!(w.is_valid().is_err(), "{:?} is not a valid group", group);
+        }
+    }
+
+    #[test]
+    fn check_expiration() {
+        let mut w = get_watcher();
+        assert_eq!(w.expiration(), None);
+        assert!(!w.expired(Utc::now()));
+
+        w.expires_at = Some("2021-12-01T18:00:00+01:00".to_string());
+        assert!(w.is_valid().is_ok());
+        let expiration = DateTime::parse_from_rfc3339("2021-12-01T17:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(w.expiration(), Some(expiration));
+        assert!(!w.expired(expiration - TimeDelta::seconds(1)));
+        assert!(w.expired(expiration));
+
+        w.expires_at = Some("tomorrow".to_string());
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_metrics_settings_are_valid() {
+        let mut w = get_watcher();
+        w.metrics = Some(MetricsSettings {
+            prefix: Some("hawkeye_".to_string()),
+            buckets_ms: Some(vec![25, 50, 100, 200]),
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.metrics = Some(MetricsSettings {
+            prefix: Some("hawkeye-".to_string()),
+            buckets_ms: None,
+        });
+        assert!(w.is_valid().is_err());
+
+        w.metrics = Some(MetricsSettings {
+            prefix: None,
+            buckets_ms: Some(vec![50, 25]),
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_clip_settings_are_valid() {
+        let mut w = get_watcher();
+        let clips = ClipSettings {
+            url: "s3://recordings/clips/".to_string(),
+            before_seconds: None,
+            after_seconds: Some(30),
+        };
+        assert_eq!(clips.s3_location().unwrap(), ("recordings", "clips"));
+        w.clips = Some(clips.clone());
+        assert!(w.is_valid().is_ok());
+
+        w.clips = Some(ClipSettings {
+            url: "gs://recordings".to_string(),
+            ..clips.clone()
+        });
+        assert!(w.is_valid().is_err());
+
+        w.clips = Some(ClipSettings {
+            before_seconds: Some(100),
+            ..clips.clone()
+        });
+        assert!(w.is_valid().is_err());
+
+        // Doesn't overflow
+        w.clips = Some(ClipSettings {
+            before_seconds: Some(u32::MAX),
+            ..clips
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_snapshot_settings_are_valid() {
+        let mut w = get_watcher();
+        w.snapshots = Some(SnapshotSettings {
+            url: "/var/lib/hawkeye/snapshots".to_string(),
+            retention: None,
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.snapshots = Some(SnapshotSettings {
+            url: "s3://".to_string(),
+            retention: None,
+        });
+        assert!(w.is_valid().is_err());
+
+        w.snapshots = Some(SnapshotSettings {
+            url: "s3://recordings/snapshots".to_string(),
+            retention: Some(0),
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_slate_region_is_within_frame() {
+        let mut w = get_watcher();
+        w.slate_region = Some(Region {
+            x: 50,
+            y: 0,
+            width: 50,
+            height: 25,
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.slate_region = Some(Region {
+            x: 60,
+            y: 0,
+            width: 50,
+            height: 25,
+        });
+        assert!(w.is_valid().is_err());
+
+        w.slate_region = Some(Region {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 25,
+        });
+        assert!(w.is_valid().is_err());
+
+        // Doesn't overflow
+        w.slate_region = Some(Region {
+            x: u32::MAX,
+            y: 0,
+            width: 1,
+            height: 25,
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_debounce_has_a_majority_of_frames() {
+        let mut w = get_watcher();
+        w.debounce = Some(Debounce {
+            frames: 3,
+            window: None,
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.debounce = Some(Debounce {
+            frames: 3,
+            window: Some(5),
+        });
+        assert!(w.is_valid().is_ok());
+
+        w.debounce = Some(Debounce {
+            frames: 2,
+            window: Some(4),
+        });
+        assert!(w.is_valid().is_err());
+
+        w.debounce = Some(Debounce {
+            frames: 1000,
+            window: None,
+        });
+        assert!(w.is_valid().is_ok());
+
+        // The frames of the window are kept in memory
+        w.debounce = Some(Debounce {
+            frames: u32::MAX,
+            window: None,
+        });
+        assert!(w.is_valid().is_err());
+        w.debounce = Some(Debounce {
+            frames: 600,
+            window: Some(1001),
+        });
+        assert!(w.is_valid().is_err());
+
+        w.debounce = Some(Debounce {
+            frames: 0,
+            window: None,
+        });
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_threshold_learning_bounds() {
+        let mut w = get_watcher();
+        let mut learning = ThresholdLearning {
+            window: 600,
+            adjust: Some(true),
+            min_threshold: Some(100),
+            max_threshold: Some(500),
+        };
+        w.slate_threshold_learning = Some(learning);
+        assert!(w.is_valid().is_ok());
+        assert_eq!(learning.bound(50), 100);
+        assert_eq!(learning.bound(300), 300);
+        assert_eq!(learning.bound(900), 500);
+
+        learning.min_threshold = Some(600);
+        w.slate_threshold_learning = Some(learning);
+        assert!(w.is_valid().is_err());
+
+        learning.min_threshold = None;
+        learning.window = 0;
+        w.slate_threshold_learning = Some(learning);
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn check_transition_from_any_mode() {
+        let mut w = get_watcher();
+        w.transitions[0].from = VideoMode::Any;
+        assert!(w.is_valid().is_ok());
+
+        w.transitions[0].to = VideoMode::Any;
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn retry_policy_delays() {
+        let policy = RetryPolicy {
+            max_attempts: 5,
+            initial_delay: Some(100),
+            max_delay: Some(1000),
+            factor: Some(3),
+            jitter: None,
+        };
+        assert_eq!(policy.delay(1), Duration::from_millis(100));
+        assert_eq!(policy.delay(2), Duration::from_millis(300));
+        assert_eq!(policy.delay(3), Duration::from_millis(900));
+        assert_eq!(policy.delay(4), Duration::from_millis(1000));
+        assert_eq!(
+            RetryPolicy::attempts(3).delay(1),
+            Duration::from_millis(500)
+        );
+
+        let mut w = get_watcher();
+        if let Action::HttpCall(call) = &mut w.transitions[0].actions[0] {
+            assert_eq!(call.retry_policy().max_attempts, 3);
+            call.retry = Some(RetryPolicy {
+                max_delay: Some(50),
+                ..policy
+            });
+        }
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn schedule_window() {
+        let schedule = Schedule {
+            days: Some(vec![Weekday::Friday]),
+            start: Some("20:00".to_string()),
+            end: Some("02:00".to_string()),
+            timezone: Some("America/New_York".to_string()),
+        };
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
+        // Friday 2021-12-03, 21:00 in New York
+        assert!(schedule.is_active(at("2021-12-04T02:00:00Z")));
+        // Saturday 01:00 in New York, in the window started on Friday
+        assert!(schedule.is_active(at("2021-12-04T06:00:00Z")));
+        // Saturday 21:00 in New York
+        assert!(!schedule.is_active(at("2021-12-05T02:00:00Z")));
+        // Friday 19:00 in New York
+        assert!(!schedule.is_active(at("2021-12-04T00:00:00Z")));
+
+        let mut w = get_watcher();
+        w.transitions[0].schedule = Some(Schedule {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..schedule
+        });
+        assert!(w.is_valid().is_err());
+    }
+
     #[test]
     fn check_source_port_is_in_range() {
         let mut w = get_watcher();
@@ -260,6 +2185,112 @@ mod tests {
         assert!(w.is_valid().is_err());
     }
 
+    #[test]
+    fn source_port_is_optional() {
+        let source: Source = serde_json::from_str(
+            r#"{"container": "mpeg-ts", "codec": "h264", "transport": {"protocol": "rtp"}}"#,
+        )
+        .unwrap();
+        // Allocated by the API
+        assert_eq!(source.ingest_port, 0);
+    }
+
+    #[test]
+    fn collect_all_validation_errors() {
+        let mut w = get_watcher();
+        w.slate_url = "ftp://slate.png".to_string();
+        w.transitions[0].to = VideoMode::Any;
+        w.source.ingest_port = 1000;
+
+        let errors = w.validate().unwrap_err();
+        let fields: Vec<&str> = errors.0.iter().map(|error| error.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            ["slate_url", "transitions[0].to", "source.ingest_port"]
+        );
+        // The report of `is_valid` keeps them
+        let report = w.is_valid().unwrap_err();
+        assert_eq!(
+            report.downcast_ref::<ValidationErrors>().unwrap().0.len(),
+            3
+        );
+        assert!(report.to_string().contains("ftp://slate.png"));
+    }
+
+    #[test]
+    fn check_action_condition_is_valid() {
+        let mut w = get_watcher();
+        if let Action::HttpCall(call) = &mut w.transitions[0].actions[0] {
+            call.condition = Some("similarity > 0.95".to_string());
+        }
+        assert!(w.is_valid().is_ok());
+
+        if let Action::HttpCall(call) = &mut w.transitions[0].actions[0] {
+            call.condition = Some("similarity >> 0.95".to_string());
+        }
+        assert!(w.is_valid().is_err());
+    }
+
+    #[test]
+    fn apply_partial_update() {
+        let original = get_watcher();
+        let mut w = original.clone();
+        let update: WatcherUpdate = serde_json::from_str(
+            r#"{
+                "description": null,
+                "slate_threshold": 30,
+                "transitions": [
+                    {"from": "slate", "to": "content", "actions": [], "min_dwell": 10},
+                    {"from": "content", "to": "slate", "remove": true}
+                ]
+            }"#,
+        )
+        .unwrap();
+        update.apply(&mut w).unwrap();
+        assert_eq!(w.description, None);
+        assert_eq!(w.slate_threshold, Some(30));
+        assert_eq!(w.slate_url, original.slate_url);
+        assert_eq!(w.transitions.len(), 1);
+        assert_eq!(w.transitions[0].from, VideoMode::Slate);
+        assert_eq!(w.transitions[0].min_dwell, Some(10));
+        assert!(w.transitions[0].actions.is_empty());
+
+        let update: WatcherUpdate =
+            serde_json::from_str(r#"{"transitions": [{"from": "black", "to": "content"}]}"#)
+                .unwrap();
+        assert!(update.apply(&mut w).is_err());
+        assert!(serde_json::from_str::<WatcherUpdate>(r#"{"source": {}}"#).is_err());
+    }
+
+    #[test]
+    fn resolve_secret_references() {
+        let w = get_watcher();
+        let secrets = w.secret_sources();
+        assert_eq!(secrets.len(), 1);
+        assert_eq!(secrets[0].env, "ADBREAK_PASSWORD");
+        let values = w.secret_values();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[1].0, "transitions[1].actions[0].authorization");
+
+        let secret = SecretValue::Reference {
+            value_from: SecretSource {
+                env: "HAWKEYE_TEST_SECRET".to_string(),
+                secret: None,
+                key: None,
+            },
+        };
+        assert!(secret.resolve().is_err());
+        std::env::set_var("HAWKEYE_TEST_SECRET", "something");
+        assert_eq!(secret.resolve().unwrap(), "something");
+        assert_eq!(SecretValue::from("plain").resolve().unwrap(), "plain");
+
+        let secret: SecretValue =
+            serde_json::from_str(r#"{"file": "../resources/missing.pem"}"#).unwrap();
+        assert!(secret.resolve().is_err());
+        let secret: SecretValue = serde_json::from_str(r#"{"file": "../Cargo.toml"}"#).unwrap();
+        assert!(secret.resolve().unwrap().contains("[workspace]"));
+    }
+
     #[test]
     fn deserialize_as_expected() {
         let mut fixture = File::open("../fixtures/watcher.json").expect("Fixture was not found!");
@@ -0,0 +1,75 @@
+use crate::config;
+use opentelemetry::sdk::trace::{self, Sampler};
+use opentelemetry::sdk::Resource;
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::Registry;
+
+/// Exports the remaining spans when dropped.
+pub struct TracingGuard;
+
+impl Drop for TracingGuard {
+    fn drop(&mut self) {
+        opentelemetry::global::shutdown_tracer_provider();
+    }
+}
+
+/// Helper for exporting the spans to an OpenTelemetry collector when `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// is set. The spans are exported in the background, so it must be called within a Tokio runtime.
+pub fn maybe_bootstrap_tracing(service_name: &'static str) -> Option<TracingGuard> {
+    let endpoint = match config::OTLP_ENDPOINT.as_ref() {
+        Some(endpoint) => endpoint,
+        None => {
+            log::debug!("OTEL_EXPORTER_OTLP_ENDPOINT is not set. Skipping tracing initialization.");
+            return None;
+        }
+    };
+
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(
+            opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(endpoint),
+        )
+        .with_trace_config(
+            trace::config()
+                .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                    *config::TRACES_SAMPLE_RATIO,
+                ))))
+                .with_resource(Resource::new(vec![
+                    KeyValue::new("service.name", service_name),
+                    KeyValue::new("deployment.environment", config::HAWKEYE_ENV.as_str()),
+                ])),
+        )
+        .install_batch(opentelemetry::runtime::Tokio);
+    let tracer = match tracer {
+        Ok(tracer) => tracer,
+        Err(err) => {
+            log::error!("Could not export the traces to {}: {}", endpoint, err);
+            return None;
+        }
+    };
+
+    // The logs keep going through the `log` crate, only the spans are handled here.
+    let subscriber = Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+    if let Err(err) = tracing::subscriber::set_global_default(subscriber) {
+        log::error!("Could not initialize tracing: {}", err);
+        return None;
+    }
+    log::info!("Exporting traces to {}", endpoint);
+    Some(TracingGuard)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telemetry;
+
+    #[test]
+    fn test_no_endpoint_prevents_tracing_bootstrap() {
+        std::env::remove_var("OTEL_EXPORTER_OTLP_ENDPOINT");
+        let tracing = telemetry::maybe_bootstrap_tracing("hawkeye-test");
+        assert!(tracing.is_none());
+    }
+}
@@ -1,4 +1,7 @@
 use crate::config;
+use crate::logging;
+use crate::models::Watcher;
+use sentry::protocol::{Breadcrumb, Level};
 use sentry::ClientInitGuard;
 use std::borrow::Cow;
 
@@ -15,9 +18,7 @@ pub fn maybe_bootstrap_sentry() -> Option<ClientInitGuard> {
         return None;
     }
 
-    let mut log_builder = pretty_env_logger::formatted_builder();
-    log_builder.parse_filters("info");
-    let logger = sentry_log::SentryLogger::with_dest(log_builder.build());
+    let logger = sentry_log::SentryLogger::with_dest(logging::logger("info"));
     log::set_boxed_logger(Box::new(logger)).unwrap();
     // Log <= INFO as breadcrumbs. Anything higher is an "error" which generates a Sentry Issue.
     log::set_max_level(log::LevelFilter::Info);
@@ -36,6 +37,49 @@ pub fn maybe_bootstrap_sentry() -> Option<ClientInitGuard> {
     return Some(sentry_client);
 }
 
+/// Tags the Sentry events with the watcher, so the issues show the affected channel.
+pub fn set_sentry_watcher(watcher: &Watcher) {
+    sentry::configure_scope(|scope| {
+        scope.set_tag("watcher_id", watcher.id.as_deref().unwrap_or_default());
+        if let Some(description) = watcher.description.as_ref() {
+            // Values of the tags are limited to 200 characters
+            scope.set_tag("channel", description.chars().take(200).collect::<String>());
+        }
+        scope.set_tag(
+            "source_protocol",
+            format!("{:?}", watcher.source.transport).to_lowercase(),
+        );
+        scope.set_tag(
+            "source_container",
+            format!("{:?}", watcher.source.container).to_lowercase(),
+        );
+    });
+}
+
+/// Records what happened before the next Sentry events, like the transitions of the stream.
+pub fn add_sentry_breadcrumb(
+    category: &str,
+    level: log::Level,
+    message: String,
+    data: &[(&str, serde_json::Value)],
+) {
+    sentry::add_breadcrumb(Breadcrumb {
+        category: Some(category.to_string()),
+        level: match level {
+            log::Level::Error => Level::Error,
+            log::Level::Warn => Level::Warning,
+            log::Level::Info => Level::Info,
+            log::Level::Debug | log::Level::Trace => Level::Debug,
+        },
+        message: Some(message),
+        data: data
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect(),
+        ..Default::default()
+    });
+}
+
 #[cfg(test)]
 mod tests {
     use crate::utils;
@@ -1,28 +1,80 @@
+use crate::aws;
+use crate::circuit_breaker::CircuitBreaker;
+use crate::control;
+use crate::dead_letter;
+use crate::health;
+use crate::history;
+use crate::http;
 use crate::metrics::{
+    ACTIONS_CHANNEL_DEPTH, ACTIONS_DROPPED_COUNTER, ACTIONS_QUEUE_DEPTH, HTTP_CALL_ATTEMPTS,
     HTTP_CALL_DURATION, HTTP_CALL_ERROR_COUNTER, HTTP_CALL_RETRIED_COUNT,
-    HTTP_CALL_RETRIES_EXHAUSTED_COUNT, HTTP_CALL_SUCCESS_COUNTER,
+    HTTP_CALL_RETRIES_EXHAUSTED_COUNT, HTTP_CALL_SUCCESS_COUNTER, SCHEDULE_SUPPRESSED_COUNTER,
+    TRANSITION_LATENCY,
 };
-use crate::video_stream::Event;
+use crate::mqtt::{self, Broker};
+use crate::video_stream::{Detection, Event};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use color_eyre::Result;
 use crossbeam::channel::Receiver;
-use hawkeye_core::models::{self, Action, HttpAuth, HttpCall, VideoMode};
+use hawkeye_core::condition::Condition;
+use hawkeye_core::logging;
+use hawkeye_core::models::{
+    self, Action, ChatNotification, ChatPlatform, EventBridgePut, HttpCall, MqttPublish,
+    RedisDelivery, RedisPublish, RetryPolicy, SecretValue, SnsPublish, VideoMode,
+};
+use hawkeye_core::utils::add_sentry_breadcrumb;
 use log::{debug, error, info, warn};
+use rand::{thread_rng, Rng};
+use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde_json::json;
+use std::fmt;
 use std::time::Duration;
+use tokio::runtime::Builder;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{info_span, Instrument, Span};
+use uuid::Uuid;
 
 #[cfg(test)]
 use sn_fake_clock::FakeClock as Instant;
 #[cfg(not(test))]
 use std::time::Instant;
 
+const WORKER_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Maximum number of events waiting for an executor, the next ones are dropped until it catches up.
+const EXECUTOR_QUEUE_SIZE: usize = 100;
+
+/// Minimum time between two executions of an action when the transition doesn't configure it.
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(5);
+
 /// Abstracts execution call for every action type.
+#[async_trait]
 trait ActionExecution {
-    fn execute(&mut self) -> Result<()>;
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()>;
 }
 
+/// Executes the action, regardless of the executor managing it.
+pub(crate) async fn execute_action(
+    action: &mut Action,
+    transition: &Transition,
+    context: &EventContext,
+) -> Result<()> {
+    action.execute(transition, context).await
+}
+
+#[async_trait]
 impl ActionExecution for Action {
-    fn execute(&mut self) -> Result<()> {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
         match self {
-            Action::HttpCall(a) => a.execute(),
+            Action::HttpCall(a) => a.execute(transition, context).await,
+            Action::SnsPublish(a) => a.execute(transition, context).await,
+            Action::EventBridgePut(a) => a.execute(transition, context).await,
+            Action::ChatNotification(a) => a.execute(transition, context).await,
+            Action::MqttPublish(a) => a.execute(transition, context).await,
+            Action::RedisPublish(a) => a.execute(transition, context).await,
             Action::FakeAction(a) => a.execute(),
         }
     }
@@ -32,14 +84,198 @@ impl ActionExecution for Action {
 #[derive(Clone, Eq, PartialEq)]
 pub struct Transition(VideoMode, VideoMode);
 
+impl Transition {
+    pub fn new(from: VideoMode, to: VideoMode) -> Self {
+        Self(from, to)
+    }
+
+    fn involves(&self, mode: VideoMode) -> bool {
+        self.0 == mode || self.1 == mode
+    }
+
+    /// Check if the change between the video modes is described by this transition.
+    fn matches(&self, from: VideoMode, to: VideoMode) -> bool {
+        match self.0 {
+            VideoMode::Any => from != to && self.1 == to,
+            _ => self.0 == from && self.1 == to,
+        }
+    }
+}
+
+impl fmt::Display for Transition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-to-{}", self.0, self.1)
+    }
+}
+
+/// Describes the transition in the messages published to other systems.
+fn event_payload(transition: &Transition, context: &EventContext) -> serde_json::Value {
+    let mut payload = json!({
+        "watcher_id": context.watcher_id,
+        "event_id": context.event_id,
+        "transition": transition.to_string(),
+        "from": transition.0.to_string(),
+        "to": transition.1.to_string(),
+        "detected_at": context.detected_at.to_rfc3339(),
+        "worker_version": WORKER_VERSION,
+    });
+    if context.simulation.is_some() {
+        payload["simulated"] = json!(true);
+    }
+    payload
+}
+
+/// Replaces the `{{variable}}` placeholders in the template with the details of the event:
+/// `watcher_id`, `description`, `event_id`, `transition`, `from`, `to`, `detected_at`,
+/// `timestamp` (seconds since the epoch), `slate_url` and `similarity` (DSSIM score of the slate,
+/// empty when the slate wasn't found by comparing images). Unknown placeholders are left as is.
+fn render_template(template: &str, transition: &Transition, context: &EventContext) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end + 2,
+            None => break,
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end - 2].trim();
+        match template_variable(name, transition, context) {
+            Some(value) => rendered.push_str(&value),
+            None => rendered.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn template_variable(
+    name: &str,
+    transition: &Transition,
+    context: &EventContext,
+) -> Option<String> {
+    let value = match name {
+        "watcher_id" => context.watcher_id.clone(),
+        "description" => context.watcher_description.clone().unwrap_or_default(),
+        "event_id" => context.event_id.clone(),
+        "transition" => transition.to_string(),
+        "from" => transition.0.to_string(),
+        "to" => transition.1.to_string(),
+        "detected_at" => context.detected_at.to_rfc3339(),
+        "timestamp" => context.detected_at.timestamp().to_string(),
+        "slate_url" => context.slate_url.clone().unwrap_or_default(),
+        "similarity" => context
+            .similarity
+            .map(|similarity| similarity.to_string())
+            .unwrap_or_default(),
+        _ => return None,
+    };
+    Some(value)
+}
+
+/// Describes a detection event being processed by the actions runtime.
+///
+/// All actions executed because of the same event share the same context, so downstream systems
+/// can correlate the calls they receive.
+#[derive(Clone, Debug)]
+pub struct EventContext {
+    pub watcher_id: String,
+    pub watcher_description: Option<String>,
+    pub event_id: String,
+    pub mode: VideoMode,
+    pub detected_at: DateTime<Utc>,
+    /// When the frame the mode was detected in arrived from the pipeline.
+    pub frame_received_at: std::time::Instant,
+    /// Slate image matching the frame, without the query string.
+    pub slate_url: Option<String>,
+    /// DSSIM score of the frame compared with the slate image.
+    pub similarity: Option<f64>,
+    /// Span of the frame the mode was detected in.
+    pub span: Span,
+    /// Transition requested to test the actions, instead of one detected in the stream.
+    pub simulation: Option<Simulation>,
+}
+
+/// A transition requested to test the actions end-to-end, without waiting for it in the stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Simulation {
+    pub event_id: String,
+    pub from: VideoMode,
+    pub to: VideoMode,
+    /// Logs the actions instead of executing them, even if the worker runs them.
+    pub dry_run: bool,
+}
+
+impl Simulation {
+    pub fn new(from: VideoMode, to: VideoMode, dry_run: bool) -> Self {
+        Self {
+            event_id: Uuid::new_v4().to_string(),
+            from,
+            to,
+            dry_run,
+        }
+    }
+}
+
+impl EventContext {
+    pub fn new(watcher_id: &str, watcher_description: Option<&str>, detection: Detection) -> Self {
+        Self {
+            watcher_id: watcher_id.to_string(),
+            watcher_description: watcher_description.map(String::from),
+            event_id: Uuid::new_v4().to_string(),
+            mode: detection.mode,
+            detected_at: detection.detected_at,
+            frame_received_at: detection.frame_received_at,
+            similarity: detection
+                .slate
+                .as_ref()
+                .map(|slate| slate.dssim as f64 / 1000f64),
+            slate_url: detection.slate.map(|slate| slate.slate_url),
+            span: detection.span,
+            simulation: None,
+        }
+    }
+
+    /// Context of the simulated transition, as if its `to` mode was just detected.
+    pub fn simulated(
+        watcher_id: &str,
+        watcher_description: Option<&str>,
+        simulation: Simulation,
+    ) -> Self {
+        Self {
+            event_id: simulation.event_id.clone(),
+            simulation: Some(simulation.clone()),
+            ..Self::new(
+                watcher_id,
+                watcher_description,
+                Detection::now(simulation.to),
+            )
+        }
+    }
+
+    fn dry_run(&self) -> bool {
+        matches!(&self.simulation, Some(simulation) if simulation.dry_run)
+    }
+}
+
 /// Manages the execution of an `Action` based on a flow of `VideoMode`s.
 ///
 /// The `ActionExecutor` abstracts the logic of execution that is inherent to all `Action` types.
 pub struct ActionExecutor {
     transition: Transition,
     action: Action,
+    min_dwell: Duration,
+    cooldown: Duration,
+    schedule: Option<models::Schedule>,
     last_mode: Option<VideoMode>,
+    last_mode_since: Option<Instant>,
     last_call: Option<Instant>,
+    circuit_breaker: Option<CircuitBreaker>,
+    delay: Duration,
+    abort_on_failure: bool,
+    dry_run: bool,
+    /// Next step of a chain, runs after this action instead of on its own.
+    next: Option<Box<ActionExecutor>>,
 }
 
 impl ActionExecutor {
@@ -48,35 +284,283 @@ impl ActionExecutor {
         Self {
             transition,
             action,
+            min_dwell: Duration::from_secs(0),
+            cooldown: DEFAULT_COOLDOWN,
+            schedule: None,
             last_mode: None,
+            last_mode_since: None,
             last_call: None,
+            circuit_breaker: None,
+            delay: Duration::from_secs(0),
+            abort_on_failure: false,
+            dry_run: false,
+            next: None,
         }
     }
 
-    // Manage the execution of an action based on the provided video mode.
-    pub fn execute(&mut self, mode: VideoMode) {
-        if let Some(result) = self.call_action(mode) {
-            match result {
-                Ok(_) => self.last_call = Some(Instant::now()),
-                Err(err) => error!(
-                    "Error while processing action in mode {:?}: {:#}",
-                    mode, err
-                ),
+    /// Only executes the action when the video was in the previous mode for at least `min_dwell`.
+    pub fn with_min_dwell(mut self, min_dwell: Duration) -> Self {
+        self.min_dwell = min_dwell;
+        self
+    }
+
+    /// Minimum time between two executions of the action.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Only executes the action within the window of time of the schedule.
+    pub fn with_schedule(mut self, schedule: Option<models::Schedule>) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Skips the action while it keeps failing.
+    pub fn with_circuit_breaker(mut self, circuit_breaker: Option<CircuitBreaker>) -> Self {
+        self.circuit_breaker = circuit_breaker;
+        self
+    }
+
+    /// Waits before executing the action, when it's part of a chain.
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Stops the chain when the action doesn't run successfully.
+    pub fn with_abort_on_failure(mut self, abort_on_failure: bool) -> Self {
+        self.abort_on_failure = abort_on_failure;
+        self
+    }
+
+    /// Logs the action instead of executing it, along with the rest of the chain.
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self.next = self.next.map(|next| Box::new(next.with_dry_run(dry_run)));
+        self
+    }
+
+    /// Chains the executor, its action runs after this one. Only the transition, dwell time,
+    /// cooldown and schedule of the first executor of a chain are taken into account.
+    pub fn with_next(mut self, next: ActionExecutor) -> Self {
+        self.next = Some(Box::new(next));
+        self
+    }
+
+    // Manage the execution of an action based on the video mode of the provided event.
+    pub async fn execute(&mut self, context: &EventContext) {
+        if let Some(simulation) = &context.simulation {
+            // The dwell time, cooldown and schedule are left out, and the mode the executor
+            // tracks is kept for the transitions detected in the stream
+            if self.transition.matches(simulation.from, simulation.to) {
+                self.run_chain(context).await;
             }
+            return;
+        }
+        let mode = context.mode;
+        if matches!(mode, VideoMode::Black | VideoMode::Bars) && !self.transition.involves(mode) {
+            // Black frames and color bars are ignored by transitions not interested in them,
+            // otherwise a fade to black between content and slate would hide the transition.
+            return;
+        }
+        if self.triggered_by(mode) {
+            self.run_chain(context).await;
+        }
+        if self.last_mode != Some(mode) {
+            self.last_mode_since = Some(Instant::now());
         }
         self.last_mode = Some(mode);
     }
 
-    /// Executes the action if the video mode matches the transition and if the action is
-    /// allowed to run.
-    fn call_action(&mut self, mode: VideoMode) -> Option<Result<()>> {
-        self.last_mode.and_then(|last_mode| {
-            if Transition(last_mode, mode) == self.transition && self.allowed_to_run() {
-                Some(self.action.execute())
-            } else {
-                None
+    /// Check if the video mode matches the transition and if the action is allowed to run.
+    fn triggered_by(&self, mode: VideoMode) -> bool {
+        match self.last_mode {
+            Some(last_mode) => {
+                self.transition.matches(last_mode, mode)
+                    && self.dwelled_long_enough()
+                    && self.allowed_to_run()
+                    && self.within_schedule()
             }
-        })
+            None => false,
+        }
+    }
+
+    /// Executes the action, followed by the next actions of the chain.
+    async fn run_chain(&mut self, context: &EventContext) {
+        let mut step = Some(self);
+        while let Some(executor) = step {
+            if executor.delay > Duration::from_secs(0) {
+                tokio::time::sleep(executor.delay).await;
+            }
+            let succeeded = executor.run_action(context).await;
+            if !succeeded && executor.abort_on_failure && executor.next.is_some() {
+                warn!(
+                    "Stopping the chain of actions of transition {}, an action did not succeed",
+                    executor.transition
+                );
+                break;
+            }
+            step = executor.next.as_deref_mut();
+        }
+    }
+
+    /// Executes the action, returns if it ran successfully.
+    async fn run_action(&mut self, context: &EventContext) -> bool {
+        let started = Instant::now();
+        let dry_run = self.dry_run || context.dry_run();
+        let span = info_span!(
+            parent: &context.span,
+            "action",
+            transition = %self.transition,
+            target = %history::target(&self.action),
+        );
+        let result = self.call_action(context).instrument(span).await;
+        let fields = [
+            ("transition", json!(self.transition.to_string())),
+            ("event_id", json!(context.event_id)),
+            ("duration_ms", json!(started.elapsed().as_millis() as u64)),
+        ];
+        match result {
+            Some(Ok(_)) => {
+                logging::with_fields(&fields, || {
+                    info!("Action of transition {} executed", self.transition)
+                });
+                if !dry_run && context.simulation.is_none() {
+                    TRANSITION_LATENCY
+                        .with_label_values(&[&self.transition.to_string()])
+                        .observe(context.frame_received_at.elapsed().as_secs_f64());
+                }
+                history::record(
+                    &self.transition,
+                    &self.action,
+                    context,
+                    None,
+                    started.elapsed(),
+                    dry_run,
+                );
+                if context.simulation.is_none() {
+                    self.last_call = Some(Instant::now());
+                }
+                true
+            }
+            Some(Err(err)) => {
+                add_sentry_breadcrumb(
+                    "action",
+                    log::Level::Error,
+                    format!("Action of transition {} failed", self.transition),
+                    &[
+                        ("target", json!(history::target(&self.action))),
+                        ("event_id", json!(context.event_id)),
+                        ("error", json!(format!("{:#}", err))),
+                    ],
+                );
+                logging::with_fields(&fields, || {
+                    error!(
+                        "Error while processing action in mode {:?}: {:#}",
+                        context.mode, err
+                    )
+                });
+                history::record(
+                    &self.transition,
+                    &self.action,
+                    context,
+                    Some(&err),
+                    started.elapsed(),
+                    dry_run,
+                );
+                dead_letter::record(&self.transition, &self.action, context, &err);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Executes the action if the event satisfies its condition.
+    async fn call_action(&mut self, context: &EventContext) -> Option<Result<()>> {
+        if !self.condition_satisfied(context) {
+            return None;
+        }
+        if let Some(left) = control::actions_muted() {
+            info!(
+                "Skipping action of transition {}, the actions are muted for {}s",
+                self.transition,
+                left.as_secs()
+            );
+            return None;
+        }
+        if self.dry_run || context.dry_run() {
+            info!(
+                "Dry run, not executing the action of transition {} for event {}: {}",
+                self.transition,
+                context.event_id,
+                history::target(&self.action)
+            );
+            return Some(Ok(()));
+        }
+        let breaker = match self.circuit_breaker.as_mut() {
+            None => return Some(self.action.execute(&self.transition, context).await),
+            Some(breaker) => breaker,
+        };
+        if !breaker.allow() {
+            warn!(
+                "Skipping action of transition {}, its circuit is open",
+                self.transition
+            );
+            return None;
+        }
+        let result = self.action.execute(&self.transition, context).await;
+        breaker.record(result.is_ok());
+        Some(result)
+    }
+
+    /// Check if the action can run at this time of the day.
+    fn within_schedule(&self) -> bool {
+        match &self.schedule {
+            Some(schedule) if !schedule.is_active(Utc::now()) => {
+                info!(
+                    "Action of transition {} suppressed outside of its schedule",
+                    self.transition
+                );
+                SCHEDULE_SUPPRESSED_COUNTER
+                    .with_label_values(&[self.transition.to_string().as_str()])
+                    .inc();
+                false
+            }
+            _ => true,
+        }
+    }
+
+    /// Check if the details of the event satisfy the condition of the action, if any.
+    fn condition_satisfied(&self, context: &EventContext) -> bool {
+        let expression = match self.action.condition() {
+            None => return true,
+            Some(expression) => expression,
+        };
+        let satisfied = match Condition::parse(expression) {
+            Ok(condition) => {
+                condition.evaluate(|name| template_variable(name, &self.transition, context))
+            }
+            Err(err) => {
+                error!("Error while evaluating action condition: {:#}", err);
+                false
+            }
+        };
+        if !satisfied {
+            info!(
+                "Skipping action of transition {}, condition `{}` not satisfied",
+                self.transition, expression
+            );
+        }
+        satisfied
+    }
+
+    /// Check if the video stayed in the previous mode long enough for the transition to count.
+    fn dwelled_long_enough(&self) -> bool {
+        match &self.last_mode_since {
+            None => true,
+            Some(since) => since.elapsed() >= self.min_dwell,
+        }
     }
 
     /// Check if the action is allowed to run within the timeframe it was called.
@@ -86,7 +570,7 @@ impl ActionExecutor {
     fn allowed_to_run(&self) -> bool {
         match &self.last_call {
             None => true,
-            Some(last_call) => last_call.elapsed() > Duration::from_secs(5),
+            Some(last_call) => last_call.elapsed() > self.cooldown,
         }
     }
 }
@@ -97,57 +581,178 @@ pub(crate) struct Executors(pub(crate) Vec<ActionExecutor>);
 impl From<models::Transition> for Executors {
     fn from(transition: models::Transition) -> Self {
         let target_transition = Transition(transition.from, transition.to);
+        let min_dwell = Duration::from_secs(transition.min_dwell.unwrap_or(0) as u64);
+        let cooldown = transition
+            .cooldown_seconds
+            .map(|seconds| Duration::from_secs(seconds as u64))
+            .unwrap_or(DEFAULT_COOLDOWN);
+        let circuit_breaker = transition.circuit_breaker;
+        let schedule = transition.schedule;
+        let executors: Vec<ActionExecutor> = transition
+            .actions
+            .into_iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let breaker = circuit_breaker.map(|config| {
+                    CircuitBreaker::new(
+                        &format!("{}/{}", target_transition, index),
+                        config.failures,
+                        Duration::from_secs(config.reset_timeout.unwrap_or(60) as u64),
+                    )
+                });
+                let delay = Duration::from_millis(action.delay().unwrap_or(0) as u64);
+                let abort_on_failure = action.abort_on_failure();
+                ActionExecutor::new(target_transition.clone(), action)
+                    .with_min_dwell(min_dwell)
+                    .with_cooldown(cooldown)
+                    .with_schedule(schedule.clone())
+                    .with_circuit_breaker(breaker)
+                    .with_delay(delay)
+                    .with_abort_on_failure(abort_on_failure)
+            })
+            .collect();
+        if !transition.chain.unwrap_or(false) {
+            return Self(executors);
+        }
+        // The first executor of the chain runs the rest after its own action
         Self(
-            transition
-                .actions
+            executors
+                .into_iter()
+                .rev()
+                .reduce(|next, executor| executor.with_next(next))
                 .into_iter()
-                .map(|action| ActionExecutor::new(target_transition.clone(), action))
                 .collect(),
         )
     }
 }
 
 pub struct Runtime {
+    watcher_id: String,
+    watcher_description: Option<String>,
     receiver: Receiver<Event>,
     actions: Vec<ActionExecutor>,
 }
 
 impl Runtime {
-    pub fn new(receiver: Receiver<Event>, processors: Vec<ActionExecutor>) -> Self {
+    pub fn new(
+        watcher_id: String,
+        watcher_description: Option<String>,
+        receiver: Receiver<Event>,
+        processors: Vec<ActionExecutor>,
+    ) -> Self {
         Runtime {
+            watcher_id,
+            watcher_description,
             receiver,
             actions: processors,
         }
     }
 
+    /// Executes the actions of the events until the stream terminates.
+    ///
+    /// Each executor runs in its own task, so an action waiting on a slow system only delays the
+    /// next executions of the same action.
     pub fn run_blocking(&mut self) -> Result<()> {
+        let _alive = health::ActionsRuntimeAlive::mark();
+        let runtime = Builder::new_multi_thread().enable_all().build()?;
+        let mut senders = Vec::new();
+        let mut tasks = Vec::new();
+        for mut executor in self.actions.drain(..) {
+            let (sender, mut receiver) = mpsc::channel::<EventContext>(EXECUTOR_QUEUE_SIZE);
+            let transition = executor.transition.to_string();
+            let queue_depth = ACTIONS_QUEUE_DEPTH.with_label_values(&[&transition]);
+            let dropped = ACTIONS_DROPPED_COUNTER.with_label_values(&[&transition]);
+            senders.push((sender, queue_depth.clone(), dropped));
+            tasks.push(runtime.spawn(async move {
+                while let Some(context) = receiver.recv().await {
+                    queue_depth.dec();
+                    executor.execute(&context).await;
+                }
+            }));
+        }
+
         loop {
-            match self.receiver.recv()? {
+            let event = self.receiver.recv()?;
+            ACTIONS_CHANNEL_DEPTH.set(self.receiver.len() as i64);
+            let context = match event {
                 Event::Terminate => break,
-                Event::Mode(mode) => {
-                    for p in self.actions.iter_mut() {
-                        p.execute(mode);
+                Event::Mode(detection) => EventContext::new(
+                    &self.watcher_id,
+                    self.watcher_description.as_deref(),
+                    detection,
+                ),
+                Event::Simulate(simulation) => {
+                    info!(
+                        "Simulating transition {}-to-{} for event {}",
+                        simulation.from, simulation.to, simulation.event_id
+                    );
+                    EventContext::simulated(
+                        &self.watcher_id,
+                        self.watcher_description.as_deref(),
+                        simulation,
+                    )
+                }
+            };
+            for (sender, queue_depth, dropped) in senders.iter() {
+                match sender.try_send(context.clone()) {
+                    Ok(()) => queue_depth.inc(),
+                    // The executor is stuck, the event is dropped rather than piling up
+                    Err(TrySendError::Full(context)) => {
+                        warn!(
+                            "Dropped event {}, the queue of the actions is full",
+                            context.event_id
+                        );
+                        dropped.inc();
                     }
+                    Err(err) => return Err(err.into()),
                 }
             }
         }
-        Ok(())
+
+        // Let the executors finish with the events they received
+        drop(senders);
+        runtime.block_on(async {
+            for task in tasks {
+                task.await?;
+            }
+            Ok(())
+        })
     }
 }
 
+#[async_trait]
 impl ActionExecution for HttpCall {
-    fn execute(&mut self) -> Result<()> {
-        let mut tries = 0;
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let policy = self.retry_policy();
+        let mut attempt = 1;
         loop {
-            match try_call(&self) {
+            let result = try_call(self, transition, context)
+                .instrument(info_span!("http_call", attempt))
+                .await;
+            HTTP_CALL_ATTEMPTS
+                .with_label_values(&[
+                    attempt.to_string().as_str(),
+                    if result.is_ok() { "success" } else { "error" },
+                ])
+                .inc();
+            match result {
                 Ok(_) => break,
                 Err(err) => {
-                    HTTP_CALL_RETRIED_COUNT.inc();
-                    tries += 1;
-                    if tries >= self.retries.unwrap_or(0) {
+                    if attempt >= policy.max_attempts {
                         HTTP_CALL_RETRIES_EXHAUSTED_COUNT.inc();
                         return Err(err);
                     }
+                    let delay = retry_delay(&policy, attempt);
+                    warn!(
+                        "HTTP call failed (attempt {} of {}), retrying in {}ms: {}",
+                        attempt,
+                        policy.max_attempts,
+                        delay.as_millis(),
+                        err
+                    );
+                    HTTP_CALL_RETRIED_COUNT.inc();
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
                 }
             }
         }
@@ -155,46 +760,220 @@ impl ActionExecution for HttpCall {
     }
 }
 
-fn try_call(call: &HttpCall) -> Result<()> {
-    let timer = HTTP_CALL_DURATION.start_timer();
-    let method = call.method.to_string();
-    let mut request = ureq::request(&method, call.url.as_str());
+/// Delay before the retry number `retry`, with jitter when enabled in the policy.
+fn retry_delay(policy: &RetryPolicy, retry: u32) -> Duration {
+    let delay = policy.delay(retry);
+    if policy.jitter.unwrap_or(true) {
+        Duration::from_millis(thread_rng().gen_range(0..=delay.as_millis() as u64))
+    } else {
+        delay
+    }
+}
 
-    request.timeout_connect(500);
+#[async_trait]
+impl ActionExecution for SnsPublish {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let message = event_payload(transition, context).to_string();
+        aws::publish_sns(&self.topic_arn, self.subject.as_deref(), &message).await
+    }
+}
 
-    if let Some(HttpAuth::Basic { username, password }) = &call.authorization {
-        request.auth(username, password);
+#[async_trait]
+impl ActionExecution for EventBridgePut {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let detail = event_payload(transition, context).to_string();
+        let source = self.source.as_deref().unwrap_or("hawkeye");
+        aws::put_event(&self.event_bus, source, &self.detail_type, &detail).await
     }
+}
 
-    if let Some(timeout) = &call.timeout {
-        request.timeout(Duration::from_secs(*timeout as u64));
+const DEFAULT_CHAT_TEMPLATE: &str =
+    "Watcher {{watcher_id}} ({{description}}) changed from {{from}} to {{to}} at {{detected_at}}";
+
+#[async_trait]
+impl ActionExecution for ChatNotification {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let template = self.template.as_deref().unwrap_or(DEFAULT_CHAT_TEMPLATE);
+        let mut text = render_template(template, transition, context);
+        if let Some(api_url) = &self.api_url {
+            let frame_url = format!(
+                "{}/v1/watchers/{}/video-frame",
+                api_url.trim_end_matches('/'),
+                context.watcher_id
+            );
+            text.push_str(&match self.platform {
+                ChatPlatform::Slack => format!("\n<{}|Latest frame>", frame_url),
+                ChatPlatform::Teams => format!("\n\n[Latest frame]({})", frame_url),
+            });
+        }
+
+        let client = http::client(&http::ClientSettings {
+            identity: None,
+            proxy: self.proxy.clone(),
+        })?;
+        let request = client
+            .post(&self.webhook_url)
+            .timeout(Duration::from_secs(10))
+            .header(CONTENT_TYPE, "application/json")
+            .body(json!({ "text": text }).to_string());
+        let (status, body) = http::send(request).await?;
+        if (200..300).contains(&status) {
+            Ok(())
+        } else {
+            Err(color_eyre::eyre::eyre!(
+                "Error while sending chat notification ({}): {}",
+                status,
+                body
+            ))
+        }
     }
+}
+
+#[async_trait]
+impl ActionExecution for MqttPublish {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let payload = match &self.payload {
+            Some(template) => render_template(template, transition, context),
+            None => event_payload(transition, context).to_string(),
+        };
+        let password = self
+            .password
+            .as_ref()
+            .map(SecretValue::resolve)
+            .transpose()?;
+        // The MQTT client is blocking
+        let action = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let broker = Broker {
+                url: &action.broker_url,
+                username: action.username.as_deref(),
+                password: password.as_deref(),
+                ca_certificate: action.ca_certificate.as_deref(),
+            };
+            mqtt::publish(
+                &broker,
+                &action.topic,
+                action.qos.unwrap_or(1),
+                action.retain.unwrap_or(false),
+                payload,
+            )
+        })
+        .await?
+    }
+}
 
-    if let Some(headers) = &call.headers {
-        for (k, v) in headers.iter() {
-            request.set(k, v);
+fn redis_command(action: &RedisPublish, payload: String) -> redis::Cmd {
+    match action.delivery {
+        RedisDelivery::Stream => {
+            let mut cmd = redis::cmd("XADD");
+            cmd.arg(&action.key);
+            if let Some(max_len) = action.max_len {
+                cmd.arg("MAXLEN").arg("~").arg(max_len);
+            }
+            cmd.arg("*").arg("event").arg(payload);
+            cmd
+        }
+        RedisDelivery::Channel => {
+            let mut cmd = redis::cmd("PUBLISH");
+            cmd.arg(&action.key).arg(payload);
+            cmd
         }
     }
+}
+
+#[async_trait]
+impl ActionExecution for RedisPublish {
+    async fn execute(&mut self, transition: &Transition, context: &EventContext) -> Result<()> {
+        let payload = match &self.payload {
+            Some(template) => render_template(template, transition, context),
+            None => event_payload(transition, context).to_string(),
+        };
+        let command = redis_command(self, payload);
+        let client = redis::Client::open(self.redis_url.as_str())?;
+        // The Redis connection is blocking
+        tokio::task::spawn_blocking(move || {
+            let mut connection = client.get_connection_with_timeout(Duration::from_secs(10))?;
+            command.query::<redis::Value>(&mut connection)?;
+            Ok(())
+        })
+        .await?
+    }
+}
 
-    let response = match call.body.as_ref() {
-        Some(data) => request.send_string(data),
-        None => request.call(),
+async fn try_call(call: &HttpCall, transition: &Transition, context: &EventContext) -> Result<()> {
+    let timer = HTTP_CALL_DURATION.start_timer();
+    let method = Method::from_bytes(call.method.to_string().as_bytes())?;
+    let url = render_template(&call.url, transition, context);
+    let identity = match &call.client_certificate {
+        Some(identity) => Some(format!(
+            "{}\n{}",
+            identity.key.resolve()?,
+            identity.certificate.resolve()?
+        )),
+        None => None,
     };
-    if response.ok() {
-        HTTP_CALL_SUCCESS_COUNTER.inc();
-        debug!(
-            "Successfully called backend API {}",
-            response.into_string()?
-        );
-    } else {
-        HTTP_CALL_ERROR_COUNTER.inc();
-        warn!(
-            "Error while calling backend API ({}): {}",
-            response.status(),
-            response.into_string()?
-        );
+    let client = http::client(&http::ClientSettings {
+        identity,
+        proxy: call.proxy.as_deref().cloned(),
+    })?;
+    let mut request = client.request(method, url.as_str());
+
+    if let Some(authorization) = &call.authorization {
+        request = http::authorize(&http::CLIENT, request, authorization).await?;
+    }
+
+    if let Some(timeout) = &call.timeout {
+        request = request.timeout(Duration::from_secs(*timeout as u64));
     }
 
+    // Provenance of the call, so the receiving end can log which event triggered it.
+    // Set before the user defined headers, which take precedence.
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        "x-hawkeye-watcher-id",
+        HeaderValue::from_str(&context.watcher_id)?,
+    );
+    headers.insert(
+        "x-hawkeye-event-id",
+        HeaderValue::from_str(&context.event_id)?,
+    );
+    headers.insert(
+        "x-hawkeye-transition",
+        HeaderValue::from_str(&transition.to_string())?,
+    );
+    headers.insert(
+        "x-hawkeye-detected-at",
+        HeaderValue::from_str(&context.detected_at.to_rfc3339())?,
+    );
+    headers.insert(
+        "x-hawkeye-worker-version",
+        HeaderValue::from_static(WORKER_VERSION),
+    );
+
+    if let Some(call_headers) = &call.headers {
+        for (k, v) in call_headers.iter() {
+            headers.insert(
+                HeaderName::from_bytes(k.as_bytes())?,
+                HeaderValue::from_str(&render_template(v, transition, context))?,
+            );
+        }
+    }
+    request = request.headers(headers);
+
+    if let Some(data) = call.body.as_ref() {
+        request = request.body(render_template(data, transition, context));
+    }
+    let (status, body) = match http::send(request).await {
+        Ok(response) => response,
+        Err(err) => {
+            HTTP_CALL_ERROR_COUNTER.inc();
+            return Err(color_eyre::eyre::eyre!(
+                "Could not call backend API: {:#}",
+                err
+            ));
+        }
+    };
+
     // Report how long it took to call the backend.
     // Keep it out of the log macro, so it will execute every time independent of log level
     let seconds = timer.stop_and_record();
@@ -203,6 +982,30 @@ fn try_call(call: &HttpCall) -> Result<()> {
         Duration::from_secs_f64(seconds).as_millis()
     );
 
+    let expected_status = match &call.expected_status {
+        Some(codes) => codes.contains(&status),
+        None => (200..300).contains(&status),
+    };
+    if !expected_status {
+        HTTP_CALL_ERROR_COUNTER.inc();
+        return Err(color_eyre::eyre::eyre!(
+            "Unexpected status calling backend API ({}): {}",
+            status,
+            body
+        ));
+    }
+    if let Some(pattern) = &call.expected_body {
+        if !Regex::new(pattern)?.is_match(&body) {
+            HTTP_CALL_ERROR_COUNTER.inc();
+            return Err(color_eyre::eyre::eyre!(
+                "Response of backend API does not match `{}`: {}",
+                pattern,
+                body
+            ));
+        }
+    }
+    HTTP_CALL_SUCCESS_COUNTER.inc();
+    debug!("Successfully called backend API {}", body);
     Ok(())
 }
 
@@ -210,7 +1013,7 @@ fn try_call(call: &HttpCall) -> Result<()> {
 mod tests {
     use super::*;
     use crossbeam::channel::unbounded;
-    use hawkeye_core::models::{FakeAction, HttpMethod};
+    use hawkeye_core::models::{FakeAction, HttpAuth, HttpMethod};
     use mockito::{mock, server_url, Matcher};
     use sn_fake_clock::FakeClock;
     use std::collections::HashMap;
@@ -221,8 +1024,12 @@ mod tests {
         FakeClock::advance_time(d.as_millis() as u64);
     }
 
-    #[test]
-    fn executor_slate_action_called_when_transition_content_to_slate() {
+    fn event(mode: VideoMode) -> EventContext {
+        EventContext::new("watcher-id", Some("Test watcher"), Detection::now(mode))
+    }
+
+    #[tokio::test]
+    async fn executor_slate_action_called_when_transition_content_to_slate() {
         let called = Arc::new(AtomicBool::new(false));
         let fake_action = FakeAction {
             called: called.clone(),
@@ -232,17 +1039,17 @@ mod tests {
             Transition(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
+        executor.execute(&event(VideoMode::Content)).await;
         // Didn't call since it was the first state found
         assert_eq!(called.load(Ordering::SeqCst), false);
 
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Slate)).await;
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
-    #[test]
-    fn executor_slate_action_cannot_be_called_twice_in_short_timeframe() {
+    #[tokio::test]
+    async fn executor_slate_action_cannot_be_called_twice_in_short_timeframe() {
         let called = Arc::new(AtomicBool::new(false));
         let fake_action = FakeAction {
             called: called.clone(),
@@ -252,19 +1059,19 @@ mod tests {
             Transition(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
         called.store(false, Ordering::SeqCst);
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         assert_eq!(called.load(Ordering::SeqCst), false);
     }
 
-    #[test]
-    fn executor_slate_action_can_be_called_twice_after_some_time_passes() {
+    #[tokio::test]
+    async fn executor_slate_action_can_be_called_twice_after_some_time_passes() {
         let called = Arc::new(AtomicBool::new(false));
         let fake_action = FakeAction {
             called: called.clone(),
@@ -274,8 +1081,8 @@ mod tests {
             Transition(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
@@ -284,13 +1091,13 @@ mod tests {
         // Move time forward over the delay
         sleep(Duration::from_secs(11));
 
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
-    #[test]
-    fn executor_slate_action_cannot_be_called_twice_if_no_mode_change() {
+    #[tokio::test]
+    async fn executor_slate_action_cannot_be_called_twice_if_no_mode_change() {
         let called = Arc::new(AtomicBool::new(false));
         let fake_action = FakeAction {
             called: called.clone(),
@@ -300,8 +1107,8 @@ mod tests {
             Transition(VideoMode::Content, VideoMode::Slate),
             Action::FakeAction(fake_action),
         );
-        executor.execute(VideoMode::Content);
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         // Must be called since we had a state transition that matches what we defined in the executor
         assert_eq!(called.load(Ordering::SeqCst), true);
         // Reset state of our mock to "not called"
@@ -310,8 +1117,301 @@ mod tests {
         // Move time forward over the delay
         sleep(Duration::from_secs(20));
 
-        executor.execute(VideoMode::Slate);
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+    }
+
+    #[tokio::test]
+    async fn executor_observes_transition_latency() {
+        let transition = Transition(VideoMode::Bars, VideoMode::Black);
+        let latency = TRANSITION_LATENCY.with_label_values(&[&transition.to_string()]);
+        let fake_action = FakeAction {
+            called: Arc::new(AtomicBool::new(false)),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(transition, Action::FakeAction(fake_action));
+        executor.execute(&event(VideoMode::Bars)).await;
+        executor.execute(&event(VideoMode::Black)).await;
+        assert_eq!(latency.get_sample_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn executor_skips_action_while_circuit_is_open() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Err(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        )
+        .with_circuit_breaker(Some(CircuitBreaker::new(
+            "executor-test",
+            1,
+            Duration::from_secs(60),
+        )));
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+
+        called.store(false, Ordering::SeqCst);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
         assert_eq!(called.load(Ordering::SeqCst), false);
+
+        // Probes the action once the circuit timeout passes
+        FakeClock::advance_time(60_000);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_chain_stops_after_failure() {
+        async fn chain(first_returns: Result<(), ()>, abort_on_failure: bool) -> bool {
+            let called = Arc::new(AtomicBool::new(false));
+            let first = FakeAction {
+                called: Arc::new(AtomicBool::new(false)),
+                execute_returns: Some(first_returns),
+            };
+            let second = FakeAction {
+                called: called.clone(),
+                execute_returns: Some(Ok(())),
+            };
+            let transition = Transition(VideoMode::Content, VideoMode::Slate);
+            let mut executor = ActionExecutor::new(transition.clone(), Action::FakeAction(first))
+                .with_abort_on_failure(abort_on_failure)
+                .with_next(
+                    ActionExecutor::new(transition, Action::FakeAction(second))
+                        .with_delay(Duration::from_millis(10)),
+                );
+            executor.execute(&event(VideoMode::Content)).await;
+            executor.execute(&event(VideoMode::Slate)).await;
+            called.load(Ordering::SeqCst)
+        }
+
+        assert!(chain(Ok(()), true).await);
+        assert!(!chain(Err(()), true).await);
+        assert!(chain(Err(()), false).await);
+    }
+
+    #[tokio::test]
+    async fn executor_action_not_called_outside_of_schedule() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        )
+        .with_schedule(Some(models::Schedule {
+            days: Some(vec![]),
+            start: None,
+            end: None,
+            timezone: None,
+        }));
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+        assert_eq!(
+            SCHEDULE_SUPPRESSED_COUNTER
+                .with_label_values(&["content-to-slate"])
+                .get(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn executor_action_called_when_condition_satisfied() {
+        let path = "/conditional";
+        let server = mock("POST", path).with_status(200).expect(1).create();
+        let action = |condition: &str| {
+            Action::HttpCall(HttpCall {
+                method: HttpMethod::POST,
+                url: format!("{}{}", server_url(), path),
+                description: None,
+                condition: Some(condition.to_string()),
+                delay: None,
+                abort_on_failure: None,
+                authorization: None,
+                headers: None,
+                body: None,
+                retries: None,
+                retry: None,
+                expected_status: None,
+                expected_body: None,
+                timeout: None,
+                client_certificate: None,
+                proxy: None,
+            })
+        };
+
+        // No slate matched, so there is no similarity
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            action("similarity > 0.9"),
+        );
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            action("to == 'slate' && watcher_id == 'watcher-id'"),
+        );
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        server.assert();
+    }
+
+    #[tokio::test]
+    async fn executor_action_not_called_in_dry_run() {
+        let called = Arc::new(AtomicBool::new(false));
+        let action = Action::FakeAction(FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        });
+        let mut executor =
+            ActionExecutor::new(Transition(VideoMode::Content, VideoMode::Slate), action)
+                .with_dry_run(true);
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert!(!called.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn executor_action_not_called_before_min_dwell() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Slate, VideoMode::Content),
+            Action::FakeAction(fake_action),
+        )
+        .with_min_dwell(Duration::from_secs(10));
+        executor.execute(&event(VideoMode::Slate)).await;
+        sleep(Duration::from_secs(2));
+        executor.execute(&event(VideoMode::Content)).await;
+        // The slate was not present for long enough
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        executor.execute(&event(VideoMode::Slate)).await;
+        sleep(Duration::from_secs(10));
+        executor.execute(&event(VideoMode::Slate)).await;
+        executor.execute(&event(VideoMode::Content)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_ignores_black_frames_when_not_in_transition() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        );
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Black)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_black_action_called_when_transition_content_to_black() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Black),
+            Action::FakeAction(fake_action),
+        );
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Black)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_bars_action_called_when_transition_content_to_bars() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Bars),
+            Action::FakeAction(fake_action),
+        );
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Bars)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_action_called_when_transition_from_any_mode() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Any, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        );
+        executor.execute(&event(VideoMode::Slate)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        executor.execute(&event(VideoMode::Bars)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        executor.execute(&event(VideoMode::Content)).await;
+        executor.execute(&event(VideoMode::Slate)).await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+    }
+
+    #[tokio::test]
+    async fn executor_runs_simulated_transitions() {
+        let called = Arc::new(AtomicBool::new(false));
+        let fake_action = FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        };
+        let mut executor = ActionExecutor::new(
+            Transition(VideoMode::Content, VideoMode::Slate),
+            Action::FakeAction(fake_action),
+        )
+        .with_min_dwell(Duration::from_secs(60));
+        let simulated = |from, to, dry_run| {
+            EventContext::simulated("watcher-id", None, Simulation::new(from, to, dry_run))
+        };
+
+        executor
+            .execute(&simulated(VideoMode::Slate, VideoMode::Content, false))
+            .await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        executor
+            .execute(&simulated(VideoMode::Content, VideoMode::Slate, true))
+            .await;
+        assert_eq!(called.load(Ordering::SeqCst), false);
+
+        // Without a mode detected in the stream first, nor the dwell time
+        executor
+            .execute(&simulated(VideoMode::Content, VideoMode::Slate, false))
+            .await;
+        assert_eq!(called.load(Ordering::SeqCst), true);
+        assert_eq!(executor.last_mode, None);
+        assert!(executor.last_call.is_none());
     }
 
     #[test]
@@ -326,23 +1426,27 @@ mod tests {
             Action::FakeAction(fake_action),
         );
         // Prepare executor to be ready in the next call with `VideoMode::Slate`
-        executor.execute(VideoMode::Content);
+        Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(executor.execute(&event(VideoMode::Content)));
         assert_eq!(called.load(Ordering::SeqCst), false);
 
         let (s, r) = unbounded();
         // Pile up some events for the runtime to consume
-        s.send(Event::Mode(VideoMode::Slate)).unwrap();
+        s.send(Event::Mode(Detection::now(VideoMode::Slate)))
+            .unwrap();
         s.send(Event::Terminate).unwrap();
 
-        let mut runtime = Runtime::new(r, vec![executor]);
+        let mut runtime = Runtime::new("watcher-id".to_string(), None, r, vec![executor]);
         runtime.run_blocking().expect("Should run successfully!");
 
         // Check the action was called
         assert_eq!(called.load(Ordering::SeqCst), true);
     }
 
-    #[test]
-    fn action_http_call_performs_request() {
+    #[tokio::test]
+    async fn action_http_call_performs_request() {
         let path = "/do-something";
         let req_body = "{\"duration\":20}";
 
@@ -357,9 +1461,12 @@ mod tests {
             method: HttpMethod::POST,
             url: format!("{}{}", server_url(), path),
             description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
             authorization: Some(HttpAuth::Basic {
                 username: "user".to_string(),
-                password: "pass".to_string(),
+                password: "pass".into(),
             }),
             headers: Some(
                 [("content-type", "application/json")]
@@ -369,26 +1476,385 @@ mod tests {
             ),
             body: Some(req_body.to_string()),
             retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        };
+
+        action
+            .execute(
+                &Transition(VideoMode::Content, VideoMode::Slate),
+                &event(VideoMode::Slate),
+            )
+            .await
+            .expect("Should execute successfully!");
+        assert!(server.matched());
+    }
+
+    #[tokio::test]
+    async fn action_http_call_sends_api_key() {
+        let path = "/with-api-key";
+        let server = mock("GET", path)
+            .match_header("x-api-key", "secret")
+            .match_header("authorization", Matcher::Missing)
+            .with_status(200)
+            .create();
+
+        let mut action = HttpCall {
+            method: HttpMethod::GET,
+            url: format!("{}{}", server_url(), path),
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: Some(HttpAuth::ApiKey {
+                header: "X-Api-Key".to_string(),
+                value: "secret".into(),
+            }),
+            headers: None,
+            body: None,
+            retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
             timeout: None,
+            client_certificate: None,
+            proxy: None,
         };
 
-        action.execute().expect("Should execute successfully!");
+        action
+            .execute(
+                &Transition(VideoMode::Content, VideoMode::Slate),
+                &event(VideoMode::Slate),
+            )
+            .await
+            .expect("Should execute successfully!");
         assert!(server.matched());
     }
 
+    #[tokio::test]
+    async fn action_http_call_retries_with_policy() {
+        let mut action = HttpCall {
+            method: HttpMethod::GET,
+            // Nothing listens on the port, so the call fails
+            url: "http://127.0.0.1:9/unavailable".to_string(),
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: None,
+            headers: None,
+            body: None,
+            retries: None,
+            retry: Some(RetryPolicy {
+                max_attempts: 3,
+                initial_delay: Some(1),
+                max_delay: Some(2),
+                factor: None,
+                jitter: Some(false),
+            }),
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        };
+        let result = action
+            .execute(
+                &Transition(VideoMode::Content, VideoMode::Slate),
+                &event(VideoMode::Slate),
+            )
+            .await;
+        assert!(result.is_err());
+        assert_eq!(
+            HTTP_CALL_ATTEMPTS.with_label_values(&["3", "error"]).get(),
+            1
+        );
+    }
+
+    #[tokio::test]
+    async fn action_http_call_checks_response() {
+        let path = "/ad-break";
+        let server = mock("POST", path)
+            .with_status(409)
+            .with_body("{\"status\":\"in-progress\"}")
+            .expect(4)
+            .create();
+
+        let mut action = HttpCall {
+            method: HttpMethod::POST,
+            url: format!("{}{}", server_url(), path),
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: None,
+            headers: None,
+            body: None,
+            retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        };
+        let transition = Transition(VideoMode::Content, VideoMode::Slate);
+        assert!(action
+            .execute(&transition, &event(VideoMode::Slate))
+            .await
+            .is_err());
+
+        action.expected_status = Some(vec![200, 409]);
+        assert!(action
+            .execute(&transition, &event(VideoMode::Slate))
+            .await
+            .is_ok());
+
+        action.expected_body = Some("\"status\":\"(started|in-progress)\"".to_string());
+        assert!(action
+            .execute(&transition, &event(VideoMode::Slate))
+            .await
+            .is_ok());
+
+        action.expected_body = Some("\"status\":\"started\"".to_string());
+        assert!(action
+            .execute(&transition, &event(VideoMode::Slate))
+            .await
+            .is_err());
+        server.assert();
+    }
+
+    #[test]
+    fn retry_delay_with_jitter() {
+        let policy = RetryPolicy::attempts(3);
+        for _ in 0..10 {
+            assert!(retry_delay(&policy, 2) <= Duration::from_millis(1000));
+        }
+    }
+
+    #[test]
+    fn event_payload_describes_transition() {
+        let context = event(VideoMode::Slate);
+        let payload = event_payload(&Transition(VideoMode::Content, VideoMode::Slate), &context);
+
+        assert_eq!(payload["watcher_id"], "watcher-id");
+        assert_eq!(payload["event_id"], context.event_id.as_str());
+        assert_eq!(payload["transition"], "content-to-slate");
+        assert_eq!(payload["from"], "content");
+        assert_eq!(payload["to"], "slate");
+        assert_eq!(
+            payload["detected_at"],
+            context.detected_at.to_rfc3339().as_str()
+        );
+        assert!(payload.get("simulated").is_none());
+
+        let context = EventContext::simulated(
+            "watcher-id",
+            None,
+            Simulation::new(VideoMode::Content, VideoMode::Slate, false),
+        );
+        let payload = event_payload(&Transition(VideoMode::Content, VideoMode::Slate), &context);
+        assert_eq!(payload["event_id"], context.event_id.as_str());
+        assert_eq!(payload["simulated"], true);
+    }
+
+    #[test]
+    fn redis_publish_command() {
+        let mut action = RedisPublish {
+            redis_url: "redis://localhost".to_string(),
+            key: "hawkeye-events".to_string(),
+            delivery: RedisDelivery::Stream,
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            payload: None,
+            max_len: Some(1000),
+        };
+        assert_eq!(
+            redis_command(&action, "slate".to_string()).get_packed_command(),
+            redis::cmd("XADD")
+                .arg("hawkeye-events")
+                .arg("MAXLEN")
+                .arg("~")
+                .arg(1000)
+                .arg("*")
+                .arg("event")
+                .arg("slate")
+                .get_packed_command()
+        );
+
+        action.delivery = RedisDelivery::Channel;
+        assert_eq!(
+            redis_command(&action, "slate".to_string()).get_packed_command(),
+            redis::cmd("PUBLISH")
+                .arg("hawkeye-events")
+                .arg("slate")
+                .get_packed_command()
+        );
+    }
+
+    #[tokio::test]
+    async fn action_chat_notification_sends_message() {
+        let server = mock("POST", "/chat-webhook")
+            .match_header("content-type", "application/json")
+            .match_body(Matcher::Json(json!({
+                "text": "Test watcher is now slate\n<https://hawkeye.example.com/v1/watchers/watcher-id/video-frame|Latest frame>"
+            })))
+            .with_status(200)
+            .create();
+
+        let mut action = ChatNotification {
+            webhook_url: format!("{}/chat-webhook", server_url()),
+            platform: ChatPlatform::Slack,
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            template: Some("{{description}} is now {{to}}".to_string()),
+            api_url: Some("https://hawkeye.example.com/".to_string()),
+            proxy: None,
+        };
+
+        action
+            .execute(
+                &Transition(VideoMode::Content, VideoMode::Slate),
+                &event(VideoMode::Slate),
+            )
+            .await
+            .expect("Should execute successfully!");
+        server.assert();
+    }
+
+    #[tokio::test]
+    async fn action_http_call_sends_event_metadata_headers() {
+        let path = "/with-metadata";
+        let context = event(VideoMode::Slate);
+
+        let server = mock("GET", path)
+            .match_header("x-hawkeye-watcher-id", "watcher-id")
+            .match_header("x-hawkeye-event-id", context.event_id.as_str())
+            .match_header("x-hawkeye-transition", "content-to-slate")
+            .match_header(
+                "x-hawkeye-detected-at",
+                context.detected_at.to_rfc3339().as_str(),
+            )
+            .match_header("x-hawkeye-worker-version", WORKER_VERSION)
+            .with_status(200)
+            .create();
+
+        let mut action = HttpCall {
+            method: HttpMethod::GET,
+            url: format!("{}{}", server_url(), path),
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: None,
+            headers: None,
+            body: None,
+            retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        };
+
+        action
+            .execute(&Transition(VideoMode::Content, VideoMode::Slate), &context)
+            .await
+            .expect("Should execute successfully!");
+        assert!(server.matched());
+    }
+
+    #[tokio::test]
+    async fn action_http_call_renders_templates() {
+        let mut context = event(VideoMode::Slate);
+        context.slate_url = Some("https://example.com/slate.jpg".to_string());
+        context.similarity = Some(0.125);
+
+        let server = mock("POST", "/watchers/watcher-id/slate")
+            .match_header("x-transition", "content-to-slate")
+            .match_body(Matcher::Json(json!({
+                "event": context.event_id,
+                "slate": "https://example.com/slate.jpg",
+                "similarity": 0.125,
+                "timestamp": context.detected_at.timestamp(),
+            })))
+            .with_status(200)
+            .create();
+
+        let mut action = HttpCall {
+            method: HttpMethod::POST,
+            url: format!("{}/watchers/{{{{watcher_id}}}}/{{{{ to }}}}", server_url()),
+            description: None,
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: None,
+            headers: Some(
+                [("x-transition", "{{transition}}")]
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect::<HashMap<String, String>>(),
+            ),
+            body: Some(
+                r#"{"event":"{{event_id}}","slate":"{{slate_url}}","similarity":{{similarity}},"timestamp":{{timestamp}}}"#
+                    .to_string(),
+            ),
+            retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        };
+
+        action
+            .execute(&Transition(VideoMode::Content, VideoMode::Slate), &context)
+            .await
+            .expect("Should execute successfully!");
+        assert!(server.matched());
+    }
+
+    #[test]
+    fn render_template_keeps_unknown_placeholders() {
+        let rendered = render_template(
+            "{{from}}-{{unknown}}-{{slate_url}}-{{to",
+            &Transition(VideoMode::Content, VideoMode::Slate),
+            &event(VideoMode::Slate),
+        );
+        assert_eq!(rendered, "content-{{unknown}}--{{to");
+    }
+
     #[test]
     fn build_executor_from_models() {
         let transition = models::Transition {
             from: models::VideoMode::Content,
             to: models::VideoMode::Slate,
+            min_dwell: Some(10),
+            cooldown_seconds: Some(60),
+            schedule: None,
+            circuit_breaker: None,
+            chain: None,
             actions: vec![models::Action::HttpCall(HttpCall {
                 description: Some("Trigger AdBreak using API".to_string()),
+                condition: None,
+                delay: None,
+                abort_on_failure: None,
                 method: HttpMethod::POST,
                 url: "http://non-existent.cbsi.com/v1/organization/cbsa/channel/sl/ad-break"
                     .to_string(),
                 authorization: Some(HttpAuth::Basic {
                     username: "dev_user".to_string(),
-                    password: "something".to_string(),
+                    password: "something".into(),
                 }),
                 headers: Some(
                     [("content-type", "application/json")]
@@ -398,10 +1864,36 @@ mod tests {
                 ),
                 body: Some("{\"duration\":320}".to_string()),
                 retries: Some(3),
+                retry: None,
+                expected_status: None,
+                expected_body: None,
                 timeout: Some(10),
+                client_certificate: None,
+                proxy: None,
             })],
         };
 
-        let _executors: Executors = transition.into();
+        let executors: Executors = transition.clone().into();
+        assert_eq!(executors.0.len(), 1);
+        assert_eq!(executors.0[0].min_dwell, Duration::from_secs(10));
+        assert_eq!(executors.0[0].cooldown, Duration::from_secs(60));
+
+        let mut chained = transition.actions[0].clone();
+        if let models::Action::HttpCall(call) = &mut chained {
+            call.delay = Some(2000);
+        }
+        let executors: Executors = models::Transition {
+            chain: Some(true),
+            actions: vec![transition.actions[0].clone(), chained],
+            ..transition
+        }
+        .into();
+        assert_eq!(executors.0.len(), 1);
+        let next = executors.0[0]
+            .next
+            .as_ref()
+            .expect("Should chain the actions");
+        assert_eq!(next.delay, Duration::from_secs(2));
+        assert!(next.next.is_none());
     }
 }
@@ -0,0 +1,98 @@
+//! Calls to AWS services, which use the default credentials chain. It includes the web identity
+//! token of the service account (IRSA) when running in EKS.
+use aws_sdk_eventbridge::model::PutEventsRequestEntry;
+use color_eyre::{eyre::eyre, Result};
+use tokio::runtime::Builder;
+
+pub fn download_s3_object(bucket: &str, key: &str) -> Result<Vec<u8>> {
+    runtime()?.block_on(get_object(bucket, key))
+}
+
+/// The AWS SDK is async, while the slates are loaded before starting any runtime.
+fn runtime() -> Result<tokio::runtime::Runtime> {
+    Ok(Builder::new_current_thread().enable_all().build()?)
+}
+
+async fn get_object(bucket: &str, key: &str) -> Result<Vec<u8>> {
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_s3::Client::new(&config);
+    let object = client.get_object().bucket(bucket).key(key).send().await?;
+    let data = object.body.collect().await?;
+    Ok(data.into_bytes().to_vec())
+}
+
+pub fn upload_s3_object(
+    bucket: &str,
+    key: &str,
+    content_type: &str,
+    contents: Vec<u8>,
+) -> Result<()> {
+    runtime()?.block_on(put_object(bucket, key, content_type, contents))
+}
+
+pub fn delete_s3_object(bucket: &str, key: &str) -> Result<()> {
+    runtime()?.block_on(delete_object(bucket, key))
+}
+
+async fn put_object(bucket: &str, key: &str, content_type: &str, contents: Vec<u8>) -> Result<()> {
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_s3::Client::new(&config);
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(key)
+        .content_type(content_type)
+        .body(contents.into())
+        .send()
+        .await?;
+    Ok(())
+}
+
+async fn delete_object(bucket: &str, key: &str) -> Result<()> {
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_s3::Client::new(&config);
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn publish_sns(topic_arn: &str, subject: Option<&str>, message: &str) -> Result<()> {
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_sns::Client::new(&config);
+    client
+        .publish()
+        .topic_arn(topic_arn)
+        .set_subject(subject.map(String::from))
+        .message(message)
+        .send()
+        .await?;
+    Ok(())
+}
+
+pub async fn put_event(
+    event_bus: &str,
+    source: &str,
+    detail_type: &str,
+    detail: &str,
+) -> Result<()> {
+    let config = aws_config::load_from_env().await;
+    let client = aws_sdk_eventbridge::Client::new(&config);
+    let entry = PutEventsRequestEntry::builder()
+        .event_bus_name(event_bus)
+        .source(source)
+        .detail_type(detail_type)
+        .detail(detail)
+        .build();
+    let output = client.put_events().entries(entry).send().await?;
+    if output.failed_entry_count > 0 {
+        return Err(eyre!(
+            "EventBridge rejected the event: {:?}",
+            output.entries
+        ));
+    }
+    Ok(())
+}
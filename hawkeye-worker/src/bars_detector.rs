@@ -0,0 +1,127 @@
+use image::{GenericImageView, Rgb};
+use log::debug;
+
+/// Channels (red, green, blue) expected to be lit in each bar of the SMPTE color bars:
+/// white, yellow, cyan, green, magenta, red and blue.
+const SMPTE_BARS: [[bool; 3]; 7] = [
+    [true, true, true],
+    [true, true, false],
+    [false, true, true],
+    [false, true, false],
+    [true, false, true],
+    [true, false, false],
+    [false, false, true],
+];
+
+/// Minimum value of a lit channel, bars are usually at 75% or 100% intensity.
+const LIT_CHANNEL_MIN: u32 = 140;
+/// Maximum value of an unlit channel.
+const UNLIT_CHANNEL_MAX: u32 = 60;
+
+/// Detects the standard SMPTE color bars, commonly sent by encoders when there's no program.
+///
+/// Only the top half of the frame is considered, since the bottom part of the bars pattern varies
+/// between the SMPTE variants.
+#[derive(Default)]
+pub struct BarsDetector;
+
+impl BarsDetector {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn is_match(&self, image_buffer: &[u8]) -> bool {
+        let frame = match image::load_from_memory(image_buffer) {
+            Ok(frame) => frame.to_rgb8(),
+            Err(err) => {
+                debug!("Could not decode frame to check for color bars: {}", err);
+                return false;
+            }
+        };
+        let (width, height) = frame.dimensions();
+        let bar_width = width / SMPTE_BARS.len() as u32;
+        if bar_width < 4 || height < 4 {
+            return false;
+        }
+
+        SMPTE_BARS.iter().enumerate().all(|(index, expected)| {
+            // Sample the middle of the bar, avoiding the blurry edges between bars
+            let left = index as u32 * bar_width + bar_width / 4;
+            let color = average_color(&frame, left, height / 8, bar_width / 2, height / 2);
+            color
+                .0
+                .iter()
+                .zip(expected.iter())
+                .all(|(value, lit)| match lit {
+                    true => *value >= LIT_CHANNEL_MIN,
+                    false => *value <= UNLIT_CHANNEL_MAX,
+                })
+        })
+    }
+}
+
+fn average_color(
+    frame: &image::RgbImage,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+) -> Rgb<u32> {
+    let area = frame.view(left, top, width, height);
+    let mut total = [0u32; 3];
+    for (_, _, pixel) in area.pixels() {
+        for (sum, value) in total.iter_mut().zip(pixel.0.iter()) {
+            *sum += *value as u32;
+        }
+    }
+    let pixels = width * height;
+    Rgb([total[0] / pixels, total[1] / pixels, total[2] / pixels])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageBuffer, ImageFormat};
+    use std::io::Read;
+
+    fn color_bars(intensity: u8) -> Vec<u8> {
+        let img = ImageBuffer::from_fn(213, 120, |x, y| {
+            if y > 80 {
+                return Rgb([0, 0, 0]);
+            }
+            let bar = SMPTE_BARS[(x as usize * SMPTE_BARS.len() / 213).min(6)];
+            Rgb([
+                if bar[0] { intensity } else { 0 },
+                if bar[1] { intensity } else { 0 },
+                if bar[2] { intensity } else { 0 },
+            ])
+        });
+        let mut contents = Vec::new();
+        DynamicImage::ImageRgb8(img)
+            .write_to(&mut contents, ImageFormat::Png)
+            .unwrap();
+        contents
+    }
+
+    fn read_bytes(path: &str) -> Vec<u8> {
+        let mut img = std::fs::File::open(path).expect("Missing file in resources folder");
+        let mut buffer = Vec::new();
+        img.read_to_end(&mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn color_bars_are_detected() {
+        let detector = BarsDetector::new();
+        assert!(detector.is_match(color_bars(191).as_slice()));
+        assert!(detector.is_match(color_bars(255).as_slice()));
+    }
+
+    #[test]
+    fn other_frames_are_not_bars() {
+        let detector = BarsDetector::new();
+        assert!(!detector.is_match(read_bytes("../resources/slate_120px.jpg").as_slice()));
+        assert!(!detector.is_match(read_bytes("../resources/non-slate_120px.jpg").as_slice()));
+        assert!(!detector.is_match(read_bytes("../resources/black_120px.jpg").as_slice()));
+    }
+}
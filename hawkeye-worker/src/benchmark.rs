@@ -0,0 +1,222 @@
+use crate::bars_detector::BarsDetector;
+use crate::black_detector::{BlackDetector, DEFAULT_BLACK_THRESHOLD};
+use crate::calibration::to_url;
+use crate::img_detector::{SlateDetector, DEFAULT_SLATE_THRESHOLD};
+use crate::slate;
+use color_eyre::{eyre::eyre, Result};
+use hawkeye_core::models::VideoMode;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Settings of the detectors benchmarked, like in the watcher.
+pub struct BenchmarkSettings {
+    pub slates: Vec<String>,
+    pub slate_threshold: Option<u32>,
+    pub black_threshold: Option<u8>,
+    pub detect_bars: bool,
+    pub iterations: u32,
+}
+
+/// Durations of the runs of a detection algorithm.
+#[derive(Default)]
+struct Latencies(Vec<Duration>);
+
+impl Latencies {
+    fn measure<R>(&mut self, f: impl FnOnce() -> R) -> R {
+        let started = Instant::now();
+        let result = f();
+        self.0.push(started.elapsed());
+        result
+    }
+
+    /// Mean, 95th percentile and maximum, in milliseconds.
+    fn summary(&self) -> Option<(f64, f64, f64)> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<f64> = self
+            .0
+            .iter()
+            .map(|duration| duration.as_micros() as f64 / 1000f64)
+            .collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let mean = sorted.iter().sum::<f64>() / sorted.len() as f64;
+        let p95 = sorted[((sorted.len() as f64 * 0.95).ceil() as usize).max(1) - 1];
+        Some((mean, p95, sorted[sorted.len() - 1]))
+    }
+}
+
+/// Video mode expected for the frame, from the name of its directory like `slate/frame-1.png`.
+fn expected_mode(path: &Path) -> Option<VideoMode> {
+    let directory = path.parent()?.file_name()?.to_str()?;
+    serde_json::from_value(json!(directory.to_lowercase())).ok()
+}
+
+/// Frames in the directory and its subdirectories, sorted by path.
+fn list_frames(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut frames = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            frames.extend(list_frames(&path)?);
+        } else {
+            frames.push(path);
+        }
+    }
+    frames.sort();
+    Ok(frames)
+}
+
+/// Runs the detectors over the sample frames and prints the throughput, the latency of each
+/// algorithm and the accuracy of the detection, to size the CPU requests of the workers.
+///
+/// Frames in a directory named after a video mode, like `slate/` or `content/`, are expected to
+/// be detected as that mode.
+pub fn benchmark(settings: BenchmarkSettings, frames_dir: &Path) -> Result<()> {
+    let slates = settings
+        .slates
+        .iter()
+        .map(|slate| {
+            let url = to_url(slate);
+            let contents = slate::load_img(&url, None, None)?;
+            Ok((url, contents))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let slate_detector = SlateDetector::new(&slates, None)?
+        .with_threshold(settings.slate_threshold.unwrap_or(DEFAULT_SLATE_THRESHOLD));
+    let black_detector =
+        BlackDetector::new(settings.black_threshold.unwrap_or(DEFAULT_BLACK_THRESHOLD));
+    let bars_detector = if settings.detect_bars {
+        Some(BarsDetector::new())
+    } else {
+        None
+    };
+
+    let paths = list_frames(frames_dir)?;
+    if paths.is_empty() {
+        return Err(eyre!("No frames found in {}", frames_dir.display()));
+    }
+    let frames = paths
+        .iter()
+        .map(|path| slate::load_img(&to_url(&path.to_string_lossy()), None, None))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut black = Latencies::default();
+    let mut bars = Latencies::default();
+    let mut slate = Latencies::default();
+    let mut detected = Vec::with_capacity(frames.len());
+    let started = Instant::now();
+    for iteration in 0..settings.iterations.max(1) {
+        for frame in frames.iter() {
+            let is_black = black.measure(|| black_detector.is_match(frame));
+            let is_bars = !is_black
+                && bars_detector
+                    .as_ref()
+                    .map(|detector| bars.measure(|| detector.is_match(frame)))
+                    .unwrap_or(false);
+            let mode = if is_black {
+                VideoMode::Black
+            } else if is_bars {
+                VideoMode::Bars
+            } else if slate.measure(|| slate_detector.find_match(frame)).is_some() {
+                VideoMode::Slate
+            } else {
+                VideoMode::Content
+            };
+            if iteration == 0 {
+                detected.push(mode);
+            }
+        }
+    }
+    let elapsed = started.elapsed();
+    let analysed = frames.len() * settings.iterations.max(1) as usize;
+
+    println!(
+        "Frames: {} ({} iterations of {} frames)",
+        analysed,
+        settings.iterations.max(1),
+        frames.len()
+    );
+    println!(
+        "Throughput: {:.1} frames/s",
+        analysed as f64 / elapsed.as_secs_f64()
+    );
+    for (name, latencies) in [("black", &black), ("bars", &bars), ("slate", &slate)].iter() {
+        if let Some((mean, p95, max)) = latencies.summary() {
+            println!(
+                "{} detection: mean {:.2}ms, p95 {:.2}ms, max {:.2}ms",
+                name, mean, p95, max
+            );
+        }
+    }
+
+    // Correct and expected detections by video mode
+    let mut accuracy: BTreeMap<String, (usize, usize)> = BTreeMap::new();
+    let mut mismatches = Vec::new();
+    for (path, mode) in paths.iter().zip(detected) {
+        if let Some(expected) = expected_mode(path) {
+            let entry = accuracy.entry(expected.to_string()).or_default();
+            entry.1 += 1;
+            if mode == expected {
+                entry.0 += 1;
+            } else {
+                mismatches.push((path, expected, mode));
+            }
+        }
+    }
+    if accuracy.is_empty() {
+        println!(
+            "Accuracy: no expected video modes, name the directories of the frames after them"
+        );
+        return Ok(());
+    }
+    let (correct, total) = accuracy.values().fold((0, 0), |(correct, total), entry| {
+        (correct + entry.0, total + entry.1)
+    });
+    println!(
+        "Accuracy: {}/{} ({:.1}%)",
+        correct,
+        total,
+        correct as f64 * 100f64 / total as f64
+    );
+    for (mode, (correct, total)) in accuracy.iter() {
+        println!("  {}: {}/{}", mode, correct, total);
+    }
+    for (path, expected, mode) in mismatches {
+        println!(
+            "  {}: expected {}, detected {}",
+            path.display(),
+            expected,
+            mode
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarize_latencies() {
+        assert_eq!(Latencies::default().summary(), None);
+        let latencies = Latencies((1..=20).map(Duration::from_millis).collect());
+        assert_eq!(latencies.summary(), Some((10.5, 19.0, 20.0)));
+    }
+
+    #[test]
+    fn expected_mode_from_directory() {
+        assert_eq!(
+            expected_mode(Path::new("frames/slate/frame-1.png")),
+            Some(VideoMode::Slate)
+        );
+        assert_eq!(
+            expected_mode(Path::new("frames/Content/frame-1.png")),
+            Some(VideoMode::Content)
+        );
+        assert_eq!(expected_mode(Path::new("frames/frame-1.png")), None);
+    }
+}
@@ -0,0 +1,60 @@
+use image::GenericImageView;
+use log::debug;
+
+/// Default maximum average luminance (0-255) of a frame considered black.
+pub const DEFAULT_BLACK_THRESHOLD: u8 = 20;
+
+/// Detects black frames by their average luminance.
+pub struct BlackDetector {
+    threshold: u8,
+}
+
+impl BlackDetector {
+    pub fn new(threshold: u8) -> Self {
+        Self { threshold }
+    }
+
+    pub fn is_match(&self, image_buffer: &[u8]) -> bool {
+        let frame = match image::load_from_memory(image_buffer) {
+            Ok(frame) => frame,
+            Err(err) => {
+                debug!("Could not decode frame to check for black: {}", err);
+                return false;
+            }
+        };
+        let (width, height) = frame.dimensions();
+        let pixels = (width as u64) * (height as u64);
+        if pixels == 0 {
+            return false;
+        }
+
+        let total: u64 = frame.to_luma8().pixels().map(|p| p.0[0] as u64).sum();
+        total / pixels <= self.threshold as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn read_bytes(path: &str) -> Vec<u8> {
+        let mut img = std::fs::File::open(path).expect("Missing file in resources folder");
+        let mut buffer = Vec::new();
+        img.read_to_end(&mut buffer).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn black_frame_is_detected() {
+        let detector = BlackDetector::new(DEFAULT_BLACK_THRESHOLD);
+        assert!(detector.is_match(read_bytes("../resources/black_120px.jpg").as_slice()));
+    }
+
+    #[test]
+    fn slate_is_not_black() {
+        let detector = BlackDetector::new(DEFAULT_BLACK_THRESHOLD);
+        assert!(!detector.is_match(read_bytes("../resources/slate_120px.jpg").as_slice()));
+        assert!(!detector.is_match(read_bytes("../resources/non-slate_120px.jpg").as_slice()));
+    }
+}
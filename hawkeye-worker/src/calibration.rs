@@ -0,0 +1,92 @@
+use crate::img_detector::{SlateDetector, DEFAULT_SLATE_THRESHOLD};
+use crate::slate;
+use crate::threshold_learning::suggest_threshold;
+use color_eyre::Result;
+use hawkeye_core::models::{HttpAuth, Region};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Prints the similarity scores of the frames with the slate, so the `slate_threshold` of a
+/// Watcher can be tuned without deploying it.
+pub fn test_slate(slate: &str, frames: &[PathBuf]) -> Result<()> {
+    let slate_url = to_url(slate);
+    let contents = slate::load_img(&slate_url, None, None)?;
+    let detector = SlateDetector::new(&[(slate_url, contents)], None)?;
+
+    let mut scores = Vec::with_capacity(frames.len());
+    for path in frames {
+        let frame = slate::load_img(&to_url(&path.to_string_lossy()), None, None)?;
+        let score = detector.scores(frame.as_slice())?[0];
+        println!(
+            "{}: dssim score {}, hash distance {}{}",
+            path.display(),
+            score.dssim,
+            score.hash_distance,
+            if score.passes_prefilter() {
+                ""
+            } else {
+                " (rejected by prefilter)"
+            }
+        );
+        scores.push(score.dssim);
+    }
+
+    match suggest_threshold(scores) {
+        Some(threshold) => println!("Suggested slate_threshold: {}", threshold),
+        None => println!("Could not suggest a threshold, provide both slate and content frames"),
+    }
+    Ok(())
+}
+
+/// Slates compared with a frame of the stream, with the settings of the watcher.
+pub struct SlateCheck {
+    pub slate_urls: Vec<String>,
+    pub slate_threshold: Option<u32>,
+    pub slate_region: Option<Region>,
+    pub slate_authorization: Option<HttpAuth>,
+    pub slate_headers: Option<HashMap<String, String>>,
+}
+
+/// Scores the frame against each slate, so a new slate can be confirmed to match the stream
+/// before the watcher is changed to use it.
+pub fn score_frame(check: &SlateCheck, frame: &[u8]) -> Result<Vec<serde_json::Value>> {
+    let slates = check
+        .slate_urls
+        .iter()
+        .map(|url| {
+            let contents = slate::load_img(
+                url,
+                check.slate_authorization.as_ref(),
+                check.slate_headers.as_ref(),
+            )?;
+            Ok((url.clone(), contents))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let detector = SlateDetector::new(&slates, check.slate_region)?;
+    let threshold = check.slate_threshold.unwrap_or(DEFAULT_SLATE_THRESHOLD);
+
+    Ok(slates
+        .iter()
+        .zip(detector.scores(frame)?)
+        .map(|((url, _), score)| {
+            json!({
+                // Query strings are left out, they might contain credentials
+                "slate_url": url.split('?').next().unwrap_or_default(),
+                "dssim": score.dssim,
+                "hash_distance": score.hash_distance,
+                "passes_prefilter": score.passes_prefilter(),
+                "matches": score.passes_prefilter() && score.dssim <= threshold,
+            })
+        })
+        .collect())
+}
+
+/// Paths without a scheme are considered local files.
+pub fn to_url(location: &str) -> String {
+    if location.contains("://") {
+        location.to_string()
+    } else {
+        format!("file://{}", location)
+    }
+}
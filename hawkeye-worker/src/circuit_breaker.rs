@@ -0,0 +1,117 @@
+use crate::metrics::ACTION_CIRCUIT_STATE;
+use log::{info, warn};
+use prometheus::IntGauge;
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum State {
+    Closed,
+    Open(Instant),
+    HalfOpen,
+}
+
+/// Stops calling an action after a number of consecutive failures, so a system that is down isn't
+/// flooded with calls (and their retries) on every transition.
+///
+/// Once `reset_timeout` passes the next call is let through as a probe, the breaker closes again
+/// if it succeeds or stays open for another `reset_timeout` otherwise.
+pub struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    reset_timeout: Duration,
+    consecutive_failures: u32,
+    state: State,
+    state_gauge: IntGauge,
+}
+
+impl CircuitBreaker {
+    pub fn new(name: &str, failure_threshold: u32, reset_timeout: Duration) -> Self {
+        let breaker = Self {
+            name: name.to_string(),
+            failure_threshold,
+            reset_timeout,
+            consecutive_failures: 0,
+            state: State::Closed,
+            state_gauge: ACTION_CIRCUIT_STATE.with_label_values(&[name]),
+        };
+        breaker.state_gauge.set(0);
+        breaker
+    }
+
+    /// Check if the action can be called.
+    pub fn allow(&mut self) -> bool {
+        match self.state {
+            State::Closed | State::HalfOpen => true,
+            State::Open(since) if since.elapsed() >= self.reset_timeout => {
+                info!("Probing action {} after the circuit was open", self.name);
+                self.set_state(State::HalfOpen);
+                true
+            }
+            State::Open(_) => false,
+        }
+    }
+
+    /// Records the result of a call of the action.
+    pub fn record(&mut self, success: bool) {
+        if success {
+            self.consecutive_failures = 0;
+            self.set_state(State::Closed);
+            return;
+        }
+        self.consecutive_failures += 1;
+        if self.state == State::HalfOpen || self.consecutive_failures >= self.failure_threshold {
+            warn!(
+                "Opening circuit of action {} after {} consecutive failures",
+                self.name, self.consecutive_failures
+            );
+            self.set_state(State::Open(Instant::now()));
+        }
+    }
+
+    fn set_state(&mut self, state: State) {
+        self.state = state;
+        self.state_gauge.set(match state {
+            State::Closed => 0,
+            State::Open(_) => 1,
+            State::HalfOpen => 2,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opens_after_consecutive_failures() {
+        let mut breaker = CircuitBreaker::new("test-breaker", 2, Duration::from_secs(30));
+
+        assert!(breaker.allow());
+        breaker.record(false);
+        breaker.record(true);
+        breaker.record(false);
+        assert!(breaker.allow());
+        breaker.record(false);
+        assert!(!breaker.allow());
+        assert_eq!(breaker.state_gauge.get(), 1);
+
+        // Failed probe keeps it open
+        Instant::advance_time(30_000);
+        assert!(breaker.allow());
+        assert_eq!(breaker.state_gauge.get(), 2);
+        breaker.record(false);
+        assert!(!breaker.allow());
+
+        // Successful probe closes it
+        Instant::advance_time(30_000);
+        assert!(breaker.allow());
+        breaker.record(true);
+        assert!(breaker.allow());
+        assert_eq!(breaker.state_gauge.get(), 0);
+    }
+}
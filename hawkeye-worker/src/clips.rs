@@ -0,0 +1,217 @@
+//! Recording of the stream around the transitions, uploaded to S3 to review the detections
+//! afterwards, like false positives.
+//!
+//! The encoded stream is kept in memory as MPEG-TS for the seconds before a transition, which are
+//! uploaded with the seconds after it once they are received.
+use crate::aws;
+use crate::video_stream::Detection;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use hawkeye_core::models::{ClipSettings, VideoMode};
+use lazy_static::lazy_static;
+use log::{error, info};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+lazy_static! {
+    static ref RECORDER: Mutex<Option<Recorder>> = Mutex::new(None);
+}
+
+/// Part of the encoded stream.
+struct Chunk {
+    received_at: Instant,
+    data: Vec<u8>,
+}
+
+/// A clip waiting for the seconds after its transition.
+struct PendingClip {
+    key: String,
+    from: Instant,
+    until: Instant,
+}
+
+struct Recorder {
+    bucket: String,
+    prefix: String,
+    watcher_id: String,
+    before: Duration,
+    after: Duration,
+    mode: Option<VideoMode>,
+    chunks: VecDeque<Chunk>,
+    pending: Vec<PendingClip>,
+}
+
+impl Recorder {
+    fn new(settings: &ClipSettings, watcher_id: &str) -> Result<Self> {
+        let (bucket, prefix) = settings.s3_location()?;
+        Ok(Self {
+            bucket: bucket.to_string(),
+            prefix: prefix.to_string(),
+            watcher_id: watcher_id.to_string(),
+            before: Duration::from_secs(settings.before_seconds() as u64),
+            after: Duration::from_secs(settings.after_seconds() as u64),
+            mode: None,
+            chunks: VecDeque::new(),
+            pending: Vec::new(),
+        })
+    }
+
+    fn key(&self, mode: VideoMode, detected_at: DateTime<Utc>) -> String {
+        let prefix = if self.prefix.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", self.prefix)
+        };
+        format!(
+            "{}{}/{}-{}.ts",
+            prefix,
+            self.watcher_id,
+            detected_at.format("%Y-%m-%dT%H-%M-%S%.3fZ"),
+            mode
+        )
+    }
+
+    /// Starts a clip when the reported mode changed, the first mode of the stream is not a
+    /// transition.
+    fn transition(&mut self, mode: VideoMode, detected_at: DateTime<Utc>, now: Instant) {
+        let previous = self.mode.replace(mode);
+        if previous.is_none() || previous == Some(mode) {
+            return;
+        }
+        self.pending.push(PendingClip {
+            key: self.key(mode, detected_at),
+            from: now.checked_sub(self.before).unwrap_or(now),
+            until: now + self.after,
+        });
+    }
+
+    /// Keeps the part of the stream, returns the clips completed by it with their keys.
+    fn push(&mut self, data: Vec<u8>, now: Instant) -> Vec<(String, Vec<u8>)> {
+        self.chunks.push_back(Chunk {
+            received_at: now,
+            data,
+        });
+
+        let mut completed = Vec::new();
+        let mut index = 0;
+        while index < self.pending.len() {
+            if self.pending[index].until > now {
+                index += 1;
+                continue;
+            }
+            let clip = self.pending.remove(index);
+            let data = self
+                .chunks
+                .iter()
+                .filter(|chunk| chunk.received_at >= clip.from && chunk.received_at < clip.until)
+                .flat_map(|chunk| chunk.data.iter().copied())
+                .collect();
+            completed.push((clip.key, data));
+        }
+
+        // Drops the parts of the stream no clip can include anymore
+        let keep_from = self
+            .pending
+            .iter()
+            .map(|clip| clip.from)
+            .chain(now.checked_sub(self.before))
+            .min();
+        if let Some(keep_from) = keep_from {
+            while matches!(self.chunks.front(), Some(chunk) if chunk.received_at < keep_from) {
+                self.chunks.pop_front();
+            }
+        }
+        completed
+    }
+}
+
+/// Records the clips of the watcher, before the stream starts.
+pub fn configure(settings: &ClipSettings, watcher_id: &str) -> Result<()> {
+    *RECORDER.lock().unwrap() = Some(Recorder::new(settings, watcher_id)?);
+    info!("Recording clips of the transitions to {}", settings.url);
+    Ok(())
+}
+
+pub fn enabled() -> bool {
+    RECORDER.lock().unwrap().is_some()
+}
+
+/// Part of the pipeline description sending the encoded stream to the `clip_sink`, muxed as
+/// MPEG-TS by `muxer` when needed, next to the decoding of the frames.
+pub fn pipeline_branch(muxer: &str) -> String {
+    if !enabled() {
+        return String::new();
+    }
+    format!(
+        " ! tee name=encoded encoded. ! queue ! {}appsink name=clip_sink sync=false encoded. ! queue",
+        muxer
+    )
+}
+
+/// Keeps the part of the encoded stream, uploading the clips it completes.
+pub fn push(data: Vec<u8>) {
+    let completed = match RECORDER.lock().unwrap().as_mut() {
+        Some(recorder) => {
+            let completed = recorder.push(data, Instant::now());
+            completed
+                .into_iter()
+                .map(|(key, data)| (recorder.bucket.clone(), key, data))
+                .collect()
+        }
+        None => Vec::new(),
+    };
+    for (bucket, key, data) in completed {
+        // Uploads happen outside of the pipeline thread, so they don't hold the stream
+        thread::spawn(
+            move || match aws::upload_s3_object(&bucket, &key, "video/mp2t", data) {
+                Ok(()) => info!("Clip uploaded to s3://{}/{}", bucket, key),
+                Err(err) => error!("Could not upload clip s3://{}/{}: {:?}", bucket, key, err),
+            },
+        );
+    }
+}
+
+/// Starts recording a clip when the mode reported to the actions changed.
+pub fn transition(detection: &Detection) {
+    if let Some(recorder) = RECORDER.lock().unwrap().as_mut() {
+        recorder.transition(detection.mode, detection.detected_at, Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_clip_around_transition() {
+        let settings = ClipSettings {
+            url: "s3://recordings/clips".to_string(),
+            before_seconds: Some(2),
+            after_seconds: Some(2),
+        };
+        let mut recorder = Recorder::new(&settings, "watcher-id").unwrap();
+        let start = Instant::now();
+        let at = |seconds| start + Duration::from_secs(seconds);
+        let detected_at: DateTime<Utc> = "2021-11-30T10:00:04.250Z".parse().unwrap();
+
+        recorder.transition(VideoMode::Content, detected_at, at(0));
+        for second in 0..4 {
+            assert!(recorder.push(vec![second as u8], at(second)).is_empty());
+        }
+        recorder.transition(VideoMode::Slate, detected_at, at(4));
+        for second in 4..6 {
+            assert!(recorder.push(vec![second as u8], at(second)).is_empty());
+        }
+        assert_eq!(
+            recorder.push(vec![6], at(6)),
+            vec![(
+                "clips/watcher-id/2021-11-30T10-00-04.250Z-slate.ts".to_string(),
+                vec![2, 3, 4, 5]
+            )]
+        );
+        assert!(recorder.pending.is_empty());
+        assert_eq!(recorder.chunks.len(), 3);
+    }
+}
@@ -1,3 +1,4 @@
+use std::net::IpAddr;
 use std::path::PathBuf;
 use structopt::StructOpt;
 
@@ -9,5 +10,101 @@ use structopt::StructOpt;
 pub struct AppConfig {
     // Path to the watcher configuration
     #[structopt(parse(from_os_str))]
-    pub watcher_path: PathBuf,
+    pub watcher_path: Option<PathBuf>,
+
+    /// Logs the actions instead of executing them
+    #[structopt(long)]
+    pub dry_run: bool,
+
+    /// Port the stream is received on, overrides the ingest port of the watcher
+    #[structopt(long)]
+    pub ingest_port: Option<u32>,
+
+    /// Address the HTTP server of the metrics binds to
+    #[structopt(long, default_value = "0.0.0.0")]
+    pub metrics_address: IpAddr,
+
+    /// Port of the HTTP server of the metrics, defaults to the ingest port of the watcher
+    #[structopt(long)]
+    pub metrics_port: Option<u16>,
+
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Command {
+    /// Prints the similarity scores of the frames with the slate and suggests a threshold.
+    TestSlate {
+        /// URL or path of the slate image
+        slate: String,
+        /// Paths of the frames to compare with the slate
+        #[structopt(parse(from_os_str), required = true)]
+        frames: Vec<PathBuf>,
+    },
+    /// Runs the detectors over sample frames and reports the throughput, the latency of each
+    /// algorithm and the accuracy, to size the CPU requests of the workers.
+    Benchmark {
+        /// URLs or paths of the slate images
+        #[structopt(long = "slate", required = true)]
+        slates: Vec<String>,
+        /// Maximum DSSIM score (multiplied by 1000) of a frame to be considered the slate
+        #[structopt(long)]
+        slate_threshold: Option<u32>,
+        /// Maximum average luminance (0-255) of a frame to be considered black
+        #[structopt(long)]
+        black_threshold: Option<u8>,
+        /// Detects the standard SMPTE color bars
+        #[structopt(long)]
+        detect_bars: bool,
+        /// Number of times the frames are analysed
+        #[structopt(long, default_value = "1")]
+        iterations: u32,
+        /// Directory of the frames. Frames in a directory named after a video mode, like
+        /// `slate/`, are expected to be detected as that mode.
+        #[structopt(parse(from_os_str))]
+        frames_dir: PathBuf,
+    },
+    /// Sends a looping RTP/MPEG-TS test stream, splicing in a slate at intervals, for the
+    /// integration tests and the demos without a broadcast encoder.
+    StreamGen {
+        /// Host the stream is sent to
+        #[structopt(long, default_value = "127.0.0.1")]
+        host: String,
+        /// Port the stream is sent to, the ingest port of the watcher
+        #[structopt(long, default_value = "5000")]
+        port: u16,
+        /// URL or path of the video looped, a test pattern by default
+        #[structopt(long)]
+        video: Option<String>,
+        /// URL or path of the slate image spliced in the stream
+        #[structopt(long)]
+        slate: Option<String>,
+        /// Seconds of content between the slates
+        #[structopt(long, default_value = "60")]
+        slate_interval: u64,
+        /// Seconds the slate is shown
+        #[structopt(long, default_value = "10")]
+        slate_duration: u64,
+    },
+    /// Runs a worker for each watcher configuration in the directory, for the deployments
+    /// without Kubernetes, and serves their status.
+    Supervise {
+        /// Address the HTTP server of the status binds to
+        #[structopt(long, default_value = "0.0.0.0")]
+        address: IpAddr,
+        /// Port of the HTTP server of the status
+        #[structopt(long, default_value = "8080")]
+        port: u16,
+        /// Directory of the watcher configurations
+        #[structopt(parse(from_os_str))]
+        watchers_dir: PathBuf,
+    },
+    /// Checks the watcher configuration: its settings, the slate images, the ingest port and the
+    /// pipeline. Exits with an error when any check fails.
+    Validate {
+        /// Path to the watcher configuration
+        #[structopt(parse(from_os_str))]
+        watcher_path: PathBuf,
+    },
 }
@@ -0,0 +1,104 @@
+//! Controls of a running worker, to pause the detection or mute the actions during planned
+//! maintenance windows without scaling the deployment to zero.
+use lazy_static::lazy_static;
+use log::info;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use warp::Filter;
+
+const CONTROL_TOKEN_ENV: &str = "HAWKEYE_CONTROL_TOKEN";
+
+lazy_static! {
+    /// Token required by the control endpoints, they are disabled without it.
+    static ref CONTROL_TOKEN: Option<String> = std::env::var(CONTROL_TOKEN_ENV).ok();
+
+    static ref MUTED_UNTIL: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+static DETECTION_PAUSED: AtomicBool = AtomicBool::new(false);
+
+pub fn pause_detection() {
+    info!("Detection paused");
+    DETECTION_PAUSED.store(true, Ordering::SeqCst);
+}
+
+pub fn resume_detection() {
+    info!("Detection resumed");
+    DETECTION_PAUSED.store(false, Ordering::SeqCst);
+}
+
+pub fn detection_paused() -> bool {
+    DETECTION_PAUSED.load(Ordering::SeqCst)
+}
+
+/// Skips the actions for the given time.
+pub fn mute_actions(duration: Duration) {
+    info!("Actions muted for {}s", duration.as_secs());
+    *MUTED_UNTIL.lock().unwrap() = Some(Instant::now() + duration);
+}
+
+pub fn unmute_actions() {
+    info!("Actions unmuted");
+    *MUTED_UNTIL.lock().unwrap() = None;
+}
+
+/// Time left until the actions run again, if they are muted.
+pub fn actions_muted() -> Option<Duration> {
+    let muted_until = (*MUTED_UNTIL.lock().unwrap())?;
+    let now = Instant::now();
+    if muted_until > now {
+        Some(muted_until - now)
+    } else {
+        None
+    }
+}
+
+pub fn state() -> serde_json::Value {
+    json!({
+        "detection_paused": detection_paused(),
+        "actions_muted_seconds": actions_muted().map(|left| left.as_secs()),
+    })
+}
+
+/// Rejects the requests without the control token as bearer token.
+pub fn authorized() -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("authorization")
+        .and_then(|header: Option<String>| async move {
+            let token = header
+                .as_deref()
+                .and_then(|header| header.strip_prefix("Bearer "));
+            match (CONTROL_TOKEN.as_deref(), token) {
+                (Some(expected), Some(token)) if expected == token => Ok(()),
+                _ => Err(warp::reject::not_found()),
+            }
+        })
+        .untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn require_control_token() {
+        std::env::set_var(CONTROL_TOKEN_ENV, "secret");
+        let filter = warp::path!("control").and(authorized()).map(warp::reply);
+
+        let request = || warp::test::request().path("/control");
+        assert!(!request().matches(&filter).await);
+        assert!(
+            !request()
+                .header("authorization", "Bearer other")
+                .matches(&filter)
+                .await
+        );
+        assert!(
+            request()
+                .header("authorization", "Bearer secret")
+                .matches(&filter)
+                .await
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use crate::actions::{execute_action, EventContext, Transition};
+use crate::metrics::DEAD_LETTERS_GAUGE;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use hawkeye_core::models::Action;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use tracing::Span;
+use uuid::Uuid;
+
+/// Maximum number of failed executions kept, the oldest are dropped first.
+const CAPACITY: usize = 100;
+
+lazy_static! {
+    static ref DEAD_LETTERS: Mutex<VecDeque<DeadLetter>> = Mutex::new(VecDeque::new());
+}
+
+/// An execution of an action that failed after all its retries, kept so it can be replayed.
+#[derive(Clone)]
+struct DeadLetter {
+    id: String,
+    transition: Transition,
+    action: Action,
+    context: EventContext,
+    error: String,
+    failed_at: DateTime<Utc>,
+    replays: u32,
+}
+
+impl DeadLetter {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "transition": self.transition.to_string(),
+            "action": self.action.description(),
+            "event_id": self.context.event_id,
+            "detected_at": self.context.detected_at.to_rfc3339(),
+            "error": self.error,
+            "failed_at": self.failed_at.to_rfc3339(),
+            "replays": self.replays,
+        })
+    }
+}
+
+/// Keeps a failed execution of the action, returns its identifier.
+pub fn record(
+    transition: &Transition,
+    action: &Action,
+    context: &EventContext,
+    error: &color_eyre::Report,
+) -> String {
+    let letter = DeadLetter {
+        id: Uuid::new_v4().to_string(),
+        transition: transition.clone(),
+        action: action.clone(),
+        // Keeping the span would keep the trace of the frame open until the letter is dropped
+        context: EventContext {
+            span: Span::none(),
+            ..context.clone()
+        },
+        error: format!("{:#}", error),
+        failed_at: Utc::now(),
+        replays: 0,
+    };
+    let id = letter.id.clone();
+    push(letter);
+    id
+}
+
+fn push(letter: DeadLetter) {
+    let mut letters = DEAD_LETTERS.lock().unwrap();
+    if letters.len() >= CAPACITY {
+        if let Some(dropped) = letters.pop_front() {
+            warn!(
+                "Dropping failed execution {} of event {}",
+                dropped.id, dropped.context.event_id
+            );
+        }
+    }
+    letters.push_back(letter);
+    DEAD_LETTERS_GAUGE.set(letters.len() as i64);
+}
+
+/// Failed executions, oldest first.
+pub fn list() -> serde_json::Value {
+    let letters = DEAD_LETTERS.lock().unwrap();
+    serde_json::Value::Array(letters.iter().map(DeadLetter::to_json).collect())
+}
+
+/// Executes the action again with the context of the original event. The execution is removed
+/// once it succeeds, otherwise it's kept with the new error.
+///
+/// Returns `None` when there is no failed execution with the identifier.
+pub async fn replay(id: &str) -> Option<Result<()>> {
+    let mut letter = {
+        let mut letters = DEAD_LETTERS.lock().unwrap();
+        let position = letters.iter().position(|letter| letter.id == id)?;
+        let letter = letters.remove(position)?;
+        DEAD_LETTERS_GAUGE.set(letters.len() as i64);
+        letter
+    };
+
+    info!(
+        "Replaying action of transition {} for event {}",
+        letter.transition, letter.context.event_id
+    );
+    let result = execute_action(&mut letter.action, &letter.transition, &letter.context).await;
+    if let Err(err) = &result {
+        letter.error = format!("{:#}", err);
+        letter.failed_at = Utc::now();
+        letter.replays += 1;
+        push(letter);
+    }
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_stream::Detection;
+    use hawkeye_core::models::{FakeAction, VideoMode};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn replay_failed_execution() {
+        let called = Arc::new(AtomicBool::new(false));
+        let action = Action::FakeAction(FakeAction {
+            called: called.clone(),
+            execute_returns: Some(Ok(())),
+        });
+        let context = EventContext::new("watcher-id", None, Detection::now(VideoMode::Slate));
+        let id = record(
+            &Transition::new(VideoMode::Content, VideoMode::Slate),
+            &action,
+            &context,
+            &color_eyre::eyre::eyre!("Service unavailable"),
+        );
+        let letters = list();
+        let letter = letters
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|letter| letter["id"] == id.as_str())
+            .unwrap();
+        assert_eq!(letter["event_id"], context.event_id.as_str());
+        assert_eq!(letter["error"], "Service unavailable");
+
+        assert!(replay(&id).await.unwrap().is_ok());
+        assert!(called.load(Ordering::SeqCst));
+        // Removed after succeeding
+        assert!(replay(&id).await.is_none());
+    }
+}
@@ -0,0 +1,91 @@
+use hawkeye_core::models::{Debounce, VideoMode};
+use std::collections::VecDeque;
+
+/// Filters out video modes that were not found in enough of the most recent frames.
+pub struct Debouncer {
+    frames: usize,
+    window: usize,
+    recent: VecDeque<VideoMode>,
+}
+
+impl Debouncer {
+    /// Creates a debouncer based on the settings, without settings every mode is reported.
+    pub fn new(settings: Option<Debounce>) -> Self {
+        let (frames, window) = settings
+            .map(|d| (d.frames as usize, d.window() as usize))
+            .unwrap_or((1, 1));
+        Self {
+            frames,
+            window,
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Records the video mode found in a frame, returning it when it was found in enough of the
+    /// recent frames to be reported.
+    pub fn observe(&mut self, mode: VideoMode) -> Option<VideoMode> {
+        if self.recent.len() == self.window {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(mode);
+
+        let found = self.recent.iter().filter(|m| **m == mode).count();
+        if found >= self.frames {
+            Some(mode)
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn without_settings_reports_every_mode() {
+        let mut debouncer = Debouncer::new(None);
+        assert_eq!(
+            debouncer.observe(VideoMode::Content),
+            Some(VideoMode::Content)
+        );
+        assert_eq!(debouncer.observe(VideoMode::Slate), Some(VideoMode::Slate));
+        assert_eq!(
+            debouncer.observe(VideoMode::Content),
+            Some(VideoMode::Content)
+        );
+    }
+
+    #[test]
+    fn consecutive_frames_required() {
+        let mut debouncer = Debouncer::new(Some(Debounce {
+            frames: 3,
+            window: None,
+        }));
+        assert_eq!(debouncer.observe(VideoMode::Content), None);
+        assert_eq!(debouncer.observe(VideoMode::Content), None);
+        assert_eq!(
+            debouncer.observe(VideoMode::Content),
+            Some(VideoMode::Content)
+        );
+        // A single noisy frame is ignored
+        assert_eq!(debouncer.observe(VideoMode::Slate), None);
+        assert_eq!(debouncer.observe(VideoMode::Content), None);
+        assert_eq!(debouncer.observe(VideoMode::Slate), None);
+        assert_eq!(debouncer.observe(VideoMode::Slate), None);
+        assert_eq!(debouncer.observe(VideoMode::Slate), Some(VideoMode::Slate));
+    }
+
+    #[test]
+    fn majority_of_window_required() {
+        let mut debouncer = Debouncer::new(Some(Debounce {
+            frames: 3,
+            window: Some(4),
+        }));
+        assert_eq!(debouncer.observe(VideoMode::Slate), None);
+        assert_eq!(debouncer.observe(VideoMode::Content), None);
+        assert_eq!(debouncer.observe(VideoMode::Slate), None);
+        assert_eq!(debouncer.observe(VideoMode::Slate), Some(VideoMode::Slate));
+        assert_eq!(debouncer.observe(VideoMode::Content), None);
+    }
+}
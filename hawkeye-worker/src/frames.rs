@@ -0,0 +1,124 @@
+//! The latest frames analysed by the worker, to see what the detectors saw around a transition.
+use crate::img_detector::SlateMatch;
+use chrono::{DateTime, Utc};
+use hawkeye_core::models::VideoMode;
+use lazy_static::lazy_static;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+const FRAME_HISTORY_ENV: &str = "HAWKEYE_FRAME_HISTORY";
+
+lazy_static! {
+    // Number of frames kept, the oldest are dropped first.
+    static ref CAPACITY: usize = std::env::var(FRAME_HISTORY_ENV)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(30);
+
+    static ref FRAMES: Mutex<VecDeque<Frame>> = Mutex::new(VecDeque::new());
+}
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A frame and the result of its detection.
+struct Frame {
+    id: u64,
+    analysed_at: DateTime<Utc>,
+    mode: VideoMode,
+    /// Mode reported to the actions, frames before it might have been ignored by the debouncer.
+    reported_mode: Option<VideoMode>,
+    slate: Option<SlateMatch>,
+    image: Vec<u8>,
+}
+
+impl Frame {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "id": self.id,
+            "analysed_at": self.analysed_at.to_rfc3339(),
+            "mode": self.mode,
+            "reported_mode": self.reported_mode,
+            "slate_url": self.slate.as_ref().map(|slate| slate.slate_url.as_str()),
+            "similarity": self.slate.as_ref().map(|slate| slate.dssim as f64 / 1000f64),
+            "image": format!("/frames/{}", self.id),
+        })
+    }
+}
+
+/// If the frames are kept at all, so they are only copied when needed.
+pub fn enabled() -> bool {
+    *CAPACITY > 0
+}
+
+/// Keeps the analysed frame, a PNG image.
+pub fn record(
+    image: Vec<u8>,
+    mode: VideoMode,
+    reported_mode: Option<VideoMode>,
+    slate: Option<&SlateMatch>,
+) {
+    let frame = Frame {
+        id: NEXT_ID.fetch_add(1, Ordering::SeqCst),
+        analysed_at: Utc::now(),
+        mode,
+        reported_mode,
+        slate: slate.cloned(),
+        image,
+    };
+    let mut frames = FRAMES.lock().unwrap();
+    while frames.len() >= *CAPACITY {
+        frames.pop_front();
+    }
+    frames.push_back(frame);
+}
+
+/// Details of the kept frames, oldest first.
+pub fn list() -> serde_json::Value {
+    let frames = FRAMES.lock().unwrap();
+    serde_json::Value::Array(frames.iter().map(Frame::to_json).collect())
+}
+
+/// PNG image of the frame, if it's still kept.
+pub fn image(id: u64) -> Option<Vec<u8>> {
+    let frames = FRAMES.lock().unwrap();
+    frames
+        .iter()
+        .find(|frame| frame.id == id)
+        .map(|frame| frame.image.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keep_latest_frames() {
+        let slate = SlateMatch {
+            slate_url: "https://example.com/slate.jpg".to_string(),
+            dssim: 12,
+        };
+        for _ in 0..=*CAPACITY {
+            record(vec![1, 2, 3], VideoMode::Content, None, None);
+        }
+        record(
+            vec![4, 5, 6],
+            VideoMode::Slate,
+            Some(VideoMode::Slate),
+            Some(&slate),
+        );
+
+        let frames = list();
+        let frames = frames.as_array().unwrap();
+        assert_eq!(frames.len(), *CAPACITY);
+        let latest = frames.last().unwrap();
+        assert_eq!(latest["mode"], "slate");
+        assert_eq!(latest["reported_mode"], "slate");
+        assert_eq!(latest["slate_url"], "https://example.com/slate.jpg");
+        assert_eq!(latest["similarity"], 0.012);
+        assert_eq!(image(latest["id"].as_u64().unwrap()), Some(vec![4, 5, 6]));
+        let oldest = frames[0]["id"].as_u64().unwrap();
+        assert_eq!(image(oldest - 1), None);
+    }
+}
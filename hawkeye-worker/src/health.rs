@@ -0,0 +1,114 @@
+//! State of the worker checked by the liveness and readiness probes of Kubernetes.
+use lazy_static::lazy_static;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const FRAMES_TIMEOUT_ENV: &str = "HAWKEYE_FRAMES_TIMEOUT";
+
+lazy_static! {
+    // Seconds without frames, after the first one, before the worker is considered stuck.
+    static ref FRAMES_TIMEOUT: Duration = Duration::from_secs(
+        std::env::var(FRAMES_TIMEOUT_ENV)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(60)
+    );
+
+    static ref LAST_FRAME: Mutex<Option<Instant>> = Mutex::new(None);
+}
+
+static PIPELINE_PLAYING: AtomicBool = AtomicBool::new(false);
+static ACTIONS_RUNTIMES: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_pipeline_playing(playing: bool) {
+    PIPELINE_PLAYING.store(playing, Ordering::SeqCst);
+}
+
+pub fn frame_received() {
+    *LAST_FRAME.lock().unwrap() = Some(Instant::now());
+}
+
+/// Marks the actions runtime alive until dropped, even when its thread panics.
+pub struct ActionsRuntimeAlive;
+
+impl ActionsRuntimeAlive {
+    pub fn mark() -> Self {
+        ACTIONS_RUNTIMES.fetch_add(1, Ordering::SeqCst);
+        Self
+    }
+}
+
+impl Drop for ActionsRuntimeAlive {
+    fn drop(&mut self) {
+        ACTIONS_RUNTIMES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Result of a probe, with the checks it's made of.
+pub struct Probe {
+    pub healthy: bool,
+    pub checks: serde_json::Value,
+}
+
+/// Frames stopped flowing. There are no frames until the stream is sent to the worker, which is
+/// not a reason to restart it.
+fn frames_stalled() -> bool {
+    match *LAST_FRAME.lock().unwrap() {
+        Some(last_frame) => last_frame.elapsed() > *FRAMES_TIMEOUT,
+        None => false,
+    }
+}
+
+/// The worker is stuck and should be restarted unless this succeeds.
+pub fn liveness() -> Probe {
+    let actions_runtime = ACTIONS_RUNTIMES.load(Ordering::SeqCst) > 0;
+    let frames_flowing = !frames_stalled();
+    Probe {
+        healthy: actions_runtime && frames_flowing,
+        checks: json!({
+            "actions_runtime": actions_runtime,
+            "frames_flowing": frames_flowing,
+        }),
+    }
+}
+
+/// The worker can receive the stream. Frames are not required, they only arrive once the worker
+/// is ready.
+pub fn readiness() -> Probe {
+    let pipeline_playing = PIPELINE_PLAYING.load(Ordering::SeqCst);
+    let actions_runtime = ACTIONS_RUNTIMES.load(Ordering::SeqCst) > 0;
+    Probe {
+        healthy: pipeline_playing && actions_runtime,
+        checks: json!({
+            "pipeline_playing": pipeline_playing,
+            "actions_runtime": actions_runtime,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn probes_follow_the_worker_state() {
+        assert_eq!(readiness().checks["pipeline_playing"], false);
+        set_pipeline_playing(true);
+        assert_eq!(readiness().checks["pipeline_playing"], true);
+
+        let alive = ActionsRuntimeAlive::mark();
+        assert!(readiness().healthy);
+        drop(alive);
+
+        // Waiting for the stream
+        assert_eq!(liveness().checks["frames_flowing"], true);
+        frame_received();
+        assert_eq!(liveness().checks["frames_flowing"], true);
+        *LAST_FRAME.lock().unwrap() = Instant::now().checked_sub(*FRAMES_TIMEOUT * 2);
+        let probe = liveness();
+        assert!(!probe.healthy);
+        assert_eq!(probe.checks["frames_flowing"], false);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::actions::{EventContext, Transition};
+use chrono::{DateTime, Utc};
+use hawkeye_core::models::Action;
+use lazy_static::lazy_static;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Maximum number of executions kept, the oldest are dropped first.
+const CAPACITY: usize = 1000;
+
+lazy_static! {
+    static ref HISTORY: Mutex<VecDeque<Execution>> = Mutex::new(VecDeque::new());
+}
+
+/// An execution of an action, successful or not.
+struct Execution {
+    executed_at: DateTime<Utc>,
+    transition: String,
+    action: Option<String>,
+    target: String,
+    event_id: String,
+    error: Option<String>,
+    latency: Duration,
+    dry_run: bool,
+}
+
+impl Execution {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "executed_at": self.executed_at.to_rfc3339(),
+            "transition": self.transition,
+            "action": self.action,
+            "target": self.target,
+            "event_id": self.event_id,
+            "result": match (self.dry_run, &self.error) {
+                (true, _) => "dry_run",
+                (false, None) => "success",
+                (false, Some(_)) => "error",
+            },
+            "error": self.error,
+            "latency_ms": self.latency.as_millis() as u64,
+        })
+    }
+}
+
+/// System the action calls. Query strings and URLs of brokers are left out, they might contain
+/// credentials.
+pub fn target(action: &Action) -> String {
+    match action {
+        Action::HttpCall(call) => format!(
+            "{} {}",
+            call.method.to_string(),
+            call.url.split('?').next().unwrap_or_default()
+        ),
+        Action::SnsPublish(sns) => sns.topic_arn.clone(),
+        Action::EventBridgePut(put) => put.event_bus.clone(),
+        Action::ChatNotification(chat) => format!("{:?}", chat.platform).to_lowercase(),
+        Action::MqttPublish(mqtt) => mqtt.topic.clone(),
+        Action::RedisPublish(redis) => redis.key.clone(),
+        Action::FakeAction(_) => "fake".to_string(),
+    }
+}
+
+/// Keeps the execution of the action, started `latency` ago. Executions of a dry run only logged
+/// the action.
+pub fn record(
+    transition: &Transition,
+    action: &Action,
+    context: &EventContext,
+    error: Option<&color_eyre::Report>,
+    latency: Duration,
+    dry_run: bool,
+) {
+    let execution = Execution {
+        executed_at: Utc::now(),
+        transition: transition.to_string(),
+        action: action.description().map(String::from),
+        target: target(action),
+        event_id: context.event_id.clone(),
+        error: error.map(|err| format!("{:#}", err)),
+        latency,
+        dry_run,
+    };
+    let mut history = HISTORY.lock().unwrap();
+    if history.len() >= CAPACITY {
+        history.pop_front();
+    }
+    history.push_back(execution);
+}
+
+/// Executions of the actions, oldest first.
+pub fn list() -> serde_json::Value {
+    let history = HISTORY.lock().unwrap();
+    serde_json::Value::Array(history.iter().map(Execution::to_json).collect())
+}
+
+/// Latest execution of each action of each transition, most recent first.
+pub fn latest() -> serde_json::Value {
+    let history = HISTORY.lock().unwrap();
+    let mut seen = Vec::new();
+    let mut latest = Vec::new();
+    for execution in history.iter().rev() {
+        let key = (&execution.transition, &execution.action, &execution.target);
+        if !seen.contains(&key) {
+            seen.push(key);
+            latest.push(execution.to_json());
+        }
+    }
+    serde_json::Value::Array(latest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::video_stream::Detection;
+    use hawkeye_core::models::{HttpCall, HttpMethod, VideoMode};
+
+    #[test]
+    fn record_executions() {
+        let action = Action::HttpCall(HttpCall {
+            method: HttpMethod::POST,
+            url: "https://ads.example.com/break?token=secret".to_string(),
+            description: Some("Ad break".to_string()),
+            condition: None,
+            delay: None,
+            abort_on_failure: None,
+            authorization: None,
+            headers: None,
+            body: None,
+            retries: None,
+            retry: None,
+            expected_status: None,
+            expected_body: None,
+            timeout: None,
+            client_certificate: None,
+            proxy: None,
+        });
+        let transition = Transition::new(VideoMode::Content, VideoMode::Slate);
+        let context = EventContext::new("watcher-id", None, Detection::now(VideoMode::Slate));
+        record(
+            &transition,
+            &action,
+            &context,
+            Some(&color_eyre::eyre::eyre!("Service unavailable")),
+            Duration::from_millis(120),
+            false,
+        );
+
+        let history = list();
+        let execution = history
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|execution| execution["event_id"] == context.event_id.as_str())
+            .unwrap();
+        assert_eq!(execution["action"], "Ad break");
+        assert_eq!(execution["target"], "POST https://ads.example.com/break");
+        assert_eq!(execution["result"], "error");
+        assert_eq!(execution["error"], "Service unavailable");
+        assert_eq!(execution["latency_ms"], 120);
+    }
+}
@@ -0,0 +1,248 @@
+//! HTTP client of the actions and the slates, shared by all of them so connections to the same
+//! systems are kept alive and reused. HTTP/2 is negotiated with the servers that support it.
+use crate::metrics::{HTTP_CONNECTIONS_OPENED, HTTP_REQUESTS};
+use crate::oauth::{self, ClientCredentials};
+use color_eyre::{eyre::eyre, Result};
+use hawkeye_core::models::{HttpAuth, HttpProxy};
+use hyper::client::connect::dns::Name;
+use lazy_static::lazy_static;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::{Client, ClientBuilder, Identity, NoProxy, Proxy, RequestBuilder};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::timeout;
+
+const CONNECT_TIMEOUT_ENV: &str = "HAWKEYE_HTTP_CONNECT_TIMEOUT";
+const READ_TIMEOUT_ENV: &str = "HAWKEYE_HTTP_READ_TIMEOUT";
+const POOL_IDLE_TIMEOUT_ENV: &str = "HAWKEYE_HTTP_POOL_IDLE_TIMEOUT";
+
+lazy_static! {
+    // Milliseconds to wait for a connection to be established.
+    static ref CONNECT_TIMEOUT: Duration = Duration::from_millis(env_u64(CONNECT_TIMEOUT_ENV).unwrap_or(500));
+
+    // Seconds to wait for the server to answer, or to send the next part of the body.
+    static ref READ_TIMEOUT: Duration = Duration::from_secs(env_u64(READ_TIMEOUT_ENV).unwrap_or(30));
+
+    // Seconds an unused connection is kept open.
+    static ref POOL_IDLE_TIMEOUT: Duration = Duration::from_secs(env_u64(POOL_IDLE_TIMEOUT_ENV).unwrap_or(90));
+
+    pub static ref CLIENT: Client = builder().build().expect("Could not build the HTTP client");
+
+    // Clients with settings of their own.
+    static ref CLIENTS: Mutex<HashMap<ClientSettings, Client>> = Mutex::new(HashMap::new());
+}
+
+fn builder() -> ClientBuilder {
+    Client::builder()
+        .connect_timeout(*CONNECT_TIMEOUT)
+        .pool_idle_timeout(*POOL_IDLE_TIMEOUT)
+        .dns_resolver(Arc::new(CountingResolver))
+}
+
+/// Settings of the requests of an action that require a client of their own.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ClientSettings {
+    /// PEM of the private key and certificate presented to servers requiring mutual TLS.
+    pub identity: Option<String>,
+    pub proxy: Option<HttpProxy>,
+}
+
+/// Client for the settings, the shared client unless they differ from the defaults. Clients are
+/// kept, so their connections are reused as well.
+///
+/// Without a proxy in the settings, the `HTTPS_PROXY`, `HTTP_PROXY` and `NO_PROXY` environment
+/// variables of the worker apply.
+pub fn client(settings: &ClientSettings) -> Result<Client> {
+    if *settings == ClientSettings::default() {
+        return Ok(CLIENT.clone());
+    }
+    let mut clients = CLIENTS.lock().unwrap();
+    if let Some(client) = clients.get(settings) {
+        return Ok(client.clone());
+    }
+    let mut builder = builder();
+    if let Some(pem) = &settings.identity {
+        let identity = Identity::from_pem(pem.as_bytes())
+            .map_err(|err| eyre!("Invalid client certificate: {}", err))?;
+        builder = builder.identity(identity);
+    }
+    if let Some(proxy) = &settings.proxy {
+        let no_proxy = proxy
+            .no_proxy
+            .as_ref()
+            .and_then(|hosts| NoProxy::from_string(&hosts.join(",")));
+        let proxy = Proxy::all(proxy.url.as_str())
+            .map_err(|err| eyre!("Invalid proxy {}: {}", proxy.url, err))?
+            .no_proxy(no_proxy);
+        builder = builder.proxy(proxy);
+    }
+    let client = builder.build()?;
+    clients.insert(settings.clone(), client.clone());
+    Ok(client)
+}
+
+/// A client with the default settings, not shared, for the requests sent on a runtime of their
+/// own, like the downloads of the slates: connections can't be reused once their runtime is
+/// dropped.
+pub fn standalone_client() -> Result<Client> {
+    Ok(builder().build()?)
+}
+
+fn env_u64(name: &str) -> Option<u64> {
+    std::env::var(name)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+/// Resolves the names with the system resolver. Names are only resolved when opening a new
+/// connection, which is counted to tell how often connections are reused. Connections to IP
+/// addresses are not resolved, so they are not counted.
+struct CountingResolver;
+
+impl Resolve for CountingResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        HTTP_CONNECTIONS_OPENED
+            .with_label_values(&[name.as_str()])
+            .inc();
+        // The port is set by the connector
+        let host = format!("{}:0", name.as_str());
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host(host).await?;
+            Ok(Box::new(addrs) as Addrs)
+        })
+    }
+}
+
+/// Sends the request with the client it was built from, returns the status code and body of the
+/// response.
+///
+/// The server has `HAWKEYE_HTTP_READ_TIMEOUT` to answer, or the timeout of the request when it's
+/// longer.
+pub async fn send(request: RequestBuilder) -> Result<(u16, String)> {
+    let (client, request) = request.build_split();
+    let request = request?;
+    HTTP_REQUESTS
+        .with_label_values(&[request.url().host_str().unwrap_or_default()])
+        .inc();
+
+    let read_timeout = read_timeout(request.timeout());
+    let timed_out = |_| eyre!("No response after {}s", read_timeout.as_secs());
+    let mut response = timeout(read_timeout, client.execute(request))
+        .await
+        .map_err(timed_out)??;
+    let status = response.status().as_u16();
+    let mut body = Vec::new();
+    while let Some(chunk) = timeout(read_timeout, response.chunk())
+        .await
+        .map_err(timed_out)??
+    {
+        body.extend_from_slice(&chunk);
+    }
+    Ok((status, String::from_utf8_lossy(&body).into_owned()))
+}
+
+/// Time to wait for the server to answer a request with the `request_timeout`, so it's not cut
+/// short by `HAWKEYE_HTTP_READ_TIMEOUT`.
+fn read_timeout(request_timeout: Option<&Duration>) -> Duration {
+    match request_timeout {
+        Some(request_timeout) => (*request_timeout).max(*READ_TIMEOUT),
+        None => *READ_TIMEOUT,
+    }
+}
+
+/// Adds the credentials to the request, fetching an access token for OAuth2 with the `client`.
+pub async fn authorize(
+    client: &Client,
+    request: RequestBuilder,
+    authorization: &HttpAuth,
+) -> Result<RequestBuilder> {
+    let request = match authorization {
+        HttpAuth::Basic { username, password } => {
+            request.basic_auth(username, Some(password.resolve()?))
+        }
+        HttpAuth::Bearer { token } => request.bearer_auth(token.resolve()?),
+        HttpAuth::ApiKey { header, value } => request.header(header.as_str(), value.resolve()?),
+        HttpAuth::OAuth2 {
+            token_url,
+            client_id,
+            client_secret,
+            scopes,
+        } => {
+            let client_secret = client_secret.resolve()?;
+            let token = oauth::access_token(
+                client,
+                &ClientCredentials {
+                    token_url,
+                    client_id,
+                    client_secret: &client_secret,
+                    scopes: scopes.as_deref(),
+                },
+            )
+            .await?;
+            request.bearer_auth(token)
+        }
+    };
+    Ok(request)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::{mock, server_url, Matcher};
+
+    #[tokio::test]
+    async fn send_request_with_shared_client() {
+        let server = mock("GET", "/shared-client")
+            .with_status(202)
+            .with_body("accepted")
+            .create();
+
+        let response = send(CLIENT.get(format!("{}/shared-client", server_url())))
+            .await
+            .unwrap();
+        assert_eq!(response, (202, "accepted".to_string()));
+        assert!(HTTP_REQUESTS.with_label_values(&["127.0.0.1"]).get() >= 1);
+        server.assert();
+    }
+
+    #[tokio::test]
+    async fn send_request_through_proxy() {
+        let server = mock("GET", Matcher::Any)
+            .match_header("host", "api.hawkeye.invalid")
+            .with_status(200)
+            .create();
+
+        let client = client(&ClientSettings {
+            identity: None,
+            proxy: Some(HttpProxy {
+                url: server_url(),
+                no_proxy: Some(vec!["internal.hawkeye.invalid".to_string()]),
+            }),
+        })
+        .unwrap();
+        let response = send(client.get("http://api.hawkeye.invalid/proxied"))
+            .await
+            .unwrap();
+        assert_eq!(response.0, 200);
+        server.assert();
+    }
+
+    #[test]
+    fn keep_longer_request_timeouts() {
+        assert_eq!(read_timeout(None), *READ_TIMEOUT);
+        assert_eq!(read_timeout(Some(&Duration::from_secs(1))), *READ_TIMEOUT);
+        let longer = *READ_TIMEOUT + Duration::from_secs(60);
+        assert_eq!(read_timeout(Some(&longer)), longer);
+    }
+
+    #[test]
+    fn reject_invalid_client_certificate() {
+        let pem = "-----BEGIN CERTIFICATE-----\nnot a certificate\n-----END CERTIFICATE-----\n";
+        let settings = ClientSettings {
+            identity: Some(pem.to_string()),
+            proxy: None,
+        };
+        assert!(client(&settings).is_err());
+    }
+}
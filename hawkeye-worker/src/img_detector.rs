@@ -1,35 +1,324 @@
+use crate::metrics::{
+    SLATE_PREFILTER_HIT_COUNTER, SLATE_PREFILTER_MISS_COUNTER, SLATE_SIMILARITY, SLATE_THRESHOLD,
+    SLATE_THRESHOLD_RECOMMENDED,
+};
+use crate::threshold_learning::ThresholdLearner;
 use color_eyre::Result;
 use dssim::{DssimImage, ToRGBAPLU, RGBAPLU};
+use hawkeye_core::models::{Region, ThresholdLearning};
 use imgref::{Img, ImgVec};
 use load_image::{Image, ImageData};
+use log::info;
+use prometheus::Gauge;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+/// Maximum number of different bits between the average hashes of the slate and a frame for the
+/// frame to still be compared using DSSIM.
+const PREFILTER_MAX_DISTANCE: u32 = 12;
+
+/// Default maximum DSSIM score (multiplied by 1000) of a frame matching the slate.
+pub const DEFAULT_SLATE_THRESHOLD: u32 = 900;
+
+/// A reference image of the slate.
+struct Slate {
+    id: String,
+    image: DssimImage<f32>,
+    hash: u64,
+    /// Pixels of the slate that are fully transparent, excluded from the comparison.
+    mask: Option<Vec<bool>>,
+    threshold: Cell<u32>,
+    learner: Option<RefCell<ThresholdLearner>>,
+    similarity: Gauge,
+}
+
+impl Slate {
+    fn set_threshold(&self, threshold: u32) {
+        self.threshold.set(threshold);
+        SLATE_THRESHOLD
+            .with_label_values(&[self.id.as_str()])
+            .set(threshold as f64);
+    }
+}
+
+/// How similar a frame is to one of the slate images.
+#[derive(Copy, Clone, Debug)]
+pub struct Score {
+    /// DSSIM score multiplied by 1000, zero means the images are identical.
+    pub dssim: u32,
+    /// Number of different bits between the average hashes of the images.
+    pub hash_distance: u32,
+}
+
+impl Score {
+    /// Check if the frame would be compared with DSSIM or rejected by the prefilter.
+    pub fn passes_prefilter(&self) -> bool {
+        self.hash_distance <= PREFILTER_MAX_DISTANCE
+    }
+}
+
+/// The slate image matching a frame.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SlateMatch {
+    /// URL of the slate image, without the query string.
+    pub slate_url: String,
+    /// DSSIM score multiplied by 1000.
+    pub dssim: u32,
+}
 
 pub struct SlateDetector {
-    slate: DssimImage<f32>,
+    slates: Vec<Slate>,
     similarity_algorithm: dssim::Dssim,
+    region: Option<Region>,
+    learning: Option<ThresholdLearning>,
 }
 
 impl SlateDetector {
-    pub fn new(slate: &[u8]) -> Result<Self> {
+    /// Creates a detector for the slate images, identified by their URLs, a frame matching any
+    /// of them is a slate. The comparison is restricted to the `region` of the images when
+    /// provided.
+    pub fn new(slates: &[(String, Vec<u8>)], region: Option<Region>) -> Result<Self> {
         let similarity_algorithm = dssim::Dssim::new();
-        let slate_img = load_data(slate)?;
-        let slate = similarity_algorithm.create_image(&slate_img).unwrap();
+        let slates = slates
+            .iter()
+            .map(|(url, slate)| {
+                let slate_img = crop(load_data(slate)?, region.as_ref());
+                let slate = Slate {
+                    id: url.clone(),
+                    hash: average_hash(&slate_img),
+                    mask: transparency_mask(&slate_img),
+                    image: similarity_algorithm.create_image(&slate_img).unwrap(),
+                    threshold: Cell::new(DEFAULT_SLATE_THRESHOLD),
+                    learner: None,
+                    similarity: SLATE_SIMILARITY.with_label_values(&[url.as_str()]),
+                };
+                slate.set_threshold(DEFAULT_SLATE_THRESHOLD);
+                Ok(slate)
+            })
+            .collect::<Result<Vec<_>>>()?;
 
         Ok(Self {
-            slate,
+            slates,
             similarity_algorithm,
+            region,
+            learning: None,
         })
     }
 
+    /// Maximum DSSIM score (multiplied by 1000) of a frame matching the slate.
+    pub fn with_threshold(self, threshold: u32) -> Self {
+        for slate in self.slates.iter() {
+            slate.set_threshold(threshold);
+        }
+        self
+    }
+
+    /// Learns the threshold of each slate image from the frames seen during the learning window.
+    ///
+    /// While learning, all frames are compared with DSSIM so the scores of the content are known,
+    /// even if they are rejected by the prefilter.
+    pub fn with_threshold_learning(mut self, learning: Option<ThresholdLearning>) -> Self {
+        for slate in self.slates.iter_mut() {
+            slate.learner = learning.map(|learning| {
+                RefCell::new(ThresholdLearner::new(
+                    slate.id.as_str(),
+                    Duration::from_secs(learning.window as u64),
+                ))
+            });
+        }
+        self.learning = learning;
+        self
+    }
+
     pub fn is_match(&self, image_buffer: &[u8]) -> bool {
-        let frame_img = load_data(image_buffer).unwrap();
-        let frame = self.similarity_algorithm.create_image(&frame_img).unwrap();
+        self.find_match(image_buffer).is_some()
+    }
+
+    /// Finds the first slate image matching the frame. The frame is compared with every slate
+    /// image, even after a match, so the similarity of each of them is up to date.
+    pub fn find_match(&self, image_buffer: &[u8]) -> Option<SlateMatch> {
+        let frame_img = crop(load_data(image_buffer).unwrap(), self.region.as_ref());
+        let frame_hash = average_hash(&frame_img);
+        let mut frame = None;
+
+        let matches: Vec<_> = self
+            .slates
+            .iter()
+            .map(|slate| match &slate.mask {
+                None => self.slate_match(slate, &frame_img, frame_hash, &mut frame),
+                Some(mask) => {
+                    let masked_img = apply_mask(&frame_img, mask);
+                    let masked_hash = average_hash(&masked_img);
+                    self.slate_match(slate, &masked_img, masked_hash, &mut None)
+                }
+            })
+            .collect();
+        self.slates.iter().zip(matches).find_map(|(slate, dssim)| {
+            Some(SlateMatch {
+                slate_url: slate.id.clone(),
+                dssim: dssim?,
+            })
+        })
+    }
+
+    /// Scores the frame against every slate image, regardless of the prefilter.
+    pub fn scores(&self, image_buffer: &[u8]) -> Result<Vec<Score>> {
+        let frame_img = crop(load_data(image_buffer)?, self.region.as_ref());
+
+        Ok(self
+            .slates
+            .iter()
+            .map(|slate| {
+                let masked_img;
+                let frame_img = match &slate.mask {
+                    None => &frame_img,
+                    Some(mask) => {
+                        masked_img = apply_mask(&frame_img, mask);
+                        &masked_img
+                    }
+                };
+                let frame = self.similarity_algorithm.create_image(frame_img).unwrap();
+                Score {
+                    dssim: self.dssim(slate, &frame),
+                    hash_distance: (slate.hash ^ average_hash(frame_img)).count_ones(),
+                }
+            })
+            .collect())
+    }
+
+    /// Compares the frame with one of the slate images, returning the DSSIM score when they match.
+    /// `frame` caches the frame prepared for DSSIM so it's only created when needed.
+    fn slate_match(
+        &self,
+        slate: &Slate,
+        frame_img: &ImgVec<RGBAPLU>,
+        frame_hash: u64,
+        frame: &mut Option<DssimImage<f32>>,
+    ) -> Option<u32> {
+        let learner = slate
+            .learner
+            .as_ref()
+            .filter(|learner| learner.borrow().is_learning());
+
+        // Cheap check to skip DSSIM for frames that are clearly not the slate
+        let passes_prefilter = (slate.hash ^ frame_hash).count_ones() <= PREFILTER_MAX_DISTANCE;
+        if passes_prefilter {
+            SLATE_PREFILTER_MISS_COUNTER.inc();
+        } else {
+            SLATE_PREFILTER_HIT_COUNTER.inc();
+            if learner.is_none() {
+                // Not compared, rather than keeping the score of an earlier frame
+                slate.similarity.set(f64::NAN);
+                return None;
+            }
+        }
+
+        let frame =
+            frame.get_or_insert_with(|| self.similarity_algorithm.create_image(frame_img).unwrap());
+        let val = self.dssim(slate, frame);
+        slate.similarity.set(val as f64 / 1000f64);
+        let is_match = passes_prefilter && val <= slate.threshold.get();
+
+        if let Some(learner) = learner {
+            if let Some(threshold) = learner.borrow_mut().observe(val, is_match) {
+                self.learned_threshold(slate, threshold);
+            }
+        }
+        if is_match {
+            Some(val)
+        } else {
+            None
+        }
+    }
+
+    fn learned_threshold(&self, slate: &Slate, threshold: u32) {
+        SLATE_THRESHOLD_RECOMMENDED
+            .with_label_values(&[slate.id.as_str()])
+            .set(threshold as f64);
+        if let Some(learning) = self.learning.filter(|l| l.adjust.unwrap_or(false)) {
+            let threshold = learning.bound(threshold);
+            info!("Using threshold {} for slate {}", threshold, slate.id);
+            slate.set_threshold(threshold);
+        }
+    }
 
-        let (res, _) = self.similarity_algorithm.compare(&self.slate, frame);
+    fn dssim(&self, slate: &Slate, frame: &DssimImage<f32>) -> u32 {
+        let (res, _) = self.similarity_algorithm.compare(&slate.image, frame);
         let val: f64 = res.into();
-        let val = (val * 1000f64) as u32;
+        (val * 1000f64) as u32
+    }
+}
+
+/// Crops the image to the region, so things like tickers and clocks outside of it don't
+/// influence the comparison.
+fn crop(img: ImgVec<RGBAPLU>, region: Option<&Region>) -> ImgVec<RGBAPLU> {
+    match region {
+        None => img,
+        Some(region) => {
+            let left = img.width() * region.x as usize / 100;
+            let top = img.height() * region.y as usize / 100;
+            let width = (img.width() * region.width as usize / 100).max(1);
+            let height = (img.height() * region.height as usize / 100).max(1);
+            let cropped = img.sub_image(left, top, width, height);
+            Img::new(cropped.pixels().collect(), width, height)
+        }
+    }
+}
+
+/// Fully transparent pixels of the image, `None` when the image is opaque.
+fn transparency_mask(img: &ImgVec<RGBAPLU>) -> Option<Vec<bool>> {
+    let mask: Vec<bool> = img.as_ref().pixels().map(|pixel| pixel.a == 0f32).collect();
+    if mask.iter().any(|transparent| *transparent) {
+        Some(mask)
+    } else {
+        None
+    }
+}
+
+/// Makes the frame transparent where the slate is, so these pixels are the same in both images
+/// and don't influence the comparison.
+fn apply_mask(img: &ImgVec<RGBAPLU>, mask: &[bool]) -> ImgVec<RGBAPLU> {
+    if mask.len() != img.width() * img.height() {
+        return img.clone();
+    }
+    let pixels = img
+        .as_ref()
+        .pixels()
+        .zip(mask.iter())
+        .map(|(pixel, transparent)| {
+            if *transparent {
+                RGBAPLU::new(0f32, 0f32, 0f32, 0f32)
+            } else {
+                pixel
+            }
+        })
+        .collect();
+    Img::new(pixels, img.width(), img.height())
+}
 
-        val <= 900u32
+/// Average hash of the image: the image is reduced to a 8x8 grid of luminance values and each
+/// bit is set when the cell is brighter than the mean of all cells.
+fn average_hash(img: &ImgVec<RGBAPLU>) -> u64 {
+    let (width, height) = (img.width(), img.height());
+    let mut cells = [0f32; 64];
+    let mut counts = [0u32; 64];
+    for (y, row) in img.rows().enumerate() {
+        for (x, pixel) in row.iter().enumerate() {
+            let cell = (y * 8 / height) * 8 + x * 8 / width;
+            cells[cell] += 0.2126 * pixel.r + 0.7152 * pixel.g + 0.0722 * pixel.b;
+            counts[cell] += 1;
+        }
+    }
+    for (cell, count) in cells.iter_mut().zip(counts.iter()) {
+        *cell /= (*count).max(1) as f32;
     }
+
+    let mean = cells.iter().sum::<f32>() / 64f32;
+    cells
+        .iter()
+        .enumerate()
+        .filter(|(_, cell)| **cell > mean)
+        .fold(0u64, |hash, (bit, _)| hash | 1 << bit)
 }
 
 fn load_data(data: &[u8]) -> Result<ImgVec<RGBAPLU>> {
@@ -53,6 +342,7 @@ fn match_img_bitmap(img: Image) -> ImgVec<RGBAPLU> {
 #[cfg(test)]
 mod test {
     use super::*;
+    use image::ImageBuffer;
     use std::fs::File;
     use std::io::Read;
     use std::path::Path;
@@ -65,6 +355,14 @@ mod test {
         buffer
     }
 
+    fn encode_png(img: image::RgbaImage) -> Vec<u8> {
+        let mut contents = Vec::new();
+        image::DynamicImage::ImageRgba8(img)
+            .write_to(&mut contents, image::ImageFormat::Png)
+            .unwrap();
+        contents
+    }
+
     #[test]
     fn compare_equal_images() {
         let mut slate =
@@ -73,7 +371,7 @@ mod test {
         slate
             .read_to_end(&mut buffer)
             .expect("Failed to write to buffer");
-        let detector = SlateDetector::new(buffer.as_slice()).unwrap();
+        let detector = SlateDetector::new(&[("slate".to_string(), buffer)], None).unwrap();
         let slate_img = read_bytes("../resources/slate_120px.jpg");
 
         assert!(detector.is_match(slate_img.as_slice()));
@@ -87,9 +385,135 @@ mod test {
         slate
             .read_to_end(&mut buffer)
             .expect("Failed to write to buffer");
-        let detector = SlateDetector::new(buffer.as_slice()).unwrap();
+        let detector = SlateDetector::new(&[("slate".to_string(), buffer)], None).unwrap();
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+
+        assert_eq!(detector.is_match(frame_img.as_slice()), false);
+    }
+
+    #[test]
+    fn compare_images_with_slate_variants() {
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        let black_img = read_bytes("../resources/black_120px.jpg");
+        let detector = SlateDetector::new(
+            &[
+                ("black".to_string(), black_img),
+                ("slate".to_string(), slate_img.clone()),
+            ],
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            detector.find_match(slate_img.as_slice()),
+            Some(SlateMatch {
+                slate_url: "slate".to_string(),
+                dssim: 0
+            })
+        );
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+        assert_eq!(detector.is_match(frame_img.as_slice()), false);
+    }
+
+    #[test]
+    fn update_similarity_of_every_slate() {
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        let detector = SlateDetector::new(
+            &[
+                ("similarity-first".to_string(), slate_img.clone()),
+                ("similarity-second".to_string(), slate_img.clone()),
+            ],
+            None,
+        )
+        .unwrap();
+        let similarity = |slate: &str| SLATE_SIMILARITY.with_label_values(&[slate]).get();
+
+        SLATE_SIMILARITY
+            .with_label_values(&["similarity-second"])
+            .set(1.0);
+        assert!(detector.is_match(slate_img.as_slice()));
+        assert_eq!(similarity("similarity-first"), 0.0);
+        assert_eq!(similarity("similarity-second"), 0.0);
+
         let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+        assert!(!detector.is_match(frame_img.as_slice()));
+        assert!(similarity("similarity-first").is_nan());
+        assert!(similarity("similarity-second").is_nan());
+    }
+
+    #[test]
+    fn score_images() {
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        let detector =
+            SlateDetector::new(&[("slate".to_string(), slate_img.clone())], None).unwrap();
+
+        let scores = detector.scores(slate_img.as_slice()).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert_eq!(scores[0].dssim, 0);
+        assert!(scores[0].passes_prefilter());
+
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
+        let scores = detector.scores(frame_img.as_slice()).unwrap();
+        assert!(scores[0].dssim > 0);
+        assert!(!scores[0].passes_prefilter());
+    }
+
+    #[test]
+    fn compare_images_with_transparent_slate_areas() {
+        let slate = image::load_from_memory(&read_bytes("../resources/slate_120px.jpg"))
+            .unwrap()
+            .to_rgba8();
+        let content = image::load_from_memory(&read_bytes("../resources/non-slate_120px.jpg"))
+            .unwrap()
+            .to_rgba8();
+        let (width, height) = slate.dimensions();
+
+        // The bottom half of the slate is transparent, where the frame shows content
+        let masked_slate = ImageBuffer::from_fn(width, height, |x, y| {
+            let mut pixel = *slate.get_pixel(x, y);
+            if y >= height / 2 {
+                pixel.0[3] = 0;
+            }
+            pixel
+        });
+        let frame = ImageBuffer::from_fn(width, height, |x, y| {
+            if y >= height / 2 {
+                *content.get_pixel(x, y)
+            } else {
+                *slate.get_pixel(x, y)
+            }
+        });
 
+        let detector =
+            SlateDetector::new(&[("slate".to_string(), encode_png(masked_slate))], None).unwrap();
+        let scores = detector.scores(encode_png(frame).as_slice()).unwrap();
+        assert_eq!(scores[0].dssim, 0);
+    }
+
+    #[test]
+    fn prefilter_rejects_diff_images() {
+        let slate = load_data(read_bytes("../resources/slate_120px.jpg").as_slice()).unwrap();
+        let frame = load_data(read_bytes("../resources/non-slate_120px.jpg").as_slice()).unwrap();
+
+        assert!(
+            (average_hash(&slate) ^ average_hash(&frame)).count_ones() > PREFILTER_MAX_DISTANCE
+        );
+    }
+
+    #[test]
+    fn compare_diff_images_in_region() {
+        let slate_img = read_bytes("../resources/slate_120px.jpg");
+        let region = Region {
+            x: 25,
+            y: 25,
+            width: 50,
+            height: 50,
+        };
+        let detector =
+            SlateDetector::new(&[("slate".to_string(), slate_img.clone())], Some(region)).unwrap();
+
+        assert!(detector.is_match(slate_img.as_slice()));
+        let frame_img = read_bytes("../resources/non-slate_120px.jpg");
         assert_eq!(detector.is_match(frame_img.as_slice()), false);
     }
 }
@@ -1,26 +1,60 @@
 mod actions;
+mod aws;
+mod bars_detector;
+mod benchmark;
+mod black_detector;
+mod calibration;
+mod circuit_breaker;
+mod clips;
 mod config;
+mod control;
+mod dead_letter;
+mod debounce;
+mod frames;
+mod health;
+mod history;
+mod http;
 mod img_detector;
 mod metrics;
+mod mqtt;
+mod oauth;
 mod slate;
+mod snapshots;
+mod state;
+mod streamgen;
+mod supervisor;
+mod text_detector;
+mod threshold_learning;
+mod transitions;
+mod validation;
 mod video_stream;
+mod watcher_file;
 
 use crate::actions::{ActionExecutor, Executors};
-use crate::config::AppConfig;
-use crate::img_detector::SlateDetector;
+use crate::bars_detector::BarsDetector;
+use crate::black_detector::{BlackDetector, DEFAULT_BLACK_THRESHOLD};
+use crate::config::{AppConfig, Command};
+use crate::debounce::Debouncer;
+use crate::img_detector::{SlateDetector, DEFAULT_SLATE_THRESHOLD};
 use crate::metrics::run_metrics_service;
-use crate::video_stream::{process_frames, VideoStream};
-use color_eyre::Result;
+use crate::text_detector::TextDetector;
+use crate::video_stream::{process_frames, Detectors, VideoStream};
+use color_eyre::{eyre::eyre, Result};
 use crossbeam::channel::unbounded;
 use gstreamer as gst;
+use hawkeye_core::logging;
 use hawkeye_core::models::Watcher;
-use hawkeye_core::utils::maybe_bootstrap_sentry;
+use hawkeye_core::telemetry::maybe_bootstrap_tracing;
+use hawkeye_core::utils::{maybe_bootstrap_sentry, set_sentry_watcher};
 use log::info;
-use std::fs::File;
+use serde_json::json;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
+use std::time::Duration;
 use structopt::StructOpt;
+use tokio::runtime::Builder;
 
 fn main() -> Result<()> {
     color_eyre::install()?;
@@ -28,30 +62,131 @@ fn main() -> Result<()> {
     // `sentry_client` must be in scope in main() to stay alive and functional.
     let sentry_client = maybe_bootstrap_sentry();
     if sentry_client.is_none() {
-        pretty_env_logger::init();
+        hawkeye_core::logging::init();
     }
 
     let config: AppConfig = AppConfig::from_args();
-    let watcher_config = File::open(config.watcher_path)?;
-    let watcher: Watcher = serde_json::from_reader(watcher_config)?;
+    match config.command {
+        Some(Command::TestSlate { slate, frames }) => {
+            gst::init().expect("Could not initialize GStreamer!");
+            return calibration::test_slate(slate.as_str(), frames.as_slice());
+        }
+        Some(Command::Benchmark {
+            slates,
+            slate_threshold,
+            black_threshold,
+            detect_bars,
+            iterations,
+            frames_dir,
+        }) => {
+            gst::init().expect("Could not initialize GStreamer!");
+            let settings = benchmark::BenchmarkSettings {
+                slates,
+                slate_threshold,
+                black_threshold,
+                detect_bars,
+                iterations,
+            };
+            return benchmark::benchmark(settings, &frames_dir);
+        }
+        Some(Command::StreamGen {
+            host,
+            port,
+            video,
+            slate,
+            slate_interval,
+            slate_duration,
+        }) => {
+            gst::init().expect("Could not initialize GStreamer!");
+            return streamgen::stream(streamgen::StreamGenSettings {
+                host,
+                port,
+                video,
+                slate,
+                slate_interval: Duration::from_secs(slate_interval),
+                slate_duration: Duration::from_secs(slate_duration),
+            });
+        }
+        Some(Command::Supervise {
+            address,
+            port,
+            watchers_dir,
+        }) => {
+            return supervisor::supervise(
+                &watchers_dir,
+                SocketAddr::new(address, port),
+                config.dry_run,
+            );
+        }
+        Some(Command::Validate { watcher_path }) => {
+            gst::init().expect("Could not initialize GStreamer!");
+            return validation::validate(&watcher_path);
+        }
+        None => {}
+    }
+
+    // The spans are exported from a runtime of their own, dropped after `tracing`.
+    let telemetry_runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("telemetry")
+        .enable_all()
+        .build()?;
+    // `tracing` must be in scope in main() for the remaining spans to be exported on exit.
+    let _tracing = {
+        let _runtime = telemetry_runtime.enter();
+        maybe_bootstrap_tracing("hawkeye-worker")
+    };
+
+    let watcher_path = config
+        .watcher_path
+        .ok_or_else(|| eyre!("Path to the watcher configuration is required"))?;
+    let mut watcher: Watcher = watcher_file::load(&watcher_path)?;
+    if let Some(port) = config.ingest_port {
+        watcher.source.ingest_port = port;
+    }
     watcher
         .is_valid()
         .expect("Invalid configuration for Watcher");
 
+    set_sentry_watcher(&watcher);
+
+    metrics::configure(watcher.metrics.as_ref())?;
+    if let Some(settings) = watcher.clips.as_ref() {
+        clips::configure(settings, watcher.id.as_deref().unwrap_or_default())?;
+    }
+    if let Some(settings) = watcher.snapshots.as_ref() {
+        snapshots::configure(settings, watcher.id.as_deref().unwrap_or_default())?;
+    }
+
     info!("Initializing GStreamer..");
     gst::init().expect("Could not initialize GStreamer!");
 
     let (sender, receiver) = unbounded();
 
     info!("Loading executors..");
+    let dry_run = config.dry_run || watcher.dry_run.unwrap_or(false);
+    if dry_run {
+        info!("Dry run, the actions will be logged instead of executed");
+    }
     let mut executors: Vec<ActionExecutor> = Vec::new();
     for transition in watcher.transitions.iter() {
-        let mut execs: Executors = transition.clone().into();
-        executors.append(&mut execs.0);
+        let mut transition = transition.clone();
+        transition.cooldown_seconds = transition.cooldown_seconds.or(watcher.cooldown_seconds);
+        let execs: Executors = transition.into();
+        executors.extend(
+            execs
+                .0
+                .into_iter()
+                .map(|executor| executor.with_dry_run(dry_run)),
+        );
     }
 
+    let watcher_id = watcher.id.clone().unwrap_or_default();
+    logging::set_field("watcher_id", json!(watcher_id));
+    let watcher_description = watcher.description.clone();
     thread::spawn(move || {
-        let mut runtime = actions::Runtime::new(receiver, executors);
+        let mut runtime =
+            actions::Runtime::new(watcher_id, watcher_description, receiver, executors);
 
         info!("Starting actions runtime..");
         runtime
@@ -59,29 +194,88 @@ fn main() -> Result<()> {
             .expect("Actions runtime ended unexpectedly!");
     });
 
-    // starts metrics web app
-    let metrics_port = watcher.source.ingest_port as u16;
-    thread::spawn(move || run_metrics_service(metrics_port));
-
     let running = Arc::new(AtomicBool::new(true));
 
+    // starts metrics web app
+    let metrics_address = SocketAddr::new(
+        config.metrics_address,
+        config
+            .metrics_port
+            .unwrap_or(watcher.source.ingest_port as u16),
+    );
+    let metrics_watcher_id = watcher.id.clone().unwrap_or_default();
+    let metrics_events = sender.clone();
+    let metrics_running = running.clone();
+    let metrics_server = thread::spawn(move || {
+        run_metrics_service(
+            metrics_address,
+            metrics_watcher_id,
+            metrics_events,
+            metrics_running,
+        )
+    });
+
     let r = running.clone();
     ctrlc::set_handler(move || {
         r.store(false, Ordering::SeqCst);
     })
     .expect("Error setting termination handler");
 
-    let detector = SlateDetector::new(&slate::load_img(watcher.slate_url.as_str())?)?;
+    let detectors = Detectors {
+        slate: SlateDetector::new(
+            &watcher
+                .slate_urls()
+                .map(|url| {
+                    let contents = slate::load_img(
+                        url.as_str(),
+                        watcher.slate_authorization.as_ref(),
+                        watcher.slate_headers.as_ref(),
+                    )?;
+                    // Query strings are left out of the metrics, they might contain credentials
+                    let id = url.split('?').next().unwrap_or_default().to_string();
+                    Ok((id, contents))
+                })
+                .collect::<Result<Vec<_>>>()?,
+            watcher.slate_region,
+        )?
+        .with_threshold(watcher.slate_threshold.unwrap_or(DEFAULT_SLATE_THRESHOLD))
+        .with_threshold_learning(watcher.slate_threshold_learning),
+        slate_text: watcher
+            .slate_text
+            .as_deref()
+            .map(TextDetector::new)
+            .transpose()?,
+        black: BlackDetector::new(watcher.black_threshold.unwrap_or(DEFAULT_BLACK_THRESHOLD)),
+        bars: if watcher.detect_bars.unwrap_or(false) {
+            Some(BarsDetector::new())
+        } else {
+            None
+        },
+    };
 
     let server = VideoStream::new(
         watcher.source.ingest_port,
         watcher.source.container,
         watcher.source.codec,
-    ).expect("Could not start video stream");
+    )
+    .expect("Could not start video stream");
     log::info!(
         "Starting pipeline at rtp://0.0.0.0:{}",
         watcher.source.ingest_port
     );
 
-    process_frames(server.into_iter(), detector, running, sender)
+    let result = process_frames(
+        server.into_iter(),
+        detectors,
+        Debouncer::new(watcher.debounce),
+        running.clone(),
+        sender,
+    );
+
+    // The stream can end by itself, the metrics server stops with it
+    running.store(false, Ordering::SeqCst);
+    if metrics_server.join().is_err() {
+        log::error!("Metrics server ended unexpectedly!");
+    }
+    result
 }
@@ -1,14 +1,128 @@
-use crate::video_stream;
+use crate::actions::Simulation;
+use crate::calibration::{self, SlateCheck};
+use crate::control;
+use crate::dead_letter;
+use crate::frames;
+use crate::health::{self, Probe};
+use crate::history;
+use crate::http;
+use crate::snapshots;
+use crate::state;
+use crate::transitions;
+use crate::video_stream::{self, Event};
+use chrono::DateTime;
+use color_eyre::{eyre::eyre, Result};
+use crossbeam::channel::Sender;
+use hawkeye_core::logging;
+use hawkeye_core::models::{MetricsSettings, VideoMode, SLATE_URL_SCHEMES};
+use image::imageops::FilterType;
+use image::ImageOutputFormat;
 use lazy_static::lazy_static;
-use log::debug;
+use log::{debug, error, info, warn};
+use prometheus::proto::{MetricFamily, MetricType};
 use prometheus::{self, Encoder, TextEncoder};
-use prometheus::{register_histogram, register_int_counter, Histogram, IntCounter};
+use prometheus::{
+    register_gauge_vec, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, GaugeVec, Histogram,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+use tokio::net::UdpSocket;
 use tokio::runtime::Builder;
 use warp::hyper::header::{HeaderValue, CACHE_CONTROL, CONTENT_TYPE};
 use warp::hyper::{Body, StatusCode};
 use warp::reply::Response;
 use warp::Filter;
 
+/// Largest width or height of the resized frames.
+const MAX_THUMBNAIL_SIZE: u32 = 4096;
+const JPEG_QUALITY: u8 = 80;
+
+const PREFIX_ENV: &str = "HAWKEYE_METRICS_PREFIX";
+const BUCKETS_ENV: &str = "HAWKEYE_METRICS_BUCKETS_MS";
+const PUSH_ENV: &str = "HAWKEYE_METRICS_PUSH";
+const PUSH_URL_ENV: &str = "HAWKEYE_METRICS_PUSH_URL";
+const PUSH_INTERVAL_ENV: &str = "HAWKEYE_METRICS_PUSH_INTERVAL";
+
+lazy_static! {
+    // Settings from the environment and the watcher, applied at startup.
+    static ref SETTINGS: RwLock<MetricsSettings> = RwLock::new(MetricsSettings::default());
+
+    // Seconds between two pushes of the metrics.
+    static ref PUSH_INTERVAL: Duration = Duration::from_secs(
+        std::env::var(PUSH_INTERVAL_ENV)
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .unwrap_or(15)
+    );
+}
+
+/// Applies the metrics settings of the environment and the ones of the watcher, which take
+/// precedence, before any histogram is used.
+pub fn configure(watcher_settings: Option<&MetricsSettings>) -> Result<()> {
+    let settings = merge(env_settings()?, watcher_settings);
+    settings.is_valid()?;
+    *SETTINGS.write().unwrap() = settings;
+    Ok(())
+}
+
+fn env_settings() -> Result<MetricsSettings> {
+    let buckets_ms = match std::env::var(BUCKETS_ENV) {
+        Ok(buckets) => Some(parse_buckets(&buckets)?),
+        Err(_) => None,
+    };
+    Ok(MetricsSettings {
+        prefix: std::env::var(PREFIX_ENV).ok(),
+        buckets_ms,
+    })
+}
+
+fn parse_buckets(buckets: &str) -> Result<Vec<u32>> {
+    buckets
+        .split(',')
+        .map(|bucket| {
+            bucket
+                .trim()
+                .parse()
+                .map_err(|_| eyre!("Invalid {}: {}", BUCKETS_ENV, buckets))
+        })
+        .collect()
+}
+
+fn merge(
+    mut settings: MetricsSettings,
+    watcher_settings: Option<&MetricsSettings>,
+) -> MetricsSettings {
+    if let Some(watcher_settings) = watcher_settings {
+        if watcher_settings.prefix.is_some() {
+            settings.prefix = watcher_settings.prefix.clone();
+        }
+        if watcher_settings.buckets_ms.is_some() {
+            settings.buckets_ms = watcher_settings.buckets_ms.clone();
+        }
+    }
+    settings
+}
+
+/// Upper bounds of the buckets of the duration histograms, in seconds.
+fn buckets() -> Vec<f64> {
+    bucket_bounds(&SETTINGS.read().unwrap())
+}
+
+fn bucket_bounds(settings: &MetricsSettings) -> Vec<f64> {
+    match &settings.buckets_ms {
+        Some(buckets) => buckets.iter().map(|ms| *ms as f64 / 1000f64).collect(),
+        None => prometheus::DEFAULT_BUCKETS.to_vec(),
+    }
+}
+
 lazy_static! {
     pub static ref FOUND_SLATE_COUNTER: IntCounter = register_int_counter!(
         "slate_found_in_stream",
@@ -20,24 +134,99 @@ lazy_static! {
         "Number of times the content was found in the stream"
     )
     .unwrap();
+    pub static ref FOUND_BLACK_COUNTER: IntCounter = register_int_counter!(
+        "black_found_in_stream",
+        "Number of times a black frame was found in the stream"
+    )
+    .unwrap();
+    pub static ref FOUND_BARS_COUNTER: IntCounter = register_int_counter!(
+        "bars_found_in_stream",
+        "Number of times color bars were found in the stream"
+    )
+    .unwrap();
     pub static ref SIMILARITY_EXECUTION_COUNTER: IntCounter = register_int_counter!(
         "similarity_execution",
         "Number of times we searched for slate in the stream"
     )
     .unwrap();
+    pub static ref SLATE_PREFILTER_HIT_COUNTER: IntCounter = register_int_counter!(
+        "slate_prefilter_hit",
+        "Number of frames rejected by the prefilter before running the similarity algorithm"
+    )
+    .unwrap();
+    pub static ref SLATE_PREFILTER_MISS_COUNTER: IntCounter = register_int_counter!(
+        "slate_prefilter_miss",
+        "Number of frames that passed the prefilter and ran the similarity algorithm"
+    )
+    .unwrap();
+    pub static ref SLATE_SIMILARITY: GaugeVec = register_gauge_vec!(
+        "hawkeye_slate_similarity",
+        "DSSIM score between the latest frame and the slate image, zero means identical, NaN when the frame was rejected by the prefilter",
+        &["slate"]
+    )
+    .unwrap();
+    pub static ref SLATE_THRESHOLD: GaugeVec = register_gauge_vec!(
+        "hawkeye_slate_threshold",
+        "Maximum DSSIM score (multiplied by 1000) of a frame matching the slate",
+        &["slate"]
+    )
+    .unwrap();
+    pub static ref SLATE_THRESHOLD_RECOMMENDED: GaugeVec = register_gauge_vec!(
+        "hawkeye_slate_threshold_recommended",
+        "Threshold of the slate recommended by the learning mode",
+        &["slate"]
+    )
+    .unwrap();
+    pub static ref FRAME_CHANNEL_DEPTH: IntGauge = register_int_gauge!(
+        "hawkeye_frame_channel_depth",
+        "Number of frames from the pipeline waiting to be analysed"
+    )
+    .unwrap();
+    pub static ref FRAMES_DISCARDED_COUNTER: IntCounter = register_int_counter!(
+        "hawkeye_frames_discarded",
+        "Number of frames from the pipeline discarded because the previous frame was still waiting to be analysed"
+    )
+    .unwrap();
+    pub static ref ACTIONS_CHANNEL_DEPTH: IntGauge = register_int_gauge!(
+        "hawkeye_actions_channel_depth",
+        "Number of detections waiting to be sent to the executors of the actions"
+    )
+    .unwrap();
+    pub static ref ACTIONS_DROPPED_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "hawkeye_actions_dropped",
+        "Number of detections dropped because the queue of the executors of the actions of the transition was full",
+        &["transition"]
+    )
+    .unwrap();
+    pub static ref ACTIONS_QUEUE_DEPTH: IntGaugeVec = register_int_gauge_vec!(
+        "hawkeye_actions_queue_depth",
+        "Number of detections waiting to be handled by the executors of the actions of the transition",
+        &["transition"]
+    )
+    .unwrap();
     pub static ref SIMILARITY_EXECUTION_DURATION: Histogram = register_histogram!(
         "similarity_execution_seconds",
-        "Seconds it took to execute the similarity algorithm"
+        "Seconds it took to execute the similarity algorithm",
+        buckets()
     )
     .unwrap();
     pub static ref FRAME_PROCESSING_DURATION: Histogram = register_histogram!(
         "frame_processing_seconds",
-        "Seconds it took to execute the whole frame processing block"
+        "Seconds it took to execute the whole frame processing block",
+        buckets()
     )
     .unwrap();
     pub static ref HTTP_CALL_DURATION: Histogram = register_histogram!(
         "http_call_action_execution_seconds",
-        "Seconds it took to execute the HTTP call"
+        "Seconds it took to execute the HTTP call",
+        buckets()
+    )
+    .unwrap();
+    pub static ref TRANSITION_LATENCY: HistogramVec = register_histogram_vec!(
+        "hawkeye_transition_latency_seconds",
+        "Seconds from the arrival of the frame the transition was detected in to the completed execution of its action",
+        &["transition"],
+        buckets()
     )
     .unwrap();
     pub static ref HTTP_CALL_SUCCESS_COUNTER: IntCounter = register_int_counter!(
@@ -55,11 +244,66 @@ lazy_static! {
         "Number of times the HTTP call was retried"
     )
     .unwrap();
+    pub static ref HTTP_CALL_ATTEMPTS: IntCounterVec = register_int_counter_vec!(
+        "http_call_attempts",
+        "Number of attempts of the HTTP call by attempt number and result",
+        &["attempt", "result"]
+    )
+    .unwrap();
     pub static ref HTTP_CALL_RETRIES_EXHAUSTED_COUNT: IntCounter = register_int_counter!(
         "http_call_retries_exhausted",
         "Number of times the HTTP action has exhausted all the retries"
     )
     .unwrap();
+    pub static ref HTTP_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "hawkeye_http_requests",
+        "Number of HTTP requests sent by the actions by host",
+        &["host"]
+    )
+    .unwrap();
+    pub static ref HTTP_CONNECTIONS_OPENED: IntCounterVec = register_int_counter_vec!(
+        "hawkeye_http_connections_opened",
+        "Number of connections opened by the actions by host, requests not opening one reused a connection",
+        &["host"]
+    )
+    .unwrap();
+    pub static ref ACTION_CIRCUIT_STATE: IntGaugeVec = register_int_gauge_vec!(
+        "hawkeye_action_circuit_state",
+        "State of the circuit breaker of the action: 0 closed, 1 open, 2 half-open",
+        &["action"]
+    )
+    .unwrap();
+    pub static ref SCHEDULE_SUPPRESSED_COUNTER: IntCounterVec = register_int_counter_vec!(
+        "hawkeye_schedule_suppressed",
+        "Number of action executions suppressed because they were outside of the transition schedule",
+        &["transition"]
+    )
+    .unwrap();
+    pub static ref DEAD_LETTERS_GAUGE: IntGauge = register_int_gauge!(
+        "hawkeye_dead_letters",
+        "Number of failed action executions kept for replay"
+    )
+    .unwrap();
+}
+
+/// The metrics, with the configured prefix.
+fn gather() -> Vec<MetricFamily> {
+    let mut metric_families = prometheus::gather();
+    if let Some(prefix) = &SETTINGS.read().unwrap().prefix {
+        add_prefix(&mut metric_families, prefix);
+    }
+    metric_families
+}
+
+/// Prepends the prefix to the names of the metrics, except the ones starting with it already, like
+/// `hawkeye_slate_similarity` with `hawkeye_`.
+fn add_prefix(metric_families: &mut [MetricFamily], prefix: &str) {
+    for family in metric_families.iter_mut() {
+        if !family.get_name().starts_with(prefix) {
+            let name = format!("{}{}", prefix, family.get_name());
+            family.set_name(name);
+        }
+    }
 }
 
 fn get_metric_contents() -> String {
@@ -67,28 +311,175 @@ fn get_metric_contents() -> String {
     let mut buffer = Vec::new();
     let encoder = TextEncoder::new();
 
-    let metric_families = prometheus::gather();
+    let metric_families = gather();
     encoder.encode(&metric_families, &mut buffer).unwrap();
 
     String::from_utf8(buffer).unwrap()
 }
 
-fn latest_frame() -> impl warp::Reply {
+/// Where the metrics are pushed to, for deployments that can't scrape the workers.
+enum PushTarget {
+    /// URL of a Prometheus Pushgateway.
+    Pushgateway(String),
+    /// Address of a StatsD server, the labels are sent as DogStatsD tags.
+    Statsd(String),
+}
+
+impl PushTarget {
+    fn from_env() -> Result<Option<Self>> {
+        let mode = match std::env::var(PUSH_ENV) {
+            Ok(mode) => mode,
+            Err(_) => return Ok(None),
+        };
+        let url = std::env::var(PUSH_URL_ENV)
+            .map_err(|_| eyre!("{} is required to push the metrics", PUSH_URL_ENV))?;
+        match mode.as_str() {
+            "pushgateway" => Ok(Some(PushTarget::Pushgateway(url))),
+            "statsd" => Ok(Some(PushTarget::Statsd(url))),
+            _ => Err(eyre!("Unknown {} {}", PUSH_ENV, mode)),
+        }
+    }
+}
+
+/// Replaces the metrics of the worker in the Pushgateway.
+async fn push_to_gateway(url: &str, watcher_id: &str) -> Result<()> {
+    let mut buffer = Vec::new();
+    let encoder = TextEncoder::new();
+    encoder.encode(&gather(), &mut buffer)?;
+    let request = http::CLIENT
+        .put(format!(
+            "{}/metrics/job/hawkeye-worker/instance/{}",
+            url.trim_end_matches('/'),
+            watcher_id
+        ))
+        .header(CONTENT_TYPE, encoder.format_type())
+        .body(buffer);
+    match http::send(request).await? {
+        (status, _) if (200..300).contains(&status) => Ok(()),
+        (status, body) => Err(eyre!("Pushgateway answered {}: {}", status, body)),
+    }
+}
+
+/// StatsD lines of the metrics. Counters are sent as the increment since the previous push,
+/// histograms as the increments of their count and sum.
+fn statsd_lines(families: &[MetricFamily], previous: &mut HashMap<String, f64>) -> Vec<String> {
+    let mut lines = Vec::new();
+    for family in families {
+        for metric in family.get_metric() {
+            let tags = metric
+                .get_label()
+                .iter()
+                .map(|label| format!("{}:{}", label.get_name(), label.get_value()))
+                .collect::<Vec<_>>()
+                .join(",");
+            let tags = if tags.is_empty() {
+                tags
+            } else {
+                format!("|#{}", tags)
+            };
+            let mut count = |name: String, value: f64| {
+                let key = format!("{}{}", name, tags);
+                let last = previous.insert(key, value).unwrap_or(0f64);
+                if value > last {
+                    lines.push(format!("{}:{}|c{}", name, value - last, tags));
+                }
+            };
+            let name = family.get_name();
+            match family.get_field_type() {
+                MetricType::COUNTER => count(name.to_string(), metric.get_counter().get_value()),
+                MetricType::HISTOGRAM => {
+                    let histogram = metric.get_histogram();
+                    count(
+                        format!("{}_count", name),
+                        histogram.get_sample_count() as f64,
+                    );
+                    count(format!("{}_sum", name), histogram.get_sample_sum());
+                }
+                MetricType::GAUGE => lines.push(format!(
+                    "{}:{}|g{}",
+                    name,
+                    metric.get_gauge().get_value(),
+                    tags
+                )),
+                _ => (),
+            }
+        }
+    }
+    lines
+}
+
+/// Pushes the metrics periodically, when `HAWKEYE_METRICS_PUSH` is set to `pushgateway` or
+/// `statsd`.
+async fn push_metrics(watcher_id: String) {
+    let target = match PushTarget::from_env() {
+        Ok(Some(target)) => target,
+        Ok(None) => return,
+        Err(err) => {
+            error!("Metrics not pushed: {:#}", err);
+            return;
+        }
+    };
+    let socket = match &target {
+        PushTarget::Statsd(address) => match UdpSocket::bind("0.0.0.0:0").await {
+            Ok(socket) => match socket.connect(address).await {
+                Ok(_) => Some(socket),
+                Err(err) => {
+                    error!("Metrics not pushed, cannot reach {}: {}", address, err);
+                    return;
+                }
+            },
+            Err(err) => {
+                error!("Metrics not pushed: {}", err);
+                return;
+            }
+        },
+        PushTarget::Pushgateway(_) => None,
+    };
+    let mut previous = HashMap::new();
+    let mut interval = tokio::time::interval(*PUSH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = match (&target, &socket) {
+            (PushTarget::Pushgateway(url), _) => push_to_gateway(url, &watcher_id).await,
+            (PushTarget::Statsd(_), Some(socket)) => {
+                let mut result = Ok(());
+                for line in statsd_lines(&gather(), &mut previous) {
+                    if let Err(err) = socket.send(line.as_bytes()).await {
+                        result = Err(err.into());
+                    }
+                }
+                result
+            }
+            (PushTarget::Statsd(_), None) => Ok(()),
+        };
+        if let Err(err) = result {
+            warn!("Could not push the metrics: {:#}", err);
+        }
+    }
+}
+
+/// The latest frame, resized to fit in the `width` and `height` and encoded in the `format`
+/// (`png` or `jpeg`) of the query when given, for the thumbnails of the dashboards.
+fn latest_frame(query: HashMap<String, String>) -> impl warp::Reply {
     let image = video_stream::LATEST_FRAME.read();
-    let image_png = HeaderValue::from_static("image/png");
     let no_store = HeaderValue::from_static("no-store");
-    let response = match &*image {
-        Some(image) => {
-            let mut res = Response::new(image.clone().into());
+    let response = match (*image).as_ref().map(|image| thumbnail(image, &query)) {
+        Some(Ok((image, content_type))) => {
+            let mut res = Response::new(image.into());
             let headers = res.headers_mut();
-            headers.insert(CONTENT_TYPE, image_png);
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
             headers.insert(CACHE_CONTROL, no_store);
             res
         }
+        Some(Err(err)) => {
+            let mut res = Response::new(Body::from(format!("{:#}", err)));
+            *res.status_mut() = StatusCode::BAD_REQUEST;
+            res
+        }
         None => {
             let mut res = Response::new(Body::empty());
             let headers = res.headers_mut();
-            headers.insert(CONTENT_TYPE, image_png);
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
             headers.insert(CACHE_CONTROL, no_store);
             let status = res.status_mut();
             *status = StatusCode::NOT_FOUND;
@@ -98,17 +489,478 @@ fn latest_frame() -> impl warp::Reply {
     Ok(response)
 }
 
-pub fn run_metrics_service(metrics_port: u16) {
+/// Resizes and encodes the PNG frame as requested by the `width`, `height` and `format`
+/// parameters, returning it with its content type. The frame is kept as is without them.
+fn thumbnail(frame: &[u8], query: &HashMap<String, String>) -> Result<(Vec<u8>, &'static str)> {
+    let dimension = |name: &str| -> Result<Option<u32>> {
+        query
+            .get(name)
+            .map(|value| {
+                value
+                    .parse()
+                    .ok()
+                    .filter(|value| (1..=MAX_THUMBNAIL_SIZE).contains(value))
+                    .ok_or_else(|| eyre!("Invalid {}: {}", name, value))
+            })
+            .transpose()
+    };
+    let (width, height) = (dimension("width")?, dimension("height")?);
+    let (format, content_type) = match query.get("format").map(String::as_str) {
+        None | Some("png") => (ImageOutputFormat::Png, "image/png"),
+        Some("jpeg") | Some("jpg") => (ImageOutputFormat::Jpeg(JPEG_QUALITY), "image/jpeg"),
+        Some(format) => return Err(eyre!("Invalid format: {}", format)),
+    };
+    if width.is_none() && height.is_none() && content_type == "image/png" {
+        return Ok((frame.to_vec(), content_type));
+    }
+
+    let mut image = image::load_from_memory(frame)?;
+    if width.is_some() || height.is_some() {
+        // Keeps the aspect ratio, the frame fits in the dimensions given
+        image = image.resize(
+            width.unwrap_or(MAX_THUMBNAIL_SIZE),
+            height.unwrap_or(MAX_THUMBNAIL_SIZE),
+            FilterType::Triangle,
+        );
+    }
+    let mut contents = Vec::new();
+    image.write_to(&mut contents, format)?;
+    Ok((contents, content_type))
+}
+
+fn probe_reply(probe: Probe) -> impl warp::Reply {
+    let status = if probe.healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    warp::reply::with_status(warp::reply::json(&probe.checks), status)
+}
+
+fn frame_image(id: u64) -> impl warp::Reply {
+    png_reply(frames::image(id))
+}
+
+fn snapshot_image(name: String) -> impl warp::Reply {
+    png_reply(snapshots::image(&name))
+}
+
+fn png_reply(image: Option<Vec<u8>>) -> impl warp::Reply {
+    let mut res = match image {
+        Some(image) => Response::new(image.into()),
+        None => {
+            let mut res = Response::new(Body::empty());
+            *res.status_mut() = StatusCode::NOT_FOUND;
+            res
+        }
+    };
+    let headers = res.headers_mut();
+    headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    res
+}
+
+/// Transitions detected after the optional `since` query parameter, a RFC 3339 timestamp.
+fn transitions_reply(query: HashMap<String, String>) -> impl warp::Reply {
+    let since = match query
+        .get("since")
+        .map(|since| DateTime::parse_from_rfc3339(since))
+    {
+        Some(Ok(since)) => Some(since.into()),
+        Some(Err(err)) => {
+            return warp::reply::with_status(
+                warp::reply::json(&json!({ "error": format!("Invalid since: {}", err) })),
+                StatusCode::BAD_REQUEST,
+            )
+        }
+        None => None,
+    };
+    warp::reply::with_status(warp::reply::json(&transitions::list(since)), StatusCode::OK)
+}
+
+async fn replay_dead_letter(id: String) -> Result<impl warp::Reply, Infallible> {
+    let result = dead_letter::replay(&id).await;
+    let (body, status) = match result {
+        None => (json!({"message": "Not found"}), StatusCode::NOT_FOUND),
+        Some(Ok(_)) => (json!({"message": "Replayed"}), StatusCode::OK),
+        Some(Err(err)) => (
+            json!({ "message": format!("{:#}", err) }),
+            StatusCode::BAD_GATEWAY,
+        ),
+    };
+    Ok(warp::reply::with_status(warp::reply::json(&body), status))
+}
+
+/// Scores the latest frame against the `slate_urls` of the body, with the `slate_threshold`,
+/// `slate_region`, `slate_authorization` and `slate_headers` of the watcher when given.
+async fn slate_score(body: serde_json::Value) -> Result<impl warp::Reply, Infallible> {
+    let check = match slate_check(body) {
+        Ok(check) => check,
+        Err(err) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": format!("{:#}", err) })),
+                StatusCode::BAD_REQUEST,
+            ))
+        }
+    };
+    let frame = match video_stream::LATEST_FRAME.read().clone() {
+        Some(frame) => frame,
+        None => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&json!({ "error": "No frame received yet" })),
+                StatusCode::NOT_FOUND,
+            ))
+        }
+    };
+    // Loading the slates blocks, they might be downloaded
+    let result = tokio::task::spawn_blocking(move || calibration::score_frame(&check, &frame))
+        .await
+        .map_err(|err| eyre!("Scoring failed: {}", err))
+        .and_then(|result| result);
+    Ok(match result {
+        Ok(scores) => warp::reply::with_status(warp::reply::json(&scores), StatusCode::OK),
+        Err(err) => warp::reply::with_status(
+            warp::reply::json(&json!({ "error": format!("{:#}", err) })),
+            StatusCode::BAD_REQUEST,
+        ),
+    })
+}
+
+fn slate_check(mut body: serde_json::Value) -> Result<SlateCheck> {
+    let slate_urls: Vec<String> = serde_json::from_value(body["slate_urls"].take())
+        .map_err(|err| eyre!("Invalid slate_urls: {}", err))?;
+    if slate_urls.is_empty() {
+        return Err(eyre!("The slate_urls are required"));
+    }
+    // Downloaded by the worker, only from the places a watcher can load its slates from
+    if let Some(url) = slate_urls.iter().find(|url| {
+        !SLATE_URL_SCHEMES
+            .iter()
+            .any(|scheme| url.starts_with(scheme))
+    }) {
+        return Err(eyre!("{} not recognized as a valid URL!", url));
+    }
+    Ok(SlateCheck {
+        slate_urls,
+        slate_threshold: serde_json::from_value(body["slate_threshold"].take())
+            .map_err(|err| eyre!("Invalid slate_threshold: {}", err))?,
+        slate_region: serde_json::from_value(body["slate_region"].take())
+            .map_err(|err| eyre!("Invalid slate_region: {}", err))?,
+        slate_authorization: serde_json::from_value(body["slate_authorization"].take())
+            .map_err(|err| eyre!("Invalid slate_authorization: {}", err))?,
+        slate_headers: serde_json::from_value(body["slate_headers"].take())
+            .map_err(|err| eyre!("Invalid slate_headers: {}", err))?,
+    })
+}
+
+/// Sends a transition from the `from` mode to the `to` mode of the body to the actions, as if it
+/// was detected in the stream. They are logged instead of executed with `dry_run`.
+fn simulate(events: Sender<Event>, mut body: serde_json::Value) -> impl warp::Reply {
+    let simulation = match parse_simulation(&mut body) {
+        Ok(simulation) => simulation,
+        Err(err) => {
+            return warp::reply::with_status(
+                warp::reply::json(&json!({ "error": format!("{:#}", err) })),
+                StatusCode::BAD_REQUEST,
+            )
+        }
+    };
+    let event_id = simulation.event_id.clone();
+    match events.send(Event::Simulate(simulation)) {
+        Ok(_) => warp::reply::with_status(
+            warp::reply::json(&json!({ "event_id": event_id })),
+            StatusCode::ACCEPTED,
+        ),
+        Err(_) => warp::reply::with_status(
+            warp::reply::json(&json!({ "error": "The actions runtime stopped" })),
+            StatusCode::SERVICE_UNAVAILABLE,
+        ),
+    }
+}
+
+fn parse_simulation(body: &mut serde_json::Value) -> Result<Simulation> {
+    let from: VideoMode = serde_json::from_value(body["from"].take())
+        .map_err(|err| eyre!("Invalid from: {}", err))?;
+    let to: VideoMode =
+        serde_json::from_value(body["to"].take()).map_err(|err| eyre!("Invalid to: {}", err))?;
+    if to == VideoMode::Any || from == to {
+        return Err(eyre!("Not a transition: {} to {}", from, to));
+    }
+    let dry_run = body["dry_run"].as_bool().unwrap_or(false);
+    Ok(Simulation::new(from, to, dry_run))
+}
+
+/// Changes the log filters, like `hawkeye_worker=trace`, for `duration_seconds` (10 minutes by
+/// default, `0` to keep them).
+fn set_log_level(body: serde_json::Value) -> impl warp::Reply {
+    let level = match body["level"].as_str() {
+        Some(level) if !level.is_empty() => level,
+        _ => {
+            return warp::reply::with_status(
+                warp::reply::json(&json!({ "error": "The level is required" })),
+                StatusCode::BAD_REQUEST,
+            )
+        }
+    };
+    let revert_after = match body["duration_seconds"].as_u64().unwrap_or(600) {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds)),
+    };
+    logging::set_filters(level, revert_after);
+    log::info!(
+        "Log filters changed to \"{}\" for {:?}",
+        level,
+        revert_after
+    );
+    warp::reply::with_status(warp::reply::json(&logging::filters()), StatusCode::OK)
+}
+
+fn control_routes() -> impl Filter<Extract = impl warp::Reply, Error = warp::Rejection> + Clone {
+    let state = || warp::reply::json(&control::state());
+    let get_state = warp::get().and(warp::path!("control")).map(state);
+    let pause = warp::path!("control" / "pause").map(move || {
+        control::pause_detection();
+        state()
+    });
+    let resume = warp::path!("control" / "resume").map(move || {
+        control::resume_detection();
+        state()
+    });
+    let mute = warp::path!("control" / "mute" / u64).map(move |seconds| {
+        control::mute_actions(Duration::from_secs(seconds));
+        state()
+    });
+    let unmute = warp::path!("control" / "unmute").map(move || {
+        control::unmute_actions();
+        state()
+    });
+    let log_level = warp::path!("log_level");
+    let get_log_level = warp::get()
+        .and(log_level)
+        .map(|| warp::reply::json(&logging::filters()));
+    let change_log_level = warp::post()
+        .and(log_level)
+        .and(warp::body::json())
+        .map(set_log_level);
+    let reset_log_level = warp::delete().and(log_level).map(|| {
+        logging::reset_filters();
+        warp::reply::json(&logging::filters())
+    });
+    control::authorized().and(
+        get_state
+            .or(warp::post().and(pause.or(resume).or(mute).or(unmute)))
+            .or(get_log_level)
+            .or(change_log_level)
+            .or(reset_log_level),
+    )
+}
+
+/// Serves the metrics and the state of the worker until `running` is unset, letting the requests
+/// in flight finish. Simulated transitions are sent to the actions with `events`.
+pub fn run_metrics_service(
+    address: SocketAddr,
+    watcher_id: String,
+    events: Sender<Event>,
+    running: Arc<AtomicBool>,
+) {
     let runtime = Builder::new_multi_thread()
         .thread_name("metrics_app")
         .max_blocking_threads(2)
         .enable_all()
         .build()
         .unwrap();
-    let routes = warp::get().and(
-        warp::path("metrics")
-            .map(get_metric_contents)
-            .or(warp::path("latest_frame").map(latest_frame)),
-    );
-    runtime.block_on(warp::serve(routes).run(([0, 0, 0, 0], metrics_port)));
+    let routes = warp::get()
+        .and(
+            warp::path("metrics")
+                .map(get_metric_contents)
+                .or(warp::path("latest_frame")
+                    .and(warp::query::<HashMap<String, String>>())
+                    .map(latest_frame))
+                .or(warp::path!("dead_letters").map(|| warp::reply::json(&dead_letter::list())))
+                .or(warp::path!("actions" / "history").map(|| warp::reply::json(&history::list())))
+                .or(warp::path!("state").map(|| warp::reply::json(&state::to_json())))
+                .or(warp::path!("transitions")
+                    .and(warp::query::<HashMap<String, String>>())
+                    .map(transitions_reply))
+                .or(warp::path!("frames").map(|| warp::reply::json(&frames::list())))
+                .or(warp::path!("frames" / u64).map(frame_image))
+                .or(warp::path!("snapshots" / String).map(snapshot_image))
+                .or(warp::path!("healthz").map(|| probe_reply(health::liveness())))
+                .or(warp::path!("readyz").map(|| probe_reply(health::readiness()))),
+        )
+        .or(warp::post()
+            .and(warp::path!("dead_letters" / String / "replay"))
+            .and(control::authorized())
+            .and_then(replay_dead_letter))
+        .or(warp::post()
+            .and(warp::path!("slate_score"))
+            .and(control::authorized())
+            .and(warp::body::json())
+            .and_then(slate_score))
+        .or(warp::post()
+            .and(warp::path!("simulate"))
+            .and(control::authorized())
+            .and(warp::any().map(move || events.clone()))
+            .and(warp::body::json())
+            .map(simulate))
+        .or(control_routes());
+    runtime.spawn(push_metrics(watcher_id));
+    runtime.block_on(async move {
+        let shutdown = async move {
+            while running.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        match warp::serve(routes).try_bind_with_graceful_shutdown(address, shutdown) {
+            Ok((address, server)) => {
+                info!("Serving metrics at http://{}/metrics", address);
+                server.await;
+                info!("Metrics server stopped");
+            }
+            Err(err) => error!("Could not start the metrics server at {}: {}", address, err),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::GenericImageView;
+
+    #[test]
+    fn resize_and_encode_frames() {
+        let frame = std::fs::read("../resources/slate_120px.jpg").unwrap();
+        let mut png = Vec::new();
+        image::load_from_memory(&frame)
+            .unwrap()
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let query = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        let (same, content_type) = thumbnail(&png, &HashMap::new()).unwrap();
+        assert_eq!(same, png);
+        assert_eq!(content_type, "image/png");
+
+        let (small, content_type) =
+            thumbnail(&png, &query(&[("width", "64"), ("format", "jpeg")])).unwrap();
+        assert_eq!(content_type, "image/jpeg");
+        let small = image::load_from_memory(&small).unwrap();
+        assert_eq!(small.width(), 64);
+        assert!(small.height() < 64);
+
+        assert!(thumbnail(&png, &query(&[("width", "0")])).is_err());
+        assert!(thumbnail(&png, &query(&[("format", "gif")])).is_err());
+    }
+
+    #[test]
+    fn check_slate_urls() {
+        let check = slate_check(json!({"slate_urls": ["https://example.com/slate.jpg"]})).unwrap();
+        assert_eq!(check.slate_urls, vec!["https://example.com/slate.jpg"]);
+        assert_eq!(check.slate_threshold, None);
+
+        assert!(slate_check(json!({"slate_urls": []})).is_err());
+        assert!(slate_check(json!({})).is_err());
+        assert!(slate_check(json!({
+            "slate_urls": ["https://example.com/slate.jpg", "ftp://example.com/slate.jpg"]
+        }))
+        .is_err());
+    }
+
+    #[test]
+    fn parse_simulations() {
+        let simulation =
+            parse_simulation(&mut json!({"from": "any", "to": "slate", "dry_run": true})).unwrap();
+        assert_eq!(simulation.from, VideoMode::Any);
+        assert_eq!(simulation.to, VideoMode::Slate);
+        assert!(simulation.dry_run);
+        assert!(
+            !parse_simulation(&mut json!({"from": "content", "to": "slate"}))
+                .unwrap()
+                .dry_run
+        );
+
+        assert!(parse_simulation(&mut json!({"from": "content"})).is_err());
+        assert!(parse_simulation(&mut json!({"from": "content", "to": "ads"})).is_err());
+        assert!(parse_simulation(&mut json!({"from": "slate", "to": "slate"})).is_err());
+        assert!(parse_simulation(&mut json!({"from": "slate", "to": "any"})).is_err());
+    }
+
+    #[test]
+    fn apply_metrics_settings() {
+        let env = MetricsSettings {
+            prefix: Some("acme_".to_string()),
+            buckets_ms: Some(vec![25, 50]),
+        };
+        let settings = merge(
+            env.clone(),
+            Some(&MetricsSettings {
+                prefix: None,
+                buckets_ms: Some(vec![50, 100, 200]),
+            }),
+        );
+        assert_eq!(settings.prefix.as_deref(), Some("acme_"));
+        assert_eq!(bucket_bounds(&settings), vec![0.05, 0.1, 0.2]);
+        assert_eq!(merge(env.clone(), None), env);
+        assert_eq!(
+            bucket_bounds(&MetricsSettings::default()),
+            prometheus::DEFAULT_BUCKETS.to_vec()
+        );
+
+        assert_eq!(parse_buckets("25, 50,100").unwrap(), vec![25, 50, 100]);
+        assert!(parse_buckets("25,fifty").is_err());
+        assert!(parse_buckets("").is_err());
+    }
+
+    #[test]
+    fn prefix_metric_names() {
+        let family = |name: &str| {
+            let mut family = MetricFamily::default();
+            family.set_name(name.to_string());
+            family
+        };
+        let mut families = vec![
+            family("slate_found_in_stream"),
+            family("hawkeye_slate_similarity"),
+        ];
+        add_prefix(&mut families, "hawkeye_");
+
+        let names: Vec<_> = families.iter().map(|family| family.get_name()).collect();
+        assert_eq!(
+            names,
+            vec!["hawkeye_slate_found_in_stream", "hawkeye_slate_similarity"]
+        );
+    }
+
+    #[test]
+    fn metrics_as_statsd_lines() {
+        let registry = prometheus::Registry::new();
+        let counter = IntCounterVec::new(
+            prometheus::opts!("pushed_total", "Pushed things"),
+            &["target"],
+        )
+        .unwrap();
+        let gauge = IntGauge::new("pushed_gauge", "A pushed gauge").unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        registry.register(Box::new(gauge.clone())).unwrap();
+
+        let mut previous = HashMap::new();
+        counter.with_label_values(&["ads"]).inc_by(3);
+        gauge.set(7);
+        assert_eq!(
+            statsd_lines(&registry.gather(), &mut previous),
+            vec!["pushed_gauge:7|g", "pushed_total:3|c|#target:ads"]
+        );
+
+        // Only the increments of the counters are sent
+        counter.with_label_values(&["ads"]).inc_by(2);
+        assert_eq!(
+            statsd_lines(&registry.gather(), &mut previous),
+            vec!["pushed_gauge:7|g", "pushed_total:2|c|#target:ads"]
+        );
+    }
 }
@@ -0,0 +1,98 @@
+use color_eyre::{eyre::eyre, Result};
+use rumqttc::{Client, Event, MqttOptions, Outgoing, Packet, QoS, Transport};
+use uuid::Uuid;
+
+/// Connection details of an MQTT broker.
+pub struct Broker<'a> {
+    pub url: &'a str,
+    pub username: Option<&'a str>,
+    pub password: Option<&'a str>,
+    pub ca_certificate: Option<&'a str>,
+}
+
+/// Publishes the message and waits for the broker to acknowledge it, as required by the QoS.
+pub fn publish(broker: &Broker, topic: &str, qos: u8, retain: bool, payload: String) -> Result<()> {
+    let (tls, host, port) = broker_address(broker.url)?;
+    let qos = match qos {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        2 => QoS::ExactlyOnce,
+        _ => return Err(eyre!("Invalid MQTT QoS: {}", qos)),
+    };
+
+    // Client ids are limited to 23 characters by some brokers
+    let client_id = format!("hawkeye-{}", &Uuid::new_v4().to_simple().to_string()[..15]);
+    let mut options = MqttOptions::new(client_id, host, port);
+    if let Some(username) = broker.username {
+        options.set_credentials(username, broker.password.unwrap_or_default());
+    }
+    if tls {
+        let ca = broker
+            .ca_certificate
+            .ok_or_else(|| eyre!("A CA certificate is required to connect to {}", broker.url))?;
+        options.set_transport(Transport::tls(ca.as_bytes().to_vec(), None, None));
+    }
+
+    let (mut client, mut connection) = Client::new(options, 10);
+    client.publish(topic, qos, retain, payload.into_bytes())?;
+    for notification in connection.iter() {
+        let delivered = match (qos, notification?) {
+            (QoS::AtMostOnce, Event::Outgoing(Outgoing::Publish(_))) => true,
+            (QoS::AtLeastOnce, Event::Incoming(Packet::PubAck(_))) => true,
+            (QoS::ExactlyOnce, Event::Incoming(Packet::PubComp(_))) => true,
+            (_, Event::Outgoing(Outgoing::Disconnect)) => break,
+            _ => false,
+        };
+        if delivered {
+            client.disconnect()?;
+        }
+    }
+    Ok(())
+}
+
+/// Host and port of the broker, and if the connection uses TLS.
+fn broker_address(url: &str) -> Result<(bool, String, u16)> {
+    let (tls, address) = if let Some(address) = url.strip_prefix("mqtts://") {
+        (true, address)
+    } else if let Some(address) = url.strip_prefix("mqtt://") {
+        (false, address)
+    } else {
+        return Err(eyre!(
+            "MQTT broker URL must start with mqtt:// or mqtts://: {}",
+            url
+        ));
+    };
+    let address = address.trim_end_matches('/');
+    let (host, port) = match address.rsplit_once(':') {
+        Some((host, port)) => (
+            host,
+            port.parse()
+                .map_err(|_| eyre!("Invalid port in MQTT broker URL: {}", url))?,
+        ),
+        None => (address, if tls { 8883 } else { 1883 }),
+    };
+    if host.is_empty() {
+        return Err(eyre!("Missing host in MQTT broker URL: {}", url));
+    }
+    Ok((tls, host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_broker_address() {
+        assert_eq!(
+            broker_address("mqtt://broker.local").unwrap(),
+            (false, "broker.local".to_string(), 1883)
+        );
+        assert_eq!(
+            broker_address("mqtts://broker.local:9883/").unwrap(),
+            (true, "broker.local".to_string(), 9883)
+        );
+        assert!(broker_address("tcp://broker.local").is_err());
+        assert!(broker_address("mqtt://:1883").is_err());
+        assert!(broker_address("mqtt://broker.local:port").is_err());
+    }
+}
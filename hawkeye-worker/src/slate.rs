@@ -1,27 +1,55 @@
+use crate::aws;
+use crate::http;
 use crate::video_stream::VideoStream;
 use color_eyre::eyre::WrapErr;
 use color_eyre::Result;
+use hawkeye_core::models::HttpAuth;
 use image::imageops::FilterType;
 use image::ImageFormat;
+use lazy_static::lazy_static;
 use log::debug;
 use rand::distributions::Alphanumeric;
 use rand::{thread_rng, Rng};
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::convert::{TryFrom, TryInto};
 use std::fs::File;
 use std::io::{Read, Write};
 use std::path::Path;
 use std::time::Duration;
+use tokio::runtime::Builder;
 
 pub const SLATE_SIZE: (u32, u32) = (213, 120);
 const MEGABYTES: usize = 1024 * 1024;
 const VIDEO_FILE_EXTENSIONS: [&str; 2] = ["mp4", "mkv"];
+const SLATE_ASSETS_URL_ENV: &str = "HAWKEYE_SLATE_ASSETS_URL";
+const ASSET_SCHEME: &str = "asset://";
 
-pub fn load_img(url: &str) -> Result<Vec<u8>> {
-    let temp_file: TempFile = Url::new(url).try_into()?;
+lazy_static! {
+    // Where the slates uploaded to the API, referenced as `asset://{id}`, are downloaded from.
+    static ref SLATE_ASSETS_URL: Option<String> = std::env::var(SLATE_ASSETS_URL_ENV).ok();
+}
+
+pub fn load_img(
+    url: &str,
+    authorization: Option<&HttpAuth>,
+    headers: Option<&HashMap<String, String>>,
+) -> Result<Vec<u8>> {
+    let (url, position) = split_time_offset(url)?;
+    let url = asset_url(url, SLATE_ASSETS_URL.as_deref())?;
+    let temp_file: TempFile = if matches!(url, Cow::Borrowed(_)) {
+        Url::new(url)
+            .with_authorization(authorization.cloned())
+            .with_headers(headers.cloned().unwrap_or_default())
+            .try_into()?
+    } else {
+        // The credentials of the slates are not sent to the API
+        Url::new(url).try_into()?
+    };
 
     let contents = if temp_file.is_video() {
-        let mut pipeline = FrameCapture::new(temp_file, SLATE_SIZE);
-        pipeline.get_first_frame_contents()?
+        let mut pipeline = FrameCapture::new(temp_file, SLATE_SIZE).with_position(position);
+        pipeline.get_frame_contents()?
     } else {
         let path = temp_file.full_path();
         debug!("Loading slate image from file: {}", path);
@@ -43,6 +71,43 @@ pub fn load_img(url: &str) -> Result<Vec<u8>> {
     Ok(contents)
 }
 
+/// Splits the time offset of a media fragment (e.g. `slate.mp4#t=2.5`) from the URL, used to
+/// select the frame of video slates.
+fn split_time_offset(url: &str) -> Result<(&str, Option<Duration>)> {
+    match url.split_once("#t=") {
+        None => Ok((url, None)),
+        Some((url, seconds)) => {
+            let seconds: f64 = seconds
+                .parse()
+                .ok()
+                .filter(|s: &f64| s.is_finite() && *s >= 0f64)
+                .ok_or_else(|| color_eyre::eyre::eyre!("Invalid time offset: {}", seconds))?;
+            Ok((url, Some(Duration::from_secs_f64(seconds))))
+        }
+    }
+}
+
+/// URL of the slate asset referenced by an `asset://{id}` URL, other URLs are kept.
+fn asset_url<'a>(url: &'a str, assets_url: Option<&str>) -> Result<Cow<'a, str>> {
+    match url.strip_prefix(ASSET_SCHEME) {
+        None => Ok(Cow::Borrowed(url)),
+        Some(id) => {
+            let assets_url = assets_url.ok_or_else(|| {
+                color_eyre::eyre::eyre!(
+                    "{} requires {} to be configured",
+                    url,
+                    SLATE_ASSETS_URL_ENV
+                )
+            })?;
+            Ok(Cow::Owned(format!(
+                "{}/{}",
+                assets_url.trim_end_matches('/'),
+                id
+            )))
+        }
+    }
+}
+
 pub trait FileLike {
     fn full_path(&self) -> String;
 
@@ -59,18 +124,47 @@ pub trait FileLike {
 
 pub struct Url {
     url: String,
+    authorization: Option<HttpAuth>,
+    headers: HashMap<String, String>,
 }
 
 impl Url {
     fn new<S: AsRef<str>>(url: S) -> Self {
         Self {
             url: String::from(url.as_ref()),
+            authorization: None,
+            headers: HashMap::new(),
         }
     }
 
+    /// Credentials used when downloading the file over HTTP.
+    fn with_authorization(mut self, authorization: Option<HttpAuth>) -> Self {
+        self.authorization = authorization;
+        self
+    }
+
+    /// Headers sent when downloading the file over HTTP.
+    fn with_headers(mut self, headers: HashMap<String, String>) -> Self {
+        self.headers = headers;
+        self
+    }
+
     fn is_http(&self) -> bool {
         self.url.starts_with("http://") || self.url.starts_with("https://")
     }
+
+    fn is_s3(&self) -> bool {
+        self.url.starts_with("s3://")
+    }
+
+    /// Bucket and key of an `s3://bucket/key` URL.
+    fn s3_location(&self) -> Result<(&str, &str)> {
+        self.url
+            .trim_start_matches("s3://")
+            .split_once('/')
+            .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+            .ok_or_else(|| color_eyre::eyre::eyre!("Invalid S3 URL: {}", self.url))
+    }
 }
 
 impl FileLike for Url {
@@ -142,19 +236,21 @@ impl TryFrom<Url> for TempFile {
         let f = if url.is_http() {
             let path = url.full_path();
             debug!("Downloading slate from: {}", path);
-            let res = ureq::get(path.as_str())
-                .timeout(Duration::from_secs(10))
-                .timeout_connect(1000)
-                .call();
-            if res.error() {
-                return Err(color_eyre::eyre::eyre!(
-                    "HTTP error ({}) while calling URL of backend: {}",
-                    res.status(),
-                    url.full_path()
-                ));
-            }
+            // Slates are loaded before the actions runtime starts
+            let contents = Builder::new_current_thread()
+                .enable_all()
+                .build()?
+                .block_on(download(&url))?;
+            let mut temp_file = TempFile::new("downloaded", url.extension()?)?;
+            temp_file.write_all(contents.as_slice())?;
+            temp_file
+        } else if url.is_s3() {
+            let (bucket, key) = url.s3_location()?;
+            debug!("Downloading slate from bucket {}: {}", bucket, key);
+            let contents = aws::download_s3_object(bucket, key)
+                .wrap_err_with(|| format!("Failed to download slate: {}", url.full_path()))?;
             let mut temp_file = TempFile::new("downloaded", url.extension()?)?;
-            temp_file.write_all(res.into_reader())?;
+            temp_file.write_all(contents.as_slice())?;
             temp_file
         } else {
             TempFile::from_original(url.full_path().replace("file://", "").as_str())?
@@ -164,24 +260,60 @@ impl TryFrom<Url> for TempFile {
     }
 }
 
+/// Downloads the slate with the credentials and headers of the URL. The client is not the shared
+/// one of the actions: its connections would belong to the runtime of the download, which is
+/// dropped once the slate is loaded.
+async fn download(url: &Url) -> Result<Vec<u8>> {
+    let client = http::standalone_client()?;
+    let mut request = client
+        .get(url.full_path().as_str())
+        .timeout(Duration::from_secs(10));
+    if let Some(authorization) = &url.authorization {
+        request = http::authorize(&client, request, authorization).await?;
+    }
+    for (k, v) in url.headers.iter() {
+        request = request.header(k.as_str(), v.as_str());
+    }
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(color_eyre::eyre::eyre!(
+            "HTTP error ({}) while calling URL of backend: {}",
+            response.status().as_u16(),
+            url.full_path()
+        ));
+    }
+    Ok(response.bytes().await?.to_vec())
+}
+
 pub struct FrameCapture {
     source: TempFile,
     frame_size: (u32, u32),
+    position: Option<Duration>,
 }
 
 impl FrameCapture {
     pub fn new(source: TempFile, frame_size: (u32, u32)) -> Self {
-        Self { source, frame_size }
+        Self {
+            source,
+            frame_size,
+            position: None,
+        }
+    }
+
+    /// Captures the frame at the position of the video instead of the first one.
+    pub fn with_position(mut self, position: Option<Duration>) -> Self {
+        self.position = position;
+        self
     }
 
-    pub fn get_first_frame_contents(&mut self) -> Result<Vec<u8>> {
+    pub fn get_frame_contents(&mut self) -> Result<Vec<u8>> {
         let pipeline = format!(
             "uridecodebin uri=file://{} ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\"",
             self.source.full_path(),
             self.frame_size.0,
             self.frame_size.1
         );
-        for frame in VideoStream::new_from_description(pipeline)? {
+        for frame in VideoStream::new_from_description_at(pipeline, self.position)? {
             match frame? {
                 Some(contents) => return Ok(contents),
                 None => continue,
@@ -190,3 +322,73 @@ impl FrameCapture {
         Err(color_eyre::eyre::eyre!("Failed to capture video frame"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_asset_urls() {
+        assert_eq!(
+            asset_url("asset://0e6c.png", Some("http://hawkeye-api/v1/slates/")).unwrap(),
+            "http://hawkeye-api/v1/slates/0e6c.png"
+        );
+        assert_eq!(
+            asset_url("s3://slates/day.jpg", None).unwrap(),
+            "s3://slates/day.jpg"
+        );
+        assert!(asset_url("asset://0e6c.png", None).is_err());
+    }
+
+    #[test]
+    fn download_with_credentials() {
+        let server = mockito::mock("GET", "/private/slate.jpg")
+            .match_header("authorization", "Bearer secret")
+            .match_header("x-api-key", "key")
+            .with_status(200)
+            .with_body("slate")
+            .create();
+
+        let url = Url::new(format!("{}/private/slate.jpg", mockito::server_url()))
+            .with_authorization(Some(HttpAuth::Bearer {
+                token: "secret".into(),
+            }))
+            .with_headers(
+                [("X-Api-Key".to_string(), "key".to_string())]
+                    .iter()
+                    .cloned()
+                    .collect(),
+            );
+        let temp_file = TempFile::try_from(url).expect("Should download the slate");
+
+        assert_eq!(std::fs::read(temp_file.full_path()).unwrap(), b"slate");
+        server.assert();
+    }
+
+    #[test]
+    fn video_url_time_offset() {
+        assert_eq!(
+            split_time_offset("https://slates/slate.mp4#t=2.5").unwrap(),
+            (
+                "https://slates/slate.mp4",
+                Some(Duration::from_millis(2500))
+            )
+        );
+        assert_eq!(
+            split_time_offset("file:///slates/slate.mp4").unwrap(),
+            ("file:///slates/slate.mp4", None)
+        );
+        assert!(split_time_offset("file:///slates/slate.mp4#t=start").is_err());
+        assert!(split_time_offset("file:///slates/slate.mp4#t=-1").is_err());
+    }
+
+    #[test]
+    fn s3_url_location() {
+        let url = Url::new("s3://slates/day/slate.jpg");
+        assert!(url.is_s3());
+        assert_eq!(url.s3_location().unwrap(), ("slates", "day/slate.jpg"));
+
+        assert!(Url::new("s3://slates").s3_location().is_err());
+        assert!(Url::new("s3:///slate.jpg").s3_location().is_err());
+    }
+}
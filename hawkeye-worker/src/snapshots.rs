@@ -0,0 +1,252 @@
+//! Frames of the transitions, kept in a local directory or S3 to review the detections
+//! afterwards. They are linked from the transitions log.
+use crate::aws;
+use crate::video_stream::Detection;
+use color_eyre::Result;
+use hawkeye_core::models::{self, SnapshotSettings};
+use lazy_static::lazy_static;
+use log::{error, info};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+lazy_static! {
+    static ref KEEPER: Mutex<Option<Keeper>> = Mutex::new(None);
+}
+
+/// Links to the frames of a transition.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshots {
+    /// Frame the new video mode was reported in.
+    pub frame: String,
+    /// Frame before it, unless the transition happened in the first frame.
+    pub previous_frame: Option<String>,
+}
+
+impl Snapshots {
+    pub fn to_json(&self) -> serde_json::Value {
+        json!({
+            "frame": self.frame,
+            "previous_frame": self.previous_frame,
+        })
+    }
+}
+
+enum Store {
+    Dir(PathBuf),
+    S3 { bucket: String, prefix: String },
+}
+
+impl Store {
+    fn new(settings: &SnapshotSettings, watcher_id: &str) -> Result<Self> {
+        if settings.url.starts_with("s3://") {
+            let (bucket, prefix) = models::s3_location(&settings.url)?;
+            let prefix = if prefix.is_empty() {
+                watcher_id.to_string()
+            } else {
+                format!("{}/{}", prefix, watcher_id)
+            };
+            Ok(Store::S3 {
+                bucket: bucket.to_string(),
+                prefix,
+            })
+        } else {
+            let dir = PathBuf::from(&settings.url);
+            fs::create_dir_all(&dir)?;
+            Ok(Store::Dir(dir))
+        }
+    }
+
+    /// Local snapshots are served by the worker.
+    fn link(&self, name: &str) -> String {
+        match self {
+            Store::Dir(_) => format!("/snapshots/{}", name),
+            Store::S3 { bucket, prefix } => format!("s3://{}/{}/{}", bucket, prefix, name),
+        }
+    }
+
+    fn write(&self, name: &str, contents: &[u8]) -> Result<()> {
+        match self {
+            Store::Dir(dir) => Ok(fs::write(dir.join(name), contents)?),
+            Store::S3 { bucket, prefix } => aws::upload_s3_object(
+                bucket,
+                &format!("{}/{}", prefix, name),
+                "image/png",
+                contents.to_vec(),
+            ),
+        }
+    }
+
+    fn delete(&self, name: &str) -> Result<()> {
+        match self {
+            Store::Dir(dir) => Ok(fs::remove_file(dir.join(name))?),
+            Store::S3 { bucket, prefix } => {
+                aws::delete_s3_object(bucket, &format!("{}/{}", prefix, name))
+            }
+        }
+    }
+}
+
+/// Changes to the store for a transition.
+#[derive(Default)]
+struct Job {
+    writes: Vec<(String, Vec<u8>)>,
+    deletes: Vec<String>,
+}
+
+impl Job {
+    fn run(self, store: &Store) {
+        for (name, contents) in self.writes {
+            if let Err(err) = store.write(&name, &contents) {
+                error!("Could not save snapshot {}: {:?}", store.link(&name), err);
+            }
+        }
+        for name in self.deletes {
+            if let Err(err) = store.delete(&name) {
+                error!("Could not delete snapshot {}: {:?}", store.link(&name), err);
+            }
+        }
+    }
+}
+
+struct Keeper {
+    store: Arc<Store>,
+    retention: usize,
+    /// Names of the frames of each kept transition, oldest first.
+    kept: VecDeque<Vec<String>>,
+}
+
+impl Keeper {
+    fn save(
+        &mut self,
+        detection: &Detection,
+        frame: &[u8],
+        previous_frame: Option<&[u8]>,
+    ) -> (Snapshots, Job) {
+        let name = format!(
+            "{}-{}",
+            detection.detected_at.format("%Y-%m-%dT%H-%M-%S%.3fZ"),
+            detection.mode
+        );
+        let mut job = Job::default();
+        job.writes.push((format!("{}.png", name), frame.to_vec()));
+        if let Some(previous_frame) = previous_frame {
+            job.writes
+                .push((format!("{}-previous.png", name), previous_frame.to_vec()));
+        }
+        let snapshots = Snapshots {
+            frame: self.store.link(&job.writes[0].0),
+            previous_frame: job.writes.get(1).map(|(name, _)| self.store.link(name)),
+        };
+
+        self.kept
+            .push_back(job.writes.iter().map(|(name, _)| name.clone()).collect());
+        while self.kept.len() > self.retention {
+            job.deletes
+                .extend(self.kept.pop_front().unwrap_or_default());
+        }
+        (snapshots, job)
+    }
+}
+
+/// Keeps the frames of the transitions of the watcher.
+pub fn configure(settings: &SnapshotSettings, watcher_id: &str) -> Result<()> {
+    *KEEPER.lock().unwrap() = Some(Keeper {
+        store: Arc::new(Store::new(settings, watcher_id)?),
+        retention: settings.retention() as usize,
+        kept: VecDeque::new(),
+    });
+    info!("Keeping the frames of the transitions in {}", settings.url);
+    Ok(())
+}
+
+/// Saves the frames of the transition in the background, returning the links to them.
+pub fn save(
+    detection: &Detection,
+    frame: Option<&[u8]>,
+    previous_frame: Option<&[u8]>,
+) -> Option<Snapshots> {
+    let mut keeper = KEEPER.lock().unwrap();
+    let keeper = keeper.as_mut()?;
+    let (snapshots, job) = keeper.save(detection, frame?, previous_frame);
+    let store = keeper.store.clone();
+    thread::spawn(move || job.run(&store));
+    Some(snapshots)
+}
+
+/// PNG image of a snapshot kept in the local directory.
+pub fn image(name: &str) -> Option<Vec<u8>> {
+    if name.starts_with('.') || name.contains('/') {
+        return None;
+    }
+    match KEEPER.lock().unwrap().as_ref()?.store.as_ref() {
+        Store::Dir(dir) => fs::read(dir.join(name)).ok(),
+        Store::S3 { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{DateTime, Utc};
+    use hawkeye_core::models::VideoMode;
+
+    #[test]
+    fn keep_snapshots_of_latest_transitions() {
+        let dir = std::env::temp_dir().join(format!("hawkeye-snapshots-{}", std::process::id()));
+        let settings = SnapshotSettings {
+            url: dir.to_string_lossy().to_string(),
+            retention: Some(2),
+        };
+        let mut keeper = Keeper {
+            store: Arc::new(Store::new(&settings, "watcher-id").unwrap()),
+            retention: 2,
+            kept: VecDeque::new(),
+        };
+        let detection = |mode, at: &str| Detection {
+            detected_at: at.parse::<DateTime<Utc>>().unwrap(),
+            ..Detection::now(mode)
+        };
+
+        let (snapshots, job) = keeper.save(
+            &detection(VideoMode::Slate, "2021-11-30T10:00:00Z"),
+            &[1],
+            Some(&[0]),
+        );
+        job.run(&keeper.store);
+        assert_eq!(
+            snapshots,
+            Snapshots {
+                frame: "/snapshots/2021-11-30T10-00-00.000Z-slate.png".to_string(),
+                previous_frame: Some(
+                    "/snapshots/2021-11-30T10-00-00.000Z-slate-previous.png".to_string()
+                ),
+            }
+        );
+        assert_eq!(
+            fs::read(dir.join("2021-11-30T10-00-00.000Z-slate-previous.png")).unwrap(),
+            vec![0]
+        );
+
+        for at in &["2021-11-30T10:01:00Z", "2021-11-30T10:02:00Z"] {
+            let (_, job) = keeper.save(&detection(VideoMode::Content, at), &[2], None);
+            job.run(&keeper.store);
+        }
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "2021-11-30T10-01-00.000Z-content.png",
+                "2021-11-30T10-02-00.000Z-content.png"
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
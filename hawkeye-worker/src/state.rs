@@ -0,0 +1,115 @@
+//! Live state of the detection, for the API and UI to show the state of the channel.
+use crate::control;
+use crate::history;
+use crate::img_detector::SlateMatch;
+use crate::metrics::SLATE_SIMILARITY;
+use chrono::{DateTime, Utc};
+use hawkeye_core::models::VideoMode;
+use lazy_static::lazy_static;
+use prometheus::core::Collector;
+use serde_json::json;
+use std::sync::Mutex;
+
+lazy_static! {
+    static ref STATE: Mutex<DetectionState> = Mutex::new(DetectionState::default());
+}
+
+#[derive(Default)]
+struct DetectionState {
+    mode: Option<VideoMode>,
+    mode_since: Option<DateTime<Utc>>,
+    /// Slate image matched when the mode changed to slate.
+    slate: Option<String>,
+    last_transition: Option<(VideoMode, VideoMode, DateTime<Utc>)>,
+}
+
+/// Records the video mode reported to the actions, and the slate image it matched.
+pub fn observe(mode: VideoMode, detected_at: DateTime<Utc>, slate: Option<&SlateMatch>) {
+    let mut state = STATE.lock().unwrap();
+    if state.mode == Some(mode) {
+        return;
+    }
+    if let Some(previous) = state.mode {
+        state.last_transition = Some((previous, mode, detected_at));
+    }
+    state.mode = Some(mode);
+    state.mode_since = Some(detected_at);
+    state.slate = slate.map(|slate| slate.slate_url.clone());
+}
+
+/// Latest DSSIM score of each slate image.
+fn slate_scores() -> Vec<serde_json::Value> {
+    SLATE_SIMILARITY
+        .collect()
+        .iter()
+        .flat_map(|family| family.get_metric())
+        .map(|metric| {
+            let slate = metric
+                .get_label()
+                .iter()
+                .find(|label| label.get_name() == "slate")
+                .map(|label| label.get_value());
+            json!({
+                "slate": slate,
+                "similarity": metric.get_gauge().get_value(),
+            })
+        })
+        .collect()
+}
+
+pub fn to_json() -> serde_json::Value {
+    let state = STATE.lock().unwrap();
+    json!({
+        "mode": state.mode,
+        "mode_since": state.mode_since.map(|since| since.to_rfc3339()),
+        "mode_seconds": state.mode_since.map(|since| (Utc::now() - since).num_seconds()),
+        "slate": state.slate,
+        "last_transition": state.last_transition.map(|(from, to, at)| json!({
+            "from": from,
+            "to": to,
+            "at": at.to_rfc3339(),
+        })),
+        "detection_paused": control::detection_paused(),
+        "slates": slate_scores(),
+        "actions": history::latest(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn track_mode_and_transitions() {
+        let started = Utc::now() - Duration::seconds(30);
+        observe(VideoMode::Content, started, None);
+        observe(VideoMode::Content, started + Duration::seconds(10), None);
+        SLATE_SIMILARITY
+            .with_label_values(&["https://example.com/state-slate.jpg"])
+            .set(0.25);
+
+        let state = to_json();
+        assert_eq!(state["mode"], "content");
+        assert_eq!(state["mode_since"], started.to_rfc3339());
+        assert!(state["mode_seconds"].as_i64().unwrap() >= 30);
+        assert!(state["last_transition"].is_null());
+        assert!(state["slates"].as_array().unwrap().contains(&json!({
+            "slate": "https://example.com/state-slate.jpg",
+            "similarity": 0.25,
+        })));
+
+        let switched = started + Duration::seconds(20);
+        let slate = SlateMatch {
+            slate_url: "https://example.com/state-slate.jpg".to_string(),
+            dssim: 120,
+        };
+        observe(VideoMode::Slate, switched, Some(&slate));
+        let state = to_json();
+        assert_eq!(state["mode"], "slate");
+        assert_eq!(state["slate"], "https://example.com/state-slate.jpg");
+        assert_eq!(state["last_transition"]["from"], "content");
+        assert_eq!(state["last_transition"]["to"], "slate");
+        assert_eq!(state["last_transition"]["at"], switched.to_rfc3339());
+    }
+}
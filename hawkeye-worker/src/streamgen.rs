@@ -0,0 +1,210 @@
+use crate::calibration::to_url;
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use gst::prelude::*;
+use gstreamer as gst;
+use log::info;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Size of the generated stream, the content and the slate are scaled to it.
+const FRAME_CAPS: &str = "video/x-raw,format=I420,width=1280,height=720";
+const FRAME_RATE: &str = "framerate=25/1";
+
+/// Settings of the generated stream.
+pub struct StreamGenSettings {
+    pub host: String,
+    pub port: u16,
+    /// URL or path of the video looped, a test pattern without it
+    pub video: Option<String>,
+    /// URL or path of the slate image spliced in the stream
+    pub slate: Option<String>,
+    pub slate_interval: Duration,
+    pub slate_duration: Duration,
+}
+
+/// Pipeline sending the content, switched to the slate by the `selector`, as RTP/MPEG-TS like a
+/// broadcast encoder.
+fn pipeline_description(settings: &StreamGenSettings) -> String {
+    let content = match settings.video.as_deref() {
+        Some(video) => format!(
+            "uridecodebin uri=\"{}\" ! videorate ! videoconvert ! videoscale ! {},{}",
+            to_url(video),
+            FRAME_CAPS,
+            FRAME_RATE
+        ),
+        None => format!(
+            "videotestsrc is-live=true pattern=ball ! {},{}",
+            FRAME_CAPS, FRAME_RATE
+        ),
+    };
+    let output = format!(
+        "videoconvert ! x264enc tune=zerolatency speed-preset=ultrafast key-int-max=25 ! mpegtsmux alignment=7 ! rtpmp2tpay ! udpsink host={} port={}",
+        settings.host, settings.port
+    );
+    match settings.slate.as_deref() {
+        Some(slate) => format!(
+            "input-selector name=selector ! {} {} ! selector.sink_0 uridecodebin uri=\"{}\" ! videoconvert ! videoscale ! {} ! imagefreeze ! video/x-raw,{} ! selector.sink_1",
+            output,
+            content,
+            to_url(slate),
+            FRAME_CAPS,
+            FRAME_RATE
+        ),
+        None => format!("{} ! {}", content, output),
+    }
+}
+
+/// Whether the slate is shown at this time of the stream: the content plays for the interval,
+/// then the slate for its duration, in a loop.
+fn slate_active(elapsed: Duration, interval: Duration, duration: Duration) -> bool {
+    let cycle = (interval + duration).as_millis();
+    cycle > 0 && elapsed.as_millis() % cycle >= interval.as_millis()
+}
+
+/// Sends a looping test stream to a worker, splicing in the slate at intervals, for the
+/// integration tests and the demos without a broadcast encoder. Runs until interrupted.
+pub fn stream(settings: StreamGenSettings) -> Result<()> {
+    let description = pipeline_description(&settings);
+    let pipeline = gst::parse_launch(description.as_str())
+        .context("Pipeline description invalid, cannot create")?
+        .downcast::<gst::Pipeline>()
+        .map_err(|_| eyre!("Expected a gst::Pipeline"))?;
+    let bus = pipeline
+        .bus()
+        .ok_or_else(|| eyre!("Pipeline without bus. Shouldn't happen!"))?;
+    let selector = pipeline.by_name("selector");
+    let pads = match selector.as_ref() {
+        Some(selector) => Some((
+            selector
+                .static_pad("sink_0")
+                .ok_or_else(|| eyre!("Content pad not found"))?,
+            selector
+                .static_pad("sink_1")
+                .ok_or_else(|| eyre!("Slate pad not found"))?,
+        )),
+        None => None,
+    };
+
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting termination handler");
+
+    pipeline
+        .set_state(gst::State::Playing)
+        .context("Cannot start pipeline")?;
+    info!(
+        "Streaming to rtp://{}:{}: {}",
+        settings.host, settings.port, description
+    );
+
+    let mut started = Instant::now();
+    let mut showing_slate = false;
+    let result = loop {
+        if !running.load(Ordering::SeqCst) {
+            break Ok(());
+        }
+        if let Some(msg) = bus.timed_pop(gst::ClockTime::from_mseconds(100)) {
+            use gst::MessageView;
+
+            match msg.view() {
+                MessageView::Eos(..) => {
+                    // The video is looped by restarting the pipeline, with the content first
+                    info!("End of the video, looping..");
+                    if let Err(err) = pipeline
+                        .set_state(gst::State::Null)
+                        .and_then(|_| pipeline.set_state(gst::State::Playing))
+                    {
+                        break Err(eyre!("Cannot restart pipeline: {}", err));
+                    }
+                    started = Instant::now();
+                    showing_slate = false;
+                }
+                MessageView::Error(err) => {
+                    break Err(eyre!(
+                        "Error returned by pipeline: {} ({:?})",
+                        err.error(),
+                        err.debug()
+                    ));
+                }
+                _ => (),
+            }
+        }
+        if let (Some(selector), Some((content_pad, slate_pad))) = (selector.as_ref(), pads.as_ref())
+        {
+            let slate = slate_active(
+                started.elapsed(),
+                settings.slate_interval,
+                settings.slate_duration,
+            );
+            if slate != showing_slate {
+                info!(
+                    "Switching to the {}",
+                    if slate { "slate" } else { "content" }
+                );
+                let pad = if slate { slate_pad } else { content_pad };
+                if let Err(err) = selector.set_property("active-pad", pad) {
+                    break Err(eyre!("Cannot switch the stream: {}", err));
+                }
+                showing_slate = slate;
+            }
+        }
+    };
+
+    if pipeline.set_state(gst::State::Null).is_err() {
+        log::error!("Could not stop pipeline");
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slate_spliced_at_intervals() {
+        let interval = Duration::from_secs(60);
+        let duration = Duration::from_secs(10);
+        let at = |seconds| slate_active(Duration::from_secs(seconds), interval, duration);
+        assert!(!at(0));
+        assert!(!at(59));
+        assert!(at(60));
+        assert!(at(69));
+        assert!(!at(70));
+        assert!(at(135));
+        assert!(!slate_active(
+            Duration::from_secs(5),
+            Duration::from_secs(0),
+            Duration::from_secs(0)
+        ));
+    }
+
+    #[test]
+    fn pipeline_with_slate() {
+        let settings = StreamGenSettings {
+            host: "127.0.0.1".to_string(),
+            port: 5000,
+            video: None,
+            slate: Some("/local/slate.jpg".to_string()),
+            slate_interval: Duration::from_secs(60),
+            slate_duration: Duration::from_secs(10),
+        };
+        let description = pipeline_description(&settings);
+        assert!(description.starts_with("input-selector name=selector ! videoconvert"));
+        assert!(description.contains("videotestsrc is-live=true"));
+        assert!(description.contains("uridecodebin uri=\"file:///local/slate.jpg\""));
+        assert!(description.ends_with("selector.sink_1"));
+
+        let settings = StreamGenSettings {
+            slate: None,
+            ..settings
+        };
+        assert!(pipeline_description(&settings).ends_with("udpsink host=127.0.0.1 port=5000"));
+    }
+}
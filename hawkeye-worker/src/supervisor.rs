@@ -0,0 +1,337 @@
+use crate::watcher_file;
+use chrono::{DateTime, Utc};
+use color_eyre::Result;
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::runtime::Builder;
+use warp::Filter;
+
+/// Interval between the scans of the directory of watchers.
+const SCAN_INTERVAL: Duration = Duration::from_secs(2);
+/// Delay before a worker that exited is started again.
+const RESTART_DELAY: Duration = Duration::from_secs(10);
+/// Time a worker has to stop after it's terminated, before it's killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref STATUS: Mutex<Vec<serde_json::Value>> = Mutex::new(Vec::new());
+}
+
+/// The worker process running the detection of a watcher configuration.
+struct Worker {
+    path: PathBuf,
+    modified: SystemTime,
+    watcher_id: Option<String>,
+    ingest_port: Option<u32>,
+    child: Option<Child>,
+    started_at: Option<DateTime<Utc>>,
+    restart_at: Option<Instant>,
+    restarts: u32,
+    exit_code: Option<i32>,
+    error: Option<String>,
+}
+
+impl Worker {
+    fn new(path: PathBuf, modified: SystemTime) -> Self {
+        Self {
+            path,
+            modified,
+            watcher_id: None,
+            ingest_port: None,
+            child: None,
+            started_at: None,
+            restart_at: None,
+            restarts: 0,
+            exit_code: None,
+            error: None,
+        }
+    }
+
+    /// Starts a worker process for the configuration, invalid configurations are only reported
+    /// until they change.
+    fn start(&mut self, dry_run: bool) {
+        self.restart_at = None;
+        let watcher =
+            watcher_file::load(&self.path).and_then(|watcher| watcher.is_valid().map(|_| watcher));
+        let watcher = match watcher {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                warn!("Invalid watcher {}: {:#}", self.path.display(), err);
+                self.error = Some(format!("{:#}", err));
+                return;
+            }
+        };
+        self.watcher_id = watcher.id;
+        self.ingest_port = Some(watcher.source.ingest_port);
+
+        let mut command = match std::env::current_exe() {
+            Ok(exe) => Command::new(exe),
+            Err(err) => {
+                self.error = Some(err.to_string());
+                return;
+            }
+        };
+        if dry_run {
+            command.arg("--dry-run");
+        }
+        match command.arg(&self.path).spawn() {
+            Ok(child) => {
+                info!("Started worker {} for {}", child.id(), self.path.display());
+                self.child = Some(child);
+                self.started_at = Some(Utc::now());
+                self.exit_code = None;
+                self.error = None;
+            }
+            Err(err) => {
+                error!(
+                    "Could not start worker for {}: {}",
+                    self.path.display(),
+                    err
+                );
+                self.error = Some(err.to_string());
+                self.restart_at = Some(Instant::now() + RESTART_DELAY);
+            }
+        }
+    }
+
+    /// Records the exit of the worker process, it's started again after a delay.
+    fn check_exited(&mut self) {
+        let status = match self.child.as_mut().map(Child::try_wait) {
+            Some(Ok(Some(status))) => status,
+            Some(Ok(None)) | None => return,
+            Some(Err(err)) => {
+                error!(
+                    "Could not check worker for {}: {}",
+                    self.path.display(),
+                    err
+                );
+                return;
+            }
+        };
+        warn!(
+            "Worker for {} exited with {}, restarting in {}s",
+            self.path.display(),
+            status,
+            RESTART_DELAY.as_secs()
+        );
+        self.child = None;
+        self.exit_code = status.code();
+        self.restart_at = Some(Instant::now() + RESTART_DELAY);
+    }
+
+    /// Terminates the worker process, like Kubernetes would, so it stops gracefully.
+    fn stop(&mut self) {
+        let mut child = match self.child.take() {
+            Some(child) => child,
+            None => return,
+        };
+        info!("Stopping worker {} for {}", child.id(), self.path.display());
+        let terminated = Command::new("kill")
+            .arg("-TERM")
+            .arg(child.id().to_string())
+            .status()
+            .map(|status| status.success())
+            .unwrap_or(false);
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        while terminated && Instant::now() < deadline {
+            if let Ok(Some(_)) = child.try_wait() {
+                return;
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        warn!(
+            "Worker for {} did not stop, killing it",
+            self.path.display()
+        );
+        if let Err(err) = child.kill().and_then(|_| child.wait().map(drop)) {
+            error!("Could not kill worker for {}: {}", self.path.display(), err);
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let status = match (&self.child, &self.restart_at) {
+            (Some(_), _) => "running",
+            (None, Some(_)) => "restarting",
+            (None, None) => "invalid",
+        };
+        json!({
+            "file": self.path.file_name().map(|name| name.to_string_lossy()),
+            "watcher_id": self.watcher_id,
+            "status": status,
+            "pid": self.child.as_ref().map(Child::id),
+            "ingest_port": self.ingest_port,
+            "started_at": self.started_at.map(|started_at| started_at.to_rfc3339()),
+            "restarts": self.restarts,
+            "exit_code": self.exit_code,
+            "error": self.error,
+        })
+    }
+}
+
+/// The watcher configurations in the directory, with the time they were last modified.
+fn list_configs(dir: &Path) -> Result<BTreeMap<PathBuf, SystemTime>> {
+    let mut configs = BTreeMap::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if path.extension() == Some(OsStr::new("json")) {
+            configs.insert(path, entry.metadata()?.modified()?);
+        }
+    }
+    Ok(configs)
+}
+
+/// Starts, restarts and stops the workers to match the configurations in the directory.
+fn reconcile(workers: &mut BTreeMap<PathBuf, Worker>, dir: &Path, dry_run: bool) {
+    let configs = match list_configs(dir) {
+        Ok(configs) => configs,
+        Err(err) => {
+            error!("Could not list watchers in {}: {}", dir.display(), err);
+            return;
+        }
+    };
+
+    let changed: Vec<PathBuf> = workers
+        .iter()
+        .filter(|(path, worker)| configs.get(*path) != Some(&worker.modified))
+        .map(|(path, _)| path.clone())
+        .collect();
+    for path in changed {
+        if let Some(mut worker) = workers.remove(&path) {
+            worker.stop();
+        }
+    }
+
+    for (path, modified) in configs {
+        let worker = workers
+            .entry(path.clone())
+            .or_insert_with(|| Worker::new(path, modified));
+        if worker.started_at.is_none() && worker.error.is_none() {
+            worker.start(dry_run);
+        }
+        worker.check_exited();
+        if matches!(worker.restart_at, Some(restart_at) if restart_at <= Instant::now()) {
+            worker.restarts += 1;
+            worker.start(dry_run);
+        }
+    }
+
+    *STATUS.lock().unwrap() = workers.values().map(Worker::to_json).collect();
+}
+
+fn status_reply(file: Option<String>) -> Box<dyn warp::Reply> {
+    let status = STATUS.lock().unwrap();
+    match file {
+        None => Box::new(warp::reply::json(&*status)),
+        Some(file) => match status.iter().find(|worker| worker["file"] == json!(file)) {
+            Some(worker) => Box::new(warp::reply::json(worker)),
+            None => Box::new(warp::reply::with_status(
+                "Unknown watcher",
+                warp::http::StatusCode::NOT_FOUND,
+            )),
+        },
+    }
+}
+
+fn run_status_service(address: SocketAddr, running: Arc<AtomicBool>) {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("supervisor_app")
+        .enable_all()
+        .build()
+        .unwrap();
+    let routes = warp::get().and(
+        warp::path!("watchers")
+            .map(|| status_reply(None))
+            .or(warp::path!("watchers" / String).map(|file| status_reply(Some(file)))),
+    );
+    runtime.block_on(async move {
+        let shutdown = async move {
+            while running.load(Ordering::SeqCst) {
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        };
+        match warp::serve(routes).try_bind_with_graceful_shutdown(address, shutdown) {
+            Ok((address, server)) => {
+                info!(
+                    "Serving the status of the watchers at http://{}/watchers",
+                    address
+                );
+                server.await;
+            }
+            Err(err) => error!("Could not start the status server at {}: {}", address, err),
+        }
+    });
+}
+
+/// Runs a worker process for each watcher configuration in the directory, for the deployments
+/// without Kubernetes. Workers are started for the new files, restarted when their file changes
+/// or they exit, and stopped when their file is removed.
+pub fn supervise(dir: &Path, address: SocketAddr, dry_run: bool) -> Result<()> {
+    let running = Arc::new(AtomicBool::new(true));
+    let r = running.clone();
+    ctrlc::set_handler(move || {
+        r.store(false, Ordering::SeqCst);
+    })
+    .expect("Error setting termination handler");
+
+    let status_running = running.clone();
+    let status_server = thread::spawn(move || run_status_service(address, status_running));
+
+    info!("Supervising the watchers in {}", dir.display());
+    let mut workers = BTreeMap::new();
+    while running.load(Ordering::SeqCst) {
+        reconcile(&mut workers, dir, dry_run);
+        let scanned = Instant::now();
+        while running.load(Ordering::SeqCst) && scanned.elapsed() < SCAN_INTERVAL {
+            thread::sleep(Duration::from_millis(100));
+        }
+    }
+
+    for worker in workers.values_mut() {
+        worker.stop();
+    }
+    if status_server.join().is_err() {
+        error!("Status server ended unexpectedly!");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_watchers_are_not_started() {
+        let dir = std::env::temp_dir().join(format!("watchers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("invalid.json"), "{\"slate_url\": ").unwrap();
+        fs::write(dir.join("README.md"), "Not a watcher").unwrap();
+
+        let mut workers = BTreeMap::new();
+        reconcile(&mut workers, &dir, true);
+        assert_eq!(workers.len(), 1);
+        let status = STATUS.lock().unwrap().clone();
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0]["file"], "invalid.json");
+        assert_eq!(status[0]["status"], "invalid");
+        assert!(status[0]["error"].is_string());
+
+        fs::remove_file(dir.join("invalid.json")).unwrap();
+        reconcile(&mut workers, &dir, true);
+        assert!(workers.is_empty());
+        assert!(STATUS.lock().unwrap().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
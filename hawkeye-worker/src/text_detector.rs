@@ -0,0 +1,60 @@
+use color_eyre::eyre::WrapErr;
+use color_eyre::Result;
+use image::imageops::FilterType;
+use image::ImageFormat;
+use leptess::LepTess;
+use regex::Regex;
+use std::cell::RefCell;
+
+/// Frames are captured in a resolution too small for the OCR engine to recognize text, so they
+/// are upscaled by this factor before looking for text.
+const UPSCALE_FACTOR: u32 = 4;
+
+/// Detects a slate by the text present in the frame, instead of comparing the frame with a
+/// reference image.
+pub struct TextDetector {
+    ocr: RefCell<LepTess>,
+    pattern: Regex,
+}
+
+impl TextDetector {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let ocr = LepTess::new(None, "eng").wrap_err("Failed to initialize the OCR engine")?;
+        let pattern = Regex::new(pattern).wrap_err("Invalid slate text expression")?;
+
+        Ok(Self {
+            ocr: RefCell::new(ocr),
+            pattern,
+        })
+    }
+
+    pub fn is_match(&self, image_buffer: &[u8]) -> bool {
+        match self.extract_text(image_buffer) {
+            Ok(text) => {
+                log::trace!("Text found in frame: {:?}", text);
+                self.pattern.is_match(text.as_str())
+            }
+            Err(err) => {
+                log::debug!("Could not extract text from frame: {:#}", err);
+                false
+            }
+        }
+    }
+
+    fn extract_text(&self, image_buffer: &[u8]) -> Result<String> {
+        let frame = image::load_from_memory(image_buffer)?;
+        let frame = frame
+            .resize(
+                frame.width() * UPSCALE_FACTOR,
+                frame.height() * UPSCALE_FACTOR,
+                FilterType::Triangle,
+            )
+            .grayscale();
+        let mut contents = Vec::new();
+        frame.write_to(&mut contents, ImageFormat::Png)?;
+
+        let mut ocr = self.ocr.borrow_mut();
+        ocr.set_image_from_mem(contents.as_slice())?;
+        Ok(ocr.get_utf8_text()?)
+    }
+}
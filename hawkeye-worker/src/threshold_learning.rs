@@ -0,0 +1,104 @@
+use log::{info, warn};
+use std::time::Duration;
+
+#[cfg(test)]
+use sn_fake_clock::FakeClock as Instant;
+#[cfg(not(test))]
+use std::time::Instant;
+
+/// Records the DSSIM scores of the frames compared with a slate during the learning window and
+/// recommends a threshold separating the frames of the slate from the content.
+pub struct ThresholdLearner {
+    slate: String,
+    window: Duration,
+    started_at: Option<Instant>,
+    scores: Vec<(u32, bool)>,
+    done: bool,
+}
+
+impl ThresholdLearner {
+    pub fn new(slate: &str, window: Duration) -> Self {
+        Self {
+            slate: slate.to_string(),
+            window,
+            started_at: None,
+            scores: Vec::new(),
+            done: false,
+        }
+    }
+
+    pub fn is_learning(&self) -> bool {
+        !self.done
+    }
+
+    /// Records the score of a frame and whether it matched the slate with the current threshold,
+    /// returns the recommended threshold once the learning window is over.
+    pub fn observe(&mut self, score: u32, matched: bool) -> Option<u32> {
+        if self.done {
+            return None;
+        }
+        let started_at = *self.started_at.get_or_insert_with(Instant::now);
+        self.scores.push((score, matched));
+        if started_at.elapsed() < self.window {
+            return None;
+        }
+
+        self.done = true;
+        let scores = std::mem::take(&mut self.scores);
+        let matched = scores.iter().filter(|(_, matched)| *matched).count();
+        let recommendation = suggest_threshold(scores.iter().map(|(score, _)| *score).collect());
+        match recommendation {
+            Some(threshold) => info!(
+                "Recommended threshold for slate {}: {} ({} matched and {} unmatched frames)",
+                self.slate,
+                threshold,
+                matched,
+                scores.len() - matched
+            ),
+            None => warn!(
+                "Could not learn the threshold for slate {}, all {} frames had the same score",
+                self.slate,
+                scores.len()
+            ),
+        }
+        recommendation
+    }
+}
+
+/// Suggests the middle of the largest gap between the scores, which should separate the frames
+/// matching the slate from the content frames.
+pub fn suggest_threshold(mut scores: Vec<u32>) -> Option<u32> {
+    scores.sort_unstable();
+    scores
+        .windows(2)
+        .filter(|pair| pair[1] > pair[0])
+        .max_by_key(|pair| pair[1] - pair[0])
+        .map(|pair| pair[0] + (pair[1] - pair[0]) / 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sn_fake_clock::FakeClock;
+
+    #[test]
+    fn threshold_splits_scores_at_largest_gap() {
+        assert_eq!(suggest_threshold(vec![620, 12, 30, 700, 5]), Some(325));
+        assert_eq!(suggest_threshold(vec![10, 10]), None);
+        assert_eq!(suggest_threshold(vec![]), None);
+    }
+
+    #[test]
+    fn learner_recommends_threshold_after_window() {
+        let mut learner = ThresholdLearner::new("slate", Duration::from_secs(60));
+        assert_eq!(learner.observe(20, true), None);
+        FakeClock::advance_time(30 * 1000);
+        assert_eq!(learner.observe(640, false), None);
+        assert!(learner.is_learning());
+
+        FakeClock::advance_time(30 * 1000);
+        assert_eq!(learner.observe(600, false), Some(310));
+        assert!(!learner.is_learning());
+        assert_eq!(learner.observe(10, true), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! Log of the changes of video mode reported to the actions, to reconstruct the recent state
+//! changes of the channel.
+use crate::snapshots::Snapshots;
+use crate::video_stream::Detection;
+use chrono::{DateTime, Utc};
+use hawkeye_core::models::VideoMode;
+use hawkeye_core::utils::add_sentry_breadcrumb;
+use lazy_static::lazy_static;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Maximum number of transitions kept, the oldest are dropped first.
+const CAPACITY: usize = 1000;
+
+lazy_static! {
+    static ref TRANSITIONS: Mutex<VecDeque<TransitionChange>> = Mutex::new(VecDeque::new());
+}
+
+/// A change of video mode of the stream.
+struct TransitionChange {
+    /// Mode before the change, none for the first mode found in the stream.
+    from: Option<VideoMode>,
+    to: VideoMode,
+    /// When the stream entered the previous mode.
+    from_since: Option<DateTime<Utc>>,
+    detected_at: DateTime<Utc>,
+    slate_url: Option<String>,
+    /// DSSIM score of the slate image matching the frame.
+    similarity: Option<f64>,
+    snapshots: Option<Snapshots>,
+}
+
+impl TransitionChange {
+    fn to_json(&self) -> serde_json::Value {
+        json!({
+            "from": self.from,
+            "to": self.to,
+            "from_since": self.from_since.map(|since| since.to_rfc3339()),
+            "detected_at": self.detected_at.to_rfc3339(),
+            "slate_url": self.slate_url,
+            "similarity": self.similarity,
+            "snapshots": self.snapshots.as_ref().map(Snapshots::to_json),
+        })
+    }
+}
+
+/// The mode reported to the actions is different from the last one.
+pub fn changed(mode: VideoMode) -> bool {
+    let transitions = TRANSITIONS.lock().unwrap();
+    transitions.back().map(|last| last.to) != Some(mode)
+}
+
+/// Keeps the mode reported to the actions, if it changed, with the frames saved for it.
+pub fn record(detection: &Detection, snapshots: Option<Snapshots>) {
+    let mut transitions = TRANSITIONS.lock().unwrap();
+    let previous = transitions.back().map(|last| (last.to, last.detected_at));
+    if previous.map(|(mode, _)| mode) == Some(detection.mode) {
+        return;
+    }
+    let slate = detection.slate.as_ref();
+    let transition = TransitionChange {
+        from: previous.map(|(mode, _)| mode),
+        to: detection.mode,
+        from_since: previous.map(|(_, since)| since),
+        detected_at: detection.detected_at,
+        slate_url: slate.map(|slate| slate.slate_url.clone()),
+        similarity: slate.map(|slate| slate.dssim as f64 / 1000f64),
+        snapshots,
+    };
+    add_sentry_breadcrumb(
+        "transition",
+        log::Level::Info,
+        match transition.from {
+            Some(from) => format!("{} -> {}", from, transition.to),
+            None => format!("Stream started in {}", transition.to),
+        },
+        &[
+            ("slate_url", json!(transition.slate_url)),
+            ("similarity", json!(transition.similarity)),
+        ],
+    );
+    if transitions.len() >= CAPACITY {
+        transitions.pop_front();
+    }
+    transitions.push_back(transition);
+}
+
+/// Kept transitions detected after `since`, oldest first.
+pub fn list(since: Option<DateTime<Utc>>) -> serde_json::Value {
+    let transitions = TRANSITIONS.lock().unwrap();
+    serde_json::Value::Array(
+        transitions
+            .iter()
+            .filter(|transition| match since {
+                Some(since) => transition.detected_at > since,
+                None => true,
+            })
+            .map(TransitionChange::to_json)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::img_detector::SlateMatch;
+    use chrono::Duration;
+
+    #[test]
+    fn record_transitions() {
+        let started = Utc::now() - Duration::minutes(90);
+        let detection = |mode, minutes| Detection {
+            detected_at: started + Duration::minutes(minutes),
+            ..Detection::now(mode)
+        };
+        record(&detection(VideoMode::Content, 0), None);
+        assert!(!changed(VideoMode::Content));
+        assert!(changed(VideoMode::Slate));
+        record(&detection(VideoMode::Content, 10), None);
+        record(
+            &detection(VideoMode::Slate, 40).with_slate(Some(SlateMatch {
+                slate_url: "https://example.com/slate.jpg".to_string(),
+                dssim: 5,
+            })),
+            Some(Snapshots {
+                frame: "/snapshots/slate.png".to_string(),
+                previous_frame: None,
+            }),
+        );
+        record(&detection(VideoMode::Content, 60), None);
+
+        let transitions = list(None);
+        let transitions = transitions.as_array().unwrap();
+        assert_eq!(transitions.len(), 3);
+        assert!(transitions[0]["from"].is_null());
+        assert_eq!(transitions[1]["from"], "content");
+        assert_eq!(transitions[1]["to"], "slate");
+        assert_eq!(transitions[1]["from_since"], started.to_rfc3339());
+        assert_eq!(transitions[1]["slate_url"], "https://example.com/slate.jpg");
+        assert_eq!(transitions[1]["similarity"], 0.005);
+        assert_eq!(transitions[1]["snapshots"]["frame"], "/snapshots/slate.png");
+        assert!(transitions[2]["slate_url"].is_null());
+        assert!(transitions[2]["snapshots"].is_null());
+
+        // The last hour only
+        let last_hour = list(Some(Utc::now() - Duration::hours(1)));
+        assert_eq!(last_hour.as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,139 @@
+use crate::slate;
+use crate::video_stream::VideoStream;
+use crate::watcher_file;
+use color_eyre::{eyre::eyre, Result};
+use std::net::{TcpListener, UdpSocket};
+use std::path::Path;
+
+/// Results of the checks of a watcher configuration.
+#[derive(Default)]
+struct Report {
+    checks: Vec<(String, Result<()>)>,
+}
+
+impl Report {
+    fn check(&mut self, name: impl Into<String>, result: Result<()>) -> bool {
+        let passed = result.is_ok();
+        self.checks.push((name.into(), result));
+        passed
+    }
+
+    fn failures(&self) -> usize {
+        self.checks
+            .iter()
+            .filter(|(_, result)| result.is_err())
+            .count()
+    }
+
+    fn print(&self) {
+        for (name, result) in self.checks.iter() {
+            match result {
+                Ok(()) => println!("ok     {}", name),
+                Err(err) => println!("error  {}: {:#}", name, err),
+            }
+        }
+    }
+}
+
+/// The ingest port is bound twice by the worker: for the stream (UDP) and for the metrics (TCP).
+fn port_free(port: u32) -> Result<()> {
+    if port > u16::MAX as u32 {
+        return Err(eyre!("Invalid port"));
+    }
+    UdpSocket::bind(("0.0.0.0", port as u16))?;
+    TcpListener::bind(("0.0.0.0", port as u16))?;
+    Ok(())
+}
+
+/// Runs the checks of the watcher, the deeper checks only when the configuration is valid.
+fn check_watcher(path: &Path) -> Report {
+    let mut report = Report::default();
+    let watcher = match watcher_file::load(path) {
+        Ok(watcher) => {
+            report.check("watcher configuration parsed", Ok(()));
+            watcher
+        }
+        Err(err) => {
+            report.check("watcher configuration parsed", Err(err));
+            return report;
+        }
+    };
+    if !report.check("watcher configuration valid", watcher.is_valid()) {
+        return report;
+    }
+
+    for url in watcher.slate_urls() {
+        let result = slate::load_img(
+            url,
+            watcher.slate_authorization.as_ref(),
+            watcher.slate_headers.as_ref(),
+        );
+        // Query strings are left out of the report, they might contain credentials
+        report.check(
+            format!("slate {} loaded", url.split('?').next().unwrap_or_default()),
+            result.map(drop),
+        );
+    }
+
+    let port = watcher.source.ingest_port;
+    if report.check(format!("port {} free", port), port_free(port)) {
+        report.check(
+            "pipeline created",
+            VideoStream::new(port, watcher.source.container, watcher.source.codec).map(drop),
+        );
+    }
+    report
+}
+
+/// Checks the watcher configuration before it's deployed, like in the CI of the configurations.
+/// Fails when any check fails.
+pub fn validate(path: &Path) -> Result<()> {
+    let report = check_watcher(path);
+    report.print();
+    match report.failures() {
+        0 => Ok(()),
+        failures => Err(eyre!(
+            "{} of {} checks failed for {}",
+            failures,
+            report.checks.len(),
+            path.display()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+
+    fn write_config(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        File::create(&path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn report_invalid_configurations() {
+        let path = write_config("not-a-watcher", "{\"slate_url\": ");
+        let report = check_watcher(&path);
+        assert_eq!(report.checks.len(), 1);
+        assert_eq!(report.checks[0].0, "watcher configuration parsed");
+        assert_eq!(report.failures(), 1);
+
+        let path = write_config(
+            "invalid-watcher",
+            &std::fs::read_to_string("../fixtures/watcher.json")
+                .unwrap()
+                .replace("file://", "ftp://"),
+        );
+        let report = check_watcher(&path);
+        assert_eq!(report.checks.len(), 2);
+        assert!(report.checks[0].1.is_ok());
+        assert_eq!(report.checks[1].0, "watcher configuration valid");
+        assert!(report.checks[1].1.is_err());
+    }
+}
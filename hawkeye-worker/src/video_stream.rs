@@ -1,9 +1,23 @@
-use crate::img_detector::SlateDetector;
+use crate::actions::Simulation;
+use crate::bars_detector::BarsDetector;
+use crate::black_detector::BlackDetector;
+use crate::clips;
+use crate::control;
+use crate::debounce::Debouncer;
+use crate::frames;
+use crate::health;
+use crate::img_detector::{SlateDetector, SlateMatch};
 use crate::metrics::{
-    FOUND_CONTENT_COUNTER, FOUND_SLATE_COUNTER, FRAME_PROCESSING_DURATION,
+    FOUND_BARS_COUNTER, FOUND_BLACK_COUNTER, FOUND_CONTENT_COUNTER, FOUND_SLATE_COUNTER,
+    FRAMES_DISCARDED_COUNTER, FRAME_CHANNEL_DEPTH, FRAME_PROCESSING_DURATION,
     SIMILARITY_EXECUTION_COUNTER, SIMILARITY_EXECUTION_DURATION,
 };
 use crate::slate::SLATE_SIZE;
+use crate::snapshots;
+use crate::state;
+use crate::text_detector::TextDetector;
+use crate::transitions;
+use chrono::{DateTime, Utc};
 use color_eyre::eyre::{bail, eyre, Context, Result};
 use concread::CowCell;
 use crossbeam::channel::{bounded, Receiver, Sender, TryRecvError, TrySendError};
@@ -18,7 +32,8 @@ use log::{debug, info};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use tracing::{field, info_span, Span};
 
 lazy_static! {
     pub(crate) static ref LATEST_FRAME: CowCell<Option<Vec<u8>>> = CowCell::new(None);
@@ -33,28 +48,73 @@ struct ErrorMessage {
     source: glib::Error,
 }
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum Event {
     Terminate,
-    Mode(VideoMode),
+    Mode(Detection),
+    /// A transition requested to test the actions, the stream didn't change.
+    Simulate(Simulation),
+}
+
+/// A video mode found in the stream and the moment the frame was analysed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Detection {
+    pub mode: VideoMode,
+    pub detected_at: DateTime<Utc>,
+    /// When the frame arrived from the pipeline, before it was analysed.
+    pub frame_received_at: Instant,
+    /// Slate image matching the frame, when it was detected by comparing images.
+    pub slate: Option<SlateMatch>,
+    /// Span of the processing of the frame, the executions of the actions are part of it.
+    pub span: Span,
+}
+
+impl Detection {
+    pub fn now(mode: VideoMode) -> Self {
+        Self {
+            mode,
+            detected_at: Utc::now(),
+            frame_received_at: Instant::now(),
+            slate: None,
+            span: Span::current(),
+        }
+    }
+
+    pub fn with_frame_received_at(mut self, frame_received_at: Instant) -> Self {
+        self.frame_received_at = frame_received_at;
+        self
+    }
+
+    pub fn with_slate(mut self, slate: Option<SlateMatch>) -> Self {
+        self.slate = slate;
+        self
+    }
+}
+
+/// Detectors used to find out the video mode of each frame.
+pub struct Detectors {
+    pub slate: SlateDetector,
+    pub slate_text: Option<TextDetector>,
+    pub black: BlackDetector,
+    pub bars: Option<BarsDetector>,
 }
 
 pub fn process_frames(
     frame_source: impl Iterator<Item = Result<Option<Vec<u8>>>>,
-    detector: SlateDetector,
+    detectors: Detectors,
+    mut debouncer: Debouncer,
     running: Arc<AtomicBool>,
     action_sink: Sender<Event>,
 ) -> Result<()> {
-    let black_image = include_bytes!("../../resources/black_120px.jpg");
-    let black_detector = SlateDetector::new(black_image)?;
-
     let mut empty_iterations = 0;
     for frame in frame_source {
         let frame_processing_timer = FRAME_PROCESSING_DURATION.start_timer();
+        let frame_received_at = Instant::now();
         let local_buffer = match frame? {
             Some(contents) => {
                 log::trace!("Empty iterations: {}", empty_iterations);
                 empty_iterations = 0;
+                health::frame_received();
                 contents
             }
             None => {
@@ -67,41 +127,102 @@ pub fn process_frames(
                 }
             }
         };
+        if control::detection_paused() {
+            frame_processing_timer.stop_and_discard();
+            if !running.load(Ordering::SeqCst) {
+                break;
+            }
+            continue;
+        }
+        let frame_span = info_span!("process_frame", mode = field::Empty);
+        let _frame = frame_span.enter();
 
-        let is_black = black_detector.is_match(local_buffer.as_slice());
+        let is_black = detectors.black.is_match(local_buffer.as_slice());
+        let is_bars = !is_black
+            && detectors
+                .bars
+                .as_ref()
+                .map(|d| d.is_match(local_buffer.as_slice()))
+                .unwrap_or(false);
 
         let mut is_match = false;
-        if !is_black {
+        let mut slate_match = None;
+        if !is_black && !is_bars {
             let t = SIMILARITY_EXECUTION_DURATION.start_timer();
+            let _comparison = info_span!("slate_comparison").entered();
 
-            is_match = detector.is_match(local_buffer.as_slice());
+            slate_match = detectors.slate.find_match(local_buffer.as_slice());
+            is_match = slate_match.is_some()
+                || detectors
+                    .slate_text
+                    .as_ref()
+                    .map(|d| d.is_match(local_buffer.as_slice()))
+                    .unwrap_or(false);
 
             let took_in_seconds = t.stop_and_record();
             log::trace!("Similarity algorithm ran in {} seconds", took_in_seconds);
         }
 
-        {
+        let frame = if frames::enabled() {
+            Some(local_buffer.clone())
+        } else {
+            None
+        };
+        let previous_frame = {
             // Save latest image bytes
             let mut write_txn = LATEST_FRAME.write();
             // Moves the local buffer
-            *write_txn = Some(local_buffer);
+            let previous_frame = std::mem::replace(&mut *write_txn, Some(local_buffer));
             write_txn.commit();
-        }
-
-        if is_black {
-            continue;
-        }
+            previous_frame
+        };
 
-        if is_match {
+        let mode = if is_black {
+            log::trace!("Black frame in video stream!");
+            FOUND_BLACK_COUNTER.inc();
+            VideoMode::Black
+        } else if is_bars {
+            log::trace!("Color bars in video stream!");
+            FOUND_BARS_COUNTER.inc();
+            VideoMode::Bars
+        } else if is_match {
             log::trace!("Found slate image in video stream!");
             FOUND_SLATE_COUNTER.inc();
-            action_sink.send(Event::Mode(VideoMode::Slate)).unwrap();
+            VideoMode::Slate
         } else {
             FOUND_CONTENT_COUNTER.inc();
-            action_sink.send(Event::Mode(VideoMode::Content)).unwrap();
             log::trace!("Content in video stream!");
+            VideoMode::Content
+        };
+        if !is_black && !is_bars {
+            SIMILARITY_EXECUTION_COUNTER.inc();
+        }
+        frame_span.record("mode", field::display(mode));
+
+        let reported_mode = debouncer.observe(mode);
+        if let Some(frame) = frame {
+            frames::record(frame, mode, reported_mode, slate_match.as_ref());
+        }
+        if let Some(mode) = reported_mode {
+            let detection = Detection::now(mode)
+                .with_frame_received_at(frame_received_at)
+                .with_slate(slate_match);
+            state::observe(
+                detection.mode,
+                detection.detected_at,
+                detection.slate.as_ref(),
+            );
+            if transitions::changed(detection.mode) {
+                let snapshots = snapshots::save(
+                    &detection,
+                    LATEST_FRAME.read().as_deref(),
+                    previous_frame.as_deref(),
+                );
+                transitions::record(&detection, snapshots);
+            }
+            clips::transition(&detection);
+            action_sink.send(Event::Mode(detection)).unwrap();
         }
-        SIMILARITY_EXECUTION_COUNTER.inc();
 
         let took_in_seconds = frame_processing_timer.stop_and_record();
         log::trace!("Frame processing took {} seconds", took_in_seconds);
@@ -130,14 +251,16 @@ impl VideoStream {
         let (width, height) = SLATE_SIZE;
         let pipeline_description = match (container, codec) {
             (Container::MpegTs, Codec::H264) => format!(
-                "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)MP2T, payload=(int)33\" ! .recv_rtp_sink_0 rtpbin ! rtpmp2tdepay ! tsdemux ! h264parse ! avdec_h264 ! videorate ! video/x-raw,framerate=10/1 ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\"",
+                "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)MP2T, payload=(int)33\" ! .recv_rtp_sink_0 rtpbin ! rtpmp2tdepay{} ! tsdemux ! h264parse ! avdec_h264 ! videorate ! video/x-raw,framerate=10/1 ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\"",
                 ingest_port,
+                clips::pipeline_branch(""),
                 width,
                 height
             ),
             (Container::RawVideo, Codec::H264) => format!(
-                "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)H264, payload=(int)96\" ! rtph264depay ! decodebin ! videorate ! video/x-raw,framerate=10/1 ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\"",
+                "udpsrc port={} caps=\"application/x-rtp, media=(string)video, clock-rate=(int)90000, encoding-name=(string)H264, payload=(int)96\" ! rtph264depay{} ! decodebin ! videorate ! video/x-raw,framerate=10/1 ! videoconvert ! videoscale ! capsfilter caps=\"video/x-raw, width={}, height={}\"",
                 ingest_port,
+                clips::pipeline_branch("h264parse config-interval=-1 ! mpegtsmux ! "),
                 width,
                 height
             ),
@@ -149,6 +272,15 @@ impl VideoStream {
 
     /// Create a new Gstreamer pipeline from the given description.
     pub fn new_from_description<S: AsRef<str>>(pipeline_description: S) -> Result<Self> {
+        Self::new_from_description_at(pipeline_description, None)
+    }
+
+    /// Create a new Gstreamer pipeline from the given description, the stream starts at
+    /// `position` when provided. Seeking only works with non-live sources, like files.
+    pub fn new_from_description_at<S: AsRef<str>>(
+        pipeline_description: S,
+        position: Option<Duration>,
+    ) -> Result<Self> {
         let (sender, receiver) = bounded(1);
         let pipeline_description = pipeline_description.as_ref().into();
 
@@ -220,9 +352,13 @@ impl VideoStream {
                     log::trace!("Frame extracted from pipeline");
 
                     match sender.try_send(Ok(Some(buffer.to_vec()))) {
-                        Ok(_) => Ok(gst::FlowSuccess::Ok),
+                        Ok(_) => {
+                            FRAME_CHANNEL_DEPTH.set(sender.len() as i64);
+                            Ok(gst::FlowSuccess::Ok)
+                        }
                         Err(TrySendError::Full(_)) => {
                             log::trace!("Channel is full, discarded frame");
+                            FRAMES_DISCARDED_COUNTER.inc();
                             Ok(gst::FlowSuccess::Ok)
                         }
                         Err(TrySendError::Disconnected(_)) => {
@@ -234,13 +370,51 @@ impl VideoStream {
                 .build(),
         );
 
+        // The encoded stream, when the clips of the transitions are recorded
+        if let Some(clip_sink) = pipeline.by_name("clip_sink") {
+            let clip_sink = clip_sink
+                .downcast::<gst_app::AppSink>()
+                .map_err(|_| eyre!("Clip sink element is expected to be an appsink!"))?;
+            clip_sink.set_callbacks(
+                gst_app::AppSinkCallbacks::builder()
+                    .new_sample(|appsink| {
+                        let sample = appsink.pull_sample().map_err(|_| gst::FlowError::Eos)?;
+                        if let Some(buffer) = sample.buffer() {
+                            let buffer =
+                                buffer.map_readable().map_err(|_| gst::FlowError::Error)?;
+                            clips::push(buffer.to_vec());
+                        }
+                        Ok(gst::FlowSuccess::Ok)
+                    })
+                    .build(),
+            );
+        }
+
         let bus = pipeline
             .bus()
             .ok_or_else(|| eyre!("Pipeline without bus. Shouldn't happen!"))?;
 
+        if let Some(position) = position {
+            pipeline
+                .set_state(gst::State::Paused)
+                .context("Cannot pause pipeline")?;
+            // The pipeline can only seek after it's prerolled
+            pipeline
+                .state(gst::ClockTime::NONE)
+                .0
+                .context("Pipeline failed to preroll")?;
+            pipeline
+                .seek_simple(
+                    gst::SeekFlags::FLUSH | gst::SeekFlags::ACCURATE,
+                    gst::ClockTime::from_nseconds(position.as_nanos() as u64),
+                )
+                .context("Cannot seek in pipeline")?;
+        }
+
         pipeline
             .set_state(gst::State::Playing)
             .context("Cannot start pipeline")?;
+        health::set_pipeline_playing(true);
         info!("Pipeline started: {}", pipeline_description);
 
         Ok(Self {
@@ -257,7 +431,10 @@ impl Iterator for VideoStream {
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.receiver.try_recv() {
-            Ok(event) => return Some(event),
+            Ok(event) => {
+                FRAME_CHANNEL_DEPTH.set(self.receiver.len() as i64);
+                return Some(event);
+            }
             Err(TryRecvError::Empty) => {
                 // Check if there are errors in the GStreamer pipeline itself.
                 if let Some(msg) = self.bus.pop() {
@@ -268,6 +445,7 @@ impl Iterator for VideoStream {
                             // The End-of-stream message is posted when the stream is done, which in our case
                             // happens immediately after matching the slate image because we return
                             // gst::FlowError::Eos then.
+                            health::set_pipeline_playing(false);
                             return None;
                         }
                         MessageView::Error(err) => {
@@ -281,6 +459,7 @@ impl Iterator for VideoStream {
                                 source: err.error(),
                             };
                             log::error!("Error returned by pipeline: {:?}", error_msg);
+                            health::set_pipeline_playing(false);
                             // TODO: Should return a proper error here, returning `None` will simply stop the iterator.
                             return None;
                         }
@@ -289,7 +468,10 @@ impl Iterator for VideoStream {
                 }
             }
             Err(TryRecvError::Disconnected) => {
-                log::debug!("The Pipeline channel is disconnected: {}", self.pipeline_description);
+                log::debug!(
+                    "The Pipeline channel is disconnected: {}",
+                    self.pipeline_description
+                );
                 return None;
             }
         }
@@ -301,6 +483,7 @@ impl Iterator for VideoStream {
 
 impl Drop for VideoStream {
     fn drop(&mut self) {
+        health::set_pipeline_playing(false);
         if self.pipeline.set_state(gst::State::Null).is_err() {
             log::error!("Could not stop pipeline");
         }
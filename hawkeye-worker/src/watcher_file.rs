@@ -0,0 +1,126 @@
+use color_eyre::{
+    eyre::{eyre, WrapErr},
+    Result,
+};
+use hawkeye_core::models::Watcher;
+use serde_json::Value;
+use std::fs::File;
+use std::path::Path;
+
+/// Loads the watcher configuration, resolving the `${ENV_VAR}` placeholders in its strings.
+pub fn load(path: &Path) -> Result<Watcher> {
+    let file = File::open(path).wrap_err_with(|| format!("Could not open {}", path.display()))?;
+    let mut config: Value = serde_json::from_reader(file)?;
+    resolve(&mut config, &|name| std::env::var(name).ok())?;
+    Ok(serde_json::from_value(config)?)
+}
+
+/// Replaces the placeholders in all the strings of the configuration. The values are
+/// substituted after the JSON is parsed, so they don't need to be escaped.
+fn resolve(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains('$') => *s = interpolate(s, lookup)?,
+        Value::Array(items) => {
+            for item in items.iter_mut() {
+                resolve(item, lookup)?;
+            }
+        }
+        Value::Object(fields) => {
+            for field in fields.values_mut() {
+                resolve(field, lookup)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces `${NAME}` with the value of the variable, `${NAME:-default}` falls back to the
+/// default when it's unset and `$${` is a literal `${`. Unset variables without a default fail
+/// the loading, instead of calling the actions with an empty credential.
+fn interpolate(s: &str, lookup: &dyn Fn(&str) -> Option<String>) -> Result<String> {
+    let mut resolved = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('$') {
+        resolved.push_str(&rest[..start]);
+        let placeholder = &rest[start..];
+        if let Some(escaped) = placeholder.strip_prefix("$${") {
+            resolved.push_str("${");
+            rest = escaped;
+        } else if let Some(placeholder) = placeholder.strip_prefix("${") {
+            let end = placeholder
+                .find('}')
+                .ok_or_else(|| eyre!("Unterminated placeholder in \"{}\"", s))?;
+            let (name, default) = match placeholder[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&placeholder[..end], None),
+            };
+            if name.is_empty() {
+                return Err(eyre!("Empty placeholder in \"{}\"", s));
+            }
+            match lookup(name).or_else(|| default.map(String::from)) {
+                Some(value) => resolved.push_str(&value),
+                None => return Err(eyre!("Environment variable {} is not set", name)),
+            }
+            rest = &placeholder[end + 1..];
+        } else {
+            resolved.push('$');
+            rest = &placeholder[1..];
+        }
+    }
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "API_TOKEN" => Some("secret\"token".to_string()),
+            "ENDPOINT" => Some("https://prod.example.com".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn interpolate_placeholders() {
+        assert_eq!(
+            interpolate("${ENDPOINT}/breaks?token=${API_TOKEN}", &lookup).unwrap(),
+            "https://prod.example.com/breaks?token=secret\"token"
+        );
+        assert_eq!(
+            interpolate("${REGION:-us-east-1}", &lookup).unwrap(),
+            "us-east-1"
+        );
+        assert_eq!(
+            interpolate("${ENDPOINT:-http://localhost}", &lookup).unwrap(),
+            "https://prod.example.com"
+        );
+        assert_eq!(
+            interpolate("$${ENDPOINT} $5", &lookup).unwrap(),
+            "${ENDPOINT} $5"
+        );
+        assert!(interpolate("${MISSING}", &lookup).is_err());
+        assert!(interpolate("${ENDPOINT", &lookup).is_err());
+        assert!(interpolate("${}", &lookup).is_err());
+    }
+
+    #[test]
+    fn resolve_nested_strings() {
+        let mut config = json!({
+            "transitions": [{"actions": [{"url": "${ENDPOINT}/breaks", "timeout": 10}]}],
+            "slate_headers": {"Authorization": "Bearer ${API_TOKEN}"}
+        });
+        resolve(&mut config, &lookup).unwrap();
+        assert_eq!(
+            config,
+            json!({
+                "transitions": [{"actions": [{"url": "https://prod.example.com/breaks", "timeout": 10}]}],
+                "slate_headers": {"Authorization": "Bearer secret\"token"}
+            })
+        );
+    }
+}